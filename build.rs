@@ -0,0 +1,23 @@
+//! Generates the bindings of the protocols that the version of
+//! wayland-protocols in use does not have yet.
+
+use std::env::var;
+use std::path::Path;
+
+use wayland_scanner::{generate_code, Side};
+
+// The protocols in protocols/, by the name of their file
+static PROTOCOLS: &[&str] = &["ext-session-lock-v1"];
+
+fn main() {
+    let out_dir = var("OUT_DIR").unwrap();
+    for name in PROTOCOLS {
+        let file = Path::new("protocols").join(format!("{}.xml", name));
+        println!("cargo:rerun-if-changed={}", file.display());
+        generate_code(
+            &file,
+            Path::new(&out_dir).join(format!("{}_client_api.rs", name)),
+            Side::Client,
+        );
+    }
+}
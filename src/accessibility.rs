@@ -0,0 +1,243 @@
+//! Accessibility mode
+//!
+//! Swaps the dashboard to a high-contrast palette with larger text and
+//! thicker bars and cursors. The widget tree is built again from the config
+//! with the changes applied, so that everything is laid out anew at the
+//! larger sizes.
+
+use crate::color::Color;
+use crate::config::Widget;
+use crate::widgets::bar_widget::BarColors;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn default_font_scale() -> f32 {
+    1.5
+}
+
+fn default_background() -> Color {
+    Color::new(0.0, 0.0, 0.0, 1.0)
+}
+
+fn default_foreground() -> Color {
+    Color::new(1.0, 1.0, 1.0, 1.0)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Accessibility {
+    /// Whether to start in accessibility mode.
+    #[serde(default)]
+    pub enabled: bool,
+    /// The factor to scale font sizes, and the widgets with them, by.
+    #[serde(default = "default_font_scale")]
+    pub font_scale: f32,
+    /// The background of the dashboard, replacing those of styled widgets.
+    #[serde(default = "default_background")]
+    pub background: Color,
+    /// The color of bars, borders and lock keys.
+    #[serde(default = "default_foreground")]
+    pub foreground: Color,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Accessibility {
+            enabled: false,
+            font_scale: default_font_scale(),
+            background: default_background(),
+            foreground: default_foreground(),
+        }
+    }
+}
+
+impl Accessibility {
+    /// Changes the widget tree for accessibility mode, if enabled.
+    pub fn apply(&self, mut widget: Widget) -> Widget {
+        if is_enabled() {
+            self.adapt(&mut widget);
+        }
+        widget
+    }
+
+    fn scale(&self, v: &mut u32) {
+        *v = (*v as f32 * self.font_scale).round() as u32;
+    }
+
+    fn solid(&self) -> Option<BarColors> {
+        Some(BarColors {
+            stops: vec![(1.0, self.foreground)],
+            gradient: false,
+        })
+    }
+
+    fn adapt(&self, widget: &mut Widget) {
+        let scale = self.font_scale;
+        match widget {
+            Widget::HorizontalLayout(children) | Widget::VerticalLayout(children) => {
+                for child in children.iter_mut() {
+                    self.adapt(child);
+                }
+            }
+            Widget::Fixed {
+                width,
+                height,
+                widget,
+            } => {
+                self.scale(width);
+                self.scale(height);
+                self.adapt(widget);
+            }
+            Widget::Style {
+                background,
+                border,
+                widget,
+                ..
+            } => {
+                *background = None;
+                if let Some(border) = border {
+                    border.color = self.foreground;
+                }
+                self.adapt(widget);
+            }
+            Widget::Margin { widget, .. }
+            | Widget::Constrain { widget, .. }
+            | Widget::FocusedOutput { widget }
+            | Widget::Bind { widget, .. }
+            | Widget::Help { widget }
+            | Widget::Outline { widget, .. } => self.adapt(widget),
+            Widget::Warning { .. } => (),
+            Widget::Clock { font_size, .. }
+            | Widget::Date { font_size, .. }
+            | Widget::WorldClock { font_size, .. }
+            | Widget::Label { font_size, .. }
+            | Widget::Calendar { font_size, .. }
+            | Widget::ColorPicker { font_size, .. } => *font_size *= scale,
+            Widget::Locks {
+                font_size,
+                active_color,
+                only_active,
+                ..
+            } => {
+                *font_size *= scale;
+                *active_color = self.foreground;
+                *only_active = true;
+            }
+            Widget::Launcher {
+                font_size,
+                length,
+                prompt_font_size,
+                results_font_size,
+                calc_font_size,
+                ..
+            } => {
+                *font_size *= scale;
+                self.scale(length);
+                for size in [prompt_font_size, results_font_size, calc_font_size]
+                    .into_iter()
+                    .flatten()
+                {
+                    *size *= scale;
+                }
+            }
+            Widget::Notes {
+                font_size,
+                length,
+                height,
+                ..
+            } => {
+                *font_size *= scale;
+                self.scale(length);
+                self.scale(height);
+            }
+            Widget::Metrics {
+                font_size, length, ..
+            }
+            | Widget::Processes {
+                font_size, length, ..
+            }
+            | Widget::CommandBar {
+                font_size, length, ..
+            }
+            | Widget::Greeting {
+                font_size, length, ..
+            }
+            | Widget::NextEvent {
+                font_size, length, ..
+            }
+            | Widget::WeekStrip {
+                font_size, length, ..
+            }
+            | Widget::Feeds {
+                font_size, length, ..
+            }
+            | Widget::Latency {
+                font_size, length, ..
+            } => {
+                *font_size *= scale;
+                self.scale(length);
+            }
+            #[cfg(feature = "battery-widget")]
+            Widget::Battery {
+                font_size,
+                length,
+                colors,
+                ..
+            } => {
+                *font_size *= scale;
+                self.scale(length);
+                *colors = self.solid();
+            }
+            Widget::Backlight {
+                font_size,
+                length,
+                colors,
+                ..
+            } => {
+                *font_size *= scale;
+                self.scale(length);
+                *colors = self.solid();
+            }
+            #[cfg(feature = "pulseaudio-widget")]
+            Widget::PulseAudio {
+                font_size,
+                length,
+                colors,
+                ..
+            }
+            | Widget::Microphone {
+                font_size,
+                length,
+                colors,
+                ..
+            } => {
+                *font_size *= scale;
+                self.scale(length);
+                *colors = self.solid();
+            }
+            #[cfg(feature = "alsa-widget")]
+            Widget::AlsaSound {
+                font_size,
+                length,
+                colors,
+                ..
+            } => {
+                *font_size *= scale;
+                self.scale(length);
+                *colors = self.solid();
+            }
+        }
+    }
+}
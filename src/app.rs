@@ -1,42 +1,361 @@
 use std::collections::VecDeque;
+use std::fs;
+use std::io::{Error, ErrorKind, Read};
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
 
 use chrono::{Duration, Local, NaiveDateTime};
+use memmap::MmapMut;
 
-use crate::keyboard::{keysyms, map_keyboard, Event as KbEvent, KeyState, ModifiersState};
+use crate::keyboard::{map_keyboard, Event as KbEvent, KeyState, ModifiersState};
 
-use wayland_client::protocol::{wl_compositor, wl_output, wl_pointer, wl_seat, wl_shm, wl_surface};
+use wayland_client::protocol::{
+    wl_buffer, wl_compositor, wl_data_device, wl_data_device_manager, wl_data_offer, wl_output,
+    wl_pointer, wl_seat, wl_shm, wl_surface,
+};
 use wayland_client::{Display, EventQueue, GlobalEvent, GlobalManager, Main};
+use wayland_protocols::staging::xdg_activation::v1::client::{
+    xdg_activation_token_v1, xdg_activation_v1,
+};
+use wayland_protocols::viewporter::client::{wp_viewport, wp_viewporter};
 use wayland_protocols::wlr::unstable::layer_shell::v1::client::{
     zwlr_layer_shell_v1, zwlr_layer_surface_v1,
 };
+use wayland_protocols::xdg_shell::client::{xdg_surface, xdg_toplevel, xdg_wm_base};
 
 use crate::buffer::Buffer;
 use crate::color::Color;
-use crate::widget::{DrawContext, WaitContext, Widget};
+use crate::scroll::ScrollState;
+use crate::widget::{DrawContext, DrawReport, WaitContext, Widget};
+
+use crate::cmd::{Action, Cmd};
+use crate::data::state_file;
+use crate::doublemempool::{DoubleMemPool, Frame, MemPool};
+use crate::keybindings::{self, GlobalKey};
+use crate::metrics;
+use crate::osd::Osd;
+use crate::png;
+use crate::protocol::{self, PointerFrame};
+use crate::session_lock::client::{
+    ext_session_lock_manager_v1, ext_session_lock_surface_v1, ext_session_lock_v1,
+};
+
+// Damage rectangles collected for a buffer before it is considered fully
+// damaged instead.
+const MAX_DAMAGE: usize = 32;
 
-use crate::cmd::Cmd;
-use crate::doublemempool::DoubleMemPool;
+type Rect = (i32, i32, i32, i32);
 
-#[derive(Debug)]
+fn missing_global(interface: &str) -> Error {
+    Error::other(format!("server didn't advertise `{}`", interface))
+}
+
+// Adds damage to a list, which becomes the full buffer once too long.
+fn add_damage(list: &mut Vec<Rect>, damage: &[Rect], full: Rect) {
+    if list.len() + damage.len() > MAX_DAMAGE {
+        *list = vec![full];
+    } else {
+        list.extend_from_slice(damage);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum OutputMode {
     Active,
     All,
 }
 
+/// A small surface kept on an edge or corner while the dashboard is hidden,
+/// which expands the dashboard when the pointer touches it.
+#[derive(Debug, Clone, Copy)]
+pub struct HotCorner {
+    pub anchor: zwlr_layer_surface_v1::Anchor,
+    pub size: u32,
+    pub margin: u32,
+    pub interactivity: zwlr_layer_surface_v1::KeyboardInteractivity,
+}
+
+#[derive(Clone, Copy)]
+enum Placement {
+    Center(zwlr_layer_surface_v1::KeyboardInteractivity),
+    Expanded(HotCorner),
+    Collapsed(HotCorner),
+}
+
+// The buffers and damage history of the surface on one output.
+struct SurfaceBuffers {
+    pools: DoubleMemPool,
+    // What the idle pool misses compared to the most recent buffer
+    last_damage: Option<Vec<Rect>>,
+    // What changed on the focused surface since the most recent buffer,
+    // which grows while the pools are busy
+    missed: Vec<Rect>,
+    last_dim: (u32, u32),
+}
+
+impl SurfaceBuffers {
+    fn new(shm: Main<wl_shm::WlShm>) -> Result<SurfaceBuffers, ::std::io::Error> {
+        Ok(SurfaceBuffers {
+            pools: DoubleMemPool::new(shm)?,
+            last_damage: None,
+            missed: Vec::new(),
+            last_dim: (0, 0),
+        })
+    }
+
+    // Keeps track of what the idle pool misses, which grows while the same
+    // pool is redrawn in place.
+    fn drawn(&mut self, damage: &[Rect], in_place: bool, size: (u32, u32)) {
+        let full = (0, 0, size.0 as i32, size.1 as i32);
+        self.last_damage = match self.last_damage.take() {
+            Some(mut last) if in_place => {
+                add_damage(&mut last, damage, full);
+                Some(last)
+            }
+            _ if in_place => Some(vec![full]),
+            _ => Some(damage.to_vec()),
+        };
+        self.last_dim = size;
+    }
+
+    // Brings the surface up to date with the focused surface by copying what
+    // changed, leaving out what is only shown on the focused output. Returns
+    // the buffer to attach and its damage, or None if both pools are busy,
+    // in which case the damage is kept for later.
+    fn mirror(
+        &mut self,
+        source: &Buffer,
+        size: (u32, u32),
+        buffer_size: (i32, i32),
+        damage: &[Rect],
+        focused_only: &[Rect],
+        bg: &Color,
+    ) -> Option<(wl_buffer::WlBuffer, Vec<Rect>)> {
+        let full = (0, 0, size.0 as i32, size.1 as i32);
+        if self.last_dim != size {
+            self.missed = vec![full];
+        } else {
+            add_damage(&mut self.missed, damage, full);
+        }
+
+        let Frame {
+            pool,
+            newest,
+            blank,
+        } = self.pools.pool()?;
+        let in_place = newest.is_none();
+        pool.resize((4 * size.0 * size.1) as usize)
+            .expect("Failed to resize the memory pool.");
+
+        // The focused surface has everything, so the pool can catch up on
+        // what it missed before the most recent buffer from there as well.
+        let mut copy = self.missed.clone();
+        match &self.last_damage {
+            _ if blank || self.last_dim != size => copy = vec![full],
+            Some(last) if !in_place => add_damage(&mut copy, last, full),
+            None if !in_place => copy = vec![full],
+            _ => (),
+        }
+
+        let mmap = pool.mmap();
+        let mut buf = Buffer::new(mmap, size);
+        for d in copy {
+            source.copy_to(&mut buf, d);
+        }
+        for r in focused_only {
+            if r.2 > 0 && r.3 > 0 {
+                buf.clip((r.0 as u32, r.1 as u32, r.2 as u32, r.3 as u32))
+                    .memset(bg);
+            }
+        }
+        mmap.flush().unwrap();
+
+        let buffer = pool.buffer(
+            0,
+            buffer_size.0,
+            buffer_size.1,
+            4 * size.0 as i32,
+            wl_shm::Format::Argb8888,
+        );
+        let damage = std::mem::take(&mut self.missed);
+        self.drawn(&damage, in_place, size);
+        Some((buffer, damage))
+    }
+}
+
+// The role of a surface, which is a layer surface unless the session is
+// locked or the dashboard is shown as a window.
+enum ShellSurface {
+    Layer(Main<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>),
+    Lock(Main<ext_session_lock_surface_v1::ExtSessionLockSurfaceV1>),
+    Window(
+        Main<xdg_surface::XdgSurface>,
+        Main<xdg_toplevel::XdgToplevel>,
+    ),
+}
+
+impl ShellSurface {
+    // Lock surfaces are sized by the compositor to cover their output.
+    // Windows are drawn at the size the compositor gives them, but are not
+    // to be made smaller than the widgets.
+    fn set_size(&self, size: (u32, u32), widget_size: (u32, u32)) {
+        match self {
+            ShellSurface::Layer(shell_surface) => shell_surface.set_size(size.0, size.1),
+            ShellSurface::Lock(_) => (),
+            ShellSurface::Window(xdg_surface, toplevel) => {
+                toplevel.set_min_size(widget_size.0 as i32, widget_size.1 as i32);
+                xdg_surface.set_window_geometry(0, 0, size.0 as i32, size.1 as i32);
+            }
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            ShellSurface::Layer(shell_surface) => shell_surface.destroy(),
+            ShellSurface::Lock(shell_surface) => shell_surface.destroy(),
+            ShellSurface::Window(xdg_surface, toplevel) => {
+                toplevel.destroy();
+                xdg_surface.destroy();
+            }
+        }
+    }
+}
+
+const WINDOW_FILE: &str = "window";
+
+// How the dashboard window was last shown, so that it is opened the same way
+// the next time. The size is in surface pixels, and is the size the window
+// had when it was last not maximized.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct WindowGeometry {
+    size: Option<(u32, u32)>,
+    maximized: bool,
+}
+
+impl WindowGeometry {
+    // A line such as "1280 720" or "1280 720 maximized"
+    fn parse(s: &str) -> WindowGeometry {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let size = match words.as_slice() {
+            [width, height, ..] => width.parse().ok().zip(height.parse().ok()),
+            _ => None,
+        };
+        WindowGeometry {
+            size: size.filter(|&(width, height)| width > 0 && height > 0),
+            maximized: words.contains(&"maximized"),
+        }
+    }
+
+    fn load() -> WindowGeometry {
+        state_file(WINDOW_FILE)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| WindowGeometry::parse(&content))
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut content = match self.size {
+            Some((width, height)) => format!("{} {}", width, height),
+            None => String::new(),
+        };
+        if self.maximized {
+            content.push_str(" maximized");
+        }
+        content.push('\n');
+        fs::write(state_file(WINDOW_FILE)?, content.trim_start())?;
+        Ok(())
+    }
+}
+
+// Fills a surface with a color, at the size the compositor gives it. With a
+// viewporter, a single pixel is stretched over the surface, and otherwise a
+// buffer the size of the surface is filled.
+struct Fill {
+    surface: Main<wl_surface::WlSurface>,
+    pool: MemPool,
+    viewport: Option<Main<wp_viewport::WpViewport>>,
+    color: Color,
+}
+
+impl Fill {
+    fn fill(&mut self, width: u32, height: u32) {
+        let size = match self.viewport {
+            Some(_) => (1, 1),
+            None => (width, height),
+        };
+        if size.0 == 0 || size.1 == 0 || self.pool.resize((4 * size.0 * size.1) as usize).is_err() {
+            return;
+        }
+        Buffer::new(self.pool.mmap(), size).memset(&self.color);
+        self.pool.mmap().flush().unwrap();
+        let buffer = self.pool.buffer(
+            0,
+            size.0 as i32,
+            size.1 as i32,
+            4 * size.0 as i32,
+            wl_shm::Format::Argb8888,
+        );
+        if let Some(viewport) = &self.viewport {
+            viewport.set_destination(width as i32, height as i32);
+        }
+        self.surface.attach(Some(&buffer), 0, 0);
+        self.surface
+            .damage_buffer(0, 0, size.0 as i32, size.1 as i32);
+        self.surface.commit();
+    }
+}
+
 struct AppInner {
     compositor: Option<Main<wl_compositor::WlCompositor>>,
     surfaces: Vec<Main<wl_surface::WlSurface>>,
-    shell_surfaces: Vec<Main<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>>,
+    shell_surfaces: Vec<ShellSurface>,
     configured_surfaces: Arc<Mutex<usize>>,
     outputs: Vec<(u32, Main<wl_output::WlOutput>)>,
+    // The current mode of every output, in output pixels
+    output_sizes: Vec<(u32, (u32, u32))>,
+    // The output the surface was last shown on, in the active output mode
+    entered: Arc<Mutex<Option<u32>>>,
     shell: Option<Main<zwlr_layer_shell_v1::ZwlrLayerShellV1>>,
+    wm_base: Option<Main<xdg_wm_base::XdgWmBase>>,
+    // Whether the dashboard is shown as a window rather than on the layer
+    // shell
+    windowed: bool,
+    // The size of the window, in pixels, if the compositor or the last
+    // session decided it rather than the widgets
+    window_size: Arc<Mutex<Option<(u32, u32)>>>,
     draw_tx: Sender<Cmd>,
     output_mode: OutputMode,
     visible: bool,
     scale: u32,
+    hot_corner: Option<HotCorner>,
+    interactivity: zwlr_layer_surface_v1::KeyboardInteractivity,
+    // Bumped whenever the surfaces are replaced
+    generation: usize,
+    // The surface with keyboard or pointer focus
+    focused: usize,
+    // The color shown behind the dashboard while it is open, and its
+    // surfaces
+    backdrop: Option<Color>,
+    backdrops: Vec<Main<wl_surface::WlSurface>>,
+    backdrop_shells: Vec<ShellSurface>,
+    shm: Option<Main<wl_shm::WlShm>>,
+    viewporter: Option<Main<wp_viewporter::WpViewporter>>,
+    // Set if the compositor supports locking the session
+    lock_manager: Option<Main<ext_session_lock_manager_v1::ExtSessionLockManagerV1>>,
+    // The lock while the session is locked, which replaces the layer
+    // surfaces with lock surfaces, and whether the compositor confirmed it
+    session_lock: Option<Main<ext_session_lock_v1::ExtSessionLockV1>>,
+    locked: Arc<AtomicBool>,
+    // What the outputs without widgets are filled with while locked
+    lock_background: Color,
+    // The size of the lock surface the widgets are shown on, in pixels, as
+    // given by the compositor
+    lock_size: Arc<Mutex<Option<(u32, u32)>>>,
 }
 
 impl AppInner {
@@ -47,11 +366,30 @@ impl AppInner {
             shell_surfaces: Vec::new(),
             configured_surfaces: Arc::new(Mutex::new(0)),
             outputs: Vec::new(),
+            output_sizes: Vec::new(),
+            entered: Arc::new(Mutex::new(None)),
             shell: None,
+            wm_base: None,
+            windowed: false,
+            window_size: Arc::new(Mutex::new(None)),
             draw_tx: tx,
             output_mode,
             visible: true,
             scale,
+            hot_corner: None,
+            interactivity: zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive,
+            generation: 0,
+            focused: 0,
+            backdrop: None,
+            backdrops: Vec::new(),
+            backdrop_shells: Vec::new(),
+            shm: None,
+            viewporter: None,
+            lock_manager: None,
+            session_lock: None,
+            locked: Arc::new(AtomicBool::new(false)),
+            lock_background: Color::new(0.0, 0.0, 0.0, 1.0),
+            lock_size: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -62,6 +400,7 @@ impl AppInner {
         configured_surfaces: Arc<Mutex<usize>>,
         tx: Sender<Cmd>,
         output: Option<&wl_output::WlOutput>,
+        placement: Placement,
     ) -> (
         Main<wl_surface::WlSurface>,
         Main<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
@@ -90,18 +429,244 @@ impl AppInner {
             _ => unreachable!(),
         });
 
-        shell_surface.set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive);
+        let interactivity = match placement {
+            Placement::Center(interactivity) => interactivity,
+            Placement::Expanded(hot_corner) | Placement::Collapsed(hot_corner) => {
+                let margin = hot_corner.margin as i32;
+                shell_surface.set_anchor(hot_corner.anchor);
+                shell_surface.set_margin(margin, margin, margin, margin);
+                match placement {
+                    Placement::Collapsed(_) => zwlr_layer_surface_v1::KeyboardInteractivity::None,
+                    _ => hot_corner.interactivity,
+                }
+            }
+        };
+        // On-demand focus needs version 4, before which only exclusive
+        // focus or none at all is available.
+        let interactivity = match interactivity {
+            zwlr_layer_surface_v1::KeyboardInteractivity::OnDemand
+                if shell.as_ref().version() < 4 =>
+            {
+                zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive
+            }
+            interactivity => interactivity,
+        };
+        shell_surface.set_keyboard_interactivity(interactivity);
         shell_surface.set_size(1, 1);
         surface.set_buffer_scale(scale as i32);
         surface.commit();
         (surface, shell_surface)
     }
 
-    fn outputs_changed(&mut self) {
-        let shell = match self.shell {
-            Some(ref shell) => shell.to_owned(),
-            None => return,
+    fn fill(&self, surface: &Main<wl_surface::WlSurface>, color: Color) -> Option<Fill> {
+        let pool = match MemPool::new(self.shm.clone()?, |_| {}) {
+            Ok(pool) => pool,
+            Err(err) => {
+                eprintln!("Unable to allocate the backdrop: {}", err);
+                return None;
+            }
         };
+        let viewport = self
+            .viewporter
+            .as_ref()
+            .map(|viewporter| viewporter.get_viewport(surface));
+        Some(Fill {
+            surface: surface.clone(),
+            pool,
+            viewport,
+            color,
+        })
+    }
+
+    // A surface covering the output below the dashboard, filled with the
+    // color.
+    fn add_backdrop(
+        &self,
+        color: Color,
+        output: Option<&wl_output::WlOutput>,
+    ) -> Option<(
+        Main<wl_surface::WlSurface>,
+        Main<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
+    )> {
+        let compositor = self.compositor.as_ref()?;
+        let shell = self.shell.as_ref()?;
+        let surface = compositor.create_surface();
+        let mut fill = self.fill(&surface, color)?;
+        // The namespace lets compositors that blur layers by rule blur it
+        let shell_surface = shell.get_layer_surface(
+            &surface,
+            output,
+            zwlr_layer_shell_v1::Layer::Top,
+            "wldash-backdrop".to_string(),
+        );
+        shell_surface.quick_assign(move |layer, event, _| {
+            if let zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } = event
+            {
+                layer.ack_configure(serial);
+                fill.fill(width, height);
+            }
+        });
+
+        shell_surface.set_anchor(
+            zwlr_layer_surface_v1::Anchor::Top
+                | zwlr_layer_surface_v1::Anchor::Bottom
+                | zwlr_layer_surface_v1::Anchor::Left
+                | zwlr_layer_surface_v1::Anchor::Right,
+        );
+        // Covers panels too, rather than keeping clear of them
+        shell_surface.set_exclusive_zone(-1);
+        shell_surface
+            .set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::None);
+        shell_surface.set_size(0, 0);
+        surface.commit();
+        Some((surface, shell_surface))
+    }
+
+    // A lock surface for every output. The widgets are shown on the first,
+    // at the size the compositor gives it, and the others are filled with
+    // the background.
+    fn add_lock_surfaces(
+        &mut self,
+        compositor: &wl_compositor::WlCompositor,
+        lock: &ext_session_lock_v1::ExtSessionLockV1,
+    ) {
+        *self.lock_size.lock().unwrap() = None;
+        for (idx, (_, output)) in self.outputs.iter().enumerate() {
+            let surface = compositor.create_surface();
+            let lock_surface = lock.get_lock_surface(&surface, output);
+            if idx > 0 {
+                let mut fill = match self.fill(&surface, self.lock_background) {
+                    Some(fill) => fill,
+                    None => continue,
+                };
+                lock_surface.quick_assign(move |lock_surface, event, _| {
+                    let ext_session_lock_surface_v1::Event::Configure {
+                        serial,
+                        width,
+                        height,
+                    } = event;
+                    lock_surface.ack_configure(serial);
+                    fill.fill(width, height);
+                });
+                self.backdrops.push(surface);
+                self.backdrop_shells.push(ShellSurface::Lock(lock_surface));
+                continue;
+            }
+
+            let configured = AtomicBool::new(false);
+            let configured_surfaces = self.configured_surfaces.clone();
+            let lock_size = self.lock_size.clone();
+            let tx = self.draw_tx.clone();
+            let scale = self.scale;
+            lock_surface.quick_assign(move |lock_surface, event, _| {
+                let ext_session_lock_surface_v1::Event::Configure {
+                    serial,
+                    width,
+                    height,
+                } = event;
+                lock_surface.ack_configure(serial);
+                *lock_size.lock().unwrap() = Some((width * scale, height * scale));
+                if !configured.swap(true, Ordering::SeqCst) {
+                    *configured_surfaces.lock().unwrap() += 1;
+                }
+                tx.send(Cmd::ForceDraw).unwrap();
+            });
+            // Unlike layer surfaces, nothing is committed before the
+            // first configure
+            surface.set_buffer_scale(scale as i32);
+            self.surfaces.push(surface);
+            self.shell_surfaces.push(ShellSurface::Lock(lock_surface));
+        }
+    }
+
+    // The dashboard as a window of its own, which the compositor places and
+    // decorates like any other. Closing it closes the dashboard. The window
+    // asks for the size and maximized state it last had, which are saved
+    // whenever the compositor changes them.
+    fn add_window(
+        &mut self,
+        compositor: &wl_compositor::WlCompositor,
+        wm_base: &xdg_wm_base::XdgWmBase,
+    ) {
+        let surface = compositor.create_surface();
+        let xdg_surface = wm_base.get_xdg_surface(&surface);
+        let toplevel = xdg_surface.get_toplevel();
+        toplevel.set_title("wldash".to_string());
+        toplevel.set_app_id("wldash".to_string());
+
+        let scale = self.scale;
+        let mut geometry = WindowGeometry::load();
+        if geometry.maximized {
+            toplevel.set_maximized();
+        }
+        if let Some((width, height)) = geometry.size {
+            xdg_surface.set_window_geometry(0, 0, width as i32, height as i32);
+        }
+        let window_size = self.window_size.clone();
+        *window_size.lock().unwrap() = geometry.size.map(|(w, h)| (w * scale, h * scale));
+
+        let configured = AtomicBool::new(false);
+        let configured_surfaces = self.configured_surfaces.clone();
+        let tx = self.draw_tx.clone();
+        xdg_surface.quick_assign(move |xdg_surface, event, _| {
+            if let xdg_surface::Event::Configure { serial } = event {
+                xdg_surface.ack_configure(serial);
+                if !configured.swap(true, Ordering::SeqCst) {
+                    *configured_surfaces.lock().unwrap() += 1;
+                    tx.send(Cmd::ForceDraw).unwrap();
+                } else {
+                    // The window may have been resized
+                    tx.send(Cmd::Draw).unwrap();
+                }
+            }
+        });
+        // Without a size from the compositor, the window keeps the size it
+        // had when not maximized, or otherwise that of the widgets. Nothing
+        // is saved while the window is being resized.
+        let tx = self.draw_tx.clone();
+        let mut saved = geometry;
+        toplevel.quick_assign(move |_, event, _| match event {
+            xdg_toplevel::Event::Configure {
+                width,
+                height,
+                states,
+            } => {
+                let states = protocol::toplevel_states(&states);
+                let size = match (width, height) {
+                    (width, height) if width > 0 && height > 0 => {
+                        Some((width as u32, height as u32))
+                    }
+                    _ => geometry.size,
+                };
+                *window_size.lock().unwrap() = size.map(|(w, h)| (w * scale, h * scale));
+                if !states.maximized {
+                    geometry.size = size;
+                }
+                geometry.maximized = states.maximized;
+                if !states.resizing && geometry != saved {
+                    if let Err(err) = geometry.save() {
+                        eprintln!("Unable to save the window size: {}", err);
+                    }
+                    saved = geometry;
+                }
+            }
+            xdg_toplevel::Event::Close => {
+                let _ = tx.send(Cmd::Exit);
+            }
+            _ => (),
+        });
+        surface.set_buffer_scale(self.scale as i32);
+        surface.commit();
+        self.track_entered(&surface);
+        self.surfaces = vec![surface];
+        self.shell_surfaces = vec![ShellSurface::Window(xdg_surface, toplevel)];
+    }
+
+    fn outputs_changed(&mut self) {
         let compositor = match self.compositor {
             Some(ref c) => c.to_owned(),
             None => return,
@@ -113,21 +678,67 @@ impl AppInner {
         for surface in self.surfaces.iter() {
             surface.destroy();
         }
+        for shell_surface in self.backdrop_shells.iter() {
+            shell_surface.destroy();
+        }
+        for surface in self.backdrops.iter() {
+            surface.destroy();
+        }
+        self.surfaces = Vec::new();
+        self.shell_surfaces = Vec::new();
+        self.backdrops = Vec::new();
+        self.backdrop_shells = Vec::new();
+        self.generation += 1;
+        self.focused = 0;
 
         self.configured_surfaces = Arc::new(Mutex::new(0));
 
-        if !self.visible {
-            self.surfaces = Vec::new();
-            self.shell_surfaces = Vec::new();
+        if let Some(lock) = self.session_lock.clone() {
+            self.add_lock_surfaces(&compositor, &lock);
             return;
         }
 
-        match self.output_mode {
-            OutputMode::Active => {
-                if !self.shell_surfaces.is_empty() {
-                    return;
+        let shell = match (&self.shell, &self.wm_base) {
+            (Some(shell), _) if !self.windowed => shell.to_owned(),
+            (_, Some(wm_base)) => {
+                // A hidden window is simply gone, without a hot corner
+                if self.visible {
+                    self.add_window(&compositor, &wm_base.to_owned());
+                    self.draw_tx.send(Cmd::ForceDraw).unwrap();
                 }
+                return;
+            }
+            _ => return,
+        };
+
+        let placement = match (self.visible, self.hot_corner) {
+            (true, None) => Placement::Center(self.interactivity),
+            (true, Some(hot_corner)) => Placement::Expanded(hot_corner),
+            (false, Some(hot_corner)) => Placement::Collapsed(hot_corner),
+            (false, None) => return,
+        };
 
+        // Only the open dashboard has a backdrop
+        if let (Some(color), Placement::Center(_) | Placement::Expanded(_)) =
+            (self.backdrop, placement)
+        {
+            let outputs: Vec<Option<Main<wl_output::WlOutput>>> = match self.output_mode {
+                OutputMode::Active => vec![None],
+                OutputMode::All => self.outputs.iter().map(|(_, o)| Some(o.clone())).collect(),
+            };
+            for output in outputs {
+                if let Some((surface, shell_surface)) =
+                    self.add_backdrop(color, output.as_deref().map(|o| &**o))
+                {
+                    self.backdrops.push(surface);
+                    self.backdrop_shells
+                        .push(ShellSurface::Layer(shell_surface));
+                }
+            }
+        }
+
+        match self.output_mode {
+            OutputMode::Active => {
                 let (surface, shell_surface) = AppInner::add_shell_surface(
                     &compositor,
                     &shell,
@@ -135,9 +746,11 @@ impl AppInner {
                     self.configured_surfaces.clone(),
                     self.draw_tx.clone(),
                     None,
+                    placement,
                 );
+                self.track_entered(&surface);
                 self.surfaces = vec![surface];
-                self.shell_surfaces = vec![shell_surface];
+                self.shell_surfaces = vec![ShellSurface::Layer(shell_surface)];
             }
             OutputMode::All => {
                 let mut surfaces = Vec::new();
@@ -150,9 +763,10 @@ impl AppInner {
                         self.configured_surfaces.clone(),
                         self.draw_tx.clone(),
                         Some(&output.1),
+                        placement,
                     );
                     surfaces.push(surface);
-                    shell_surfaces.push(shell_surface);
+                    shell_surfaces.push(ShellSurface::Layer(shell_surface));
                 }
                 self.surfaces = surfaces;
                 self.shell_surfaces = shell_surfaces;
@@ -161,6 +775,55 @@ impl AppInner {
         self.draw_tx.send(Cmd::ForceDraw).unwrap();
     }
 
+    // The compositor picks the output in the active output mode, so the
+    // surface has to be shown before its size is known.
+    fn track_entered(&self, surface: &Main<wl_surface::WlSurface>) {
+        let outputs = self.outputs.clone();
+        let entered = self.entered.clone();
+        let tx = self.draw_tx.clone();
+        surface.quick_assign(move |_, event, _| {
+            if let wl_surface::Event::Enter { output } = event {
+                let id = outputs
+                    .iter()
+                    .find(|(_, o)| o.as_ref().equals(output.as_ref()))
+                    .map(|(id, _)| *id);
+                let mut entered = entered.lock().unwrap();
+                if id.is_some() && id != *entered {
+                    *entered = id;
+                    tx.send(Cmd::Resize).unwrap();
+                }
+            }
+        });
+    }
+
+    fn set_output_size(&mut self, id: u32, size: (u32, u32)) {
+        match self
+            .output_sizes
+            .iter_mut()
+            .find(|(output_id, _)| *output_id == id)
+        {
+            Some((_, old)) if *old == size => return,
+            Some((_, old)) => *old = size,
+            None => self.output_sizes.push((id, size)),
+        }
+        self.draw_tx.send(Cmd::Resize).unwrap();
+    }
+
+    // The size the widgets are laid out for. The surfaces of all outputs
+    // show the same widgets, so those have to fit the smallest output.
+    fn output_size(&self) -> Option<(u32, u32)> {
+        let entered = match self.output_mode {
+            OutputMode::Active => *self.entered.lock().unwrap(),
+            OutputMode::All => None,
+        };
+        self.output_sizes
+            .iter()
+            .filter(|(id, _)| entered.is_none_or(|entered| *id == entered))
+            .map(|(_, size)| *size)
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1)))
+            .map(|(width, height)| (width / self.scale, height / self.scale))
+    }
+
     fn add_output(&mut self, id: u32, output: Main<wl_output::WlOutput>) {
         self.outputs.push((id, output));
         self.outputs_changed();
@@ -179,7 +842,30 @@ impl AppInner {
                 output.1.release()
             }
             self.outputs = new_outputs;
+            self.output_sizes.retain(|(output_id, _)| *output_id != id);
+            let mut entered = self.entered.lock().unwrap();
+            if *entered == Some(id) {
+                *entered = None;
+            }
+            drop(entered);
             self.outputs_changed();
+            self.draw_tx.send(Cmd::Resize).unwrap();
+        }
+    }
+
+    // Moves the focus to the given surface, which needs the widgets drawn
+    // there in full. Returns true if the focus changed.
+    fn focus(&mut self, surface: &wl_surface::WlSurface) -> bool {
+        match self
+            .surfaces
+            .iter()
+            .position(|s| s.as_ref().equals(surface.as_ref()))
+        {
+            Some(idx) if idx != self.focused => {
+                self.focused = idx;
+                true
+            }
+            _ => false,
         }
     }
 
@@ -190,6 +876,21 @@ impl AppInner {
     fn set_shell(&mut self, shell: Option<Main<zwlr_layer_shell_v1::ZwlrLayerShellV1>>) {
         self.shell = shell
     }
+
+    fn is_backdrop(&self, surface: &wl_surface::WlSurface) -> bool {
+        self.backdrops
+            .iter()
+            .any(|s| s.as_ref().equals(surface.as_ref()))
+    }
+}
+
+// The surface of the on-screen messages, which passes input through
+struct OsdSurface {
+    surface: Main<wl_surface::WlSurface>,
+    shell_surface: Main<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
+    pools: DoubleMemPool,
+    configured: Arc<AtomicBool>,
+    last_dim: (u32, u32),
 }
 
 struct AppKeyboard {
@@ -200,16 +901,24 @@ struct AppKeyboard {
 }
 
 pub struct App<'a> {
-    pools: DoubleMemPool,
+    shm: Main<wl_shm::WlShm>,
+    surfaces: Vec<SurfaceBuffers>,
+    generation: usize,
     display: Display,
     event_queue: EventQueue,
     cmd_queue: Arc<Mutex<VecDeque<Cmd>>>,
     widget: Option<Box<dyn Widget + Send + 'a>>,
     bg: Color,
     inner: Arc<Mutex<AppInner>>,
-    last_damage: Option<Vec<(i32, i32, i32, i32)>>,
-    last_dim: (u32, u32),
     keyboard: Arc<Mutex<AppKeyboard>>,
+    seat: Main<wl_seat::WlSeat>,
+    activation: Option<Main<xdg_activation_v1::XdgActivationV1>>,
+    last_serial: Arc<Mutex<u32>>,
+    // Whether an overlay was drawn in the last frame, covering other widgets
+    overlay: bool,
+    osd: Option<OsdSurface>,
+    // Cleared once reading from or writing to the compositor failed
+    connected: bool,
 }
 
 impl<'a> App<'a> {
@@ -222,7 +931,8 @@ impl<'a> App<'a> {
         let inner = self.inner.lock().unwrap();
         let time = Local::now().naive_local();
 
-        if !inner.visible
+        let collapsed = !inner.visible && inner.hot_corner.is_some();
+        if !(inner.visible || collapsed)
             || inner.shell_surfaces.len() != *inner.configured_surfaces.lock().unwrap()
             || inner.surfaces.is_empty()
         {
@@ -230,17 +940,54 @@ impl<'a> App<'a> {
             return Ok(());
         }
 
-        let (last, pool) = match self.pools.pool() {
-            Some((last, pool)) => (last, pool),
-            None => {
-                self.pools.never_mind();
-                return Ok(());
-            }
+        // The surfaces are replaced when the outputs change, which starts
+        // their buffers and damage history over.
+        if self.generation != inner.generation || self.surfaces.len() != inner.surfaces.len() {
+            self.surfaces = inner
+                .surfaces
+                .iter()
+                .map(|_| SurfaceBuffers::new(self.shm.clone()))
+                .collect::<Result<_, _>>()?;
+            self.generation = inner.generation;
+        }
+
+        let start = Instant::now();
+        let focused = inner.focused.min(self.surfaces.len() - 1);
+        let (before, rest) = self.surfaces.split_at_mut(focused);
+        let (primary, after) = rest.split_first_mut().unwrap();
+
+        // The widgets are drawn on the focused surface, and copied from
+        // there to the other surfaces.
+        let Frame {
+            pool,
+            newest,
+            blank,
+        } = match primary.pools.pool() {
+            Some(frame) => frame,
+            None => return Ok(()),
         };
+        let in_place = newest.is_none();
 
-        let size = widget.size();
-        let size_changed = self.last_dim != size;
-        let force = force | size_changed;
+        let lock_size = *inner.lock_size.lock().unwrap();
+        let window_size = match inner.windowed {
+            true => *inner.window_size.lock().unwrap(),
+            false => None,
+        };
+        let widget_size = widget.size();
+        let size = match (lock_size.or(window_size), inner.hot_corner) {
+            // The widgets fill the lock surface or window, and place
+            // themselves on it
+            (Some(size), _) => size,
+            (None, Some(hot_corner)) if collapsed => {
+                (hot_corner.size * inner.scale, hot_corner.size * inner.scale)
+            }
+            _ => widget_size,
+        };
+        let full = (0, 0, size.0 as i32, size.1 as i32);
+        let size_changed = primary.last_dim != size;
+        // A trimmed pool redrawn in place has nothing left to build on, and
+        // what an overlay covered is restored by drawing it all again.
+        let force = force | size_changed | (blank && in_place) | self.overlay;
 
         // resize the pool if relevant
         pool.resize((4 * size.0 * size.1) as usize)
@@ -248,17 +995,19 @@ impl<'a> App<'a> {
         let mmap = pool.mmap();
         let mut buf = Buffer::new(mmap, size);
 
-        // Copy old damage
-        let force = match (force, &self.last_damage) {
+        // Copy the damage the pool missed from the most recent frame
+        let force = match (force, &primary.last_damage) {
             (false, Some(d)) => {
-                let lastmmap = last.mmap();
-                let last = Buffer::new(lastmmap, size);
+                if let Some(last) = newest {
+                    let last = Buffer::new(last.mmap(), size);
+                    let d = if blank { &vec![full] } else { d };
 
-                if cfg!(feature = "damage_debug") {
-                    buf.memset(&Color::new(0.5, 0.75, 0.75, 1.0));
-                }
-                for d in d {
-                    last.copy_to(&mut buf, d.clone());
+                    if cfg!(feature = "damage_debug") {
+                        buf.memset(&Color::new(0.5, 0.75, 0.75, 1.0));
+                    }
+                    for d in d {
+                        last.copy_to(&mut buf, *d);
+                    }
                 }
                 false
             }
@@ -268,78 +1017,431 @@ impl<'a> App<'a> {
         if force {
             buf.memset(&self.bg);
         }
-        let report = widget.draw(
-            &mut DrawContext {
+        let mut report = if collapsed {
+            buf.memset(&self.bg);
+            DrawReport {
+                width: size.0,
+                height: size.1,
+                damage: Vec::new(),
+                full_damage: true,
+            }
+        } else {
+            widget.draw(
+                &mut DrawContext {
+                    buf: &mut buf,
+                    bg: &self.bg,
+                    time,
+                    force,
+                },
+                (0, 0),
+                size,
+            )?
+        };
+
+        // Overlays are drawn last, above everything else
+        self.overlay = !collapsed && widget.has_overlay();
+        if self.overlay {
+            widget.draw_overlay(&mut DrawContext {
                 buf: &mut buf,
                 bg: &self.bg,
                 time,
                 force,
-            },
-            (0, 0),
-            size,
-        )?;
+            })?;
+            report.full_damage = true;
+        }
 
         mmap.flush().unwrap();
 
         if !force && !report.full_damage && report.damage.is_empty() {
             // Nothing to do
-            self.pools.never_mind();
+            primary.pools.never_mind();
             return Ok(());
         }
 
-        // get a buffer and attach it
+        let damage = if force || report.full_damage {
+            vec![full]
+        } else {
+            report.damage
+        };
+        let buffer_size = (report.width as i32, report.height as i32);
         let new_buffer = pool.buffer(
             0,
-            report.width as i32,
-            report.height as i32,
+            buffer_size.0,
+            buffer_size.1,
             4 * size.0 as i32,
             wl_shm::Format::Argb8888,
         );
-        if size_changed {
-            for shell_surface in inner.shell_surfaces.iter() {
-                shell_surface.set_size(size.0 / inner.scale, size.1 / inner.scale);
+        let mut buffers = vec![(focused, new_buffer, damage.clone(), size_changed)];
+
+        let mut focused_only = Vec::new();
+        if !collapsed {
+            widget.focused_only(&mut focused_only);
+        }
+        let source = Buffer::new(pool.mmap(), size);
+        let others = before.iter_mut().enumerate().chain(
+            after
+                .iter_mut()
+                .enumerate()
+                .map(|(idx, s)| (focused + 1 + idx, s)),
+        );
+        for (idx, target) in others {
+            let size_changed = target.last_dim != size;
+            if let Some((buffer, damage)) =
+                target.mirror(&source, size, buffer_size, &damage, &focused_only, &self.bg)
+            {
+                buffers.push((idx, buffer, damage, size_changed));
             }
         }
-        for surface in inner.surfaces.iter() {
-            surface.attach(Some(&new_buffer), 0, 0);
-            if cfg!(feature = "damage_debug") || force || report.full_damage {
+        primary.drawn(&damage, in_place, size);
+
+        for (idx, buffer, damage, size_changed) in buffers {
+            if size_changed {
+                let scale = inner.scale;
+                inner.shell_surfaces[idx].set_size(
+                    (size.0 / scale, size.1 / scale),
+                    (widget_size.0 / scale, widget_size.1 / scale),
+                );
+            }
+            let surface = &inner.surfaces[idx];
+            surface.attach(Some(&buffer), 0, 0);
+            if cfg!(feature = "damage_debug") {
                 surface.damage_buffer(0, 0, size.0 as i32, size.1 as i32);
             } else {
-                for d in report.damage.iter() {
+                for d in damage.iter() {
                     surface.damage_buffer(d.0, d.1, d.2, d.3);
                 }
             }
             surface.commit();
         }
-        self.last_damage = if force || report.full_damage {
-            Some(vec![(0, 0, size.0 as i32, size.1 as i32)])
-        } else {
-            Some(report.damage)
+
+        let allocated = self
+            .surfaces
+            .iter()
+            .map(|s| s.pools.allocated())
+            .sum::<usize>();
+        metrics::record_frame(start.elapsed(), allocated as u64);
+        Ok(())
+    }
+
+    fn add_osd_surface(&self, osd: &Osd) -> Option<OsdSurface> {
+        let inner = self.inner.lock().unwrap();
+        let compositor = inner.compositor.as_ref()?;
+        let shell = inner.shell.as_ref()?;
+        let pools = match DoubleMemPool::new(self.shm.clone()) {
+            Ok(pools) => pools,
+            Err(err) => {
+                eprintln!("Unable to allocate the on-screen display: {}", err);
+                return None;
+            }
         };
-        self.last_dim = size;
+
+        let surface = compositor.create_surface();
+        let shell_surface = shell.get_layer_surface(
+            &surface,
+            None,
+            zwlr_layer_shell_v1::Layer::Overlay,
+            "wldash-osd".to_string(),
+        );
+        let configured = Arc::new(AtomicBool::new(false));
+        let osd_configured = configured.clone();
+        let tx = inner.draw_tx.clone();
+        shell_surface.quick_assign(move |layer, event, _| {
+            if let zwlr_layer_surface_v1::Event::Configure { serial, .. } = event {
+                layer.ack_configure(serial);
+                if !osd_configured.swap(true, Ordering::SeqCst) {
+                    let _ = tx.send(Cmd::OsdDraw);
+                }
+            }
+        });
+
+        let style = osd.style();
+        let margin = style.margin as i32;
+        shell_surface.set_anchor(style.anchor.to_anchor());
+        shell_surface.set_margin(margin, margin, margin, margin);
+        shell_surface
+            .set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::None);
+        shell_surface.set_size(1, 1);
+        // An empty input region lets the pointer through
+        let region = compositor.create_region();
+        surface.set_input_region(Some(&region));
+        region.destroy();
+        surface.set_buffer_scale(inner.scale as i32);
+        surface.commit();
+        Some(OsdSurface {
+            surface,
+            shell_surface,
+            pools,
+            configured,
+            last_dim: (0, 0),
+        })
+    }
+
+    /// Shows the message of the on-screen display on a surface of its own,
+    /// or removes the surface once there is no message to show.
+    pub fn draw_osd(&mut self, osd: &mut Osd) -> Result<(), ::std::io::Error> {
+        if !osd.is_shown() {
+            if let Some(surface) = self.osd.take() {
+                surface.shell_surface.destroy();
+                surface.surface.destroy();
+            }
+            return Ok(());
+        }
+        let scale = self.inner.lock().unwrap().scale;
+        let target = match &mut self.osd {
+            Some(target) => target,
+            None => {
+                // Drawn once configured
+                self.osd = self.add_osd_surface(osd);
+                return Ok(());
+            }
+        };
+        if !target.configured.load(Ordering::SeqCst) || !osd.is_changed() {
+            return Ok(());
+        }
+        let Frame { pool, .. } = match target.pools.pool() {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        let size = osd.size();
+        pool.resize((4 * size.0 * size.1) as usize)?;
+        osd.draw(&mut Buffer::new(pool.mmap(), size))?;
+        pool.mmap().flush()?;
+        let buffer = pool.buffer(
+            0,
+            size.0 as i32,
+            size.1 as i32,
+            4 * size.0 as i32,
+            wl_shm::Format::Argb8888,
+        );
+        if target.last_dim != size {
+            target
+                .shell_surface
+                .set_size(size.0 / scale, size.1 / scale);
+            target.last_dim = size;
+        }
+        target.surface.attach(Some(&buffer), 0, 0);
+        target
+            .surface
+            .damage_buffer(0, 0, size.0 as i32, size.1 as i32);
+        target.surface.commit();
         Ok(())
     }
 
     pub fn hide(&mut self) {
         let mut inner = self.inner.lock().unwrap();
         inner.visible = false;
-        self.last_dim = (0, 0);
         inner.outputs_changed();
+        // The surfaces are gone, and with them the need for their buffers
+        self.surfaces.clear();
     }
 
     pub fn show(&mut self) {
         let mut inner = self.inner.lock().unwrap();
         inner.visible = true;
-        self.last_dim = (0, 0);
         inner.outputs_changed();
     }
 
+    /// Renders the widgets into an offscreen buffer and writes it as a PNG.
+    /// The widgets consider themselves drawn afterwards, so the surfaces
+    /// need a forced redraw.
+    pub fn export(&mut self, path: &Path) -> Result<(), ::std::io::Error> {
+        let widget = match self.widget {
+            Some(ref mut widget) => widget,
+            None => return Ok(()),
+        };
+        let size = widget.size();
+        if size.0 == 0 || size.1 == 0 {
+            return Err(::std::io::Error::other("nothing to export"));
+        }
+
+        let mut mmap = MmapMut::map_anon((4 * size.0 * size.1) as usize)?;
+        let mut buf = Buffer::new(&mut mmap, size);
+        buf.memset(&self.bg);
+        widget.draw(
+            &mut DrawContext {
+                buf: &mut buf,
+                bg: &self.bg,
+                time: Local::now().naive_local(),
+                force: true,
+            },
+            (0, 0),
+            size,
+        )?;
+        png::write_argb8888(path, size, &mmap)
+    }
+
+    pub fn set_background(&mut self, bg: Color) {
+        self.bg = bg;
+    }
+
+    pub fn set_keyboard_interactivity(
+        &mut self,
+        interactivity: zwlr_layer_surface_v1::KeyboardInteractivity,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.interactivity = interactivity;
+        inner.outputs_changed();
+    }
+
+    pub fn set_hot_corner(&mut self, hot_corner: Option<HotCorner>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.hot_corner = hot_corner;
+        inner.outputs_changed();
+    }
+
+    /// Shows the color behind the dashboard while it is open, covering the
+    /// rest of the output. Clicking it dismisses the dashboard.
+    pub fn set_backdrop(&mut self, backdrop: Option<Color>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.backdrop = backdrop;
+        inner.outputs_changed();
+    }
+
+    /// Shows the dashboard as a window rather than on the layer shell, or
+    /// the other way around. The surfaces are made anew while the widgets
+    /// are kept as they are.
+    pub fn set_windowed(&mut self, windowed: bool) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+        match windowed {
+            true if inner.wm_base.is_none() => return Err(missing_global("xdg_wm_base")),
+            false if inner.shell.is_none() => return Err(missing_global("zwlr_layer_shell_v1")),
+            _ => (),
+        }
+        if inner.windowed != windowed {
+            inner.windowed = windowed;
+            inner.outputs_changed();
+        }
+        Ok(())
+    }
+
+    pub fn is_windowed(&self) -> bool {
+        self.inner.lock().unwrap().windowed
+    }
+
+    /// Locks the session, showing the widgets on lock surfaces in place of
+    /// the layer surfaces until unlocked. The compositor may refuse, such as
+    /// while another client holds the lock, which exits.
+    pub fn lock_session(&mut self) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let lock = inner
+            .lock_manager
+            .as_ref()
+            .ok_or_else(|| missing_global("ext_session_lock_manager_v1"))?
+            .lock();
+        let locked = inner.locked.clone();
+        let tx = inner.draw_tx.clone();
+        lock.quick_assign(move |_, event, _| match event {
+            ext_session_lock_v1::Event::Locked => locked.store(true, Ordering::SeqCst),
+            ext_session_lock_v1::Event::Finished => {
+                if !locked.load(Ordering::SeqCst) {
+                    eprintln!("The compositor refused to lock the session");
+                }
+                let _ = tx.send(Cmd::Exit);
+            }
+        });
+        inner.session_lock = Some(lock);
+        inner.lock_background = self.bg;
+        inner.outputs_changed();
+        Ok(())
+    }
+
+    /// Unlocks the session and removes the lock surfaces, waiting for the
+    /// compositor to have done so.
+    pub fn unlock_session(&mut self) {
+        let mut inner = self.inner.lock().unwrap();
+        let lock = match inner.session_lock.take() {
+            Some(lock) => lock,
+            None => return,
+        };
+        // Unlocking is only allowed once the compositor locked the session
+        if inner.locked.load(Ordering::SeqCst) {
+            lock.unlock_and_destroy();
+        } else {
+            lock.destroy();
+        }
+        *inner.lock_size.lock().unwrap() = None;
+        inner.visible = false;
+        inner.outputs_changed();
+        drop(inner);
+        self.surfaces.clear();
+        if let Err(err) = self.event_queue.sync_roundtrip(&mut (), |_, _, _| {}) {
+            eprintln!("Unable to unlock the session: {}", err);
+        }
+    }
+
+    /// Requests a fresh activation token for a program about to be launched,
+    /// tied to the input event that triggered the launch.
+    pub fn activation_token(&mut self) -> Option<String> {
+        let activation = self.activation.as_ref()?;
+        let done = Arc::new(Mutex::new(None));
+        let token = activation.get_activation_token();
+        let done_clone = done.clone();
+        token.quick_assign(move |_, event, _| {
+            if let xdg_activation_token_v1::Event::Done { token } = event {
+                *done_clone.lock().unwrap() = Some(token);
+            }
+        });
+        token.set_serial(*self.last_serial.lock().unwrap(), &self.seat);
+        let inner = self.inner.lock().unwrap();
+        if let Some(surface) = inner.surfaces.get(inner.focused) {
+            token.set_surface(surface);
+        }
+        drop(inner);
+        token.commit();
+
+        // The compositor answers the commit right away, so a roundtrip is
+        // enough to receive the token.
+        if let Err(err) = self.event_queue.sync_roundtrip(&mut (), |_, _, _| {}) {
+            eprintln!("Unable to get activation token: {}", err);
+        }
+        token.destroy();
+        let token = done.lock().unwrap().take();
+        token
+    }
+
+    /// The size of the output the dashboard is laid out for, in surface
+    /// pixels, once the compositor has told.
+    pub fn output_size(&self) -> Option<(u32, u32)> {
+        self.inner.lock().unwrap().output_size()
+    }
+
     pub fn cmd_queue(&self) -> Arc<Mutex<VecDeque<Cmd>>> {
         self.cmd_queue.clone()
     }
 
     pub fn flush_display(&mut self) {
-        self.display.flush().expect("unable to flush display");
+        if let Err(err) = self.display.flush() {
+            if err.kind() != ErrorKind::WouldBlock {
+                eprintln!("Unable to flush the wayland socket: {}", err);
+                self.connected = false;
+            }
+        }
+    }
+
+    /// Reads the events from the compositor, if any, and dispatches them.
+    pub fn dispatch_events(&mut self) {
+        if let Some(guard) = self.event_queue.prepare_read() {
+            if let Err(e) = guard.read_events() {
+                if e.kind() != ErrorKind::WouldBlock {
+                    eprintln!(
+                        "Error while trying to read from the wayland socket: {:?}",
+                        e
+                    );
+                    self.connected = false;
+                }
+            }
+        }
+
+        if let Err(e) = self.event_queue.dispatch_pending(&mut (), |_, _, _| {}) {
+            eprintln!("Failed to dispatch all messages: {}", e);
+            self.connected = false;
+        }
+    }
+
+    /// Whether the connection to the compositor is still there. Once lost,
+    /// the app has to be connected anew.
+    pub fn is_connected(&self) -> bool {
+        self.connected
     }
 
     pub fn event_queue(&mut self) -> &mut EventQueue {
@@ -355,6 +1457,18 @@ impl<'a> App<'a> {
         self.redraw(true)
     }
 
+    pub fn take_widget(&mut self) -> Option<Box<dyn Widget + Send + 'a>> {
+        self.widget.take()
+    }
+
+    /// Swaps the widget tree for another, returning the one shown before.
+    pub fn replace_widget(
+        &mut self,
+        w: Box<dyn Widget + Send + 'a>,
+    ) -> Option<Box<dyn Widget + Send + 'a>> {
+        self.widget.replace(w)
+    }
+
     pub fn set_keyboard_repeat(&mut self, ctx: &mut WaitContext) {
         let kbd = self.keyboard.lock().unwrap();
         if let Some(t) = kbd.next {
@@ -375,16 +1489,25 @@ impl<'a> App<'a> {
         None
     }
 
-    pub fn new(tx: Sender<Cmd>, output_mode: OutputMode, bg: Color, scale: u32) -> App<'a> {
+    /// Connects to the compositor, with input and other events going to the
+    /// command queue.
+    pub fn connect(
+        tx: Sender<Cmd>,
+        cmd_queue: Arc<Mutex<VecDeque<Cmd>>>,
+        output_mode: OutputMode,
+        bg: Color,
+        scale: u32,
+    ) -> Result<App<'a>, Error> {
+        let scroll = ScrollState::new(tx.clone());
+        let drop_tx = tx.clone();
         let inner = Arc::new(Mutex::new(AppInner::new(tx, output_mode, scale)));
 
         //
         // Set up modules
         //
 
-        let cmd_queue = Arc::new(Mutex::new(VecDeque::new()));
-
-        let display = Display::connect_to_env().unwrap();
+        let display = Display::connect_to_env()
+            .map_err(|err| Error::new(ErrorKind::NotConnected, err.to_string()))?;
         let mut event_queue = display.create_event_queue();
         let display_wrapper = (*display).clone().attach(event_queue.token());
 
@@ -400,7 +1523,25 @@ impl<'a> App<'a> {
                     version,
                 } => {
                     if let "wl_output" = &interface[..] {
-                        let output = registry.bind(std::cmp::min(version, 3), id);
+                        let output: Main<wl_output::WlOutput> =
+                            registry.bind(std::cmp::min(version, 3), id);
+                        let inner_output = inner_global.clone();
+                        output.quick_assign(move |_, event, _| {
+                            if let wl_output::Event::Mode {
+                                flags,
+                                width,
+                                height,
+                                ..
+                            } = event
+                            {
+                                if protocol::is_current_mode(flags) {
+                                    inner_output
+                                        .lock()
+                                        .unwrap()
+                                        .set_output_size(id, (width as u32, height as u32));
+                                }
+                            }
+                        });
                         inner_global.lock().unwrap().add_output(id, output);
                     }
                 }
@@ -413,28 +1554,38 @@ impl<'a> App<'a> {
 
         // double sync to retrieve the global list
         // and the globals metadata
-        event_queue.sync_roundtrip(&mut (), |_, _, _| {}).unwrap();
-        event_queue.sync_roundtrip(&mut (), |_, _, _| {}).unwrap();
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {})?;
 
         // wl_compositor
         let compositor = manager
             .instantiate_range(1, 4)
-            .expect("server didn't advertise `wl_compositor`");
+            .map_err(|_| missing_global("wl_compositor"))?;
 
         inner.lock().unwrap().set_compositor(Some(compositor));
 
         // wl_shm
         let shm: Main<wl_shm::WlShm> = manager
             .instantiate_range(1, 1)
-            .expect("server didn't advertise `wl_shm`");
-
-        let pools = DoubleMemPool::new(shm).expect("Failed to create a memory pool !");
+            .map_err(|_| missing_global("wl_shm"))?;
+
+        // Optional, used to stretch the backdrop from a single pixel
+        let viewporter = manager.instantiate_exact(1).ok();
+        // Optional, only needed by the lock screen
+        let lock_manager = manager.instantiate_exact(1).ok();
+        let mut inner_lock = inner.lock().unwrap();
+        inner_lock.shm = Some(shm.clone());
+        inner_lock.viewporter = viewporter;
+        inner_lock.lock_manager = lock_manager;
+        drop(inner_lock);
 
         //
         // Get our seat
         //
-        let seat: Main<wl_seat::WlSeat> = manager.instantiate_range(1, 6).unwrap();
-        event_queue.sync_roundtrip(&mut (), |_, _, _| {}).unwrap();
+        let seat: Main<wl_seat::WlSeat> = manager
+            .instantiate_range(1, 6)
+            .map_err(|_| missing_global("wl_seat"))?;
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {})?;
 
         //
         // Keyboard processing
@@ -457,25 +1608,43 @@ impl<'a> App<'a> {
         }));
 
         let kb2 = keyboard.clone();
+        let last_serial = Arc::new(Mutex::new(0));
+        let kbd_serial = last_serial.clone();
+        let kbd_inner = inner.clone();
 
         map_keyboard(&seat, None, move |event: KbEvent, _, _| match event {
             KbEvent::Key {
+                serial,
                 keysym,
                 utf8,
                 state,
                 ..
             } => {
                 if let KeyState::Pressed = state {
-                    match keysym {
-                        keysyms::XKB_KEY_Escape => kbd_clone.lock().unwrap().push_back(Cmd::Exit),
-                        keysyms::XKB_KEY_c if modifiers_state.lock().unwrap().ctrl => {
-                            kbd_clone.lock().unwrap().push_back(Cmd::Exit)
-                        }
-                        v => {
+                    *kbd_serial.lock().unwrap() = serial;
+                    let modifiers = *modifiers_state.lock().unwrap();
+                    match keybindings::lookup(keybindings::GLOBAL, keysym, modifiers) {
+                        Some(GlobalKey::Hide) => kbd_clone
+                            .lock()
+                            .unwrap()
+                            .push_back(Cmd::Dismiss { focus_lost: false }),
+                        Some(GlobalKey::Help) => kbd_clone
+                            .lock()
+                            .unwrap()
+                            .push_back(Cmd::Action(Action::ToggleHelp)),
+                        Some(GlobalKey::Accessibility) => kbd_clone
+                            .lock()
+                            .unwrap()
+                            .push_back(Cmd::Accessibility(None)),
+                        Some(GlobalKey::Refresh) => kbd_clone
+                            .lock()
+                            .unwrap()
+                            .push_back(Cmd::Action(Action::Refresh)),
+                        None => {
                             let ev = Cmd::Keyboard {
-                                key: v,
+                                key: keysym,
                                 key_state: state,
-                                modifiers_state: *modifiers_state.lock().unwrap(),
+                                modifiers_state: modifiers,
                                 interpreted: utf8,
                             };
                             let mut kbd = kb2.lock().unwrap();
@@ -496,15 +1665,33 @@ impl<'a> App<'a> {
                     kb2.lock().unwrap().next = None;
                 }
             }
+            KbEvent::Enter { surface, .. } if kbd_inner.lock().unwrap().focus(&surface) => {
+                kbd_clone.lock().unwrap().push_back(Cmd::ForceDraw);
+            }
             KbEvent::Leave { .. } => {
                 kb2.lock().unwrap().next = None;
+                kbd_clone
+                    .lock()
+                    .unwrap()
+                    .push_back(Cmd::Dismiss { focus_lost: true });
             }
             KbEvent::RepeatInfo { delay, rate } => {
                 let mut kbd = kb2.lock().unwrap();
                 kbd.delay = delay;
                 kbd.rate = rate;
             }
-            KbEvent::Modifiers { modifiers } => *modifiers_state.lock().unwrap() = modifiers,
+            KbEvent::Modifiers { modifiers } => {
+                let mut state = modifiers_state.lock().unwrap();
+                let changed = (state.caps_lock, state.num_lock, state.alt)
+                    != (modifiers.caps_lock, modifiers.num_lock, modifiers.alt);
+                *state = modifiers;
+                if changed {
+                    kbd_clone
+                        .lock()
+                        .unwrap()
+                        .push_back(Cmd::Action(Action::Modifiers(modifiers)));
+                }
+            }
             _ => (),
         })
         .expect("could not map keyboard");
@@ -512,89 +1699,256 @@ impl<'a> App<'a> {
         //
         // Prepare shell so that we can create our shell surface
         //
-        inner
-            .lock()
-            .unwrap()
-            .set_shell(Some(if let Ok(layer) = manager.instantiate_exact(1) {
-                layer
-            } else {
-                panic!("server didn't advertise `zwlr_layer_shell_v1`");
-            }));
+        // Without layer shell, such as on GNOME, the dashboard is shown as
+        // a window instead
+        let layer_shell = manager.instantiate_range(1, 4).ok();
+        let wm_base: Option<Main<xdg_wm_base::XdgWmBase>> = manager.instantiate_range(1, 3).ok();
+        if let Some(wm_base) = &wm_base {
+            wm_base.quick_assign(|wm_base, event, _| {
+                if let xdg_wm_base::Event::Ping { serial } = event {
+                    wm_base.pong(serial);
+                }
+            });
+        }
+        let mut inner_lock = inner.lock().unwrap();
+        match (layer_shell, wm_base) {
+            (None, None) => return Err(missing_global("zwlr_layer_shell_v1")),
+            (layer_shell, wm_base) => {
+                inner_lock.windowed = layer_shell.is_none();
+                inner_lock.set_shell(layer_shell);
+                inner_lock.wm_base = wm_base;
+            }
+        }
+        drop(inner_lock);
 
-        event_queue.sync_roundtrip(&mut (), |_, _, _| {}).unwrap();
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {})?;
 
         //
         // Cursor processing
         //
         let pointer_clone = cmd_queue.clone();
+        let pointer_inner = inner.clone();
+        let pointer_serial = last_serial.clone();
         let pointer = seat.get_pointer();
-        let mut pos: (u32, u32) = (0, 0);
-        let mut vert_scroll: f64 = 0.0;
-        let mut horiz_scroll: f64 = 0.0;
-        let mut btn: u32 = 0;
-        let mut btn_clicked = false;
+        let mut frame = PointerFrame::new(scale, scroll);
+        let mut on_backdrop = false;
         pointer.quick_assign(move |_, event, _| match event {
-            wl_pointer::Event::Enter {
-                surface_x,
-                surface_y,
-                ..
-            } => {
-                pos = (surface_x as u32, surface_y as u32);
+            wl_pointer::Event::Enter { surface, .. }
+                if pointer_inner.lock().unwrap().is_backdrop(&surface) =>
+            {
+                on_backdrop = true;
             }
-            wl_pointer::Event::Leave { .. } => {
-                pos = (0, 0);
+            wl_pointer::Event::Button { state, .. }
+                if on_backdrop && protocol::button_released(state) =>
+            {
+                pointer_clone
+                    .lock()
+                    .unwrap()
+                    .push_back(Cmd::Dismiss { focus_lost: false });
             }
-            wl_pointer::Event::Motion {
+            wl_pointer::Event::Leave { .. } if on_backdrop => on_backdrop = false,
+            _ if on_backdrop => (),
+            wl_pointer::Event::Enter {
+                surface,
                 surface_x,
                 surface_y,
                 ..
             } => {
-                pos = (surface_x as u32 * scale, surface_y as u32 * scale);
+                frame.enter(surface_x, surface_y);
+                pointer_clone.lock().unwrap().push_back(Cmd::PointerEnter);
+                if pointer_inner.lock().unwrap().focus(&surface) {
+                    pointer_clone.lock().unwrap().push_back(Cmd::ForceDraw);
+                }
             }
-            wl_pointer::Event::Axis { axis, value, .. } => {
-                if axis == wl_pointer::Axis::VerticalScroll {
-                    vert_scroll += value;
+            wl_pointer::Event::Leave { surface, .. } => {
+                pointer_clone.lock().unwrap().push_back(frame.leave());
+                // Ignore leave events from surfaces replaced while expanding
+                // or collapsing.
+                if pointer_inner
+                    .lock()
+                    .unwrap()
+                    .surfaces
+                    .iter()
+                    .any(|s| s.as_ref().equals(surface.as_ref()))
+                {
+                    pointer_clone.lock().unwrap().push_back(Cmd::PointerLeave);
                 }
             }
-            wl_pointer::Event::Button { button, state, .. } => {
-                if let wl_pointer::ButtonState::Released = state {
-                    btn = button;
-                    btn_clicked = true;
+            event => {
+                if let wl_pointer::Event::Button { serial, .. } = event {
+                    *pointer_serial.lock().unwrap() = serial;
                 }
+                pointer_clone.lock().unwrap().extend(frame.event(event));
             }
-            wl_pointer::Event::Frame => {
-                if vert_scroll != 0.0 || horiz_scroll != 0.0 {
-                    pointer_clone.lock().unwrap().push_back(Cmd::MouseScroll {
-                        scroll: (horiz_scroll, vert_scroll),
-                        pos,
+        });
+
+        //
+        // Drag and drop processing
+        //
+        // Optional, used to launch applications with the files dropped on
+        // them. The pointer position while dragging is passed on as pointer
+        // motion, so that the result under it is highlighted, and the dropped
+        // uris are sent as an action to the widget that was under it.
+        if let Ok(data_device_manager) =
+            manager.instantiate_range::<wl_data_device_manager::WlDataDeviceManager>(1, 3)
+        {
+            let drag_clone = cmd_queue.clone();
+            let drag_inner = inner.clone();
+            let mut drag: Option<wl_data_offer::WlDataOffer> = None;
+            let data_device = data_device_manager.get_data_device(&seat);
+            data_device.quick_assign(move |_, event, _| match event {
+                wl_data_device::Event::DataOffer { id } => {
+                    id.as_ref()
+                        .user_data()
+                        .set_threadsafe(|| AtomicBool::new(false));
+                    id.quick_assign(|offer, event, _| {
+                        if let wl_data_offer::Event::Offer { mime_type } = event {
+                            if mime_type == protocol::URI_LIST {
+                                if let Some(uris) = offer.as_ref().user_data().get::<AtomicBool>() {
+                                    uris.store(true, Ordering::Relaxed);
+                                }
+                            }
+                        }
                     });
-                    vert_scroll = 0.0;
-                    horiz_scroll = 0.0;
                 }
-                if btn_clicked {
-                    pointer_clone
+                wl_data_device::Event::Enter {
+                    serial,
+                    surface,
+                    x,
+                    y,
+                    id: Some(offer),
+                } => {
+                    let uris = offer
+                        .as_ref()
+                        .user_data()
+                        .get::<AtomicBool>()
+                        .is_some_and(|uris| uris.load(Ordering::Relaxed));
+                    if !uris || drag_inner.lock().unwrap().is_backdrop(&surface) {
+                        offer.accept(serial, None);
+                        offer.destroy();
+                        return;
+                    }
+                    protocol::accept_uris(&offer, serial);
+                    drag = Some(offer);
+                    let pos = (x as u32 * scale, y as u32 * scale);
+                    drag_clone
                         .lock()
                         .unwrap()
-                        .push_back(Cmd::MouseClick { btn, pos });
-                    btn_clicked = false;
+                        .push_back(Cmd::MouseMotion { pos: Some(pos) });
                 }
-            }
-            _ => {}
-        });
+                wl_data_device::Event::Motion { x, y, .. } if drag.is_some() => {
+                    let pos = (x as u32 * scale, y as u32 * scale);
+                    drag_clone
+                        .lock()
+                        .unwrap()
+                        .push_back(Cmd::MouseMotion { pos: Some(pos) });
+                }
+                wl_data_device::Event::Leave => {
+                    if let Some(offer) = drag.take() {
+                        offer.destroy();
+                        drag_clone
+                            .lock()
+                            .unwrap()
+                            .push_back(Cmd::MouseMotion { pos: None });
+                    }
+                }
+                wl_data_device::Event::Drop => {
+                    let offer = match drag.take() {
+                        Some(offer) => offer,
+                        None => return,
+                    };
+                    let (reader, writer) = match os_pipe::pipe() {
+                        Ok(pipe) => pipe,
+                        Err(err) => {
+                            eprintln!("Unable to receive dropped files: {}", err);
+                            offer.destroy();
+                            return;
+                        }
+                    };
+                    protocol::receive(&offer, protocol::URI_LIST, &writer);
+                    drop(writer);
+                    let tx = drop_tx.clone();
+                    let _ = thread::Builder::new()
+                        .name("drop".to_string())
+                        .spawn(move || {
+                            let mut list = String::new();
+                            let mut reader = reader;
+                            if let Err(err) = reader.read_to_string(&mut list) {
+                                eprintln!("Unable to receive dropped files: {}", err);
+                            }
+                            protocol::finish(&offer);
+                            let uris = protocol::parse_uri_list(&list);
+                            if !uris.is_empty() {
+                                let _ = tx.send(Cmd::Action(Action::Drop(uris)));
+                            }
+                            let _ = tx.send(Cmd::MouseMotion { pos: None });
+                        });
+                }
+                // The clipboard is not read from here
+                wl_data_device::Event::Selection { id: Some(offer) } => offer.destroy(),
+                _ => (),
+            });
+        }
+
+        // Optional, used to pass focus to launched programs
+        let activation = manager.instantiate_exact(1).ok();
 
-        display.flush().unwrap();
+        display.flush()?;
 
-        App {
+        Ok(App {
             display,
             event_queue,
             cmd_queue,
-            pools,
+            shm,
+            surfaces: Vec::new(),
+            generation: 0,
             widget: None,
             bg,
             inner,
-            last_damage: None,
-            last_dim: (0, 0),
             keyboard,
-        }
+            seat,
+            activation,
+            last_serial,
+            overlay: false,
+            osd: None,
+            connected: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_geometry() {
+        assert_eq!(
+            WindowGeometry::parse("1280 720\n"),
+            WindowGeometry {
+                size: Some((1280, 720)),
+                maximized: false,
+            }
+        );
+        assert_eq!(
+            WindowGeometry::parse("800 600 maximized"),
+            WindowGeometry {
+                size: Some((800, 600)),
+                maximized: true,
+            }
+        );
+        // Maximized before the size was ever known
+        assert_eq!(
+            WindowGeometry::parse("maximized\n"),
+            WindowGeometry {
+                size: None,
+                maximized: true,
+            }
+        );
+        // Empty, zero, negative or missing sizes are not restored
+        assert_eq!(WindowGeometry::parse(""), WindowGeometry::default());
+        assert_eq!(WindowGeometry::parse("0 720").size, None);
+        assert_eq!(WindowGeometry::parse("-1 720").size, None);
+        assert_eq!(WindowGeometry::parse("1280").size, None);
+        assert_eq!(WindowGeometry::parse("wide tall").size, None);
     }
 }
@@ -0,0 +1,117 @@
+//! Follows the appearance preferences of the xdg-desktop-portal Settings
+//! interface, so the dashboard can switch between its dark and light
+//! backgrounds together with the rest of the desktop, and keep still when
+//! motion is to be reduced.
+
+use crate::cmd::Cmd;
+
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use dbus::arg::{RefArg, Variant};
+use dbus::{BusType, Connection, ConnectionItem, Message};
+
+const PORTAL_BUS: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS_INTERFACE: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+const REDUCED_MOTION_KEY: &str = "reduced-motion";
+
+fn dbus_error(desc: &str) -> Error {
+    Error::new(ErrorKind::Other, desc.to_string())
+}
+
+// 0 is no preference, 1 prefers dark and 2 prefers light. Without a
+// preference we keep the regular, dark background.
+fn color_scheme_cmd(value: &Variant<Box<dyn RefArg>>) -> Option<Cmd> {
+    value
+        .as_u64()
+        .map(|scheme| Cmd::ColorScheme { dark: scheme != 2 })
+}
+
+// 0 is no preference and 1 prefers reduced motion.
+fn reduced_motion_cmd(value: &Variant<Box<dyn RefArg>>) -> Option<Cmd> {
+    value.as_u64().map(|motion| Cmd::ReducedMotion {
+        reduced: motion == 1,
+    })
+}
+
+// Reads the setting, and then forwards every change of it.
+fn listen(
+    tx: Sender<Cmd>,
+    key: &str,
+    to_cmd: fn(&Variant<Box<dyn RefArg>>) -> Option<Cmd>,
+) -> Result<(), Error> {
+    let con =
+        Connection::get_private(BusType::Session).map_err(|_| dbus_error("unable to open dbus"))?;
+
+    let rule = format!(
+        "type='signal',interface='{}',member='SettingChanged'",
+        SETTINGS_INTERFACE
+    );
+    con.add_match(&rule)
+        .map_err(|_| dbus_error("unable to add match rule to dbus connection"))?;
+
+    let msg = Message::new_method_call(PORTAL_BUS, PORTAL_PATH, SETTINGS_INTERFACE, "Read")
+        .map_err(|_| dbus_error("could not make dbus method call"))?
+        .append2(APPEARANCE_NAMESPACE, key);
+    let reply = con
+        .send_with_reply_and_block(msg, 5000)
+        .map_err(|_| dbus_error("unable to read setting"))?;
+    if let Some(cmd) = reply
+        .read1::<Variant<Box<dyn RefArg>>>()
+        .ok()
+        .and_then(|v| to_cmd(&v))
+    {
+        if tx.send(cmd).is_err() {
+            return Ok(());
+        }
+    }
+
+    for item in con.iter(60_000) {
+        let m = match item {
+            ConnectionItem::Signal(m) => m,
+            _ => continue,
+        };
+        let cmd = match m.read3::<&str, &str, Variant<Box<dyn RefArg>>>() {
+            Ok((namespace, changed, value))
+                if namespace == APPEARANCE_NAMESPACE && changed == key =>
+            {
+                to_cmd(&value)
+            }
+            _ => None,
+        };
+        if let Some(cmd) = cmd {
+            if tx.send(cmd).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Watches the portal color scheme in a background thread, forwarding changes
+/// as commands.
+pub fn follow_color_scheme(tx: Sender<Cmd>) {
+    let _ = thread::Builder::new()
+        .name("color_scheme".to_string())
+        .spawn(move || {
+            if let Err(err) = listen(tx, COLOR_SCHEME_KEY, color_scheme_cmd) {
+                eprintln!("Unable to follow the color scheme: {}", err);
+            }
+        });
+}
+
+/// Watches the portal reduced motion preference in a background thread,
+/// forwarding changes as commands.
+pub fn follow_reduced_motion(tx: Sender<Cmd>) {
+    let _ = thread::Builder::new()
+        .name("reduced_motion".to_string())
+        .spawn(move || {
+            if let Err(err) = listen(tx, REDUCED_MOTION_KEY, reduced_motion_cmd) {
+                eprintln!("Unable to follow the reduced motion preference: {}", err);
+            }
+        });
+}
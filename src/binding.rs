@@ -0,0 +1,59 @@
+//! Actions bound to input in the config
+//!
+//! A binding runs a command, copies text or changes a mode of wldash. It is
+//! carried out without hiding the dashboard, unless it is the hide binding.
+
+use crate::cmd::{Action, Cmd};
+use crate::widgets::launcher::wlcopy;
+
+use std::fmt::Write;
+use std::sync::mpsc::Sender;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Binding {
+    /// Run a command.
+    Run(String),
+    /// Copy the text to the clipboard, with strftime fields such as %Y
+    /// replaced by the current date and time.
+    Copy(String),
+    /// Toggle offline mode.
+    Offline,
+    /// Toggle private mode.
+    Private,
+    /// Hide the dashboard, or exit if not running as a daemon.
+    Hide,
+    /// Refresh the data of the widgets. When bound on a widget, only that
+    /// widget is refreshed.
+    Refresh,
+}
+
+impl Binding {
+    pub fn execute(&self, tx: &Sender<Cmd>) {
+        match self {
+            Binding::Run(command) => match shlex::split(command) {
+                Some(argv) if !argv.is_empty() => {
+                    tx.send(Cmd::Launch { argv, app_id: None }).unwrap()
+                }
+                _ => eprintln!("Unable to parse command: {}", command),
+            },
+            Binding::Copy(format) => {
+                let mut text = String::new();
+                if write!(text, "{}", Local::now().format(format)).is_err() {
+                    eprintln!("Invalid date format: {}", format);
+                    return;
+                }
+                if let Err(err) = wlcopy(&text) {
+                    eprintln!("Unable to copy: {}", err);
+                }
+            }
+            Binding::Offline => tx.send(Cmd::Offline(None)).unwrap(),
+            Binding::Private => tx.send(Cmd::Private(None)).unwrap(),
+            Binding::Hide => tx.send(Cmd::Exit).unwrap(),
+            Binding::Refresh => tx.send(Cmd::Action(Action::Refresh)).unwrap(),
+        }
+    }
+}
@@ -6,6 +6,7 @@ pub struct Buffer<'a> {
     buf: &'a mut MmapMut,
     dimensions: (u32, u32),
     subdimensions: Option<(u32, u32, u32, u32)>,
+    clip: Option<(u32, u32, u32, u32)>,
 }
 
 impl<'a> Buffer<'a> {
@@ -14,6 +15,7 @@ impl<'a> Buffer<'a> {
             buf,
             dimensions,
             subdimensions: None,
+            clip: None,
         }
     }
 
@@ -38,9 +40,19 @@ impl<'a> Buffer<'a> {
         }
     }
 
-    pub fn copy_to(&self, other: &mut Buffer, (x, y, width, height): (i32, i32, i32, i32)) {
+    /// Copies an area to another buffer of the same dimensions. The part of
+    /// the area outside the buffers is left out.
+    pub fn copy_to(&self, other: &mut Buffer, area: (i32, i32, i32, i32)) {
         debug_assert!(self.dimensions == other.dimensions);
         debug_assert!(self.subdimensions.is_none() && other.subdimensions.is_none());
+        if self.dimensions != other.dimensions {
+            return;
+        }
+
+        let (w, h) = (self.dimensions.0 as i32, self.dimensions.1 as i32);
+        let (x, y) = (area.0.clamp(0, w), area.1.clamp(0, h));
+        let width = area.0.saturating_add(area.2).clamp(x, w) - x;
+        let height = area.1.saturating_add(area.3).clamp(y, h) - y;
 
         if x == 0 && width as u32 == self.dimensions.0 {
             // Full-width copy
@@ -68,65 +80,103 @@ impl<'a> Buffer<'a> {
         }
     }
 
+    // The clip for an area within this buffer, which is clipped to the
+    // buffer if it reaches past it.
+    fn clip_for(&self, area: (u32, u32, u32, u32)) -> Option<(u32, u32, u32, u32)> {
+        let bounds = self.get_bounds();
+        let fits =
+            area.0.saturating_add(area.2) <= bounds.2 && area.1.saturating_add(area.3) <= bounds.3;
+        match self.clip {
+            None if !fits => Some(bounds),
+            clip => clip,
+        }
+    }
+
+    /// Returns a buffer for an area within this one. An area reaching past
+    /// the buffer is clipped to it, so that drawing outside is discarded.
     pub fn subdimensions(
         &mut self,
         subdimensions: (u32, u32, u32, u32),
     ) -> Result<Buffer, ::std::io::Error> {
         let bounds = self.get_bounds();
-        if subdimensions.0 + subdimensions.2 >= bounds.2
-            || subdimensions.1 + subdimensions.3 >= bounds.3
-        {
-            return Err(::std::io::Error::new(
-                ::std::io::ErrorKind::Other,
-                format!(
-                    "cannot create subdimensions larger than buffer: {:?} > {:?}",
-                    subdimensions, bounds
-                ),
-            ));
-        }
-
+        let clip = self.clip_for(subdimensions);
         Ok(Buffer {
             buf: self.buf,
             dimensions: self.dimensions,
             subdimensions: Some((
-                subdimensions.0 + bounds.0,
-                subdimensions.1 + bounds.1,
+                subdimensions.0.saturating_add(bounds.0),
+                subdimensions.1.saturating_add(bounds.1),
                 subdimensions.2,
                 subdimensions.3,
             )),
+            clip,
         })
     }
 
+    /// Returns a buffer for the part of this one from the offset on. An
+    /// offset past the buffer gives a buffer where nothing is drawn.
     pub fn offset(&mut self, offset: (u32, u32)) -> Result<Buffer, ::std::io::Error> {
         let bounds = self.get_bounds();
-        if offset.0 > bounds.2 || offset.1 > bounds.3 {
-            return Err(::std::io::Error::new(
-                ::std::io::ErrorKind::Other,
-                format!(
-                    "cannot create offset outside buffer: {:?} > {:?}",
-                    offset, bounds
-                ),
-            ));
-        }
-
+        let clip = self.clip_for((offset.0, offset.1, 0, 0));
         Ok(Buffer {
             buf: self.buf,
             dimensions: self.dimensions,
             subdimensions: Some((
-                offset.0 + bounds.0,
-                offset.1 + bounds.1,
-                bounds.2 - offset.0,
-                bounds.3 - offset.1,
+                offset.0.saturating_add(bounds.0),
+                offset.1.saturating_add(bounds.1),
+                bounds.2.saturating_sub(offset.0),
+                bounds.3.saturating_sub(offset.1),
             )),
+            clip,
         })
     }
 
+    /// Returns a buffer with the same coordinates, but where drawing outside
+    /// the given area is silently discarded. Subdimensions of a clipped buffer
+    /// may extend past its bounds. Used by layouts to clip children that do
+    /// not fit.
+    pub fn clip(&mut self, area: (u32, u32, u32, u32)) -> Buffer<'_> {
+        let bounds = self.get_bounds();
+        let (x, y) = (area.0 + bounds.0, area.1 + bounds.1);
+        let c = self.clip.unwrap_or(bounds);
+        let (x1, y1) = (x.max(c.0), y.max(c.1));
+        let x2 = (x + area.2).min(c.0 + c.2).max(x1);
+        let y2 = (y + area.3).min(c.1 + c.3).max(y1);
+        Buffer {
+            buf: self.buf,
+            dimensions: self.dimensions,
+            subdimensions: self.subdimensions,
+            clip: Some((x1, y1, x2 - x1, y2 - y1)),
+        }
+    }
+
+    fn clipped(&self, pos: (u32, u32)) -> bool {
+        match self.clip {
+            Some(c) => pos.0 < c.0 || pos.1 < c.1 || pos.0 >= c.0 + c.2 || pos.1 >= c.1 + c.3,
+            None => false,
+        }
+    }
+
     pub fn memset(&mut self, c: &Color) {
-        if let Some(subdim) = self.subdimensions {
+        if self.clip.is_some() {
+            let bounds = self.get_bounds();
+            for y in 0..bounds.3 {
+                for x in 0..bounds.2 {
+                    let _ = self.put((x, y), c);
+                }
+            }
+        } else if let Some(subdim) = self.subdimensions {
+            // Only clipped buffers reach past the buffer
+            debug_assert!(
+                subdim.0 + subdim.2 <= self.dimensions.0
+                    && subdim.1 + subdim.3 <= self.dimensions.1
+            );
+            let x_end = (subdim.0 + subdim.2).min(self.dimensions.0);
+            let y_end = (subdim.1 + subdim.3).min(self.dimensions.1);
             unsafe {
                 let ptr = self.buf.as_mut_ptr();
-                for y in subdim.1..(subdim.1 + subdim.3) {
-                    for x in subdim.0..(subdim.0 + subdim.2) {
+                for y in subdim.1..y_end {
+                    for x in subdim.0..x_end {
                         *((ptr as *mut u32).offset((x + y * self.dimensions.0) as isize)) =
                             c.as_argb8888();
                     }
@@ -142,6 +192,26 @@ impl<'a> Buffer<'a> {
         }
     }
 
+    /// Blends the color over what is already drawn in the buffer, by the
+    /// given ratio. Used to shade what is behind something drawn above.
+    pub fn blend(&mut self, c: &Color, ratio: f32) {
+        let bounds = self.get_bounds();
+        let x_end = (bounds.0 + bounds.2).min(self.dimensions.0);
+        let y_end = (bounds.1 + bounds.3).min(self.dimensions.1);
+        let ptr = self.buf.as_mut_ptr() as *mut u32;
+        for y in bounds.1..y_end {
+            for x in bounds.0..x_end {
+                if self.clipped((x, y)) {
+                    continue;
+                }
+                unsafe {
+                    let p = ptr.offset((x + y * self.dimensions.0) as isize);
+                    *p = Color::from_argb8888(*p).blend(c, ratio).as_argb8888();
+                }
+            }
+        }
+    }
+
     pub fn put(&mut self, pos: (u32, u32), c: &Color) -> Result<(), ::std::io::Error> {
         let true_pos = if let Some(subdim) = self.subdimensions {
             if pos.0 >= subdim.2 || pos.1 >= subdim.3 {
@@ -153,7 +223,10 @@ impl<'a> Buffer<'a> {
                     ),
                 ));
             }
-            (pos.0 + subdim.0, pos.1 + subdim.1)
+            (
+                pos.0.saturating_add(subdim.0),
+                pos.1.saturating_add(subdim.1),
+            )
         } else {
             if pos.0 >= self.dimensions.0 || pos.1 >= self.dimensions.1 {
                 return Err(::std::io::Error::new(
@@ -166,15 +239,188 @@ impl<'a> Buffer<'a> {
             }
             pos
         };
+        if self.clipped(true_pos) {
+            return Ok(());
+        }
+        // Only clipped buffers reach past the buffer, and those were clipped
+        debug_assert!(true_pos.0 < self.dimensions.0 && true_pos.1 < self.dimensions.1);
+        if true_pos.0 >= self.dimensions.0 || true_pos.1 >= self.dimensions.1 {
+            return Ok(());
+        }
 
         unsafe {
             let ptr = self
                 .buf
                 .as_mut_ptr()
-                .offset(4 * (true_pos.0 + (true_pos.1 * self.dimensions.0)) as isize);
+                .add(4 * (true_pos.0 as usize + true_pos.1 as usize * self.dimensions.0 as usize));
             *(ptr as *mut u32) = c.as_argb8888();
         };
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // A small xorshift generator, so that the geometries are random but the
+    // same on every run.
+    pub(crate) struct Rng(u64);
+
+    impl Rng {
+        pub fn new(seed: u64) -> Rng {
+            Rng(seed.max(1))
+        }
+
+        pub fn below(&mut self, n: u32) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n.max(1) as u64) as u32
+        }
+    }
+
+    const SENTINEL: u32 = 0x12345678;
+
+    fn pixels(mmap: &MmapMut) -> Vec<u32> {
+        mmap.chunks(4)
+            .map(|p| u32::from_ne_bytes([p[0], p[1], p[2], p[3]]))
+            .collect()
+    }
+
+    fn fill(mmap: &mut MmapMut) {
+        for p in mmap.chunks_mut(4) {
+            p.copy_from_slice(&SENTINEL.to_ne_bytes());
+        }
+    }
+
+    // Drawing in an area of the buffer leaves the rest of it alone, however
+    // far the area and the drawing reach past the buffer.
+    #[test]
+    fn fuzz_areas() {
+        let mut rng = Rng::new(1);
+        let c = Color::new(1.0, 0.0, 0.0, 1.0);
+        for _ in 0..2000 {
+            let dim = (1 + rng.below(48), 1 + rng.below(48));
+            let mut mmap = MmapMut::map_anon((4 * dim.0 * dim.1) as usize).unwrap();
+            fill(&mut mmap);
+            let area = (rng.below(64), rng.below(64), rng.below(64), rng.below(64));
+            {
+                let mut buf = Buffer::new(&mut mmap, dim);
+                let mut sub = buf.subdimensions(area).unwrap();
+                match rng.below(4) {
+                    0 => sub.memset(&c),
+                    1 => sub.blend(&c, 0.5),
+                    _ => {
+                        for _ in 0..64 {
+                            let _ = sub.put((rng.below(80), rng.below(80)), &c);
+                        }
+                    }
+                }
+            }
+            for (idx, p) in pixels(&mmap).into_iter().enumerate() {
+                let (x, y) = (idx as u32 % dim.0, idx as u32 / dim.0);
+                let inside =
+                    x >= area.0 && y >= area.1 && x < area.0 + area.2 && y < area.1 + area.3;
+                assert!(inside || p == SENTINEL, "{:?} drew at {:?}", area, (x, y));
+            }
+        }
+    }
+
+    // An area reaching exactly to the edge of the buffer fits in it, so it is
+    // not clipped and its last row and column are drawn.
+    #[test]
+    fn exact_fit() {
+        let c = Color::new(1.0, 0.0, 0.0, 1.0);
+        let mut mmap = MmapMut::map_anon(4 * 8 * 8).unwrap();
+        fill(&mut mmap);
+        {
+            let mut buf = Buffer::new(&mut mmap, (8, 8));
+            let mut sub = buf.subdimensions((4, 2, 4, 6)).unwrap();
+            assert_eq!(sub.clip, None);
+            sub.put((3, 5), &c).unwrap();
+            assert!(sub.put((4, 5), &c).is_err());
+        }
+        let pixels = pixels(&mmap);
+        assert_eq!(pixels[7 + 7 * 8], c.as_argb8888());
+        assert_eq!(pixels.iter().filter(|&&p| p != SENTINEL).count(), 1);
+    }
+
+    // The part of a buffer that can be drawn to, as (x1, y1, x2, y2).
+    fn drawable(buf: &Buffer) -> (u32, u32, u32, u32) {
+        let b = buf.get_bounds();
+        let c = buf.clip.unwrap_or(b);
+        let (x1, y1) = (b.0.max(c.0), b.1.max(c.1));
+        let x2 = (b.0 + b.2).min(c.0 + c.2).min(buf.dimensions.0).max(x1);
+        let y2 = (b.1 + b.3).min(c.1 + c.3).min(buf.dimensions.1).max(y1);
+        (x1, y1, x2, y2)
+    }
+
+    fn inside(inner: (u32, u32, u32, u32), outer: (u32, u32, u32, u32)) -> bool {
+        let empty = inner.0 == inner.2 || inner.1 == inner.3;
+        empty
+            || (inner.0 >= outer.0
+                && inner.1 >= outer.1
+                && inner.2 <= outer.2
+                && inner.3 <= outer.3)
+    }
+
+    // Nested offsets, areas and clips stay inside their parent and the clip,
+    // and never draw outside what they can draw to.
+    #[test]
+    fn fuzz_nesting() {
+        let mut rng = Rng::new(2);
+        let c = Color::new(0.0, 1.0, 0.0, 1.0);
+        for _ in 0..2000 {
+            let dim = (1 + rng.below(32), 1 + rng.below(32));
+            let mut mmap = MmapMut::map_anon((4 * dim.0 * dim.1) as usize).unwrap();
+            fill(&mut mmap);
+            let mut geometry = || (rng.below(40), rng.below(40), rng.below(40), rng.below(40));
+            let (a, b, d) = (geometry(), geometry(), geometry());
+            let clip_rect = (a.0, a.1, a.0 + a.2, a.1 + a.3);
+            let allowed = {
+                let mut buf = Buffer::new(&mut mmap, dim);
+                let root = drawable(&buf);
+                let mut clipped = buf.clip(a);
+                let parent = drawable(&clipped);
+                assert!(inside(parent, root), "{:?} left the buffer", a);
+                assert!(inside(parent, clip_rect), "{:?} left the clip", a);
+                let mut sub = clipped.subdimensions(b).unwrap();
+                let area = drawable(&sub);
+                assert!(inside(area, parent), "{:?} left {:?}", b, a);
+                let mut off = sub.offset((d.0, d.1)).unwrap();
+                let allowed = drawable(&off);
+                assert!(inside(allowed, area), "{:?} left {:?}", d, b);
+                assert!(inside(allowed, clip_rect), "{:?} left the clip", d);
+                off.memset(&c);
+                off.blend(&c, 0.25);
+                for x in 0..40 {
+                    let _ = off.put((x, x), &c);
+                }
+                allowed
+            };
+            for (idx, p) in pixels(&mmap).into_iter().enumerate() {
+                let (x, y) = (idx as u32 % dim.0, idx as u32 / dim.0);
+                let drawn = p != SENTINEL;
+                assert!(
+                    !drawn || inside((x, y, x + 1, y + 1), allowed),
+                    "{:?} {:?} {:?} drew at {:?}",
+                    a,
+                    b,
+                    d,
+                    (x, y)
+                );
+            }
+
+            let last = Buffer::new(&mut mmap, dim);
+            let mut other = MmapMut::map_anon((4 * dim.0 * dim.1) as usize).unwrap();
+            let mut target = Buffer::new(&mut other, dim);
+            let (x, y) = (rng.below(40) as i32 - 20, rng.below(40) as i32 - 20);
+            last.copy_to(
+                &mut target,
+                (x, y, rng.below(60) as i32, rng.below(60) as i32),
+            );
+        }
+    }
+}
@@ -1,20 +1,111 @@
 use crate::keyboard::{KeyState, ModifiersState};
+use crate::widget::{Scroll, TreeNode, WidgetState};
+
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+/// Actions emitted by one widget to be handled by others.
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// Move the calendar back to the current month.
+    CalendarToday,
+    /// Scroll the calendar as if the scroll happened on it.
+    CalendarScroll(f64),
+    /// Select the widget with this index in the layout editor.
+    LayoutSelect(usize),
+    /// Set the margins of the widget with this index in the layout editor.
+    LayoutMargins(usize, (u32, u32, u32, u32)),
+    /// Show or hide the help overlay.
+    ToggleHelp,
+    /// Show or hide the launch statistics, in place of the help.
+    ToggleStats,
+    /// The lock keys or Alt changed state.
+    Modifiers(ModifiersState),
+    /// Clear the launcher prompt.
+    ClearPrompt,
+    /// Read the data of the widgets again right away, such as the battery
+    /// state, the feeds and the applications of the launcher.
+    Refresh,
+    /// Files or other uris were dropped where the pointer was last reported
+    /// by mouse_motion.
+    Drop(Vec<String>),
+}
 
 #[derive(Clone)]
 pub enum Cmd {
     Exit,
+    /// Exit as asked over the control socket, which the lock screen ignores.
+    Kill,
+    /// The password was accepted on the lock screen.
+    Unlock,
+    /// Escape was pressed, or keyboard focus was lost if focus_lost is set,
+    /// to be handled according to the dismiss policy.
+    Dismiss {
+        focus_lost: bool,
+    },
     Draw,
     ForceDraw,
+    /// Show a message on the on-screen display, for the timeout in
+    /// milliseconds or the configured time.
+    Osd {
+        text: String,
+        timeout: Option<u32>,
+    },
+    /// The on-screen display may need drawing.
+    OsdDraw,
     ToggleVisible,
+    /// The size of the outputs may have changed.
+    Resize,
+    /// A button was pressed, to be followed by MouseClick once released.
+    MousePress {
+        btn: u32,
+        pos: (u32, u32),
+    },
     MouseClick {
         btn: u32,
         pos: (u32, u32),
     },
     MouseScroll {
-        scroll: (f64, f64),
+        scroll: Scroll,
         pos: (u32, u32),
     },
+    MouseMotion {
+        pos: Option<(u32, u32)>,
+    },
+    PointerEnter,
+    PointerLeave,
     KeyboardTest,
+    Launch {
+        argv: Vec<String>,
+        /// The desktop file id of the application being launched
+        app_id: Option<String>,
+    },
+    Action(Action),
+    Export {
+        path: PathBuf,
+    },
+    /// Set offline mode, or toggle it if None.
+    Offline(Option<bool>),
+    /// Set private mode, or toggle it if None.
+    Private(Option<bool>),
+    /// Set accessibility mode, or toggle it if None.
+    Accessibility(Option<bool>),
+    /// Show the dashboard as a window or on the layer shell, or toggle it if
+    /// None.
+    Window(Option<bool>),
+    Query {
+        reply: Sender<Vec<WidgetState>>,
+    },
+    DumpTree {
+        reply: Sender<TreeNode>,
+    },
+    ColorScheme {
+        dark: bool,
+    },
+    #[cfg(feature = "dbus-services")]
+    ReducedMotion {
+        reduced: bool,
+    },
     Keyboard {
         key: u32,
         key_state: KeyState,
@@ -1,6 +1,8 @@
-use serde::{Deserialize, Serialize};
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Color {
     red: f32,
     green: f32,
@@ -19,6 +21,122 @@ impl Color {
         }
     }
 
+    /// Parses a color from "#RGB", "#RRGGBB", "#RRGGBBAA" or a CSS color
+    /// name.
+    pub fn parse(s: &str) -> Option<Color> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            return Color::parse_hex(hex);
+        }
+
+        let (r, g, b, a) = match s.to_lowercase().as_str() {
+            "transparent" => (0, 0, 0, 0),
+            "black" => (0, 0, 0, 255),
+            "silver" => (192, 192, 192, 255),
+            "gray" | "grey" => (128, 128, 128, 255),
+            "white" => (255, 255, 255, 255),
+            "maroon" => (128, 0, 0, 255),
+            "red" => (255, 0, 0, 255),
+            "purple" => (128, 0, 128, 255),
+            "fuchsia" | "magenta" => (255, 0, 255, 255),
+            "green" => (0, 128, 0, 255),
+            "lime" => (0, 255, 0, 255),
+            "olive" => (128, 128, 0, 255),
+            "yellow" => (255, 255, 0, 255),
+            "navy" => (0, 0, 128, 255),
+            "blue" => (0, 0, 255, 255),
+            "teal" => (0, 128, 128, 255),
+            "aqua" | "cyan" => (0, 255, 255, 255),
+            "orange" => (255, 165, 0, 255),
+            "pink" => (255, 192, 203, 255),
+            "brown" => (165, 42, 42, 255),
+            "darkgray" | "darkgrey" => (169, 169, 169, 255),
+            "lightgray" | "lightgrey" => (211, 211, 211, 255),
+            _ => return None,
+        };
+        Some(Color::from_rgba8(r, g, b, a))
+    }
+
+    fn parse_hex(hex: &str) -> Option<Color> {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |idx: usize, len: usize| {
+            let v = u8::from_str_radix(&hex[idx * len..(idx + 1) * len], 16).ok()?;
+            Some(if len == 1 { v * 17 } else { v })
+        };
+        match hex.len() {
+            3 => Some(Color::from_rgba8(
+                channel(0, 1)?,
+                channel(1, 1)?,
+                channel(2, 1)?,
+                255,
+            )),
+            6 => Some(Color::from_rgba8(
+                channel(0, 2)?,
+                channel(1, 2)?,
+                channel(2, 2)?,
+                255,
+            )),
+            8 => Some(Color::from_rgba8(
+                channel(0, 2)?,
+                channel(1, 2)?,
+                channel(2, 2)?,
+                channel(3, 2)?,
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color::new(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+    }
+
+    /// The color of a pixel as stored in a buffer.
+    pub fn from_argb8888(v: u32) -> Color {
+        Color::from_rgba8((v >> 16) as u8, (v >> 8) as u8, v as u8, (v >> 24) as u8)
+    }
+
+    /// Creates an opaque color from a hue in degrees, and a saturation and
+    /// lightness between 0 and 1.
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = lightness - chroma / 2.0;
+        Color::new(r + m, g + m, b + m, 1.0)
+    }
+
+    /// The color as tiny-skia takes it, for shapes and gradients.
+    pub fn as_skia(&self) -> tiny_skia::Color {
+        tiny_skia::Color::from_rgba(self.red, self.green, self.blue, self.opacity)
+            .unwrap_or(tiny_skia::Color::BLACK)
+    }
+
+    /// Formats the color as "#RRGGBB", leaving out the opacity.
+    pub fn as_hex_rgb(&self) -> String {
+        let byte = |v: f32| (v * 255.0).round() as u8;
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            byte(self.red),
+            byte(self.green),
+            byte(self.blue)
+        )
+    }
+
     pub fn blend(&self, other: &Color, ratio: f32) -> Color {
         let ratio = clamp_f32(ratio, 0.0, 1.0);
 
@@ -39,6 +157,66 @@ impl Color {
     }
 }
 
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let byte = |v: f32| (v * 255.0).round() as u8;
+        write!(
+            f,
+            "#{:02x}{:02x}{:02x}{:02x}",
+            byte(self.red),
+            byte(self.green),
+            byte(self.blue),
+            byte(self.opacity)
+        )
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+struct ColorVisitor;
+
+impl<'de> Visitor<'de> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a color as \"#RRGGBB\", \"#RRGGBBAA\", a color name or a map")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Color, E> {
+        Color::parse(v).ok_or_else(|| E::custom(format!("invalid color: {}", v)))
+    }
+
+    // The legacy format, with each channel as a float between 0 and 1.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Color, A::Error> {
+        let (mut red, mut green, mut blue, mut opacity) = (0.0, 0.0, 0.0, 1.0);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "red" => red = map.next_value()?,
+                "green" => green = map.next_value()?,
+                "blue" => blue = map.next_value()?,
+                "opacity" => opacity = map.next_value()?,
+                _ => {
+                    return Err(de::Error::unknown_field(
+                        &key,
+                        &["red", "green", "blue", "opacity"],
+                    ))
+                }
+            }
+        }
+        Ok(Color::new(red, green, blue, opacity))
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
 #[inline]
 fn clamp(r: f32, g: f32, b: f32, o: f32) -> (f32, f32, f32, f32) {
     clamp_naive(r, g, b, o)
@@ -69,3 +247,31 @@ fn clamp_f32(v: f32, a: f32, b: f32) -> f32 {
 fn blend_f32(a: f32, b: f32, r: f32) -> f32 {
     a + ((b - a) * r)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argb(s: &str) -> Option<u32> {
+        Color::parse(s).map(|c| c.as_argb8888())
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(argb("#fff"), Some(0xFFFFFFFF));
+        assert_eq!(argb("#f80"), Some(0xFFFF8800));
+        assert_eq!(argb("#1a2B3c"), Some(0xFF1A2B3C));
+        assert_eq!(argb("#1a2b3c80"), Some(0x801A2B3C));
+        assert_eq!(argb(" #000000 "), Some(0xFF000000));
+
+        assert_eq!(argb("red"), Some(0xFFFF0000));
+        assert_eq!(argb("Grey"), Some(0xFF808080));
+        assert_eq!(argb("transparent"), Some(0x00000000));
+
+        for s in [
+            "", "#", "#ff", "#ffff", "#fffffff", "#ggg", "#+1f", "#ff00é", "reddish",
+        ] {
+            assert_eq!(argb(s), None, "{:?}", s);
+        }
+    }
+}
@@ -1,14 +1,31 @@
+use crate::accessibility::Accessibility;
+use crate::binding::Binding;
 use crate::cmd::Cmd;
 use crate::color::Color;
+use crate::configfmt::{self, ConfigFmt, Value, CONFIG_NAMES};
+use crate::draw::Cursor;
+use crate::exec::Exec;
+use crate::help;
+use crate::layout_editor;
+use crate::matching;
 use crate::widget;
 use crate::{
-    fonts::{FontMap, FontRef},
-    widgets,
+    fonts::{FontMap, FontRef, FALLBACK_FONT},
+    widgets::{
+        self,
+        bar_widget::{BarColors, BarStyle},
+        latency, processes,
+    },
 };
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime, NaiveTime};
 use serde::{Deserialize, Serialize};
 use std::default::Default;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::{collections::HashMap, sync::mpsc::Sender};
+use wayland_protocols::wlr::unstable::layer_shell::v1::client::zwlr_layer_surface_v1;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -22,8 +39,56 @@ pub enum Widget {
         height: u32,
         widget: Box<Widget>,
     },
+    Style {
+        background: Option<Color>,
+        #[serde(default)]
+        padding: (u32, u32, u32, u32),
+        border: Option<Border>,
+        #[serde(default)]
+        radius: u32,
+        widget: Box<Widget>,
+    },
+    Constrain {
+        #[serde(default)]
+        min_size: (u32, u32),
+        #[serde(default)]
+        max_size: (u32, u32),
+        #[serde(default)]
+        priority: i32,
+        #[serde(default)]
+        overflow: Overflow,
+        widget: Box<Widget>,
+    },
+    FocusedOutput {
+        widget: Box<Widget>,
+    },
+    Bind {
+        left: Option<Binding>,
+        middle: Option<Binding>,
+        right: Option<Binding>,
+        widget: Box<Widget>,
+    },
     HorizontalLayout(Vec<Widget>),
     VerticalLayout(Vec<Widget>),
+    /// Shows the key bindings over the dashboard, never read from a config.
+    #[serde(skip)]
+    Help {
+        widget: Box<Widget>,
+    },
+    /// Tells about problems with the config, never read from a config.
+    #[serde(skip)]
+    Warning {
+        text: String,
+    },
+    /// Outlines a widget in the layout editor, never read from a config.
+    #[serde(skip)]
+    Outline {
+        index: usize,
+        label: String,
+        margins: (u32, u32, u32, u32),
+        selected: bool,
+        widget: Box<Widget>,
+    },
     Clock {
         font: Option<String>,
         font_size: f32,
@@ -32,11 +97,41 @@ pub enum Widget {
         font: Option<String>,
         font_size: f32,
     },
+    // A line of static text, running on_click when clicked
+    Label {
+        text: String,
+        font: Option<String>,
+        font_size: f32,
+        #[serde(default)]
+        on_click: Option<Binding>,
+    },
+    WorldClock {
+        zones: Vec<String>,
+        font: Option<String>,
+        font_size: f32,
+    },
     Calendar {
         font_primary: Option<String>,
         font_secondary: Option<String>,
         font_size: f32,
         sections: u32,
+        #[serde(default)]
+        holidays: Vec<String>,
+        #[serde(default)]
+        holiday_file: String,
+        #[serde(default)]
+        week_parity: bool,
+    },
+    WeekStrip {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+        #[serde(default)]
+        holidays: Vec<String>,
+        #[serde(default)]
+        holiday_file: String,
+        #[serde(default)]
+        event_file: String,
     },
     Launcher {
         font: Option<String>,
@@ -48,11 +143,149 @@ pub enum Widget {
         term_opener: String,
         #[serde(default)]
         url_opener: String,
+        #[serde(default)]
+        file_opener: String,
+        // The command shell commands are run through, such as bwrap
+        #[serde(default)]
+        shell_sandbox: String,
+        #[serde(default)]
+        prompt_font: Option<String>,
+        #[serde(default)]
+        prompt_font_size: Option<f32>,
+        #[serde(default)]
+        results_font: Option<String>,
+        #[serde(default)]
+        results_font_size: Option<f32>,
+        #[serde(default)]
+        calc_font: Option<String>,
+        #[serde(default)]
+        calc_font_size: Option<f32>,
+        #[serde(default)]
+        language: Option<String>,
+        #[serde(default)]
+        cursor: CursorStyle,
+        // Milliseconds the cursor is shown and hidden for, or 0 to not blink
+        #[serde(default)]
+        cursor_blink: u32,
+        // List the results below the prompt, above the other widgets
+        #[serde(default)]
+        overlay: bool,
+    },
+    // The launcher on a single line in one font, for thin bars
+    CommandBar {
+        font: Option<String>,
+        font_size: f32,
+        #[serde(default)]
+        length: u32,
+        #[serde(default)]
+        app_opener: String,
+        #[serde(default)]
+        term_opener: String,
+        #[serde(default)]
+        url_opener: String,
+        #[serde(default)]
+        file_opener: String,
+        #[serde(default)]
+        shell_sandbox: String,
+        #[serde(default)]
+        language: Option<String>,
+    },
+    Notes {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+        height: u32,
+    },
+    Metrics {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+    },
+    Processes {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+        #[serde(default = "default_process_count")]
+        count: usize,
+        #[serde(default)]
+        sort_by: processes::SortBy,
+    },
+    ColorPicker {
+        font: Option<String>,
+        font_size: f32,
+    },
+    Locks {
+        font: Option<String>,
+        font_size: f32,
+        #[serde(default = "default_lock_active_color")]
+        active_color: Color,
+        #[serde(default = "default_lock_inactive_color")]
+        inactive_color: Color,
+        #[serde(default)]
+        only_active: bool,
+    },
+    Greeting {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+        #[serde(default)]
+        updates_command: String,
+    },
+    NextEvent {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+        event_file: String,
+        #[serde(default)]
+        command: String,
+        // Minutes before the start of an event to run the command
+        #[serde(default = "default_notify_before")]
+        notify_before: u32,
+    },
+    Feeds {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+        feeds: Vec<Feed>,
+        #[serde(default = "default_feed_count")]
+        count: u32,
+        // Seconds between scrolling the headlines by one, or 0 to not cycle
+        #[serde(default)]
+        cycle: u32,
+        #[serde(default)]
+        opener: String,
+    },
+    Latency {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+        host: String,
+        #[serde(default)]
+        probe: latency::Probe,
+        #[serde(default = "default_latency_port")]
+        port: u16,
+        // Seconds between probes, and before giving up on one
+        #[serde(default = "default_latency_interval")]
+        interval: u32,
+        #[serde(default = "default_latency_timeout")]
+        timeout: u32,
+        // Milliseconds at which the latency is shown as degraded and bad
+        #[serde(default = "default_latency_warn")]
+        warn: u32,
+        #[serde(default = "default_latency_bad")]
+        bad: u32,
     },
+    #[cfg(feature = "battery-widget")]
     Battery {
         font: Option<String>,
         font_size: f32,
         length: u32,
+        #[serde(default)]
+        threshold_command: String,
+        #[serde(default)]
+        colors: Option<BarColors>,
+        #[serde(default)]
+        style: BarStyle,
     },
     Backlight {
         #[serde(default)]
@@ -60,18 +293,60 @@ pub enum Widget {
         font: Option<String>,
         font_size: f32,
         length: u32,
+        #[serde(default = "default_scroll_step")]
+        scroll_step: f32,
+        #[serde(default)]
+        invert_scroll: bool,
+        #[serde(default)]
+        colors: Option<BarColors>,
+        #[serde(default)]
+        style: BarStyle,
     },
     #[cfg(feature = "pulseaudio-widget")]
     PulseAudio {
         font: Option<String>,
         font_size: f32,
         length: u32,
+        #[serde(default)]
+        sink: Option<String>,
+        #[serde(default = "default_scroll_step")]
+        scroll_step: f32,
+        #[serde(default)]
+        invert_scroll: bool,
+        #[serde(default)]
+        colors: Option<BarColors>,
+        #[serde(default)]
+        style: BarStyle,
+    },
+    #[cfg(feature = "pulseaudio-widget")]
+    Microphone {
+        font: Option<String>,
+        font_size: f32,
+        length: u32,
+        #[serde(default)]
+        source: Option<String>,
+        #[serde(default = "default_scroll_step")]
+        scroll_step: f32,
+        #[serde(default)]
+        invert_scroll: bool,
+        #[serde(default)]
+        colors: Option<BarColors>,
+        #[serde(default)]
+        style: BarStyle,
     },
     #[cfg(feature = "alsa-widget")]
     AlsaSound {
         font: Option<String>,
         font_size: f32,
         length: u32,
+        #[serde(default = "default_scroll_step")]
+        scroll_step: f32,
+        #[serde(default)]
+        invert_scroll: bool,
+        #[serde(default)]
+        colors: Option<BarColors>,
+        #[serde(default)]
+        style: BarStyle,
     },
 }
 
@@ -95,6 +370,89 @@ impl Widget {
                 Some(w) => Some(widget::Fixed::new((width, height), w)),
                 None => None,
             },
+            Widget::Style {
+                background,
+                padding,
+                border,
+                radius,
+                widget,
+            } => match widget.construct(time, tx, fonts) {
+                Some(w) => Some(widget::Styled::new(
+                    background,
+                    padding,
+                    border.map(|b| (b.width, b.color)),
+                    radius,
+                    w,
+                )),
+                None => None,
+            },
+            Widget::Constrain {
+                min_size,
+                max_size,
+                priority,
+                overflow,
+                widget,
+            } => match widget.construct(time, tx, fonts) {
+                Some(w) => Some(widget::Constrained::new(
+                    min_size,
+                    max_size,
+                    priority,
+                    match overflow {
+                        Overflow::Clip => widget::Overflow::Clip,
+                        Overflow::Shrink => widget::Overflow::Shrink,
+                        Overflow::Hide => widget::Overflow::Hide,
+                    },
+                    w,
+                )),
+                None => None,
+            },
+            Widget::FocusedOutput { widget } => match widget.construct(time, tx, fonts) {
+                Some(w) => Some(widget::FocusedOutput::new(w)),
+                None => None,
+            },
+            Widget::Bind {
+                left,
+                middle,
+                right,
+                widget,
+            } => {
+                let bindings = [(BTN_LEFT, left), (BTN_MIDDLE, middle), (BTN_RIGHT, right)]
+                    .into_iter()
+                    .filter_map(|(button, binding)| Some((button, binding?)))
+                    .collect();
+                match widget.construct(time, tx.clone(), fonts) {
+                    Some(w) => Some(widget::Bound::new(bindings, tx, w)),
+                    None => None,
+                }
+            }
+            Widget::Help { widget } => {
+                let launcher = find_launcher(&widget).is_some();
+                match widget.construct(time, tx, fonts) {
+                    Some(w) => Some(help::HelpOverlay::new(launcher, get_font("sans", fonts), w)),
+                    None => None,
+                }
+            }
+            Widget::Warning { text } => Some(widgets::warning::Warning::new(
+                text,
+                get_font("sans", fonts),
+            )),
+            Widget::Outline {
+                index,
+                label,
+                margins,
+                selected,
+                widget,
+            } => match widget.construct(time, tx, fonts) {
+                Some(w) => Some(layout_editor::Outline::new(
+                    index,
+                    label,
+                    margins,
+                    selected,
+                    get_font("sans", fonts),
+                    w,
+                )),
+                None => None,
+            },
             Widget::HorizontalLayout(widgets) => Some(widget::HorizontalLayout::new(
                 widgets
                     .into_iter()
@@ -119,10 +477,36 @@ impl Widget {
                 Ok(w) => Some(w),
                 Err(_) => None,
             },
+            Widget::Label {
+                text,
+                font,
+                font_size,
+                on_click,
+            } => Some(widgets::label::Label::new(
+                text,
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+                on_click,
+                tx,
+            )),
+            Widget::WorldClock {
+                zones,
+                font,
+                font_size,
+            } => match widgets::world_clock::WorldClock::new(
+                time,
+                &zones,
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+            ) {
+                Ok(w) => Some(w),
+                Err(_) => None,
+            },
             Widget::Date { font, font_size } => match widgets::date::Date::new(
                 time,
                 get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), &fonts),
                 font_size,
+                tx,
             ) {
                 Ok(w) => Some(w),
                 Err(_) => None,
@@ -132,6 +516,9 @@ impl Widget {
                 font_secondary,
                 font_size,
                 sections,
+                holidays,
+                holiday_file,
+                week_parity,
             } => Some(widgets::calendar::Calendar::new(
                 time,
                 get_font(
@@ -144,6 +531,34 @@ impl Widget {
                 ),
                 font_size,
                 sections,
+                holidays,
+                match holiday_file.as_str() {
+                    "" => None,
+                    path => Some(path.into()),
+                },
+                week_parity,
+            )),
+            Widget::WeekStrip {
+                font,
+                font_size,
+                length,
+                holidays,
+                holiday_file,
+                event_file,
+            } => Some(widgets::week_strip::WeekStrip::new(
+                time,
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+                length,
+                holidays,
+                match holiday_file.as_str() {
+                    "" => None,
+                    path => Some(path.into()),
+                },
+                match event_file.as_str() {
+                    "" => None,
+                    path => Some(path.into()),
+                },
             )),
             Widget::Launcher {
                 font,
@@ -152,32 +567,263 @@ impl Widget {
                 app_opener,
                 term_opener,
                 url_opener,
-            } => Some(widgets::launcher::Launcher::new(
-                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), &fonts),
+                file_opener,
+                shell_sandbox,
+                prompt_font,
+                prompt_font_size,
+                results_font,
+                results_font_size,
+                calc_font,
+                calc_font_size,
+                language,
+                cursor,
+                cursor_blink,
+                overlay,
+            } => {
+                let font = font.unwrap_or_else(|| "sans".to_string());
+                // Each region falls back to the font and size of the widget
+                let region = |name: Option<String>, size: Option<f32>| {
+                    (
+                        get_font(name.as_ref().unwrap_or(&font), fonts),
+                        size.unwrap_or(font_size),
+                    )
+                };
+                Some(widgets::launcher::Launcher::new(
+                    region(prompt_font, prompt_font_size),
+                    region(results_font, results_font_size),
+                    region(calc_font, calc_font_size),
+                    length,
+                    tx,
+                    app_opener,
+                    term_opener,
+                    if url_opener.is_empty() {
+                        "xdg-open".to_string()
+                    } else {
+                        url_opener
+                    },
+                    if file_opener.is_empty() {
+                        "xdg-open".to_string()
+                    } else {
+                        file_opener
+                    },
+                    shell_sandbox,
+                    language,
+                    match cursor {
+                        CursorStyle::Line => Cursor::Line,
+                        CursorStyle::Block => Cursor::Block,
+                    },
+                    cursor_blink,
+                    overlay,
+                ))
+            }
+            Widget::CommandBar {
+                font,
                 font_size,
                 length,
-                tx,
                 app_opener,
                 term_opener,
-                if url_opener.is_empty() {
-                    "xdg_open ".to_string()
-                } else {
-                    url_opener
+                url_opener,
+                file_opener,
+                shell_sandbox,
+                language,
+            } => {
+                let font = get_font(&font.unwrap_or_else(|| "sans".to_string()), fonts);
+                Some(widgets::launcher::Launcher::new(
+                    (font, font_size),
+                    (font, font_size),
+                    (font, font_size),
+                    length,
+                    tx,
+                    app_opener,
+                    term_opener,
+                    if url_opener.is_empty() {
+                        "xdg-open".to_string()
+                    } else {
+                        url_opener
+                    },
+                    if file_opener.is_empty() {
+                        "xdg-open".to_string()
+                    } else {
+                        file_opener
+                    },
+                    shell_sandbox,
+                    language,
+                    Cursor::Line,
+                    0,
+                    false,
+                ))
+            }
+            Widget::Notes {
+                font,
+                font_size,
+                length,
+                height,
+            } => Some(widgets::notes::Notes::new(
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+                length,
+                height,
+            )),
+            Widget::Metrics {
+                font,
+                font_size,
+                length,
+            } => Some(widgets::metrics::Metrics::new(
+                time,
+                get_font(&font.or_else(|| Some("mono".to_string())).unwrap(), fonts),
+                font_size,
+                length,
+            )),
+            Widget::Processes {
+                font,
+                font_size,
+                length,
+                count,
+                sort_by,
+            } => Some(widgets::processes::Processes::new(
+                time,
+                get_font(&font.or_else(|| Some("mono".to_string())).unwrap(), fonts),
+                font_size,
+                length,
+                count,
+                sort_by,
+            )),
+            Widget::ColorPicker { font, font_size } => {
+                Some(widgets::color_picker::ColorPicker::new(
+                    get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                    font_size,
+                    tx,
+                ))
+            }
+            Widget::Locks {
+                font,
+                font_size,
+                active_color,
+                inactive_color,
+                only_active,
+            } => match widgets::locks::Locks::new(
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+                active_color,
+                inactive_color,
+                only_active,
+            ) {
+                Ok(w) => Some(w),
+                Err(_) => None,
+            },
+            Widget::Greeting {
+                font,
+                font_size,
+                length,
+                updates_command,
+            } => Some(widgets::greeting::Greeting::new(
+                time,
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+                length,
+                updates_command,
+                tx,
+            )),
+            Widget::NextEvent {
+                font,
+                font_size,
+                length,
+                event_file,
+                command,
+                notify_before,
+            } => Some(widgets::next_event::NextEvent::new(
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+                length,
+                event_file.into(),
+                command,
+                Duration::minutes(notify_before as i64),
+                tx,
+            )),
+            Widget::Feeds {
+                font,
+                font_size,
+                length,
+                feeds,
+                count,
+                cycle,
+                opener,
+            } => Some(widgets::feeds::Feeds::new(
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+                length,
+                widgets::feeds::Settings {
+                    sources: feeds
+                        .into_iter()
+                        .map(|feed| widgets::feeds::Source {
+                            url: feed.url,
+                            refresh: Duration::minutes(feed.refresh as i64),
+                        })
+                        .collect(),
+                    count,
+                    cycle: match cycle {
+                        0 => None,
+                        secs => Some(Duration::seconds(secs as i64)),
+                    },
+                    opener: if opener.is_empty() {
+                        "xdg-open".to_string()
+                    } else {
+                        opener
+                    },
+                },
+                tx,
+            )),
+            Widget::Latency {
+                font,
+                font_size,
+                length,
+                host,
+                probe,
+                port,
+                interval,
+                timeout,
+                warn,
+                bad,
+            } => Some(widgets::latency::Latency::new(
+                get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts),
+                font_size,
+                length,
+                widgets::latency::Target {
+                    host,
+                    probe,
+                    port,
+                    timeout: timeout.max(1),
                 },
+                Duration::seconds(interval.max(1) as i64),
+                (warn, bad),
+                tx,
             )),
+            #[cfg(feature = "battery-widget")]
             Widget::Battery {
                 font,
                 font_size,
                 length,
+                threshold_command,
+                colors,
+                style,
             } => {
-                match widgets::battery::UpowerBattery::new(
-                    get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), &fonts),
+                let font = get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts);
+                let w = widgets::battery::UpowerBattery::new(
+                    font,
                     font_size,
                     length,
-                    tx,
-                ) {
-                    Ok(w) => Some(w),
-                    Err(_) => None,
+                    tx.clone(),
+                    threshold_command,
+                )
+                .map(|mut w| {
+                    w.set_colors(colors);
+                    w.set_style(style);
+                    w
+                });
+                match w {
+                    // Without a battery there is nothing to show
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                    w => badged(tx, "battery", w, font, font_size, length),
                 }
             }
             Widget::Backlight {
@@ -185,58 +831,506 @@ impl Widget {
                 font,
                 font_size,
                 length,
+                scroll_step,
+                invert_scroll,
+                colors,
+                style,
             } => {
                 let d = if device == "" {
                     "intel_backlight"
                 } else {
                     &device
                 };
-                match widgets::backlight::Backlight::new(
-                    d,
-                    get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), &fonts),
+                let font = get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts);
+                let w = widgets::backlight::Backlight::new(d, font, font_size, length, tx.clone())
+                    .map(|mut w| {
+                        w.set_scroll_step(signed_scroll_step(scroll_step, invert_scroll));
+                        w.set_colors(colors);
+                        w.set_style(style);
+                        w
+                    });
+                badged(tx, "backlight", w, font, font_size, length)
+            }
+            #[cfg(feature = "pulseaudio-widget")]
+            Widget::PulseAudio {
+                font,
+                font_size,
+                length,
+                sink,
+                scroll_step,
+                invert_scroll,
+                colors,
+                style,
+            } => {
+                let font = get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts);
+                let w = widgets::audio::PulseAudio::new(
+                    font,
                     font_size,
                     length,
-                ) {
-                    Ok(w) => Some(w),
-                    Err(_) => None,
-                }
+                    widgets::audio::Direction::Sink,
+                    sink,
+                    tx.clone(),
+                )
+                .map(|mut w| {
+                    w.set_scroll_step(signed_scroll_step(scroll_step, invert_scroll));
+                    w.set_colors(colors);
+                    w.set_style(style);
+                    w
+                });
+                badged(tx, "volume", w, font, font_size, length)
             }
             #[cfg(feature = "pulseaudio-widget")]
-            Widget::PulseAudio {
+            Widget::Microphone {
                 font,
                 font_size,
                 length,
+                source,
+                scroll_step,
+                invert_scroll,
+                colors,
+                style,
             } => {
-                match widgets::audio::PulseAudio::new(
-                    get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), &fonts),
+                let font = get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts);
+                let w = widgets::audio::PulseAudio::new(
+                    font,
                     font_size,
                     length,
-                    tx,
-                ) {
-                    Ok(w) => Some(w),
-                    Err(_) => None,
-                }
+                    widgets::audio::Direction::Source,
+                    source,
+                    tx.clone(),
+                )
+                .map(|mut w| {
+                    w.set_scroll_step(signed_scroll_step(scroll_step, invert_scroll));
+                    w.set_colors(colors);
+                    w.set_style(style);
+                    w
+                });
+                badged(tx, "mic", w, font, font_size, length)
             }
             #[cfg(feature = "alsa-widget")]
             Widget::AlsaSound {
                 font,
                 font_size,
                 length,
+                scroll_step,
+                invert_scroll,
+                colors,
+                style,
             } => {
-                match widgets::audio::Alsa::new(
-                    get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), &fonts),
-                    font_size,
-                    length,
-                ) {
-                    Ok(w) => Some(w),
-                    Err(_) => None,
-                }
+                let font = get_font(&font.or_else(|| Some("sans".to_string())).unwrap(), fonts);
+                let w = widgets::audio::Alsa::new(font, font_size, length).map(|mut w| {
+                    w.set_scroll_step(signed_scroll_step(scroll_step, invert_scroll));
+                    w.set_colors(colors);
+                    w.set_style(style);
+                    w
+                });
+                badged(tx, "volume", w, font, font_size, length)
             }
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Border {
+    pub width: u32,
+    pub color: Color,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Overflow {
+    #[default]
+    Clip,
+    Shrink,
+    Hide,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum CursorStyle {
+    #[default]
+    Line,
+    Block,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    pub fn to_anchor(self) -> zwlr_layer_surface_v1::Anchor {
+        use zwlr_layer_surface_v1::Anchor as A;
+        match self {
+            Anchor::TopLeft => A::Top | A::Left,
+            Anchor::Top => A::Top,
+            Anchor::TopRight => A::Top | A::Right,
+            Anchor::Left => A::Left,
+            Anchor::Right => A::Right,
+            Anchor::BottomLeft => A::Bottom | A::Left,
+            Anchor::Bottom => A::Bottom,
+            Anchor::BottomRight => A::Bottom | A::Right,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum KeyboardInteractivity {
+    None,
+    #[default]
+    Exclusive,
+    OnDemand,
+}
+
+impl KeyboardInteractivity {
+    pub fn to_interactivity(self) -> zwlr_layer_surface_v1::KeyboardInteractivity {
+        use zwlr_layer_surface_v1::KeyboardInteractivity as K;
+        match self {
+            KeyboardInteractivity::None => K::None,
+            KeyboardInteractivity::Exclusive => K::Exclusive,
+            KeyboardInteractivity::OnDemand => K::OnDemand,
+        }
+    }
+}
+
+// Linux input event codes of the pointer buttons that can be bound
+const BTN_LEFT: u32 = 272;
+const BTN_RIGHT: u32 = 273;
+const BTN_MIDDLE: u32 = 274;
+
+fn default_scroll_step() -> f32 {
+    widgets::bar_widget::DEFAULT_SCROLL_STEP
+}
+
+fn signed_scroll_step(step: f32, invert: bool) -> f32 {
+    if invert {
+        -step
+    } else {
+        step
+    }
+}
+
+fn default_notify_before() -> u32 {
+    5
+}
+
+fn default_lock_active_color() -> Color {
+    Color::new(1.0, 1.0, 1.0, 1.0)
+}
+
+fn default_lock_inactive_color() -> Color {
+    Color::new(0.3, 0.3, 0.3, 1.0)
+}
+
+fn default_feed_count() -> u32 {
+    5
+}
+
+fn default_feed_refresh() -> u32 {
+    30
+}
+
+fn default_process_count() -> usize {
+    5
+}
+
+fn default_latency_port() -> u16 {
+    443
+}
+
+fn default_latency_interval() -> u32 {
+    10
+}
+
+fn default_latency_timeout() -> u32 {
+    2
+}
+
+fn default_latency_warn() -> u32 {
+    100
+}
+
+fn default_latency_bad() -> u32 {
+    300
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Feed {
+    pub url: String,
+    // Minutes between fetching the feed
+    #[serde(default = "default_feed_refresh")]
+    pub refresh: u32,
+}
+
+fn default_hot_corner_size() -> u32 {
+    1
+}
+
+fn default_hot_corner_delay() -> u32 {
+    500
+}
+
+// The hot corner behaves like a panel, so it only takes focus when clicked
+fn default_hot_corner_interactivity() -> KeyboardInteractivity {
+    KeyboardInteractivity::OnDemand
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HotCorner {
+    pub anchor: Anchor,
+    #[serde(default = "default_hot_corner_size")]
+    pub size: u32,
+    #[serde(default)]
+    pub margin: u32,
+    // Milliseconds before collapsing after the pointer left the dashboard
+    #[serde(default = "default_hot_corner_delay")]
+    pub delay: u32,
+    #[serde(default = "default_hot_corner_interactivity")]
+    pub keyboard_interactivity: KeyboardInteractivity,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ThemeFollow {
+    #[default]
+    Time,
+    Portal,
+}
+
+fn default_light_at() -> String {
+    "07:00".to_string()
+}
+
+fn default_dark_at() -> String {
+    "19:00".to_string()
+}
+
+// Switches between the regular background, used as the dark theme, and a
+// light background.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeSwitch {
+    pub light_background: Color,
+    #[serde(default)]
+    pub follow: ThemeFollow,
+    #[serde(default = "default_light_at")]
+    pub light_at: String,
+    #[serde(default = "default_dark_at")]
+    pub dark_at: String,
+}
+
+impl ThemeSwitch {
+    /// The times of day to switch to the light and dark theme.
+    pub fn times(&self) -> (NaiveTime, NaiveTime) {
+        let parse = |s: &str| {
+            NaiveTime::parse_from_str(s, "%H:%M")
+                .unwrap_or_else(|_| panic!("invalid time of day {}, expected HH:MM", s))
+        };
+        (parse(&self.light_at), parse(&self.dark_at))
+    }
+}
+
+/// Whether to leave out animations, see the motion module.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReduceMotion {
+    #[default]
+    Never,
+    Always,
+    /// Follow the reduced motion preference of the settings portal.
+    Portal,
+}
+
+/// What to do when Escape is pressed or the dashboard loses keyboard focus.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DismissPolicy {
+    /// Exit wldash, even when running as a daemon.
+    Exit,
+    /// Hide the dashboard until it is shown again.
+    Hide,
+    /// Clear the launcher prompt, keeping the dashboard open.
+    ClearPrompt,
+    Ignore,
+}
+
+/// The policies when running standalone and as a daemon.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DismissPolicies {
+    pub standalone: DismissPolicy,
+    pub daemon: DismissPolicy,
+}
+
+impl DismissPolicies {
+    pub fn get(&self, daemon: bool) -> DismissPolicy {
+        if daemon {
+            self.daemon
+        } else {
+            self.standalone
+        }
+    }
+}
+
+fn default_escape() -> DismissPolicies {
+    DismissPolicies {
+        standalone: DismissPolicy::Exit,
+        daemon: DismissPolicy::Hide,
+    }
+}
+
+fn default_focus_lost() -> DismissPolicies {
+    DismissPolicies {
+        standalone: DismissPolicy::Ignore,
+        daemon: DismissPolicy::Ignore,
+    }
+}
+
+fn default_osd_font_size() -> f32 {
+    32.0
+}
+
+fn default_osd_foreground() -> Color {
+    Color::new(1.0, 1.0, 1.0, 1.0)
+}
+
+fn default_osd_background() -> Color {
+    Color::new(0.0, 0.0, 0.0, 0.75)
+}
+
+fn default_osd_anchor() -> Anchor {
+    Anchor::Bottom
+}
+
+fn default_osd_margin() -> u32 {
+    64
+}
+
+fn default_osd_padding() -> u32 {
+    16
+}
+
+fn default_osd_timeout() -> u32 {
+    1500
+}
+
+fn default_pam_service() -> String {
+    "login".to_string()
+}
+
+fn default_lock_font_size() -> f32 {
+    32.0
+}
+
+/// The lock screen of `wldash lock`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Lock {
+    // The widgets shown above the password prompt, by default the date and
+    // time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub widget: Option<Widget>,
+    // The PAM service the password is checked against, from /etc/pam.d
+    #[serde(default = "default_pam_service")]
+    pub pam_service: String,
+    #[serde(default)]
+    pub font: Option<String>,
+    #[serde(default = "default_lock_font_size")]
+    pub font_size: f32,
+}
+
+impl Default for Lock {
+    fn default() -> Self {
+        Lock {
+            widget: None,
+            pam_service: default_pam_service(),
+            font: None,
+            font_size: default_lock_font_size(),
+        }
+    }
+}
+
+impl Lock {
+    pub fn font<'a>(&self, fonts: &'a FontMap) -> FontRef<'a> {
+        get_font(self.font.as_deref().unwrap_or("sans"), fonts)
+    }
+}
+
+/// How messages shown with `wldash osd` look, and for how long they are shown
+/// when no timeout is given.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OsdStyle {
+    #[serde(default)]
+    pub font: Option<String>,
+    #[serde(default = "default_osd_font_size")]
+    pub font_size: f32,
+    #[serde(default = "default_osd_foreground")]
+    pub foreground: Color,
+    #[serde(default = "default_osd_background")]
+    pub background: Color,
+    #[serde(default = "default_osd_anchor")]
+    pub anchor: Anchor,
+    #[serde(default = "default_osd_margin")]
+    pub margin: u32,
+    #[serde(default = "default_osd_padding")]
+    pub padding: u32,
+    // Milliseconds
+    #[serde(default = "default_osd_timeout")]
+    pub timeout: u32,
+}
+
+impl Default for OsdStyle {
+    fn default() -> Self {
+        OsdStyle {
+            font: None,
+            font_size: default_osd_font_size(),
+            foreground: default_osd_foreground(),
+            background: default_osd_background(),
+            anchor: default_osd_anchor(),
+            margin: default_osd_margin(),
+            padding: default_osd_padding(),
+            timeout: default_osd_timeout(),
+        }
+    }
+}
+
+impl OsdStyle {
+    pub fn font<'a>(&self, fonts: &'a FontMap) -> FontRef<'a> {
+        get_font(self.font.as_deref().unwrap_or("sans"), fonts)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Dismiss {
+    #[serde(default = "default_escape")]
+    pub escape: DismissPolicies,
+    #[serde(default = "default_focus_lost")]
+    pub focus_lost: DismissPolicies,
+}
+
+impl Default for Dismiss {
+    fn default() -> Self {
+        Dismiss {
+            escape: default_escape(),
+            focus_lost: default_focus_lost(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub enum OutputMode {
     All,
@@ -249,6 +1343,38 @@ impl Default for OutputMode {
     }
 }
 
+/// The surface sizes a preset is used for. Sizes are in surface pixels,
+/// that is output pixels divided by the scale.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeCondition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_height: Option<u32>,
+}
+
+impl SizeCondition {
+    pub fn matches(&self, (width, height): (u32, u32)) -> bool {
+        self.min_width.is_none_or(|min| width >= min)
+            && self.max_width.is_none_or(|max| width <= max)
+            && self.min_height.is_none_or(|min| height >= min)
+            && self.max_height.is_none_or(|max| height <= max)
+    }
+}
+
+// A widget tree used instead of the regular one on surfaces of some size.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub when: SizeCondition,
+    pub widget: Widget,
+}
+
 fn default_fonts() -> HashMap<String, String> {
     let mut map = HashMap::with_capacity(2);
     map.insert("mono".to_string(), "mono".to_string());
@@ -256,7 +1382,7 @@ fn default_fonts() -> HashMap<String, String> {
     map
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
     pub output_mode: OutputMode,
@@ -266,6 +1392,62 @@ pub struct Config {
 
     #[serde(default = "default_fonts")]
     pub fonts: HashMap<String, String>,
+
+    #[serde(default)]
+    pub global_shortcuts: bool,
+
+    #[serde(default)]
+    pub hot_corner: Option<HotCorner>,
+
+    #[serde(default)]
+    pub theme_switch: Option<ThemeSwitch>,
+
+    #[serde(default)]
+    pub socket_path: Option<String>,
+
+    #[serde(default)]
+    pub keyboard_interactivity: KeyboardInteractivity,
+
+    #[serde(default)]
+    pub offline: bool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub presets: Vec<Preset>,
+
+    #[serde(default)]
+    pub exec: Exec,
+
+    #[serde(default)]
+    pub accessibility: Accessibility,
+
+    #[serde(default)]
+    pub dismiss: Dismiss,
+
+    #[serde(default)]
+    pub reduce_motion: ReduceMotion,
+
+    // Shown behind the dashboard while it is open, such as "#00000080"
+    #[serde(default)]
+    pub backdrop: Option<Color>,
+
+    #[serde(default)]
+    pub osd: OsdStyle,
+
+    // Extra emoji shortcodes for the prompt, without the colons
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub shortcodes: HashMap<String, String>,
+
+    // How input is matched against names in the launcher and file browser
+    #[serde(default)]
+    pub matching: matching::Algorithm,
+
+    #[serde(default)]
+    pub lock: Lock,
+
+    // The port of localhost to serve metrics on
+    #[cfg(feature = "prometheus")]
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
 }
 
 impl Default for Config {
@@ -289,12 +1471,16 @@ impl Default for Config {
                             ])),
                         },
                         Widget::VerticalLayout(vec![
+                            #[cfg(feature = "battery-widget")]
                             Widget::Margin {
                                 margins: (0, 0, 0, 8),
                                 widget: Box::new(Widget::Battery {
                                     font: None,
                                     font_size: 24.0,
                                     length: 0,
+                                    threshold_command: "".to_string(),
+                                    colors: None,
+                                    style: Default::default(),
                                 }),
                             },
                             Widget::Margin {
@@ -304,6 +1490,10 @@ impl Default for Config {
                                     font: None,
                                     font_size: 24.0,
                                     length: 0,
+                                    scroll_step: default_scroll_step(),
+                                    invert_scroll: false,
+                                    colors: None,
+                                    style: Default::default(),
                                 }),
                             },
                             #[cfg(feature = "pulseaudio-widget")]
@@ -313,6 +1503,11 @@ impl Default for Config {
                                     font: None,
                                     font_size: 24.0,
                                     length: 0,
+                                    sink: None,
+                                    scroll_step: default_scroll_step(),
+                                    invert_scroll: false,
+                                    colors: None,
+                                    style: Default::default(),
                                 }),
                             },
                         ]),
@@ -322,6 +1517,9 @@ impl Default for Config {
                         font_secondary: None,
                         font_size: 16.0,
                         sections: 3,
+                        holidays: Vec::new(),
+                        holiday_file: "".to_string(),
+                        week_parity: false,
                     },
                     Widget::Launcher {
                         font: None,
@@ -330,6 +1528,18 @@ impl Default for Config {
                         app_opener: "".to_string(),
                         term_opener: "".to_string(),
                         url_opener: "".to_string(),
+                        file_opener: "".to_string(),
+                        shell_sandbox: "".to_string(),
+                        prompt_font: None,
+                        prompt_font_size: None,
+                        results_font: None,
+                        results_font_size: None,
+                        calc_font: None,
+                        calc_font_size: None,
+                        language: None,
+                        cursor: Default::default(),
+                        cursor_blink: 0,
+                        overlay: false,
                     },
                 ])),
             },
@@ -337,14 +1547,223 @@ impl Default for Config {
             scale: 1,
             background: Color::new(0.0, 0.0, 0.0, 0.9),
             fonts: default_fonts(),
+            global_shortcuts: false,
+            hot_corner: None,
+            theme_switch: None,
+            socket_path: None,
+            keyboard_interactivity: Default::default(),
+            offline: false,
+            presets: Vec::new(),
+            exec: Default::default(),
+            accessibility: Default::default(),
+            dismiss: Default::default(),
+            reduce_motion: Default::default(),
+            backdrop: None,
+            osd: Default::default(),
+            shortcodes: HashMap::new(),
+            matching: Default::default(),
+            lock: Default::default(),
+            #[cfg(feature = "prometheus")]
+            metrics_port: None,
+        }
+    }
+}
+
+// The width of the prompt when the configured launcher fills the dashboard.
+const PROMPT_LENGTH: u32 = 960;
+
+// The directories of the system-wide config files, least important first:
+// /etc/wldash, and then those of $XDG_CONFIG_DIRS (by default /etc/xdg),
+// which are listed most important first.
+fn system_config_dirs() -> Vec<PathBuf> {
+    let xdg = env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+    let mut dirs = vec![PathBuf::from("/etc/wldash")];
+    dirs.extend(
+        xdg.split(':')
+            .rev()
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Path::new(dir).join("wldash")),
+    );
+    dirs
+}
+
+// Reads the first config file of the directory, in the order of
+// CONFIG_NAMES.
+fn read_config_file(dir: &Path) -> Option<(PathBuf, Value)> {
+    CONFIG_NAMES.iter().find_map(|name| {
+        let path = dir.join(name);
+        let fmt = ConfigFmt::from_path(&path)?;
+        let file = File::open(&path).ok()?;
+        let value = fmt.from_reader(BufReader::new(file));
+        Some((path, value))
+    })
+}
+
+/// Loads the config, merging the config of the user in config_home over the
+/// system-wide ones, which are merged over each other in turn. Without any
+/// config file, the default config is used. Returns the path of the config
+/// file of the user, if there is one.
+pub fn load(config_home: &Path) -> (Config, Option<PathBuf>) {
+    let mut merged: Option<Value> = None;
+    let mut user_path = None;
+    let system = system_config_dirs().into_iter().map(|dir| (dir, false));
+    for (dir, user) in system.chain(std::iter::once((config_home.to_path_buf(), true))) {
+        let (path, value) = match read_config_file(&dir) {
+            Some(file) => file,
+            None => continue,
+        };
+        if user {
+            user_path = Some(path);
+        }
+        // An empty file changes nothing
+        if value.is_null() {
+            continue;
+        }
+        match &mut merged {
+            Some(merged) => configfmt::merge(merged, value),
+            None => merged = Some(value),
+        }
+    }
+    let config = match merged.map(configfmt::from_value).transpose() {
+        Ok(config) => config.unwrap_or_default(),
+        Err(err) => {
+            match missing_widget(&err) {
+                Some((name, feature)) => eprintln!(
+                    "The config uses the {} widget, but wldash was compiled without {} support (the {} feature)",
+                    name, name, feature
+                ),
+                None => eprintln!("Unable to read the config: {}", err),
+            }
+            std::process::exit(1);
+        }
+    };
+    (config, user_path)
+}
+
+// The widgets left out of this build, by their name in the config, and the
+// features they need.
+const DISABLED_WIDGETS: &[(&str, &str)] = &[
+    #[cfg(not(feature = "battery-widget"))]
+    ("battery", "battery-widget"),
+    #[cfg(not(feature = "pulseaudio-widget"))]
+    ("pulseAudio", "pulseaudio-widget"),
+    #[cfg(not(feature = "pulseaudio-widget"))]
+    ("microphone", "pulseaudio-widget"),
+    #[cfg(not(feature = "alsa-widget"))]
+    ("alsaSound", "alsa-widget"),
+];
+
+// The widget left out of this build that the config could not be read for,
+// as they are unknown to it.
+fn missing_widget(err: &str) -> Option<(&'static str, &'static str)> {
+    DISABLED_WIDGETS
+        .iter()
+        .find(|(name, _)| err.contains(&format!("unknown variant `{}`", name)))
+        .copied()
+}
+
+fn find_launcher(widget: &Widget) -> Option<&Widget> {
+    match widget {
+        Widget::Launcher { .. } => Some(widget),
+        Widget::HorizontalLayout(children) | Widget::VerticalLayout(children) => {
+            children.iter().find_map(find_launcher)
+        }
+        Widget::Margin { widget, .. }
+        | Widget::Fixed { widget, .. }
+        | Widget::Style { widget, .. }
+        | Widget::Constrain { widget, .. }
+        | Widget::FocusedOutput { widget }
+        | Widget::Bind { widget, .. } => find_launcher(widget),
+        _ => None,
+    }
+}
+
+impl Config {
+    /// The index of the first preset for the surface size, or None for the
+    /// regular widget tree.
+    pub fn preset(&self, size: (u32, u32)) -> Option<usize> {
+        self.presets
+            .iter()
+            .position(|preset| preset.when.matches(size))
+    }
+
+    /// The widget tree of a preset, or the regular one for None.
+    pub fn preset_widget(&self, preset: Option<usize>) -> Widget {
+        match preset {
+            Some(idx) => self.presets[idx].widget.clone(),
+            None => self.widget.clone(),
+        }
+    }
+
+    /// The widgets shown on the lock screen, which are the date and time
+    /// unless configured.
+    pub fn lock_widget(&self) -> Widget {
+        self.lock.widget.clone().unwrap_or_else(|| {
+            Widget::VerticalLayout(vec![
+                Widget::Date {
+                    font: None,
+                    font_size: 64.0,
+                },
+                Widget::Clock {
+                    font: None,
+                    font_size: 256.0,
+                },
+            ])
+        })
+    }
+
+    /// A widget tree with nothing but the launcher, for use as a run dialog.
+    /// The launcher of the config is used if it has one, so that its openers
+    /// and fonts apply.
+    pub fn prompt_widget(&self) -> Widget {
+        let mut launcher = find_launcher(&self.widget)
+            .cloned()
+            .or_else(|| find_launcher(&Config::default().widget).cloned())
+            .expect("the default config has a launcher");
+        if let Widget::Launcher { length, .. } = &mut launcher {
+            if *length == 0 {
+                *length = PROMPT_LENGTH;
+            }
+        }
+        Widget::Margin {
+            margins: (20, 20, 20, 20),
+            widget: Box::new(launcher),
         }
     }
 }
 
 #[inline]
+// Marks the bar widget with an error badge while its backend fails, or stands
+// in for it with the badge if the backend could not be set up at all.
+fn badged<'a>(
+    tx: Sender<Cmd>,
+    name: &str,
+    widget: std::io::Result<Box<widgets::bar_widget::BarWidget<'a>>>,
+    font: FontRef<'a>,
+    font_size: f32,
+    length: u32,
+) -> Option<Box<dyn widget::Widget + Send + 'a>> {
+    Some(match widget {
+        Ok(w) => widgets::badge::ErrorBadge::new(tx, w),
+        Err(err) => widgets::badge::ErrorBadge::new(
+            tx,
+            widgets::badge::Unavailable::new(name, err.to_string(), font, font_size, length),
+        ),
+    })
+}
+
 fn get_font<'a>(name: &str, map: &'a FontMap) -> FontRef<'a> {
     match map.get(name) {
         Some(f) => f,
-        None => panic!("Font {} is missing from the config", name),
+        None => {
+            eprintln!(
+                "Font {} is missing from the config, using {}",
+                name, FALLBACK_FONT
+            );
+            map[FALLBACK_FONT]
+        }
     }
 }
@@ -1,5 +1,14 @@
 use serde::{de::DeserializeOwned, Serialize};
 use std::io::BufRead;
+use std::path::Path;
+
+/// A config file read without its structure, so that config files can be
+/// merged before they are turned into a config. JSON files are read into
+/// YAML values when both are supported.
+#[cfg(feature = "yaml-cfg")]
+pub type Value = serde_yaml::Value;
+#[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+pub type Value = serde_json::Value;
 
 pub enum ConfigFmt {
     #[cfg(feature = "yaml-cfg")]
@@ -26,6 +35,12 @@ impl ConfigFmt {
             _ => None,
         }
     }
+    /// The format of a config file by its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(ConfigFmt::new)
+    }
     #[inline]
     pub fn from_reader<B: BufRead, T: DeserializeOwned>(&self, r: B) -> T {
         match self {
@@ -59,3 +74,72 @@ impl Default for ConfigFmt {
         }
     }
 }
+
+/// Turns merged config files into a config.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, String> {
+    #[cfg(feature = "yaml-cfg")]
+    {
+        serde_yaml::from_value(value).map_err(|err| err.to_string())
+    }
+    #[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+    {
+        serde_json::from_value(value).map_err(|err| err.to_string())
+    }
+}
+
+// Writes a tagged value such as `!margin { ... }`, as printed for the
+// variants of the config, as the map `margin: { ... }` it can also be
+// written as, so that both merge the same.
+#[cfg(feature = "yaml-cfg")]
+fn untag(value: Value) -> Value {
+    match value {
+        Value::Tagged(tagged) => {
+            let tag = tagged.tag.to_string();
+            let mut map = serde_yaml::Mapping::new();
+            map.insert(
+                Value::String(tag.trim_start_matches('!').to_string()),
+                tagged.value,
+            );
+            Value::Mapping(map)
+        }
+        value => value,
+    }
+}
+
+/// Merges a config file over another. Maps are merged key by key, so a value
+/// nested in maps is replaced by its path, while anything else, such as the
+/// children of a layout, is replaced as a whole.
+pub fn merge(base: &mut Value, over: Value) {
+    #[cfg(feature = "yaml-cfg")]
+    if let Value::Tagged(_) = base {
+        *base = untag(std::mem::take(base));
+    }
+    #[cfg(feature = "yaml-cfg")]
+    match (base, untag(over)) {
+        (Value::Mapping(base), Value::Mapping(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(old) => merge(old, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+    #[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(old) => merge(old, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
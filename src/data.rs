@@ -1,3 +1,5 @@
+use crate::private;
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
@@ -5,17 +7,49 @@ use std::error::Error;
 use std::fs::{create_dir_all, File, OpenOptions};
 use std::path::PathBuf;
 
+/// When an application was launched first and last, as Unix timestamps, and
+/// how many times in between.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct Usage {
+    pub first: i64,
+    pub last: i64,
+    pub launches: i64,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
 pub struct Data {
     pub entries: HashMap<String, i64>,
+    // Only known for launches since it was added
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub usage: HashMap<String, Usage>,
 }
 
 impl Data {
+    /// Counts a launch of the application at the given time.
+    pub fn record(&mut self, name: &str, now: i64) {
+        *self.entries.entry(name.to_string()).or_insert(0) += 1;
+        self.usage
+            .entry(name.to_string())
+            .and_modify(|usage| {
+                usage.last = now;
+                usage.launches += 1;
+            })
+            .or_insert(Usage {
+                first: now,
+                last: now,
+                launches: 1,
+            });
+    }
+
     pub fn load() -> Result<Data, Box<dyn Error>> {
         Ok(serde_yaml::from_reader(Self::read_file()?)?)
     }
 
+    /// Writes the data, unless in private mode.
     pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        if private::is_private() {
+            return Ok(());
+        }
         Ok(serde_yaml::to_writer(Self::write_file()?, self)?)
     }
 
@@ -36,22 +70,58 @@ impl Data {
     }
 
     fn path() -> Result<PathBuf, Box<dyn Error>> {
-        let xdg_cache = match env::var_os("XDG_CACHE_HOME") {
-            Some(s) => s
-                .into_string()
-                .map_err(|_| "Unable to resolve $XDG_CACHE_HOME")?,
-            None => format!(
-                "{}/.cache",
-                env::var_os("HOME")
-                    .ok_or("Unable to resolve $HOME")?
-                    .into_string()
-                    .map_err(|_| "Unable to resolve $HOME")?
-            ),
-        };
-
-        let cache_dir = PathBuf::from(xdg_cache).join("wldash");
+        let cache_dir = cache_dir()?;
         create_dir_all(&cache_dir)?;
 
         Ok(cache_dir.join("data.yaml"))
     }
 }
+
+fn cache_dir() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(xdg_dir("XDG_CACHE_HOME", ".cache")?.join("wldash"))
+}
+
+fn state_dir() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(xdg_dir("XDG_STATE_HOME", ".local/state")?.join("wldash"))
+}
+
+/// Returns the path of a file in the wldash cache directory, creating the
+/// directory if needed.
+pub fn cache_file(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let cache_dir = cache_dir()?;
+    create_dir_all(&cache_dir)?;
+
+    Ok(cache_dir.join(name))
+}
+
+/// Returns the path of a file in the wldash state directory, creating the
+/// directory if needed.
+pub fn state_file(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let state_dir = state_dir()?;
+    create_dir_all(&state_dir)?;
+
+    Ok(state_dir.join(name))
+}
+
+/// The directories wldash keeps its cache and state in, which hold
+/// everything it writes besides the config and its socket.
+pub fn data_dirs() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    Ok(vec![cache_dir()?, state_dir()?])
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> Result<PathBuf, Box<dyn Error>> {
+    match env::var_os(var) {
+        Some(s) => Ok(PathBuf::from(
+            s.into_string()
+                .map_err(|_| format!("Unable to resolve ${}", var))?,
+        )),
+        None => Ok(PathBuf::from(format!(
+            "{}/{}",
+            env::var_os("HOME")
+                .ok_or("Unable to resolve $HOME")?
+                .into_string()
+                .map_err(|_| "Unable to resolve $HOME")?,
+            fallback
+        ))),
+    }
+}
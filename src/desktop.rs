@@ -1,27 +1,115 @@
 extern crate ini;
-use ini::{Ini, ParseOption};
+use crate::cmd::Cmd;
+use crate::data::cache_file;
+use ini::{Ini, ParseOption, Properties};
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::env;
 use std::error::Error;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
 use std::io::Error as io_error;
 use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::UNIX_EPOCH;
 use walkdir::WalkDir;
 
-#[derive(Clone, Debug, Eq, Hash)]
+#[cfg(feature = "yaml-cfg")]
+const CACHE_FILE: &str = "desktop.yaml";
+#[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+const CACHE_FILE: &str = "desktop.json";
+
+#[derive(Clone, Debug, Eq, Serialize, Deserialize)]
 pub struct Desktop {
+    pub path: String,
+    /// The desktop file id, such as `org.gnome.Nautilus`
+    pub id: String,
     pub entry_type: String,
     pub name: String,
+    pub localized_name: Option<String>,
+    pub comment: Option<String>,
     pub no_display: bool,
     pub hidden: bool,
     pub exec: Option<String>,
     pub url: Option<String>,
     pub term: bool,
     pub keywords: Vec<String>,
+    pub categories: Vec<String>,
+}
+
+// Splits a semicolon separated list such as Keywords or Categories
+fn split_list(list: Option<&str>) -> Vec<String> {
+    list.map(|x| {
+        x.split(';')
+            .map(|y| y.trim().to_string())
+            .filter(|z| !z.is_empty())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+// The locale keys to look for, best match first. The desktop entry spec
+// matches lang_COUNTRY@MODIFIER, lang_COUNTRY, lang@MODIFIER and lang, while
+// the encoding is ignored.
+fn locale_keys(locale: &str) -> Vec<String> {
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale, None),
+    };
+    let locale = locale.split('.').next().unwrap_or_default();
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None),
+    };
+
+    let mut keys = Vec::with_capacity(4);
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        keys.push(format!("{}_{}@{}", lang, country, modifier));
+    }
+    if let Some(country) = country {
+        keys.push(format!("{}_{}", lang, country));
+    }
+    if let Some(modifier) = modifier {
+        keys.push(format!("{}@{}", lang, modifier));
+    }
+    keys.push(lang.to_string());
+    keys
+}
+
+/// The locale keys to pick localized desktop entry values by, from the given
+/// language or otherwise from the locale of the messages.
+pub fn locales(language: Option<&str>) -> Vec<String> {
+    match language {
+        Some(language) => locale_keys(language),
+        None => ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .map(|locale| locale_keys(&locale))
+            .unwrap_or_default(),
+    }
+}
+
+fn localized<'a>(desktop: &'a Properties, key: &str, locales: &[String]) -> Option<&'a str> {
+    locales
+        .iter()
+        .find_map(|locale| desktop.get(format!("{}[{}]", key, locale)))
 }
 
 impl Desktop {
-    fn parse(f: &str) -> Result<Desktop, Box<dyn Error>> {
+    /// The name to show, which is the localized name if there is one.
+    pub fn display_name(&self) -> &str {
+        self.localized_name.as_deref().unwrap_or(&self.name)
+    }
+
+    fn parse(f: &str, id: String, locales: &[String]) -> Result<Desktop, Box<dyn Error>> {
         let file = Ini::load_from_file_opt(
             f,
             ParseOption {
@@ -31,22 +119,27 @@ impl Desktop {
         )?;
         match file.section(Some("Desktop Entry")) {
             Some(desktop) => Ok(Desktop {
+                path: f.to_string(),
+                id,
                 entry_type: desktop.get("Type").unwrap_or(&"".to_string()).to_string(),
                 name: desktop.get("Name").unwrap_or(&"".to_string()).to_string(),
+                localized_name: localized(desktop, "Name", locales)
+                    .filter(|name| Some(*name) != desktop.get("Name"))
+                    .map(|x| x.to_string()),
+                comment: localized(desktop, "Comment", locales)
+                    .or_else(|| desktop.get("Comment"))
+                    .map(|x| x.to_string()),
                 term: desktop.get("Terminal").unwrap_or(&"".to_string()) == "true",
                 no_display: desktop.get("NoDisplay").unwrap_or(&"".to_string()) == "true",
                 hidden: desktop.get("Hidden").unwrap_or(&"".to_string()) == "true",
                 exec: desktop.get("Exec").map(|x| x.to_string()),
                 url: desktop.get("URL").map(|x| x.to_string()),
-                keywords: desktop
-                    .get("Keywords")
-                    .map(|x| {
-                        x.split(';')
-                            .map(|y| y.trim().to_string())
-                            .filter(|z| z != "")
-                            .collect()
-                    })
-                    .unwrap_or_else(|| vec![]),
+                keywords: split_list(localized(desktop, "Keywords", locales))
+                    .into_iter()
+                    .chain(split_list(desktop.get("Keywords")))
+                    .unique()
+                    .collect(),
+                categories: split_list(desktop.get("Categories")),
             }),
             None => Err(Box::new(io_error::new(
                 ErrorKind::NotFound,
@@ -55,13 +148,21 @@ impl Desktop {
         }
     }
 
-    fn parse_dir(d: &str) -> Result<Vec<Desktop>, Box<dyn Error>> {
+    fn parse_dir(d: &str, locales: &[String]) -> Result<Vec<Desktop>, Box<dyn Error>> {
         let mut files: Vec<Desktop> = Vec::with_capacity(16);
         for entry in WalkDir::new(d) {
             let entry = entry?;
             let path = entry.path();
 
-            if let Ok(d) = Desktop::parse(path.to_str().unwrap()) {
+            // The id is the path below the applications directory, with the
+            // directories joined by dashes.
+            let id = path
+                .strip_prefix(d)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .trim_end_matches(".desktop")
+                .replace('/', "-");
+            if let Ok(d) = Desktop::parse(path.to_str().unwrap(), id, locales) {
                 files.push(d)
             }
         }
@@ -88,7 +189,84 @@ impl PartialEq for Desktop {
     }
 }
 
-pub fn load_desktop_files() -> Vec<Desktop> {
+impl Hash for Desktop {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+// The local path of a file uri, with its percent escapes decoded.
+fn uri_path(uri: &str) -> Option<String> {
+    let path = uri.strip_prefix("file://")?;
+    // The host, if any, comes before the path
+    let path = &path[path.find('/')?..];
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let escaped = match tail {
+            [hi, lo, ..] if b == b'%' => std::str::from_utf8(&[*hi, *lo])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(escaped) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Whether the Exec key takes files or URLs through its field codes.
+pub fn takes_uris(exec: &str) -> bool {
+    ["%f", "%F", "%u", "%U"]
+        .iter()
+        .any(|code| exec.contains(code))
+}
+
+/// Splits the Exec key into the command line to run, with the field codes
+/// replaced by the given uris. %u and %f take the first uri, and %U and %F,
+/// which stand alone as an argument, take all of them. The file codes only
+/// take uris of local files, as paths. The other field codes are dropped.
+pub fn exec_argv(exec: &str, uris: &[String]) -> Option<Vec<String>> {
+    let paths: Vec<String> = uris.iter().filter_map(|uri| uri_path(uri)).collect();
+    let mut argv = Vec::new();
+    for arg in shlex::split(exec)? {
+        match arg.as_str() {
+            "%U" => argv.extend(uris.iter().cloned()),
+            "%F" => argv.extend(paths.iter().cloned()),
+            _ => {
+                let mut expanded = String::with_capacity(arg.len());
+                let mut chars = arg.chars();
+                while let Some(c) = chars.next() {
+                    if c != '%' {
+                        expanded.push(c);
+                        continue;
+                    }
+                    match chars.next() {
+                        Some('%') => expanded.push('%'),
+                        Some('u') => expanded.push_str(uris.first().map_or("", String::as_str)),
+                        Some('f') => expanded.push_str(paths.first().map_or("", String::as_str)),
+                        _ => (),
+                    }
+                }
+                // An argument that was only a field code goes away if empty
+                if !expanded.is_empty() || !arg.starts_with('%') {
+                    argv.push(expanded);
+                }
+            }
+        }
+    }
+    Some(argv)
+}
+
+fn application_dirs() -> Vec<String> {
     let home = env::var_os("HOME").unwrap().into_string().unwrap();
 
     let xdg_data_home = match env::var_os("XDG_DATA_HOME") {
@@ -102,12 +280,217 @@ pub fn load_desktop_files() -> Vec<Desktop> {
 
     std::iter::once(xdg_data_home.as_str())
         .chain(xdg_data_dirs.split(':'))
-        .map(|p| Desktop::parse_dir(&format!("{}/applications", p)))
+        .map(|p| format!("{}/applications", p))
+        .collect()
+}
+
+// When a directory was last modified, as seconds and nanoseconds since the
+// epoch, or None if it does not exist.
+type Mtime = Option<(u64, u32)>;
+
+fn mtime(path: &Path) -> Mtime {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let since = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some((since.as_secs(), since.subsec_nanos()))
+}
+
+// Every directory desktop files are read from, including those below the
+// applications directories, with when they were last modified. Installing or
+// removing an application modifies the directory of its desktop file.
+fn scanned_dirs(dirs: &[String]) -> Vec<(String, Mtime)> {
+    let mut scanned = Vec::new();
+    for dir in dirs {
+        scanned.push((dir.clone(), mtime(Path::new(dir))));
+        for entry in WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_dir())
+        {
+            scanned.push((
+                entry.path().to_string_lossy().to_string(),
+                mtime(entry.path()),
+            ));
+        }
+    }
+    scanned
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    // The locale keys the names are localized for
+    locales: Vec<String>,
+    dirs: Vec<(String, Mtime)>,
+    entries: Vec<Desktop>,
+}
+
+fn read_cache() -> Result<Cache, Box<dyn Error>> {
+    let file = File::open(cache_file(CACHE_FILE)?)?;
+    #[cfg(feature = "yaml-cfg")]
+    let cache = serde_yaml::from_reader(file)?;
+    #[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+    let cache = serde_json::from_reader(file)?;
+    Ok(cache)
+}
+
+fn write_cache(cache: &Cache) -> Result<(), Box<dyn Error>> {
+    let file = File::create(cache_file(CACHE_FILE)?)?;
+    #[cfg(feature = "yaml-cfg")]
+    serde_yaml::to_writer(file, cache)?;
+    #[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+    serde_json::to_writer(file, cache)?;
+    Ok(())
+}
+
+/// Loads the desktop files, with names and comments localized for the given
+/// language, or for the locale of the messages if none, and caches them.
+pub fn load_desktop_files(language: Option<&str>) -> Vec<Desktop> {
+    let locales = locales(language);
+    let dirs = application_dirs();
+    // Taken before reading, so that changes while reading make the cache
+    // stale rather than being missed
+    let scanned = scanned_dirs(&dirs);
+
+    let entries = dirs
+        .iter()
+        .map(|dir| Desktop::parse_dir(dir, &locales))
         .filter_map(Result::ok)
         .flatten()
         .filter(|d| {
             !d.hidden && !d.no_display && (d.entry_type == "Application" || d.entry_type == "Link")
         })
         .unique_by(|x| x.name.clone())
-        .collect()
+        .collect();
+
+    let cache = Cache {
+        locales,
+        dirs: scanned,
+        entries,
+    };
+    if let Err(err) = write_cache(&cache) {
+        eprintln!("Unable to save desktop cache: {}", err);
+    }
+    cache.entries
+}
+
+/// Desktop files read again in the background, as the cache they were first
+/// taken from was stale.
+pub struct DesktopFiles {
+    reloaded: Arc<Mutex<Option<Vec<Desktop>>>>,
+}
+
+impl DesktopFiles {
+    /// Returns the cached desktop files, or reads them if there is no cache
+    /// for the language. If an applications directory changed since they
+    /// were cached, they are read again in the background, asking for a
+    /// redraw once done.
+    pub fn load(language: Option<String>, tx: Sender<Cmd>) -> (DesktopFiles, Vec<Desktop>) {
+        let files = DesktopFiles {
+            reloaded: Arc::new(Mutex::new(None)),
+        };
+        let cache = match read_cache() {
+            Ok(cache) if cache.locales == locales(language.as_deref()) => cache,
+            _ => return (files, load_desktop_files(language.as_deref())),
+        };
+        if cache.dirs != scanned_dirs(&application_dirs()) {
+            let reloaded = files.reloaded.clone();
+            let _ = thread::Builder::new()
+                .name("desktop".to_string())
+                .spawn(move || {
+                    let entries = load_desktop_files(language.as_deref());
+                    *reloaded.lock().unwrap() = Some(entries);
+                    let _ = tx.send(Cmd::Draw);
+                });
+        }
+        (files, cache.entries)
+    }
+
+    /// The desktop files once they have been read again, if they were.
+    pub fn take_reloaded(&self) -> Option<Vec<Desktop>> {
+        self.reloaded.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(exec: &str, uris: &[&str]) -> Option<Vec<String>> {
+        let uris: Vec<String> = uris.iter().map(|uri| uri.to_string()).collect();
+        exec_argv(exec, &uris)
+    }
+
+    #[test]
+    fn file_uris() {
+        assert_eq!(
+            uri_path("file:///tmp/a.txt"),
+            Some("/tmp/a.txt".to_string())
+        );
+        // The host is not part of the path
+        assert_eq!(
+            uri_path("file://host/tmp/a.txt"),
+            Some("/tmp/a.txt".to_string())
+        );
+        assert_eq!(uri_path("file://host"), None);
+        assert_eq!(
+            uri_path("file:///tmp/a%20b%C3%A9.txt"),
+            Some("/tmp/a bé.txt".to_string())
+        );
+        // Malformed escapes are kept as they are
+        assert_eq!(uri_path("file:///100%zz%4"), Some("/100%zz%4".to_string()));
+        // Escapes that do not decode to UTF-8
+        assert_eq!(uri_path("file:///%ff"), None);
+        assert_eq!(uri_path("https://example.com/a"), None);
+        assert_eq!(uri_path("/tmp/a.txt"), None);
+    }
+
+    #[test]
+    fn exec_field_codes() {
+        let uris = ["file:///tmp/a%20b", "https://example.com/"];
+        assert_eq!(
+            argv("app %U", &uris),
+            Some(vec![
+                "app".to_string(),
+                "file:///tmp/a%20b".to_string(),
+                "https://example.com/".to_string(),
+            ])
+        );
+        assert_eq!(
+            argv("app %F", &uris),
+            Some(vec!["app".to_string(), "/tmp/a b".to_string()])
+        );
+        assert_eq!(
+            argv("app --open=%f %u", &uris),
+            Some(vec![
+                "app".to_string(),
+                "--open=/tmp/a b".to_string(),
+                "file:///tmp/a%20b".to_string(),
+            ])
+        );
+        // %% is a literal percent sign, and other field codes are dropped
+        assert_eq!(
+            argv("printf %% 100%%s %i %c", &[]),
+            Some(vec![
+                "printf".to_string(),
+                "%".to_string(),
+                "100%s".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn exec_without_files() {
+        let urls = ["https://example.com/"];
+        // %U takes any uri, but the file codes only local files
+        assert_eq!(
+            argv("app %U", &urls),
+            Some(vec!["app".to_string(), "https://example.com/".to_string()])
+        );
+        assert_eq!(argv("app %F", &urls), Some(vec!["app".to_string()]));
+        assert_eq!(argv("app %f", &urls), Some(vec!["app".to_string()]));
+        assert_eq!(argv("app %U", &[]), Some(vec!["app".to_string()]));
+        assert_eq!(argv("%U", &[]), Some(vec![]));
+        // Unbalanced quotes
+        assert_eq!(argv("app \"%f", &urls), None);
+    }
 }
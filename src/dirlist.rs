@@ -0,0 +1,154 @@
+//! Directory listings for browsing paths in the launcher. Directories are
+//! read in a background thread, so that typing stays responsive in large
+//! directories.
+
+use crate::cmd::Cmd;
+use crate::matching;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Only this many matches are kept, as no more fit on the launcher line.
+const MAX_MATCHES: usize = 256;
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub dir: bool,
+}
+
+struct Listing {
+    dir: PathBuf,
+    // None while the directory is being read
+    entries: Option<Vec<Entry>>,
+    updated: bool,
+}
+
+/// Splits a path typed in the launcher into the directory to list and the
+/// partial name to filter its entries by. A leading ~ is the home directory.
+pub fn split_path(input: &str) -> (PathBuf, &str) {
+    let (dir, filter) = match input.rfind('/') {
+        Some(idx) => (&input[..idx + 1], &input[idx + 1..]),
+        None if input == "~" => ("~", ""),
+        None => ("", input),
+    };
+    (expand_home(dir), filter)
+}
+
+/// Expands a leading ~ to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    let home = || env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    if path == "~" {
+        home()
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        home().join(rest)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+fn read_dir(dir: &Path) -> Vec<Entry> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut entries: Vec<Entry> = entries
+        .filter_map(Result::ok)
+        .map(|entry| Entry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            // Follows symlinks, so links to directories can be entered
+            dir: entry.path().is_dir(),
+        })
+        .collect();
+    entries.sort_by(|a, b| b.dir.cmp(&a.dir).then_with(|| a.name.cmp(&b.name)));
+    entries
+}
+
+pub struct DirReader {
+    listing: Arc<Mutex<Listing>>,
+    tx: Sender<Cmd>,
+}
+
+impl DirReader {
+    pub fn new(tx: Sender<Cmd>) -> DirReader {
+        DirReader {
+            listing: Arc::new(Mutex::new(Listing {
+                dir: PathBuf::new(),
+                entries: None,
+                updated: false,
+            })),
+            tx,
+        }
+    }
+
+    /// Returns the entries of the directory matching the filter, best match
+    /// first. Reading a directory other than the last one is started in the
+    /// background, and None is returned until it is done. Hidden entries are
+    /// only included when the filter starts with a dot.
+    pub fn matches(&self, dir: &Path, filter: &str) -> Option<Vec<Entry>> {
+        let mut listing = self.listing.lock().unwrap();
+        if listing.dir != dir {
+            listing.dir = dir.to_path_buf();
+            listing.entries = None;
+            drop(listing);
+            self.read(dir.to_path_buf());
+            return None;
+        }
+        let entries = listing.entries.as_ref()?;
+
+        let hidden = filter.starts_with('.');
+        let entries = entries
+            .iter()
+            .filter(|entry| hidden || !entry.name.starts_with('.'));
+        if filter.is_empty() {
+            return Some(entries.take(MAX_MATCHES).cloned().collect());
+        }
+        let matcher = matching::matcher();
+        let filter = filter.to_lowercase();
+        let mut matches: Vec<(i64, &Entry)> = entries
+            .filter_map(|entry| {
+                matcher
+                    .score(&entry.name.to_lowercase(), &filter)
+                    .map(|ma| (ma, entry))
+            })
+            .collect();
+        // The entries are already sorted, which the stable sort keeps for
+        // equal matches.
+        matches.sort_by(|(ma1, _), (ma2, _)| ma2.cmp(ma1));
+        Some(
+            matches
+                .into_iter()
+                .take(MAX_MATCHES)
+                .map(|(_, entry)| entry.clone())
+                .collect(),
+        )
+    }
+
+    /// Returns true once if a directory has been read since the last call.
+    pub fn take_updated(&self) -> bool {
+        std::mem::take(&mut self.listing.lock().unwrap().updated)
+    }
+
+    fn read(&self, dir: PathBuf) {
+        let listing = self.listing.clone();
+        let tx = self.tx.clone();
+        let _ = thread::Builder::new()
+            .name("dir_reader".to_string())
+            .spawn(move || {
+                let entries = read_dir(&dir);
+                let mut listing = listing.lock().unwrap();
+                // Another directory may have been requested meanwhile
+                if listing.dir != dir {
+                    return;
+                }
+                listing.entries = Some(entries);
+                listing.updated = true;
+                drop(listing);
+                let _ = tx.send(Cmd::Draw);
+            });
+    }
+}
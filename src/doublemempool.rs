@@ -20,6 +20,13 @@ use nix::{
 
 use memmap::MmapMut;
 
+use crate::protocol;
+
+// The size of a pool that has not been resized yet, or has been trimmed.
+const MIN_SIZE: usize = 128;
+// A pool that has not been drawn into for this many frames is trimmed.
+const IDLE_FRAMES: u32 = 120;
+
 use wayland_client::{
     protocol::{wl_buffer, wl_shm, wl_shm_pool},
     Main,
@@ -43,6 +50,7 @@ use wayland_client::{
 /// Mempool requires a callback that will be called when the pool becomes free, this
 /// happens when all the pools buffers are released by the server.
 pub struct MemPool {
+    shm: Main<wl_shm::WlShm>,
     file: File,
     len: usize,
     pool: Main<wl_shm_pool::WlShmPool>,
@@ -57,17 +65,11 @@ impl MemPool {
     where
         F: FnMut(wayland_client::DispatchData) + 'static,
     {
-        let mem_fd = create_shm_fd()?;
-        let mem_file = unsafe { File::from_raw_fd(mem_fd) };
-        mem_file.set_len(128)?;
-
-        let pool = shm.create_pool(mem_fd, 128);
-
-        let mmap = unsafe { MmapMut::map_mut(&mem_file).unwrap() };
-
+        let (file, pool, mmap) = allocate(&shm, MIN_SIZE)?;
         Ok(MemPool {
-            file: mem_file,
-            len: 128,
+            shm,
+            file,
+            len: MIN_SIZE,
             pool,
             buffer_count: Rc::new(RefCell::new(0)),
             mmap,
@@ -96,6 +98,33 @@ impl MemPool {
         Ok(())
     }
 
+    /// Release the memory of the pool
+    ///
+    /// As the server cannot shrink a pool, the shared memory file and the pool
+    /// are replaced by new minimal ones. The memory is allocated again by the
+    /// next resize, and the previous contents are lost.
+    ///
+    /// Does nothing and returns false if the pool is in use.
+    pub fn trim(&mut self) -> io::Result<bool> {
+        if self.is_used() {
+            return Ok(false);
+        }
+        if self.len > MIN_SIZE {
+            let (file, pool, mmap) = allocate(&self.shm, MIN_SIZE)?;
+            self.pool.destroy();
+            self.file = file;
+            self.pool = pool;
+            self.mmap = mmap;
+            self.len = MIN_SIZE;
+        }
+        Ok(true)
+    }
+
+    /// Returns the size of the pool in bytes
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
     /// Create a new buffer to this pool
     ///
     /// The parameters are:
@@ -173,6 +202,21 @@ impl io::Seek for MemPool {
     }
 }
 
+// Creates a shared memory file of the given size, together with a pool for it.
+fn allocate(
+    shm: &Main<wl_shm::WlShm>,
+    len: usize,
+) -> io::Result<(File, Main<wl_shm_pool::WlShmPool>, MmapMut)> {
+    let mem_fd = create_shm_fd()?;
+    let mem_file = unsafe { File::from_raw_fd(mem_fd) };
+    mem_file.set_len(len as u64)?;
+
+    let pool = protocol::create_pool(shm, &mem_file, len);
+
+    let mmap = unsafe { MmapMut::map_mut(&mem_file).unwrap() };
+    Ok((mem_file, pool, mmap))
+}
+
 fn create_shm_fd() -> io::Result<RawFd> {
     // Only try memfd on linux
     #[cfg(target_os = "linux")]
@@ -224,38 +268,104 @@ fn create_shm_fd() -> io::Result<RawFd> {
     }
 }
 
+/// The pools to draw a frame with.
+pub struct Frame<'a> {
+    /// The pool to draw into
+    pub pool: &'a mut MemPool,
+    /// The pool holding the most recent frame, if it is not the one drawn
+    /// into. What changed since the pool drawn into was last used must be
+    /// copied from it.
+    pub newest: Option<&'a mut MemPool>,
+    /// Whether the pool drawn into was trimmed, losing its contents
+    pub blank: bool,
+}
+
+/// Two pools used in turn, so a frame can be drawn while the compositor
+/// still reads the previous one.
+///
+/// The pool holding the most recent frame is drawn into again as soon as it
+/// is released, which saves copying the damage over and keeps the memory
+/// warm. The other pool is then only needed while the compositor holds on to
+/// a buffer, and is trimmed after being left idle for a while.
 pub struct DoubleMemPool {
-    pool1: MemPool,
-    pool2: MemPool,
-    switch: bool,
+    pools: [MemPool; 2],
+    blank: [bool; 2],
+    idle_frames: [u32; 2],
+    // The index of the pool holding the most recent frame
+    newest: usize,
+    previous: usize,
 }
 
 impl DoubleMemPool {
     pub fn new(shm: Main<wl_shm::WlShm>) -> ::std::io::Result<DoubleMemPool> {
         Ok(DoubleMemPool {
-            pool1: MemPool::new(shm.clone(), move |_| {})?,
-            pool2: MemPool::new(shm.clone(), move |_| {})?,
-            switch: false,
+            pools: [
+                MemPool::new(shm.clone(), move |_| {})?,
+                MemPool::new(shm, move |_| {})?,
+            ],
+            blank: [true; 2],
+            idle_frames: [0; 2],
+            newest: 0,
+            previous: 0,
         })
     }
 
-    pub fn pool(&mut self) -> Option<(&mut MemPool, &mut MemPool)> {
-        let switch = self.switch;
-        self.switch = !self.switch;
-        let (last, cur) = if !switch {
-            (&mut self.pool2, &mut self.pool1)
+    /// Picks the pool to draw the next frame into, preferring the pool
+    /// holding the most recent frame. Returns None if both are in use.
+    pub fn pool(&mut self) -> Option<Frame<'_>> {
+        let cur = if !self.pools[self.newest].is_used() {
+            self.newest
+        } else if !self.pools[1 - self.newest].is_used() {
+            1 - self.newest
         } else {
-            (&mut self.pool1, &mut self.pool2)
+            return None;
         };
 
-        if cur.is_used() {
-            None
-        } else {
-            Some((last, cur))
+        let other = 1 - cur;
+        self.idle_frames[cur] = 0;
+        self.idle_frames[other] += 1;
+        if self.idle_frames[other] >= IDLE_FRAMES && other != self.newest {
+            self.trim_pool(other);
         }
+
+        self.previous = self.newest;
+        self.newest = cur;
+        let blank = std::mem::replace(&mut self.blank[cur], false);
+        let (first, second) = self.pools.split_at_mut(1);
+        let (pool, last) = if cur == 0 {
+            (&mut first[0], &mut second[0])
+        } else {
+            (&mut second[0], &mut first[0])
+        };
+        Some(Frame {
+            pool,
+            newest: if cur == self.previous {
+                None
+            } else {
+                Some(last)
+            },
+            blank,
+        })
     }
 
+    /// Undoes the last pool selection when the frame was not committed.
     pub fn never_mind(&mut self) {
-        self.switch = !self.switch;
+        self.newest = self.previous;
+    }
+
+    /// Returns the memory held by the pools in bytes.
+    pub fn allocated(&self) -> usize {
+        self.pools.iter().map(|pool| pool.size()).sum()
+    }
+
+    fn trim_pool(&mut self, idx: usize) {
+        match self.pools[idx].trim() {
+            Ok(true) => {
+                self.blank[idx] = true;
+                self.idle_frames[idx] = 0;
+            }
+            Ok(false) => (),
+            Err(err) => eprintln!("Unable to trim memory pool: {}", err),
+        }
     }
 }
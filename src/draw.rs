@@ -1,8 +1,16 @@
+use crate::accessibility;
 use crate::buffer::Buffer;
+use crate::fonts;
+use crate::motion;
 use crate::{color::Color, fonts::FontRef};
 
+use chrono::{Duration, NaiveDateTime, Timelike};
 use rusttype::{point, Scale};
 use std::collections::HashMap;
+use tiny_skia::{
+    FillRule, GradientStop, LineCap, LinearGradient, Paint, Path, PathBuilder, Pixmap, Point, Rect,
+    Shader, SpreadMode, Stroke, Transform,
+};
 
 struct CachedGlyph {
     dimensions: (u32, u32),
@@ -31,6 +39,7 @@ impl CachedGlyph {
                 let pos = x + (y * dimensions.0);
                 render[pos as usize] = o;
             });
+            fonts::count_glyph(font, render.len() * std::mem::size_of::<f32>());
             CachedGlyph {
                 origin,
                 dimensions,
@@ -69,10 +78,21 @@ impl CachedGlyph {
     }
 }
 
+/// How the cursor of text with a cursor is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cursor {
+    /// A line in front of the character at the cursor
+    Line,
+    /// A block over the character at the cursor, which is shown inverted
+    Block,
+    Hidden,
+}
+
 pub struct Font<'a> {
     glyphs: HashMap<char, CachedGlyph>,
     font: FontRef<'a>,
     size: f32,
+    cursor: Cursor,
 }
 
 impl<'a> Font<'a> {
@@ -81,9 +101,14 @@ impl<'a> Font<'a> {
             glyphs: HashMap::new(),
             font,
             size,
+            cursor: Cursor::Line,
         }
     }
 
+    pub fn set_cursor(&mut self, cursor: Cursor) {
+        self.cursor = cursor;
+    }
+
     pub fn add_str_to_cache(&mut self, s: &str) {
         for ch in s.chars() {
             if self.glyphs.get(&ch).is_none() {
@@ -155,14 +180,28 @@ impl<'a> Font<'a> {
 
         let height = buf.get_bounds().3;
         for (i, glyph) in glyphs.iter().enumerate() {
-            if i == cursor {
-                self.draw_cursor(buf, c, x_off as u32, height)?;
+            match self.cursor {
+                Cursor::Block if i == cursor => {
+                    self.fill_cursor(buf, c, x_off as u32, glyph.advance as u32, height)?;
+                    glyph.draw(buf, (x_off, -off), c, bg);
+                }
+                Cursor::Line if i == cursor => {
+                    self.draw_cursor(buf, c, x_off as u32, height)?;
+                    glyph.draw(buf, (x_off, -off), bg, c);
+                }
+                _ => glyph.draw(buf, (x_off, -off), bg, c),
             }
-            glyph.draw(buf, (x_off, -off), bg, c);
             x_off += glyph.advance;
         }
         if cursor == glyphs.len() {
-            self.draw_cursor(buf, c, x_off as u32, height)?;
+            match self.cursor {
+                Cursor::Block => {
+                    let width = (self.size / 2.0) as u32;
+                    self.fill_cursor(buf, c, x_off as u32, width, height)?
+                }
+                Cursor::Line => self.draw_cursor(buf, c, x_off as u32, height)?,
+                Cursor::Hidden => (),
+            }
         }
 
         Ok((x_off as u32, self.size as u32))
@@ -175,9 +214,24 @@ impl<'a> Font<'a> {
         offset: u32,
         height: u32,
     ) -> Result<(), ::std::io::Error> {
-        // draw cursor
+        // draw cursor, thicker in accessibility mode
+        let width = if accessibility::is_enabled() { 3 } else { 1 };
+        self.fill_cursor(buf, c, offset, width, height)
+    }
+
+    // Fills the cursor as far as there is room
+    fn fill_cursor(
+        &self,
+        buf: &mut Buffer,
+        c: &Color,
+        offset: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), ::std::io::Error> {
         for i in 1..height {
-            buf.put((offset, i), c)?
+            for x in 0..width.max(1) {
+                let _ = buf.put((offset.saturating_add(x), i), c);
+            }
         }
         Ok(())
     }
@@ -203,6 +257,30 @@ impl<'a> Font<'a> {
         Ok(max as u32)
     }
 
+    /// The width the text takes when drawn.
+    pub fn auto_text_width(&mut self, s: &str) -> u32 {
+        self.add_str_to_cache(s);
+        s.chars()
+            .filter_map(|ch| self.glyphs.get(&ch))
+            .map(|glyph| glyph.advance)
+            .sum::<i32>()
+            .max(0) as u32
+    }
+
+    /// Where the characters of the text start when drawn, followed by where
+    /// the text ends.
+    pub fn auto_char_offsets(&mut self, s: &str) -> Vec<u32> {
+        self.add_str_to_cache(s);
+        let mut offsets = Vec::with_capacity(s.len() + 1);
+        let mut x_off = 0;
+        offsets.push(0);
+        for ch in s.chars() {
+            x_off += self.glyphs.get(&ch).map_or(0, |glyph| glyph.advance);
+            offsets.push(x_off.max(0) as u32);
+        }
+        offsets
+    }
+
     pub fn auto_draw_text(
         &mut self,
         buf: &mut Buffer,
@@ -306,13 +384,16 @@ impl<'a> Font<'a> {
 }
 
 pub fn draw_box(buf: &mut Buffer, c: &Color, dim: (u32, u32)) -> Result<(), ::std::io::Error> {
+    if dim.0 == 0 || dim.1 == 0 {
+        return Ok(());
+    }
     for x in 0..dim.0 {
         let _ = buf.put((x, 0), c);
         let _ = buf.put((x, dim.1 - 1), c);
     }
     for y in 0..dim.1 {
-        buf.put((0, y), c)?;
-        buf.put((dim.0 - 1, y), c)?;
+        let _ = buf.put((0, y), c);
+        let _ = buf.put((dim.0 - 1, y), c);
     }
 
     Ok(())
@@ -337,3 +418,429 @@ pub fn draw_bar(
 
     Ok(())
 }
+
+//
+// Anti-aliased shape primitives
+//
+// Shapes are rasterized with tiny-skia into a coverage mask the size of the
+// shape, which is then blended into the buffer between the background and
+// the shape color. Like glyphs, the shapes are blended against a known
+// background color rather than the current buffer content, and pixels the
+// shape does not cover are left alone.
+//
+
+#[inline]
+fn put_coverage(buf: &mut Buffer, pos: (u32, u32), bg: &Color, c: &Color, cov: f32) {
+    if cov >= 1.0 {
+        let _ = buf.put(pos, c);
+    } else if cov > 0.0 {
+        let _ = buf.put(pos, &bg.blend(c, cov));
+    }
+}
+
+// Rasterizes the path, filled or stroked, into a mask of the given
+// dimensions, and blends the color into the buffer by its coverage.
+fn draw_path(
+    buf: &mut Buffer,
+    bg: &Color,
+    c: &Color,
+    dim: (u32, u32),
+    path: Option<Path>,
+    stroke: Option<Stroke>,
+) -> Result<(), ::std::io::Error> {
+    let (path, mut mask) = match (path, Pixmap::new(dim.0, dim.1)) {
+        (Some(path), Some(mask)) => (path, mask),
+        // Nothing to draw, or nothing to draw in
+        _ => return Ok(()),
+    };
+    let mut paint = Paint {
+        anti_alias: true,
+        ..Paint::default()
+    };
+    paint.set_color(tiny_skia::Color::WHITE);
+    match stroke {
+        Some(stroke) => mask.stroke_path(&path, &paint, &stroke, Transform::identity(), None),
+        None => mask.fill_path(
+            &path,
+            &paint,
+            FillRule::Winding,
+            Transform::identity(),
+            None,
+        ),
+    }
+    for (idx, pixel) in mask.pixels().iter().enumerate() {
+        let pos = (idx as u32 % dim.0, idx as u32 / dim.0);
+        put_coverage(buf, pos, bg, c, pixel.alpha() as f32 / 255.0);
+    }
+    Ok(())
+}
+
+// Adds a rectangle with rounded corners, with its corners approximated by
+// cubic curves.
+fn push_rounded_rect(pb: &mut PathBuilder, rect: (f32, f32, f32, f32), radius: f32) {
+    let (x, y, w, h) = rect;
+    let r = radius.min(w / 2.0).min(h / 2.0).max(0.0);
+    // The distance of the control points from the corner points
+    let k = r * (1.0 - 0.552_284_8);
+    pb.move_to(x + r, y);
+    pb.line_to(x + w - r, y);
+    pb.cubic_to(x + w - k, y, x + w, y + k, x + w, y + r);
+    pb.line_to(x + w, y + h - r);
+    pb.cubic_to(x + w, y + h - k, x + w - k, y + h, x + w - r, y + h);
+    pb.line_to(x + r, y + h);
+    pb.cubic_to(x + k, y + h, x, y + h - k, x, y + h - r);
+    pb.line_to(x, y + r);
+    pb.cubic_to(x, y + k, x + k, y, x + r, y);
+    pb.close();
+}
+
+/// Fills a rounded rectangle of the given dimensions.
+pub fn draw_rounded_box(
+    buf: &mut Buffer,
+    bg: &Color,
+    c: &Color,
+    dim: (u32, u32),
+    radius: u32,
+) -> Result<(), ::std::io::Error> {
+    let mut pb = PathBuilder::new();
+    push_rounded_rect(
+        &mut pb,
+        (0.0, 0.0, dim.0 as f32, dim.1 as f32),
+        radius as f32,
+    );
+    draw_path(buf, bg, c, dim, pb.finish(), None)
+}
+
+/// Strokes the outline of a rounded rectangle of the given dimensions. The
+/// stroke is drawn on the inside of the rectangle.
+pub fn draw_rounded_outline(
+    buf: &mut Buffer,
+    bg: &Color,
+    c: &Color,
+    dim: (u32, u32),
+    radius: u32,
+    thickness: u32,
+) -> Result<(), ::std::io::Error> {
+    let half = thickness as f32 / 2.0;
+    let mut pb = PathBuilder::new();
+    push_rounded_rect(
+        &mut pb,
+        (
+            half,
+            half,
+            dim.0 as f32 - half * 2.0,
+            dim.1 as f32 - half * 2.0,
+        ),
+        radius as f32 - half,
+    );
+    let stroke = Stroke {
+        width: thickness as f32,
+        ..Stroke::default()
+    };
+    draw_path(buf, bg, c, dim, pb.finish(), Some(stroke))
+}
+
+/// Strokes a circular arc inscribed in a square of the given diameter.
+///
+/// Angles are in radians, with 0 at 12 o'clock and increasing clockwise, so
+/// that a gauge filling up from the top can be drawn with `start` of 0 and a
+/// `sweep` proportional to the value. The stroke is drawn on the inside of
+/// the circle.
+pub fn draw_arc(
+    buf: &mut Buffer,
+    bg: &Color,
+    c: &Color,
+    diameter: u32,
+    thickness: u32,
+    start: f32,
+    sweep: f32,
+) -> Result<(), ::std::io::Error> {
+    use std::f32::consts::{FRAC_PI_2, TAU};
+
+    let sweep = sweep.clamp(0.0, TAU);
+    if sweep == 0.0 {
+        return Ok(());
+    }
+    let center = diameter as f32 / 2.0;
+    let radius = center - thickness as f32 / 2.0;
+    let point = |angle: f32| (center + radius * angle.sin(), center - radius * angle.cos());
+
+    let mut pb = PathBuilder::new();
+    if sweep >= TAU {
+        pb.push_circle(center, center, radius);
+    } else {
+        // In pieces of at most a quarter circle, each a cubic curve whose
+        // control points lie on the tangents at its ends
+        let pieces = (sweep / FRAC_PI_2).ceil();
+        let step = sweep / pieces;
+        let k = radius * 4.0 / 3.0 * (step / 4.0).tan();
+        let (x, y) = point(start);
+        pb.move_to(x, y);
+        for piece in 0..pieces as u32 {
+            let from = start + step * piece as f32;
+            let to = from + step;
+            let (x0, y0) = point(from);
+            let (x1, y1) = point(to);
+            pb.cubic_to(
+                x0 + k * from.cos(),
+                y0 + k * from.sin(),
+                x1 - k * to.cos(),
+                y1 - k * to.sin(),
+                x1,
+                y1,
+            );
+        }
+    }
+    let stroke = Stroke {
+        width: thickness as f32,
+        ..Stroke::default()
+    };
+    draw_path(buf, bg, c, (diameter, diameter), pb.finish(), Some(stroke))
+}
+
+// A spinner turns once a second, in steps of this many ms.
+const SPINNER_STEP: i64 = 100;
+
+/// Draws a spinner, to show that a long operation is in progress, inscribed
+/// in a square of the given diameter. The rotation follows the time, so the
+/// spinner is animated by redrawing it at the times from next_spinner_frame.
+/// While motion is reduced, the spinner is drawn still.
+pub fn draw_spinner(
+    buf: &mut Buffer,
+    bg: &Color,
+    c: &Color,
+    diameter: u32,
+    time: NaiveDateTime,
+) -> Result<(), ::std::io::Error> {
+    use std::f32::consts::TAU;
+
+    let millis = match motion::is_reduced() {
+        true => 0,
+        false => (time.nanosecond() / 1_000_000 % 1000) as i64,
+    };
+    let start = (millis / SPINNER_STEP * SPINNER_STEP) as f32 / 1000.0 * TAU;
+    let thickness = (diameter / 6).max(2);
+    draw_arc(buf, bg, c, diameter, thickness, start, TAU / 4.0)
+}
+
+/// The time of the spinner frame following the given time.
+pub fn next_spinner_frame(time: NaiveDateTime) -> NaiveDateTime {
+    let nanos = (time.nanosecond() % 1_000_000_000) as i64 % (SPINNER_STEP * 1_000_000);
+    time + Duration::nanoseconds(SPINNER_STEP * 1_000_000 - nanos)
+}
+
+/// Strokes a straight line between two points with the given width and
+/// round ends.
+pub fn draw_line(
+    buf: &mut Buffer,
+    bg: &Color,
+    c: &Color,
+    from: (f32, f32),
+    to: (f32, f32),
+    width: f32,
+) -> Result<(), ::std::io::Error> {
+    let dim = (
+        (from.0.max(to.0) + width).ceil().max(0.0) as u32,
+        (from.1.max(to.1) + width).ceil().max(0.0) as u32,
+    );
+    let mut pb = PathBuilder::new();
+    pb.move_to(from.0, from.1);
+    pb.line_to(to.0, to.1);
+    let stroke = Stroke {
+        width,
+        line_cap: LineCap::Round,
+        ..Stroke::default()
+    };
+    draw_path(buf, bg, c, dim, pb.finish(), Some(stroke))
+}
+
+/// Returns the color at `pos` (0.0 to 1.0) of a gradient described by a list
+/// of stops, ordered by position.
+pub fn gradient_color(stops: &[(f32, Color)], pos: f32) -> Color {
+    match stops {
+        [] => Color::new(1.0, 1.0, 1.0, 1.0),
+        [(_, c)] => *c,
+        _ => {
+            let first = &stops[0];
+            if pos <= first.0 {
+                return first.1;
+            }
+            for w in stops.windows(2) {
+                let ((p1, c1), (p2, c2)) = (&w[0], &w[1]);
+                if pos <= *p2 {
+                    let span = p2 - p1;
+                    let ratio = if span > 0.0 { (pos - p1) / span } else { 1.0 };
+                    return c1.blend(c2, ratio);
+                }
+            }
+            stops[stops.len() - 1].1
+        }
+    }
+}
+
+/// Like `draw_bar`, but colors the bar using a horizontal gradient spanning
+/// the full length of the bar.
+pub fn draw_gradient_bar(
+    buf: &mut Buffer,
+    stops: &[(f32, Color)],
+    length: u32,
+    height: u32,
+    fill: f32,
+) -> Result<(), ::std::io::Error> {
+    let fill_pos = (((length as f32) * fill) as u32).min(length);
+    let mut pixmap = match Pixmap::new(length, height) {
+        Some(pixmap) => pixmap,
+        None => return Ok(()),
+    };
+    let stops = stops
+        .iter()
+        .map(|(pos, c)| GradientStop::new(*pos, c.as_skia()))
+        .collect();
+    let shader = LinearGradient::new(
+        Point::from_xy(0.0, 0.0),
+        Point::from_xy(length as f32, 0.0),
+        stops,
+        SpreadMode::Pad,
+        Transform::identity(),
+    )
+    .unwrap_or_else(|| Shader::SolidColor(gradient_color(&[], 0.0).as_skia()));
+    let paint = Paint {
+        shader,
+        ..Paint::default()
+    };
+    if let Some(rect) = Rect::from_xywh(0.0, 0.0, fill_pos as f32, height as f32) {
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+    for (idx, pixel) in pixmap.pixels().iter().enumerate() {
+        let pos = (idx as u32 % length, idx as u32 / length);
+        if pos.0 < fill_pos {
+            let pixel = pixel.demultiply();
+            let c = Color::from_rgba8(pixel.red(), pixel.green(), pixel.blue(), pixel.alpha());
+            let _ = buf.put(pos, &c);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::tests::Rng;
+
+    use memmap::MmapMut;
+
+    const FONT: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+    const CHARS: &[char] = &['a', 'W', 'g', 'j', ' ', '1', ':', 'é', 'ß', '/', '_', '|'];
+
+    // Text of random length and size, drawn at random places with and
+    // without a cursor, stays within the area it is drawn in.
+    #[test]
+    fn fuzz_text() {
+        let data = match std::fs::read(FONT) {
+            Ok(data) => data,
+            // Without the font there is nothing to draw with
+            Err(_) => return,
+        };
+        let font = rusttype::Font::try_from_vec(data).unwrap();
+        let mut rng = Rng::new(3);
+        let (bg, c) = (
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 1.0, 1.0),
+        );
+        for round in 0..500 {
+            let mut text = Font::new(&font, 4.0 + rng.below(40) as f32);
+            text.set_cursor([Cursor::Line, Cursor::Block, Cursor::Hidden][round % 3]);
+            let s: String = (0..rng.below(40))
+                .map(|_| CHARS[rng.below(CHARS.len() as u32) as usize])
+                .collect();
+            let cursor = rng.below(s.chars().count() as u32 + 2) as usize;
+
+            let dim = (1 + rng.below(64), 1 + rng.below(64));
+            let mut mmap = MmapMut::map_anon((4 * dim.0 * dim.1) as usize).unwrap();
+            let area = (rng.below(64), rng.below(64), rng.below(80), rng.below(80));
+            let offset = (rng.below(80), rng.below(80));
+            {
+                let mut buf = Buffer::new(&mut mmap, dim);
+                let mut sub = buf.subdimensions(area).unwrap();
+                let buf = &mut sub.offset(offset).unwrap();
+                text.auto_draw_text(buf, &bg, &c, &s).unwrap();
+                text.auto_draw_text_with_cursor(buf, &bg, &c, &s, cursor)
+                    .unwrap();
+                let colors = vec![c; s.len()];
+                text.auto_draw_text_individual_colors(buf, &bg, &colors, &s)
+                    .unwrap();
+            }
+            for (idx, p) in mmap.chunks(4).enumerate() {
+                let (x, y) = (idx as u32 % dim.0, idx as u32 / dim.0);
+                let inside = x >= area.0 + offset.0
+                    && y >= area.1 + offset.1
+                    && x < area.0 + area.2
+                    && y < area.1 + area.3;
+                assert!(inside || p == [0; 4], "{:?} drew at {:?}", s, (x, y));
+            }
+        }
+    }
+
+    // The pixel of a 32x32 buffer at the position, as the shapes drawn in it
+    // left it.
+    fn pixel(mmap: &MmapMut, pos: (u32, u32)) -> [u8; 4] {
+        let idx = 4 * (pos.1 * 32 + pos.0) as usize;
+        mmap[idx..idx + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn shapes() {
+        let (bg, c) = (
+            Color::new(0.0, 0.0, 0.0, 1.0),
+            Color::new(1.0, 1.0, 1.0, 1.0),
+        );
+        let white = [255; 4];
+
+        let mut mmap = MmapMut::map_anon(4 * 32 * 32).unwrap();
+        draw_rounded_box(&mut Buffer::new(&mut mmap, (32, 32)), &bg, &c, (32, 32), 8).unwrap();
+        assert_eq!(pixel(&mmap, (16, 16)), white);
+        assert_eq!(pixel(&mmap, (16, 0)), white);
+        // The corners are rounded off, and left alone
+        assert_eq!(pixel(&mmap, (0, 0)), [0; 4]);
+        assert_eq!(pixel(&mmap, (31, 31)), [0; 4]);
+
+        let mut mmap = MmapMut::map_anon(4 * 32 * 32).unwrap();
+        let buf = &mut Buffer::new(&mut mmap, (32, 32));
+        draw_rounded_outline(buf, &bg, &c, (32, 32), 0, 2).unwrap();
+        assert_eq!(pixel(&mmap, (0, 16)), white);
+        assert_eq!(pixel(&mmap, (1, 16)), white);
+        assert_eq!(pixel(&mmap, (16, 16)), [0; 4]);
+
+        // A quarter of a circle, from 12 to 3 o'clock
+        let mut mmap = MmapMut::map_anon(4 * 32 * 32).unwrap();
+        let buf = &mut Buffer::new(&mut mmap, (32, 32));
+        draw_arc(buf, &bg, &c, 32, 4, 0.0, std::f32::consts::FRAC_PI_2).unwrap();
+        assert_eq!(pixel(&mmap, (25, 6)), white);
+        assert_eq!(pixel(&mmap, (6, 25)), [0; 4]);
+        assert_eq!(pixel(&mmap, (16, 16)), [0; 4]);
+
+        let mut mmap = MmapMut::map_anon(4 * 32 * 32).unwrap();
+        let buf = &mut Buffer::new(&mut mmap, (32, 32));
+        draw_line(buf, &bg, &c, (4.0, 16.0), (28.0, 16.0), 4.0).unwrap();
+        assert_eq!(pixel(&mmap, (16, 16)), white);
+        assert_eq!(pixel(&mmap, (16, 4)), [0; 4]);
+    }
+
+    #[test]
+    fn gradient_bar() {
+        let stops = [
+            (0.0, Color::new(1.0, 0.0, 0.0, 1.0)),
+            (1.0, Color::new(0.0, 0.0, 1.0, 1.0)),
+        ];
+        let mut mmap = MmapMut::map_anon(4 * 32 * 32).unwrap();
+        draw_gradient_bar(&mut Buffer::new(&mut mmap, (32, 32)), &stops, 32, 4, 0.5).unwrap();
+        // Red fading to blue in ARGB8888, filled halfway
+        let start = pixel(&mmap, (0, 0));
+        let end = pixel(&mmap, (15, 0));
+        assert!(start[2] > 240 && start[0] < 16, "{:?}", start);
+        assert!(end[0] > start[0] && end[2] < start[2], "{:?}", end);
+        assert_eq!(pixel(&mmap, (16, 0)), [0; 4]);
+        assert_eq!(pixel(&mmap, (0, 4)), [0; 4]);
+    }
+}
@@ -0,0 +1,202 @@
+//! Timed events read from an iCalendar file
+//!
+//! Only what is needed to find the upcoming events is read: the start and
+//! summary of every event, and daily or weekly recurrence. All-day events are
+//! left out, as the calendar already shows those.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+#[derive(Clone, Debug)]
+struct Recurrence {
+    period: Duration,
+    count: Option<i64>,
+    until: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub summary: String,
+    start: NaiveDateTime,
+    recurrence: Option<Recurrence>,
+}
+
+impl Event {
+    /// The first start of the event after the given time, if any.
+    pub fn next_start(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        if self.start > after {
+            return Some(self.start);
+        }
+        let recurrence = self.recurrence.as_ref()?;
+        let period = recurrence.period.num_seconds();
+        let n = (after - self.start).num_seconds() / period + 1;
+        if recurrence.count.is_some_and(|count| n >= count) {
+            return None;
+        }
+        let start = self.start + Duration::seconds(n * period);
+        match recurrence.until {
+            Some(until) if start > until => None,
+            _ => Some(start),
+        }
+    }
+}
+
+// Date-times are local, unless they end with Z for UTC. Time zone parameters
+// are not looked up, so those times are taken as local as well.
+fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    match value.strip_suffix('Z') {
+        Some(utc) => {
+            let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+            Some(Local.from_utc_datetime(&utc).naive_local())
+        }
+        None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok(),
+    }
+}
+
+fn parse_rrule(value: &str) -> Option<Recurrence> {
+    let mut period = None;
+    let mut interval = 1;
+    let mut count = None;
+    let mut until = None;
+    for part in value.split(';') {
+        match part.split_once('=')? {
+            ("FREQ", "DAILY") => period = Some(Duration::days(1)),
+            ("FREQ", "WEEKLY") => period = Some(Duration::weeks(1)),
+            ("FREQ", _) => return None,
+            ("INTERVAL", n) => interval = n.parse().ok()?,
+            ("COUNT", n) => count = n.parse().ok(),
+            ("UNTIL", date) => {
+                until = parse_datetime(date).or_else(|| {
+                    let date = NaiveDate::parse_from_str(date, "%Y%m%d").ok()?;
+                    date.and_hms_opt(23, 59, 59)
+                })
+            }
+            // Rules such as BYDAY are not supported, and repeat the event on
+            // the day it started instead.
+            _ => (),
+        }
+    }
+    Some(Recurrence {
+        period: period? * interval.max(1),
+        count,
+        until,
+    })
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => out.push(' '),
+                Some(c) => out.push(c),
+                None => (),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reads the timed events of an iCalendar file.
+pub fn parse_events(content: &str) -> Vec<Event> {
+    // Long lines are folded by starting the continuation with whitespace
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut start = None;
+    let mut summary = String::new();
+    let mut recurrence = None;
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some(prop) => prop,
+            None => continue,
+        };
+        let name = name.split(';').next().unwrap_or_default();
+        match (name, value) {
+            ("BEGIN", "VEVENT") => {
+                start = None;
+                summary.clear();
+                recurrence = None;
+            }
+            ("DTSTART", value) => start = parse_datetime(value),
+            ("SUMMARY", value) => summary = unescape(value),
+            ("RRULE", value) => recurrence = parse_rrule(value),
+            ("END", "VEVENT") => {
+                if let Some(start) = start.take() {
+                    events.push(Event {
+                        summary: summary.clone(),
+                        start,
+                        recurrence: recurrence.take(),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    events
+}
+
+/// The events of an iCalendar file, read again when the file changes.
+pub struct EventFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    events: Vec<Event>,
+}
+
+impl EventFile {
+    pub fn new(path: PathBuf) -> EventFile {
+        let mut file = EventFile {
+            path,
+            modified: None,
+            events: Vec::new(),
+        };
+        file.reload();
+        file
+    }
+
+    /// Returns true if the file changed since it was last read.
+    pub fn reload(&mut self) -> bool {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        self.events = match fs::read_to_string(&self.path) {
+            Ok(content) => parse_events(&content),
+            Err(_) => Vec::new(),
+        };
+        true
+    }
+
+    /// Whether an event starts on the given day.
+    pub fn on(&self, date: NaiveDate) -> bool {
+        let (start, end) = match (date.and_hms_opt(0, 0, 0), date.succ_opt()) {
+            (Some(start), Some(next)) => (start, next.and_hms_opt(0, 0, 0)),
+            _ => return false,
+        };
+        let after = start - Duration::seconds(1);
+        self.events
+            .iter()
+            .filter_map(|event| event.next_start(after))
+            .any(|start| Some(start) < end)
+    }
+
+    /// The next event to start after the given time, and when it starts.
+    pub fn next(&self, after: NaiveDateTime) -> Option<(NaiveDateTime, &Event)> {
+        self.events
+            .iter()
+            .filter_map(|event| Some((event.next_start(after)?, event)))
+            .min_by_key(|(start, _)| *start)
+    }
+}
@@ -0,0 +1,122 @@
+//! Launching programs
+//!
+//! Everything the dashboard launches for the user goes through here, so
+//! that the environment, working directory and prefix from the config apply
+//! to applications, shell commands and bound commands alike.
+
+use crate::dirlist::expand_home;
+
+use std::collections::HashMap;
+use std::io;
+use std::process::{self, Command};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Exec {
+    /// Variables to set for launched programs, or to remove if null.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, Option<String>>,
+    /// The directory to start programs in, instead of that of wldash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// A command that launched programs are passed to as arguments, such as
+    /// `systemd-run --user --scope`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prefix: String,
+    /// A command that applications from desktop files are passed to as
+    /// arguments, inside of the prefix. `{id}` is replaced with the desktop
+    /// file id, escaped for use in a systemd unit name, and `{random}` with a
+    /// random string, such as in
+    /// `systemd-run --user --scope --unit app-wldash-{id}-{random}`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub app_prefix: String,
+}
+
+/// Escapes a string for use in a systemd unit name, like `systemd-escape`.
+pub fn escape_unit_name(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for (idx, b) in s.bytes().enumerate() {
+        match b {
+            b'/' => escaped.push('-'),
+            b'.' if idx == 0 => escaped.push_str("\\x2e"),
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b':' | b'_' | b'.' => escaped.push(b as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    escaped
+}
+
+// Unit names must be unique, so every launch gets its own suffix. It only
+// has to differ between launches, which the time and pid make sure of.
+fn random_suffix() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}", nanos as u64 ^ ((process::id() as u64) << 32))
+}
+
+fn split_prefix(prefix: &str) -> io::Result<Vec<String>> {
+    shlex::split(prefix).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unable to parse launch prefix: {}", prefix),
+        )
+    })
+}
+
+impl Exec {
+    /// Starts the program with the activation token, if any. The app id is
+    /// the desktop file id when launching an application.
+    pub fn spawn(
+        &self,
+        argv: &[String],
+        app_id: Option<&str>,
+        activation_token: Option<String>,
+    ) -> io::Result<()> {
+        let mut argv = argv.to_vec();
+        if let (Some(id), false) = (app_id, self.app_prefix.is_empty()) {
+            let (id, random) = (escape_unit_name(id), random_suffix());
+            let mut prefix: Vec<String> = split_prefix(&self.app_prefix)?
+                .into_iter()
+                .map(|arg| arg.replace("{id}", &id).replace("{random}", &random))
+                .collect();
+            prefix.append(&mut argv);
+            argv = prefix;
+        }
+        if !self.prefix.is_empty() {
+            let mut prefix = split_prefix(&self.prefix)?;
+            prefix.append(&mut argv);
+            argv = prefix;
+        }
+        if argv.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "nothing to run",
+            ));
+        }
+
+        let mut cmd = Command::new(&argv[0]);
+        cmd.args(&argv[1..]);
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(expand_home(cwd));
+        }
+        for (name, value) in self.env.iter() {
+            match value {
+                Some(value) => cmd.env(name, value),
+                None => cmd.env_remove(name),
+            };
+        }
+        // Never pass on the token we may have been started with
+        cmd.env_remove("XDG_ACTIVATION_TOKEN");
+        cmd.env_remove("DESKTOP_STARTUP_ID");
+        if let Some(token) = activation_token {
+            cmd.env("XDG_ACTIVATION_TOKEN", &token);
+            cmd.env("DESKTOP_STARTUP_ID", &token);
+        }
+        cmd.spawn().map(|_| ())
+    }
+}
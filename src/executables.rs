@@ -0,0 +1,121 @@
+//! The commands that can be run from the shell mode of the launcher, for
+//! telling a command that does not exist as it is typed. The directories of
+//! PATH are read in a background thread, and read again once the names are
+//! older than REFRESH, so that newly installed programs are picked up.
+
+use crate::cmd::Cmd;
+use crate::dirlist::expand_home;
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const REFRESH: Duration = Duration::from_secs(30);
+
+// The builtins and reserved words of sh, which are not found in PATH.
+const BUILTINS: &[&str] = &[
+    "!", ".", ":", "[", "alias", "bg", "break", "case", "cd", "command", "continue", "eval",
+    "exec", "exit", "export", "false", "fc", "fg", "for", "getopts", "hash", "if", "jobs", "kill",
+    "pwd", "read", "readonly", "return", "set", "shift", "test", "times", "trap", "true", "type",
+    "ulimit", "umask", "unalias", "unset", "until", "wait", "while", "{",
+];
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn read_path() -> HashSet<String> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path)
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| is_executable(&entry.path()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect()
+}
+
+struct Names {
+    // None until PATH has been read once
+    names: Option<HashSet<String>>,
+    read: Option<Instant>,
+    reading: bool,
+    updated: bool,
+}
+
+pub struct Executables {
+    names: Arc<Mutex<Names>>,
+    tx: Sender<Cmd>,
+}
+
+impl Executables {
+    pub fn new(tx: Sender<Cmd>) -> Executables {
+        Executables {
+            names: Arc::new(Mutex::new(Names {
+                names: None,
+                read: None,
+                reading: false,
+                updated: false,
+            })),
+            tx,
+        }
+    }
+
+    /// Whether the first word of the shell command can be run, as a builtin,
+    /// a program in PATH or the path of a program. Variable assignments and
+    /// anything else the first word cannot be told apart from count as found.
+    /// Returns None while PATH is first read.
+    pub fn resolves(&self, command: &str) -> Option<bool> {
+        let word = match command.split_whitespace().next() {
+            Some(word) => word,
+            None => return Some(true),
+        };
+        if BUILTINS.contains(&word) || word.contains(['=', '$', '`', '"', '\'', '\\', '(']) {
+            return Some(true);
+        }
+        if word.contains('/') {
+            return Some(is_executable(&expand_home(word)));
+        }
+
+        let mut names = self.names.lock().unwrap();
+        let stale = !matches!(names.read, Some(read) if read.elapsed() < REFRESH);
+        if stale && !names.reading {
+            names.reading = true;
+            self.read();
+        }
+        names.names.as_ref().map(|names| names.contains(word))
+    }
+
+    /// Returns true once if PATH has been read since the last call.
+    pub fn take_updated(&self) -> bool {
+        std::mem::take(&mut self.names.lock().unwrap().updated)
+    }
+
+    fn read(&self) {
+        let names = self.names.clone();
+        let tx = self.tx.clone();
+        let _ = thread::Builder::new()
+            .name("executables".to_string())
+            .spawn(move || {
+                let read = read_path();
+                let mut names = names.lock().unwrap();
+                let updated = names.names.as_ref() != Some(&read);
+                names.names = Some(read);
+                names.read = Some(Instant::now());
+                names.reading = false;
+                names.updated |= updated;
+                drop(names);
+                if updated {
+                    let _ = tx.send(Cmd::Draw);
+                }
+            });
+    }
+}
@@ -1,16 +1,27 @@
 //! Utility module for fonts
+//!
+//! Font files are mapped rather than read, so that only the parts of a large
+//! font, such as a CJK font, that glyphs are drawn from take up memory. Each
+//! file is loaded once, however many font names refer to it, and glyphs are
+//! only rasterized when first drawn at a size. `wldash fonts` reports the
+//! memory taken per font file.
 
+use crate::widget::json_string;
+
+#[cfg(feature = "fontconfig-lookup")]
 use fontconfig::Fontconfig as FontConfig;
+use memmap::Mmap;
 use rusttype::Font;
 use std::{
     collections::HashMap,
     fs::File,
-    io::Read,
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
-/// FontMap is used to store different font configurations
-pub type FontMap<'a> = HashMap<String, rusttype::Font<'a>>;
+/// FontMap is used to store different font configurations. Names referring
+/// to the same font file share it.
+pub type FontMap<'a> = HashMap<String, FontRef<'a>>;
 
 /// FontRef is used to store Fonts on widgets.
 pub type FontRef<'a> = &'a rusttype::Font<'a>;
@@ -21,9 +32,16 @@ pub(crate) struct FontSeeker;
 impl FontSeeker {
     /// Acts like fc-match.
     /// Given a string, it matches it to a font file and returns its path.
-    pub(crate) fn from_string(name: &str) -> PathBuf {
-        let fc = FontConfig::new().unwrap();
-        fc.find(name, None).unwrap().path
+    #[cfg(feature = "fontconfig-lookup")]
+    pub(crate) fn from_string(name: &str) -> Option<PathBuf> {
+        let fc = FontConfig::new()?;
+        fc.find(name, None).map(|font| font.path)
+    }
+
+    /// Without fontconfig, fonts can only be given as paths.
+    #[cfg(not(feature = "fontconfig-lookup"))]
+    pub(crate) fn from_string(_: &str) -> Option<PathBuf> {
+        None
     }
 }
 
@@ -31,17 +49,138 @@ impl FontSeeker {
 pub(crate) struct FontLoader;
 
 impl FontLoader {
-    /// Given a path, loads it as a Font, which can be rendered to the screen.
-    pub(crate) fn from_path<'a, P>(path: P) -> Option<Font<'a>>
+    /// Given a path, maps it as a Font, which can be rendered to the screen.
+    /// Fonts are kept for as long as wldash runs.
+    pub(crate) fn from_path<P>(path: P) -> Option<FontRef<'static>>
     where
         P: AsRef<Path>,
     {
-        let mut file = File::open(path).expect("Font file not found");
-        let mut data = match file.metadata() {
-            Ok(metadata) => Vec::with_capacity(metadata.len() as usize),
-            Err(_) => vec![],
+        let file = File::open(path).ok()?;
+        let data: &'static Mmap = Box::leak(Box::new(unsafe { Mmap::map(&file) }.ok()?));
+        let font = Font::try_from_bytes(&data[..])?;
+        Some(Box::leak(Box::new(font)))
+    }
+}
+
+// The memory taken by a font file
+struct Face {
+    // The address of the font, which identifies it
+    font: usize,
+    path: PathBuf,
+    names: Vec<String>,
+    mapped: usize,
+    glyphs: usize,
+    glyph_bytes: usize,
+}
+
+static FACES: Mutex<Vec<Face>> = Mutex::new(Vec::new());
+
+fn register_face(font: FontRef, path: PathBuf, name: &str) {
+    let mut faces = FACES.lock().unwrap();
+    let addr = font as *const _ as usize;
+    match faces.iter_mut().find(|face| face.font == addr) {
+        Some(face) => face.names.push(name.to_string()),
+        None => faces.push(Face {
+            font: addr,
+            mapped: std::fs::metadata(&path).map_or(0, |m| m.len() as usize),
+            path,
+            names: vec![name.to_string()],
+            glyphs: 0,
+            glyph_bytes: 0,
+        }),
+    }
+}
+
+/// Counts a glyph rasterized from the font, taking the given bytes.
+pub(crate) fn count_glyph(font: FontRef, bytes: usize) {
+    let addr = font as *const _ as usize;
+    if let Some(face) = FACES
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .find(|face| face.font == addr)
+    {
+        face.glyphs += 1;
+        face.glyph_bytes += bytes;
+    }
+}
+
+/// Formats the memory taken per font file as a JSON array. The mapped size
+/// is that of the whole file, of which only the parts read take memory.
+pub(crate) fn faces_to_json() -> String {
+    let faces: Vec<String> = FACES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|face| {
+            let names: Vec<String> = face.names.iter().map(|name| json_string(name)).collect();
+            format!(
+                "{{\"path\":{},\"names\":[{}],\"mapped\":{},\"glyphs\":{},\"glyph_bytes\":{}}}",
+                json_string(&face.path.to_string_lossy()),
+                names.join(","),
+                face.mapped,
+                face.glyphs,
+                face.glyph_bytes
+            )
+        })
+        .collect();
+    format!("[{}]", faces.join(","))
+}
+
+/// The font used in place of fonts that cannot be loaded.
+pub(crate) const FALLBACK_FONT: &str = "sans";
+
+/// Loads the fonts of the config, given as font files or fontconfig
+/// patterns. Fonts that cannot be found or loaded are replaced by the
+/// fallback font, and returned by name so that the user can be told. Fails
+/// only if the fallback font cannot be loaded either. The fallback font is
+/// the configured sans font if there is one, so that fontconfig is not
+/// needed when all fonts are given as files.
+pub(crate) fn load_fonts(
+    config: &HashMap<String, String>,
+) -> Result<(FontMap<'static>, Vec<String>), String> {
+    // Fonts by the path of their file, loaded only once
+    let mut loaded: HashMap<PathBuf, FontRef<'static>> = HashMap::new();
+    let mut load = |font: &str| {
+        let path = Path::new(font);
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            FontSeeker::from_string(font)?
         };
-        file.read_to_end(&mut data).unwrap();
-        Font::try_from_vec(data)
+        let path = path.canonicalize().unwrap_or(path);
+        if let Some(font) = loaded.get(&path) {
+            return Some((*font, path));
+        }
+        let font = FontLoader::from_path(&path)?;
+        loaded.insert(path.clone(), font);
+        Some((font, path))
+    };
+    let fallback = config
+        .get(FALLBACK_FONT)
+        .map_or(FALLBACK_FONT, String::as_str);
+    let (fallback, fallback_path) = load(fallback).ok_or_else(|| {
+        format!(
+            "unable to load the {} font, which can be set to a font file",
+            FALLBACK_FONT
+        )
+    })?;
+
+    let mut fonts = FontMap::with_capacity(config.len() + 1);
+    let mut missing = Vec::new();
+    for (name, font) in config.iter() {
+        let (font, path) = load(font).unwrap_or_else(|| {
+            missing.push(font.clone());
+            (fallback, fallback_path.clone())
+        });
+        register_face(font, path, name);
+        fonts.insert(name.clone(), font);
+    }
+    // Widgets default to the sans font
+    if !fonts.contains_key(FALLBACK_FONT) {
+        register_face(fallback, fallback_path, FALLBACK_FONT);
+        fonts.insert(FALLBACK_FONT.to_string(), fallback);
     }
+    missing.sort();
+    Ok((fonts, missing))
 }
@@ -0,0 +1,141 @@
+//! Gamepad and remote navigation through evdev
+//!
+//! Button presses from gamepads under /dev/input are translated into the same
+//! keyboard commands a real keyboard would produce, so the widgets do not need
+//! to know about the gamepad: the d-pad moves the selection, A activates and B
+//! dismisses the dashboard.
+
+use crate::cmd::Cmd;
+use crate::keyboard::{keysyms, KeyState, ModifiersState};
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::mem::size_of;
+use std::os::unix::io::AsRawFd;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use nix::libc::input_event;
+use nix::poll::{poll, PollFd, PollFlags};
+
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+
+const BTN_SOUTH: u16 = 0x130;
+const BTN_EAST: u16 = 0x131;
+const BTN_START: u16 = 0x13b;
+const BTN_MODE: u16 = 0x13c;
+const BTN_DPAD_UP: u16 = 0x220;
+const BTN_DPAD_DOWN: u16 = 0x221;
+const BTN_DPAD_LEFT: u16 = 0x222;
+const BTN_DPAD_RIGHT: u16 = 0x223;
+const KEY_CNT: usize = 0x300;
+
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+// How often to look for new devices when no gamepad is connected, in ms.
+const RESCAN_INTERVAL: i32 = 5000;
+
+// EVIOCGBIT(EV_KEY, len)
+nix::ioctl_read_buf!(eviocgbit_key, b'E', 0x21, u8);
+
+fn is_gamepad(file: &File) -> bool {
+    let mut bits = [0u8; KEY_CNT / 8];
+    if unsafe { eviocgbit_key(file.as_raw_fd(), &mut bits) }.is_err() {
+        return false;
+    }
+    let btn = BTN_SOUTH as usize;
+    bits[btn / 8] & (1 << (btn % 8)) != 0
+}
+
+fn open_gamepads() -> Vec<File> {
+    let entries = match fs::read_dir("/dev/input") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .filter_map(|entry| File::open(entry.path()).ok())
+        .filter(is_gamepad)
+        .collect()
+}
+
+fn key(keysym: u32) -> Cmd {
+    Cmd::Keyboard {
+        key: keysym,
+        key_state: KeyState::Pressed,
+        modifiers_state: ModifiersState::default(),
+        interpreted: None,
+    }
+}
+
+fn translate(type_: u16, code: u16, value: i32) -> Option<Cmd> {
+    match (type_, code, value) {
+        (EV_KEY, BTN_SOUTH, 1) | (EV_KEY, BTN_START, 1) => Some(key(keysyms::XKB_KEY_Return)),
+        (EV_KEY, BTN_EAST, 1) => Some(Cmd::Exit),
+        (EV_KEY, BTN_MODE, 1) => Some(Cmd::ToggleVisible),
+        (EV_KEY, BTN_DPAD_UP, 1) | (EV_ABS, ABS_HAT0Y, -1) => Some(key(keysyms::XKB_KEY_Up)),
+        (EV_KEY, BTN_DPAD_DOWN, 1) | (EV_ABS, ABS_HAT0Y, 1) => Some(key(keysyms::XKB_KEY_Down)),
+        (EV_KEY, BTN_DPAD_LEFT, 1) | (EV_ABS, ABS_HAT0X, -1) => Some(key(keysyms::XKB_KEY_Left)),
+        (EV_KEY, BTN_DPAD_RIGHT, 1) | (EV_ABS, ABS_HAT0X, 1) => Some(key(keysyms::XKB_KEY_Right)),
+        _ => None,
+    }
+}
+
+fn listen(tx: Sender<Cmd>) {
+    const EVENT_SIZE: usize = size_of::<input_event>();
+    let mut devices = open_gamepads();
+    let mut buf = [0u8; EVENT_SIZE * 16];
+
+    loop {
+        let mut fds: Vec<PollFd> = devices
+            .iter()
+            .map(|dev| PollFd::new(dev.as_raw_fd(), PollFlags::POLLIN))
+            .collect();
+        if poll(&mut fds, RESCAN_INTERVAL).unwrap_or(0) == 0 {
+            if devices.is_empty() {
+                devices = open_gamepads();
+            }
+            continue;
+        }
+
+        let mut alive = Vec::with_capacity(devices.len());
+        for (mut dev, fd) in devices.into_iter().zip(fds) {
+            let ready = fd.revents().is_some_and(|r| !r.is_empty());
+            if !ready {
+                alive.push(dev);
+                continue;
+            }
+            let n = match dev.read(&mut buf) {
+                Ok(n) if n > 0 => n,
+                // The device was unplugged
+                _ => continue,
+            };
+            alive.push(dev);
+
+            // type, code and value make up the last 8 bytes of the event,
+            // after the timestamp.
+            for ev in buf[..n].chunks_exact(EVENT_SIZE) {
+                let ev = &ev[EVENT_SIZE - 8..];
+                let type_ = u16::from_ne_bytes([ev[0], ev[1]]);
+                let code = u16::from_ne_bytes([ev[2], ev[3]]);
+                let value = i32::from_ne_bytes([ev[4], ev[5], ev[6], ev[7]]);
+                if let Some(cmd) = translate(type_, code, value) {
+                    if tx.send(cmd).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        devices = alive;
+    }
+}
+
+/// Reads gamepads in a background thread, forwarding navigation as commands.
+pub fn listen_gamepads(tx: Sender<Cmd>) {
+    let _ = thread::Builder::new()
+        .name("gamepad".to_string())
+        .spawn(move || listen(tx));
+}
@@ -0,0 +1,354 @@
+//! Help overlay
+//!
+//! Lists the key bindings over the dashboard. The bindings are taken from
+//! the key binding tables, with the keys named through the keymap. The
+//! launch statistics of the launcher can be shown the same way.
+
+use crate::cmd::Action;
+use crate::color::Color;
+use crate::data::Data;
+use crate::draw::Font;
+use crate::fonts::FontRef;
+use crate::keybindings::{self, GLOBAL, LAUNCHER};
+use crate::widget::{
+    self, Constraints, DrawContext, DrawReport, KeyState, ModifiersState, Scroll, StateValue,
+    TreeNode, WaitContext, WidgetState,
+};
+
+use chrono::{Local, TimeZone};
+
+const FONT_SIZE: f32 = 16.0;
+const LINE_HEIGHT: u32 = 20;
+const MARGIN: u32 = 16;
+// The number of applications listed in the launch statistics
+const MAX_STATS: usize = 20;
+const DAY: f64 = 86400.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Page {
+    Help,
+    Stats,
+}
+
+// A titled table, with the first column highlighted.
+struct Section {
+    title: &'static str,
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Section {
+    fn bindings(title: &'static str, described: Vec<(String, &'static str)>) -> Section {
+        Section {
+            title,
+            header: Vec::new(),
+            rows: described
+                .into_iter()
+                .map(|(keys, text)| vec![keys, text.to_string()])
+                .collect(),
+        }
+    }
+
+    // The most launched applications, with how often and when they were
+    // launched. Launches per day are only known since the first launch
+    // that was timed.
+    fn launches(data: &Data, now: i64) -> Section {
+        let mut entries: Vec<_> = data.entries.iter().collect();
+        entries.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let rows = entries
+            .into_iter()
+            .take(MAX_STATS)
+            .map(|(name, count)| {
+                let usage = data.usage.get(name);
+                let per_day = usage.map_or("-".to_string(), |usage| {
+                    let days = ((now - usage.first) as f64 / DAY).max(1.0);
+                    format!("{:.1}", usage.launches as f64 / days)
+                });
+                let last = usage
+                    .and_then(|usage| Local.timestamp_opt(usage.last, 0).single())
+                    .map_or("-".to_string(), |t| t.format("%Y-%m-%d %H:%M").to_string());
+                vec![name.clone(), count.to_string(), per_day, last]
+            })
+            .collect();
+        Section {
+            title: "Launches",
+            header: ["Application", "Launches", "Per day", "Last used"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            rows,
+        }
+    }
+}
+
+/// Shows the help or the launch statistics over the child when toggled, and
+/// passes everything on to the child otherwise.
+pub struct HelpOverlay<'a> {
+    launcher: bool,
+    sections: Vec<Section>,
+    shown: Option<Page>,
+    changed: bool,
+    // The size the child was last drawn at
+    dim: (u32, u32),
+    font: Font<'a>,
+    child: Box<dyn widget::Widget + Send + 'a>,
+}
+
+impl<'a> HelpOverlay<'a> {
+    pub fn new(
+        launcher: bool,
+        font: FontRef<'a>,
+        child: Box<dyn widget::Widget + Send + 'a>,
+    ) -> Box<HelpOverlay<'a>> {
+        Box::new(HelpOverlay {
+            launcher,
+            sections: Vec::new(),
+            shown: None,
+            changed: false,
+            dim: (0, 0),
+            font: Font::new(font, FONT_SIZE),
+            child,
+        })
+    }
+
+    fn set_shown(&mut self, shown: Option<Page>) {
+        if shown == self.shown {
+            return;
+        }
+        // The keymap and launches may have changed since last shown
+        self.sections = match shown {
+            Some(Page::Help) => {
+                let mut sections = vec![Section::bindings(
+                    "Dashboard",
+                    keybindings::describe(GLOBAL),
+                )];
+                if self.launcher {
+                    sections.push(Section::bindings(
+                        "Launcher",
+                        keybindings::describe(LAUNCHER),
+                    ));
+                }
+                sections
+            }
+            Some(Page::Stats) => vec![Section::launches(
+                &Data::load().unwrap_or_default(),
+                Local::now().timestamp(),
+            )],
+            None => Vec::new(),
+        };
+        self.shown = shown;
+        self.changed = true;
+    }
+
+    fn toggle(&mut self, page: Page) {
+        if self.shown == Some(page) {
+            self.set_shown(None);
+        } else {
+            self.set_shown(Some(page));
+        }
+    }
+
+    fn draw_help(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+    ) -> Result<(), ::std::io::Error> {
+        let buf = &mut ctx
+            .buf
+            .subdimensions((pos.0, pos.1, self.dim.0, self.dim.1))?;
+        buf.memset(ctx.bg);
+
+        let title = Color::new(1.0, 1.0, 1.0, 1.0);
+        let key = Color::new(1.0, 1.0, 0.0, 1.0);
+        let description = Color::new(0.75, 0.75, 0.75, 1.0);
+
+        // The titles are drawn and the rows placed first, so that the rows
+        // can be drawn a column at a time, each lined up after the widest
+        // cell of the one before it. Rows that do not fit are left out.
+        let fits = |y: u32| y + LINE_HEIGHT <= self.dim.1;
+        let mut y = MARGIN;
+        let mut rows = Vec::new();
+        for section in self.sections.iter() {
+            if !fits(y) {
+                break;
+            }
+            self.font.auto_draw_text(
+                &mut buf.offset((MARGIN, y))?,
+                ctx.bg,
+                &title,
+                section.title,
+            )?;
+            y += LINE_HEIGHT;
+            if !section.header.is_empty() && fits(y) {
+                rows.push((y, &section.header, true));
+                y += LINE_HEIGHT;
+            }
+            for row in section.rows.iter() {
+                if !fits(y) {
+                    break;
+                }
+                rows.push((y, row, false));
+                y += LINE_HEIGHT;
+            }
+            y += LINE_HEIGHT / 2;
+        }
+
+        let columns = rows.iter().map(|(_, cells, _)| cells.len()).max();
+        let mut x = MARGIN;
+        for column in 0..columns.unwrap_or(0) {
+            let mut widest = 0;
+            for (y, cells, header) in rows.iter() {
+                let cell = match cells.get(column) {
+                    Some(cell) => cell,
+                    None => continue,
+                };
+                let color = match (header, column) {
+                    (true, _) => &title,
+                    (false, 0) => &key,
+                    (false, _) => &description,
+                };
+                let (width, _) =
+                    self.font
+                        .auto_draw_text(&mut buf.offset((x, *y))?, ctx.bg, color, cell)?;
+                widest = widest.max(width);
+            }
+            x += widest + MARGIN;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> widget::Widget for HelpOverlay<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.child.wait(ctx)
+    }
+    fn enter(&mut self) {
+        self.child.enter()
+    }
+    fn leave(&mut self) {
+        self.set_shown(None);
+        self.child.leave()
+    }
+    fn size(&self) -> (u32, u32) {
+        self.child.size()
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let changed = std::mem::take(&mut self.changed);
+        if self.shown.is_some() {
+            if changed || ctx.force {
+                self.draw_help(ctx, pos)?;
+                return Ok(DrawReport {
+                    width: self.dim.0,
+                    height: self.dim.1,
+                    damage: Vec::new(),
+                    full_damage: true,
+                });
+            }
+            return Ok(DrawReport::empty(self.dim.0, self.dim.1));
+        }
+
+        // The help is gone, so everything under it is drawn again
+        let force = ctx.force;
+        if changed {
+            ctx.buf
+                .subdimensions((pos.0, pos.1, self.dim.0, self.dim.1))?
+                .memset(ctx.bg);
+            ctx.force = true;
+        }
+        let report = self.child.draw(ctx, pos, expansion);
+        ctx.force = force;
+        let mut report = report?;
+        self.dim = (report.width, report.height);
+        report.full_damage |= changed;
+        Ok(report)
+    }
+
+    fn keyboard_input(
+        &mut self,
+        keysym: u32,
+        modifier_state: ModifiersState,
+        key_state: KeyState,
+        interpreted: Option<String>,
+    ) {
+        // Any key closes the help, without reaching the widgets under it
+        if self.shown.is_some() {
+            if key_state == KeyState::Pressed {
+                self.set_shown(None);
+            }
+            return;
+        }
+        self.child
+            .keyboard_input(keysym, modifier_state, key_state, interpreted);
+    }
+
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        self.child.constraints()
+    }
+
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        if self.shown.is_some() {
+            self.set_shown(None);
+            return;
+        }
+        self.child.mouse_click(button, pos);
+    }
+
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        if self.shown.is_none() {
+            self.child.mouse_press(button, pos);
+        }
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        if self.shown.is_none() {
+            self.child.mouse_scroll(scroll, pos);
+        }
+    }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        if self.shown.is_none() {
+            self.child.mouse_motion(pos);
+        }
+    }
+
+    fn action(&mut self, action: &Action) {
+        match action {
+            Action::ToggleHelp => self.toggle(Page::Help),
+            Action::ToggleStats => self.toggle(Page::Stats),
+            _ => self.child.action(action),
+        }
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos)
+            .value("shown", StateValue::Bool(self.shown.is_some()))
+            .child(self.child.tree(pos))
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        self.child.focused_only(regions);
+    }
+
+    fn has_overlay(&self) -> bool {
+        // The help covers the child, along with what it would draw above
+        self.shown.is_none() && self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
+}
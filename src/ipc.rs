@@ -0,0 +1,58 @@
+//! The control socket protocol
+//!
+//! Clients send one command per line. A client may start with `hello` and
+//! the protocol version it speaks, which is answered with a line of JSON
+//! holding the version of the running wldash and the commands it supports,
+//! so that a client can tell an older wldash apart and leave out what it
+//! does not support. Commands that are not understood are answered with a
+//! line of JSON describing the error, rather than being dropped silently.
+//!
+//! The version is raised whenever an existing command changes in a way
+//! that would break clients. New commands only add to the capabilities.
+
+use crate::widget::json_string;
+
+pub const VERSION: u32 = 1;
+
+/// The commands understood, by their first word.
+pub const CAPABILITIES: &[&str] = &[
+    "hello",
+    "kill",
+    "toggle_visible",
+    "stats",
+    "refresh",
+    "offline",
+    "private",
+    "accessibility",
+    "window",
+    "osd",
+    "export",
+    "fonts",
+    "dump-tree",
+    "query",
+];
+
+/// The answer to a handshake with a client speaking the given version.
+pub fn hello(client: &str) -> String {
+    match client.trim().parse::<u32>() {
+        Ok(_) => format!(
+            "{{\"version\":{},\"capabilities\":[{}]}}",
+            VERSION,
+            CAPABILITIES
+                .iter()
+                .map(|c| json_string(c))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Err(_) => error("invalid-version", client),
+    }
+}
+
+/// An error, with a code for clients to match on and what it concerns.
+pub fn error(code: &str, detail: &str) -> String {
+    format!(
+        "{{\"error\":{},\"detail\":{}}}",
+        json_string(code),
+        json_string(detail)
+    )
+}
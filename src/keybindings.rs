@@ -0,0 +1,303 @@
+//! The key bindings of wldash
+//!
+//! Keys are looked up in these tables rather than matched where they are
+//! handled, so that the help overlay lists exactly the keys that work.
+
+use crate::keyboard::{keysym_name, keysyms, ModifiersState};
+
+pub const CTRL: u8 = 1;
+pub const ALT: u8 = 2;
+pub const SHIFT: u8 = 4;
+
+pub struct KeyBinding<A: 'static> {
+    pub keysym: u32,
+    // The modifiers that must be held. Other modifiers are ignored, so
+    // bindings with more modifiers must come before those with fewer.
+    pub modifiers: u8,
+    pub action: A,
+    pub description: &'static str,
+}
+
+impl<A> KeyBinding<A> {
+    fn matches(&self, keysym: u32, modifiers: ModifiersState) -> bool {
+        let held = |flag, held| self.modifiers & flag == 0 || held;
+        keysym == self.keysym
+            && held(CTRL, modifiers.ctrl)
+            && held(ALT, modifiers.alt)
+            && held(SHIFT, modifiers.shift)
+    }
+
+    /// The key combination, with the key named as in the keymap.
+    pub fn combination(&self) -> String {
+        let mut combination = String::new();
+        for (flag, name) in [(CTRL, "Ctrl+"), (ALT, "Alt+"), (SHIFT, "Shift+")] {
+            if self.modifiers & flag != 0 {
+                combination.push_str(name);
+            }
+        }
+        combination.push_str(&keysym_name(self.keysym));
+        combination
+    }
+}
+
+/// Finds the action of the first binding matching the key.
+pub fn lookup<A: Copy>(
+    bindings: &[KeyBinding<A>],
+    keysym: u32,
+    modifiers: ModifiersState,
+) -> Option<A> {
+    bindings
+        .iter()
+        .find(|binding| binding.matches(keysym, modifiers))
+        .map(|binding| binding.action)
+}
+
+/// Lists the key combinations of the bindings with their descriptions,
+/// joining the combinations of consecutive bindings that do the same.
+pub fn describe<A>(bindings: &[KeyBinding<A>]) -> Vec<(String, &'static str)> {
+    let mut described: Vec<(String, &'static str)> = Vec::new();
+    for binding in bindings {
+        match described.last_mut() {
+            Some((combinations, description)) if *description == binding.description => {
+                combinations.push_str(", ");
+                combinations.push_str(&binding.combination());
+            }
+            _ => described.push((binding.combination(), binding.description)),
+        }
+    }
+    described
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GlobalKey {
+    Hide,
+    Help,
+    Accessibility,
+    Refresh,
+}
+
+pub const GLOBAL: &[KeyBinding<GlobalKey>] = &[
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Escape,
+        modifiers: 0,
+        action: GlobalKey::Hide,
+        description: "Hide the dashboard",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_c,
+        modifiers: CTRL,
+        action: GlobalKey::Hide,
+        description: "Hide the dashboard",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_F1,
+        modifiers: 0,
+        action: GlobalKey::Help,
+        description: "Show or hide this help",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_a,
+        modifiers: CTRL | ALT,
+        action: GlobalKey::Accessibility,
+        description: "Toggle accessibility mode",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_F5,
+        modifiers: 0,
+        action: GlobalKey::Refresh,
+        description: "Refresh the widgets",
+    },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LauncherKey {
+    Clear,
+    CopyPath,
+    CopyCommand,
+    Private,
+    Search,
+    CancelSearch,
+    Sort,
+    Start,
+    End,
+    WordLeft,
+    WordRight,
+    DeleteWordLeft,
+    DeleteWordRight,
+    DeleteLeft,
+    DeleteRight,
+    Activate,
+    ActivateUnsandboxed,
+    Next,
+    Previous,
+    Left,
+    Right,
+    OlderEntry,
+    NewerEntry,
+    Help,
+}
+
+pub const LAUNCHER: &[KeyBinding<LauncherKey>] = &[
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Return,
+        modifiers: SHIFT,
+        action: LauncherKey::ActivateUnsandboxed,
+        description: "Run a shell command outside the sandbox",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Return,
+        modifiers: 0,
+        action: LauncherKey::Activate,
+        description: "Launch, open or pick the selection",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Tab,
+        modifiers: 0,
+        action: LauncherKey::Next,
+        description: "Expand a :shortcode:, or select the next result",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_ISO_Left_Tab,
+        modifiers: SHIFT,
+        action: LauncherKey::Previous,
+        description: "Select the previous result",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_u,
+        modifiers: CTRL,
+        action: LauncherKey::Clear,
+        description: "Clear the input",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_C,
+        modifiers: CTRL | ALT | SHIFT,
+        action: LauncherKey::CopyPath,
+        description: "Copy the desktop file of the selection",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_C,
+        modifiers: CTRL | SHIFT,
+        action: LauncherKey::CopyCommand,
+        description: "Copy the command of the selection",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_p,
+        modifiers: CTRL,
+        action: LauncherKey::Private,
+        description: "Toggle private mode",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_r,
+        modifiers: CTRL,
+        action: LauncherKey::Search,
+        description: "Search the history of the mode, again for an older entry",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_g,
+        modifiers: CTRL,
+        action: LauncherKey::CancelSearch,
+        description: "Cancel the history search",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_s,
+        modifiers: CTRL,
+        action: LauncherKey::Sort,
+        description: "Sort the results by best match or most used",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_a,
+        modifiers: CTRL,
+        action: LauncherKey::Start,
+        description: "Move to the start",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Home,
+        modifiers: 0,
+        action: LauncherKey::Start,
+        description: "Move to the start",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_e,
+        modifiers: CTRL,
+        action: LauncherKey::End,
+        description: "Move to the end",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_End,
+        modifiers: 0,
+        action: LauncherKey::End,
+        description: "Move to the end",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Left,
+        modifiers: CTRL,
+        action: LauncherKey::WordLeft,
+        description: "Move a word left",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Right,
+        modifiers: CTRL,
+        action: LauncherKey::WordRight,
+        description: "Move a word right",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_BackSpace,
+        modifiers: CTRL,
+        action: LauncherKey::DeleteWordLeft,
+        description: "Delete the previous word",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_w,
+        modifiers: CTRL,
+        action: LauncherKey::DeleteWordLeft,
+        description: "Delete the previous word",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_d,
+        modifiers: ALT,
+        action: LauncherKey::DeleteWordRight,
+        description: "Delete the next word",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_BackSpace,
+        modifiers: 0,
+        action: LauncherKey::DeleteLeft,
+        description: "Delete the previous character, or the category",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Delete,
+        modifiers: 0,
+        action: LauncherKey::DeleteRight,
+        description: "Delete the next character",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Left,
+        modifiers: 0,
+        action: LauncherKey::Left,
+        description: "Move left",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Right,
+        modifiers: 0,
+        action: LauncherKey::Right,
+        description: "Move right",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Page_Up,
+        modifiers: 0,
+        action: LauncherKey::OlderEntry,
+        description: "Go back through the commands or calculations",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_Page_Down,
+        modifiers: 0,
+        action: LauncherKey::NewerEntry,
+        description: "Go forward through the commands or calculations",
+    },
+    KeyBinding {
+        keysym: keysyms::XKB_KEY_question,
+        modifiers: 0,
+        action: LauncherKey::Help,
+        description: "Show this help, in an empty prompt",
+    },
+];
@@ -1,4 +1,10 @@
-#![allow(missing_docs, non_upper_case_globals, unused_parens, clippy::all)]
+#![allow(
+    dead_code,
+    missing_docs,
+    non_upper_case_globals,
+    unused_parens,
+    clippy::all
+)]
 
 pub const XKB_KEY_BackSpace: u32 = 0xff08; /* Back space, back char */
 pub const XKB_KEY_Tab: u32 = 0xff09;
@@ -7,11 +13,43 @@ pub const XKB_KEY_Escape: u32 = 0xff1b;
 pub const XKB_KEY_Delete: u32 = 0xffff; /* Delete, rubout */
 pub const XKB_KEY_Home: u32 = 0xff50;
 pub const XKB_KEY_Left: u32 = 0xff51; /* Move left, left arrow */
+pub const XKB_KEY_Up: u32 = 0xff52; /* Move up, up arrow */
 pub const XKB_KEY_Right: u32 = 0xff53; /* Move right, right arrow */
+pub const XKB_KEY_Down: u32 = 0xff54; /* Move down, down arrow */
+pub const XKB_KEY_Page_Up: u32 = 0xff55;
+pub const XKB_KEY_Page_Down: u32 = 0xff56;
 pub const XKB_KEY_End: u32 = 0xff57; /* EOL */
+pub const XKB_KEY_F1: u32 = 0xffbe;
+pub const XKB_KEY_F5: u32 = 0xffc2;
+
+pub const XKB_KEY_XF86MonBrightnessUp: u32 = 0x1008ff02; /* Monitor/panel brightness */
+pub const XKB_KEY_XF86MonBrightnessDown: u32 = 0x1008ff03; /* Monitor/panel brightness */
+pub const XKB_KEY_XF86AudioLowerVolume: u32 = 0x1008ff11; /* Volume control down */
+pub const XKB_KEY_XF86AudioMute: u32 = 0x1008ff12; /* Mute sound from the system */
+pub const XKB_KEY_XF86AudioRaiseVolume: u32 = 0x1008ff13; /* Volume control up */
+pub const XKB_KEY_XF86AudioMicMute: u32 = 0x1008ffb2; /* Mute the Mic from the system */
 
 pub const XKB_KEY_ISO_Left_Tab: u32 = 0xfe20;
+pub const XKB_KEY_Multi_key: u32 = 0xff20; /* Multi-key character compose */
+pub const XKB_KEY_dead_acute: u32 = 0xfe51;
+pub const XKB_KEY_dead_diaeresis: u32 = 0xfe57;
+pub const XKB_KEY_Shift_L: u32 = 0xffe1; /* Left shift */
+pub const XKB_KEY_Control_L: u32 = 0xffe3; /* Left control */
+pub const XKB_KEY_space: u32 = 0x0020; /* U+0020 SPACE */
 pub const XKB_KEY_e: u32 = 0x0065; /* U+0065 LATIN SMALL LETTER E */
 pub const XKB_KEY_a: u32 = 0x0061; /* U+0061 LATIN SMALL LETTER A */
 pub const XKB_KEY_c: u32 = 0x0063; /* U+0063 LATIN SMALL LETTER C */
+pub const XKB_KEY_C: u32 = 0x0043; /* U+0043 LATIN CAPITAL LETTER C */
 pub const XKB_KEY_u: u32 = 0x0075; /* U+0075 LATIN SMALL LETTER U */
+pub const XKB_KEY_s: u32 = 0x0073; /* U+0073 LATIN SMALL LETTER S */
+pub const XKB_KEY_p: u32 = 0x0070; /* U+0070 LATIN SMALL LETTER P */
+pub const XKB_KEY_w: u32 = 0x0077; /* U+0077 LATIN SMALL LETTER W */
+pub const XKB_KEY_d: u32 = 0x0064; /* U+0064 LATIN SMALL LETTER D */
+pub const XKB_KEY_r: u32 = 0x0072; /* U+0072 LATIN SMALL LETTER R */
+pub const XKB_KEY_g: u32 = 0x0067; /* U+0067 LATIN SMALL LETTER G */
+pub const XKB_KEY_k: u32 = 0x006b; /* U+006B LATIN SMALL LETTER K */
+pub const XKB_KEY_n: u32 = 0x006e; /* U+006E LATIN SMALL LETTER N */
+pub const XKB_KEY_y: u32 = 0x0079; /* U+0079 LATIN SMALL LETTER Y */
+pub const XKB_KEY_question: u32 = 0x003f; /* U+003F QUESTION MARK */
+pub const XKB_KEY_1: u32 = 0x0031; /* U+0031 DIGIT ONE */
+pub const XKB_KEY_9: u32 = 0x0039; /* U+0039 DIGIT NINE */
@@ -16,7 +16,8 @@ use std::{cell::RefCell, os::unix::io::RawFd, rc::Rc};
 
 use byteorder::{ByteOrder, NativeEndian};
 
-pub use wayland_client::protocol::wl_keyboard::KeyState;
+use crate::protocol;
+pub use crate::protocol::KeyState;
 use wayland_client::{
     protocol::{wl_keyboard, wl_seat, wl_surface},
     Attached,
@@ -127,6 +128,105 @@ where
     Ok(keyboard.detach())
 }
 
+/// The name of a keysym for display, such as `BackSpace` or `U`. Keysyms that
+/// produce a character are shown as that character, in upper case.
+pub fn keysym_name(keysym: u32) -> String {
+    if keysym == keysyms::XKB_KEY_ISO_Left_Tab {
+        // The keysym of Shift+Tab
+        return "Tab".to_string();
+    }
+    let xkbh = match ffi::XKBCOMMON_OPTION.as_ref() {
+        Some(xkbh) => xkbh,
+        None => return format!("{:#x}", keysym),
+    };
+    let c = unsafe { (xkbh.xkb_keysym_to_utf32)(keysym) };
+    match char::from_u32(c) {
+        Some(c) if !c.is_control() && !c.is_whitespace() => {
+            return c.to_uppercase().collect();
+        }
+        _ => (),
+    }
+    let mut name = [0u8; 64];
+    let len =
+        unsafe { (xkbh.xkb_keysym_get_name)(keysym, name.as_mut_ptr() as *mut _, name.len()) };
+    if len <= 0 {
+        return format!("{:#x}", keysym);
+    }
+    String::from_utf8_lossy(&name[..(len as usize).min(name.len() - 1)]).into_owned()
+}
+
+/// The parts of the keyboard state that key presses are turned into text
+/// through, apart from xkbcommon so that the compose handling can be tested.
+trait Interpret {
+    fn mods_state(&self) -> ModifiersState;
+    fn compose_feed(&mut self, keysym: u32) -> Option<ffi::xkb_compose_feed_result>;
+    fn compose_status(&mut self) -> Option<ffi::xkb_compose_status>;
+    fn compose_get_utf8(&mut self) -> Option<String>;
+    fn get_utf8_raw(&mut self, keycode: u32) -> Option<String>;
+    fn get_one_sym_raw(&mut self, keycode: u32) -> u32;
+}
+
+impl Interpret for KbState {
+    fn mods_state(&self) -> ModifiersState {
+        KbState::mods_state(self)
+    }
+    fn compose_feed(&mut self, keysym: u32) -> Option<ffi::xkb_compose_feed_result> {
+        KbState::compose_feed(self, keysym)
+    }
+    fn compose_status(&mut self) -> Option<ffi::xkb_compose_status> {
+        KbState::compose_status(self)
+    }
+    fn compose_get_utf8(&mut self) -> Option<String> {
+        KbState::compose_get_utf8(self)
+    }
+    fn get_utf8_raw(&mut self, keycode: u32) -> Option<String> {
+        KbState::get_utf8_raw(self, keycode)
+    }
+    fn get_one_sym_raw(&mut self, keycode: u32) -> u32 {
+        KbState::get_one_sym_raw(self, keycode)
+    }
+}
+
+/// The text entered by pressing a key, going through the compose sequences
+/// of the locale, such as a dead key followed by a letter. Keys that start
+/// or continue a sequence, or cancel it, enter nothing.
+fn key_text<S: Interpret>(state: &mut S, key: u32, keysym: u32) -> Option<String> {
+    use ffi::xkb_compose_feed_result::*;
+    use ffi::xkb_compose_status::*;
+
+    // Shortcuts neither finish nor cancel a pending sequence
+    let mods = state.mods_state();
+    if mods.ctrl || mods.alt || mods.logo {
+        return state.get_utf8_raw(key);
+    }
+    match state.compose_feed(keysym) {
+        // Without a compose table, or a key that compose leaves alone, such
+        // as a modifier
+        None | Some(XKB_COMPOSE_FEED_IGNORED) => state.get_utf8_raw(key),
+        Some(XKB_COMPOSE_FEED_ACCEPTED) => match state.compose_status() {
+            Some(XKB_COMPOSE_COMPOSED) => state.compose_get_utf8(),
+            Some(XKB_COMPOSE_NOTHING) | None => state.get_utf8_raw(key),
+            Some(XKB_COMPOSE_COMPOSING) | Some(XKB_COMPOSE_CANCELLED) => None,
+        },
+    }
+}
+
+/// The keysym, state and text of a key event from the compositor. Only
+/// pressing a key enters text.
+fn key_event<S: Interpret>(
+    state: &mut S,
+    key: u32,
+    key_state: wl_keyboard::KeyState,
+) -> (u32, KeyState, Option<String>) {
+    let sym = state.get_one_sym_raw(key);
+    let key_state = protocol::key_state(key_state);
+    let utf8 = match key_state {
+        KeyState::Pressed => key_text(state, key, sym),
+        KeyState::Released => None,
+    };
+    (sym, key_state, utf8)
+}
+
 /*
  * Classic handling
  */
@@ -198,14 +298,11 @@ impl KbdHandler {
                 state.de_init();
             }
         }
-        match format {
-            wl_keyboard::KeymapFormat::XkbV1 => unsafe {
-                state.init_with_fd(fd, size as usize);
-            },
-            wl_keyboard::KeymapFormat::NoKeymap => {
-                // TODO: how to handle this (hopefully never occuring) case?
+        // TODO: how to handle keymaps other than XKB (hopefully never occuring)?
+        if let Some((fd, size)) = protocol::keymap(format, fd, size) {
+            unsafe {
+                state.init_with_fd(fd, size);
             }
-            _ => unreachable!(),
         }
     }
 
@@ -242,6 +339,9 @@ impl KbdHandler {
         surface: wl_surface::WlSurface,
         dispatch_data: wayland_client::DispatchData,
     ) {
+        // A dead key pressed before the focus was lost is not combined with
+        // the first key pressed once it is back
+        self.state.borrow_mut().compose_reset();
         (&mut *self.callback.borrow_mut())(Event::Leave { serial, surface }, object, dispatch_data);
     }
 
@@ -254,41 +354,12 @@ impl KbdHandler {
         key_state: wl_keyboard::KeyState,
         dispatch_data: wayland_client::DispatchData,
     ) {
-        let (sym, utf8, repeats) = {
+        let (sym, key_state, utf8, repeats) = {
             let mut state = self.state.borrow_mut();
             // Get the values to generate a key event
-            let sym = state.get_one_sym_raw(key);
-            let utf8 = if key_state == wl_keyboard::KeyState::Pressed {
-                match state.compose_feed(sym) {
-                    Some(ffi::xkb_compose_feed_result::XKB_COMPOSE_FEED_ACCEPTED) => {
-                        if let Some(status) = state.compose_status() {
-                            match status {
-                                ffi::xkb_compose_status::XKB_COMPOSE_COMPOSED => {
-                                    state.compose_get_utf8()
-                                }
-                                ffi::xkb_compose_status::XKB_COMPOSE_NOTHING => {
-                                    state.get_utf8_raw(key)
-                                }
-                                _ => None,
-                            }
-                        } else {
-                            state.get_utf8_raw(key)
-                        }
-                    }
-                    Some(_) => {
-                        // XKB_COMPOSE_FEED_IGNORED
-                        None
-                    }
-                    None => {
-                        // XKB COMPOSE is not initialized
-                        state.get_utf8_raw(key)
-                    }
-                }
-            } else {
-                None
-            };
+            let (sym, key_state, utf8) = key_event(&mut *state, key, key_state);
             let repeats = unsafe { state.key_repeats(key + 8) };
-            (sym, utf8, repeats)
+            (sym, key_state, utf8, repeats)
         };
 
         (&mut *self.callback.borrow_mut())(
@@ -342,3 +413,211 @@ impl KbdHandler {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ffi::{xkb_compose_feed_result, xkb_compose_status};
+    use super::keysyms::*;
+    use super::*;
+
+    // Stands in for xkbcommon with a compose table of its own, following
+    // how xkbcommon moves between the compose states. Keys are numbered by
+    // their keysym, and enter the character of the keysym if it has one.
+    struct Stub {
+        mods: ModifiersState,
+        // None for a locale without a compose table
+        sequences: Option<Vec<(Vec<u32>, &'static str)>>,
+        pending: Vec<u32>,
+        status: xkb_compose_status,
+    }
+
+    impl Stub {
+        fn new() -> Stub {
+            Stub {
+                mods: ModifiersState::default(),
+                sequences: Some(vec![
+                    (vec![XKB_KEY_dead_acute, XKB_KEY_e], "é"),
+                    (vec![XKB_KEY_dead_acute, 0x45], "É"),
+                    (vec![XKB_KEY_dead_acute, XKB_KEY_space], "´"),
+                    (vec![XKB_KEY_dead_diaeresis, XKB_KEY_u], "ü"),
+                    (vec![XKB_KEY_Multi_key, XKB_KEY_a, XKB_KEY_e], "æ"),
+                ]),
+                pending: Vec::new(),
+                status: xkb_compose_status::XKB_COMPOSE_NOTHING,
+            }
+        }
+
+        fn without_compose() -> Stub {
+            Stub {
+                sequences: None,
+                ..Stub::new()
+            }
+        }
+
+        fn press(&mut self, keysym: u32) -> Option<String> {
+            key_text(self, keysym, keysym)
+        }
+    }
+
+    impl Interpret for Stub {
+        fn mods_state(&self) -> ModifiersState {
+            self.mods
+        }
+
+        fn compose_feed(&mut self, keysym: u32) -> Option<xkb_compose_feed_result> {
+            let sequences = self.sequences.as_ref()?;
+            if (XKB_KEY_Shift_L..=0xffee).contains(&keysym) {
+                return Some(xkb_compose_feed_result::XKB_COMPOSE_FEED_IGNORED);
+            }
+            if self.status != xkb_compose_status::XKB_COMPOSE_COMPOSING {
+                self.pending.clear();
+            }
+            self.pending.push(keysym);
+            let pending = &self.pending;
+            self.status = if sequences.iter().any(|(seq, _)| seq == pending) {
+                xkb_compose_status::XKB_COMPOSE_COMPOSED
+            } else if sequences.iter().any(|(seq, _)| seq.starts_with(pending)) {
+                xkb_compose_status::XKB_COMPOSE_COMPOSING
+            } else if pending.len() > 1 {
+                xkb_compose_status::XKB_COMPOSE_CANCELLED
+            } else {
+                xkb_compose_status::XKB_COMPOSE_NOTHING
+            };
+            Some(xkb_compose_feed_result::XKB_COMPOSE_FEED_ACCEPTED)
+        }
+
+        fn compose_status(&mut self) -> Option<xkb_compose_status> {
+            self.sequences.as_ref()?;
+            Some(self.status)
+        }
+
+        fn compose_get_utf8(&mut self) -> Option<String> {
+            let sequences = self.sequences.as_ref()?;
+            sequences
+                .iter()
+                .find(|(seq, _)| *seq == self.pending)
+                .map(|(_, text)| text.to_string())
+        }
+
+        fn get_utf8_raw(&mut self, keycode: u32) -> Option<String> {
+            char::from_u32(keycode)
+                .filter(|c| keycode < 0xfe00 && !c.is_control())
+                .map(String::from)
+        }
+
+        fn get_one_sym_raw(&mut self, keycode: u32) -> u32 {
+            keycode
+        }
+    }
+
+    #[test]
+    fn plain_keys() {
+        let mut state = Stub::new();
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("e"));
+        assert_eq!(state.press(XKB_KEY_space).as_deref(), Some(" "));
+        assert_eq!(state.press(XKB_KEY_Return), None);
+
+        let mut state = Stub::without_compose();
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("e"));
+        assert_eq!(state.press(XKB_KEY_dead_acute), None);
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("e"));
+    }
+
+    #[test]
+    fn dead_keys() {
+        let mut state = Stub::new();
+        assert_eq!(state.press(XKB_KEY_dead_acute), None);
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("é"));
+        // The next key starts over
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("e"));
+
+        assert_eq!(state.press(XKB_KEY_dead_diaeresis), None);
+        assert_eq!(state.press(XKB_KEY_u).as_deref(), Some("ü"));
+
+        // Space enters the accent itself
+        assert_eq!(state.press(XKB_KEY_dead_acute), None);
+        assert_eq!(state.press(XKB_KEY_space).as_deref(), Some("´"));
+    }
+
+    #[test]
+    fn compose_sequences() {
+        let mut state = Stub::new();
+        assert_eq!(state.press(XKB_KEY_Multi_key), None);
+        assert_eq!(state.press(XKB_KEY_a), None);
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("æ"));
+    }
+
+    #[test]
+    fn cancelled_sequences() {
+        let mut state = Stub::new();
+        assert_eq!(state.press(XKB_KEY_dead_acute), None);
+        // The key that does not fit the sequence is dropped along with it
+        assert_eq!(state.press(XKB_KEY_k), None);
+        assert_eq!(state.press(XKB_KEY_k).as_deref(), Some("k"));
+
+        assert_eq!(state.press(XKB_KEY_Multi_key), None);
+        assert_eq!(state.press(XKB_KEY_a), None);
+        assert_eq!(state.press(XKB_KEY_BackSpace), None);
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("e"));
+    }
+
+    #[test]
+    fn shift_within_sequence() {
+        let mut state = Stub::new();
+        assert_eq!(state.press(XKB_KEY_dead_acute), None);
+        // Modifiers are ignored by compose, and do not cancel the sequence
+        state.mods.shift = true;
+        assert_eq!(state.press(XKB_KEY_Shift_L), None);
+        assert_eq!(state.press(0x45).as_deref(), Some("É"));
+    }
+
+    #[test]
+    fn shortcuts_within_sequence() {
+        let mut state = Stub::new();
+        assert_eq!(state.press(XKB_KEY_dead_acute), None);
+        // Ctrl+E is a shortcut rather than the end of the sequence
+        state.mods.ctrl = true;
+        assert_eq!(state.press(XKB_KEY_Control_L), None);
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("e"));
+        state.mods.ctrl = false;
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("é"));
+
+        state.mods.alt = true;
+        assert_eq!(state.press(XKB_KEY_dead_acute), None);
+        state.mods.alt = false;
+        assert_eq!(state.press(XKB_KEY_e).as_deref(), Some("e"));
+    }
+
+    // Key events with the raw states a compositor sends, pressing and
+    // releasing a dead key and then a letter. Releasing the dead key leaves
+    // the sequence pending, and releases enter nothing.
+    #[test]
+    fn key_events() {
+        let mut state = Stub::new();
+        let events = [
+            (XKB_KEY_dead_acute, 1),
+            (XKB_KEY_dead_acute, 0),
+            (XKB_KEY_e, 1),
+            (XKB_KEY_e, 0),
+        ];
+        let keys: Vec<_> = events
+            .iter()
+            .map(|&(key, raw)| {
+                key_event(
+                    &mut state,
+                    key,
+                    wl_keyboard::KeyState::from_raw(raw).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                (XKB_KEY_dead_acute, KeyState::Pressed, None),
+                (XKB_KEY_dead_acute, KeyState::Released, None),
+                (XKB_KEY_e, KeyState::Pressed, Some("é".to_string())),
+                (XKB_KEY_e, KeyState::Released, None),
+            ]
+        );
+    }
+}
@@ -195,6 +195,13 @@ impl KbState {
         Some(unsafe { (XKBH.xkb_compose_state_get_status)(self.xkb_compose_state) })
     }
 
+    pub(crate) fn compose_reset(&mut self) {
+        if self.xkb_compose_state.is_null() {
+            return;
+        }
+        unsafe { (XKBH.xkb_compose_state_reset)(self.xkb_compose_state) }
+    }
+
     pub(crate) fn compose_get_utf8(&mut self) -> Option<String> {
         if !self.ready() || self.xkb_compose_state.is_null() {
             return None;
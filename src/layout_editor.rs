@@ -0,0 +1,374 @@
+//! Layout editor
+//!
+//! Outlines every widget together with its name, and lets the margins of the
+//! selected widget be nudged with the keyboard while the dashboard shows the
+//! result. The margins can then be written back to the config file.
+
+use crate::cmd::Action;
+use crate::color::Color;
+use crate::config::{Config, Widget};
+use crate::configfmt::ConfigFmt;
+use crate::draw::{draw_box, Font};
+use crate::fonts::FontRef;
+use crate::keyboard::keysyms;
+use crate::widget::{
+    self, Constraints, DrawContext, DrawReport, KeyState, ModifiersState, Scroll, StateValue,
+    TreeNode, WaitContext, WidgetState,
+};
+
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+type Margins = (u32, u32, u32, u32); // left, right, top, bottom
+
+const LABEL_SIZE: f32 = 12.0;
+// How far the arrow keys move the selected widget, or with shift held.
+const STEP: u32 = 8;
+const FINE_STEP: u32 = 1;
+
+fn is_container(widget: &Widget) -> bool {
+    matches!(
+        widget,
+        Widget::Margin { .. }
+            | Widget::Fixed { .. }
+            | Widget::Style { .. }
+            | Widget::Constrain { .. }
+            | Widget::FocusedOutput { .. }
+            | Widget::Bind { .. }
+            | Widget::HorizontalLayout(_)
+            | Widget::VerticalLayout(_)
+    )
+}
+
+// Calls f for every widget that can be moved, which is a widget that is not
+// a layout or wrapper, together with the margin directly around it if any.
+fn for_each_movable(widget: &mut Widget, f: &mut impl FnMut(&mut Widget)) {
+    match widget {
+        Widget::HorizontalLayout(children) | Widget::VerticalLayout(children) => {
+            for child in children.iter_mut() {
+                for_each_movable(child, f);
+            }
+        }
+        Widget::Margin { widget: child, .. } if is_container(child) => for_each_movable(child, f),
+        Widget::Fixed { widget: child, .. }
+        | Widget::Style { widget: child, .. }
+        | Widget::Constrain { widget: child, .. }
+        | Widget::FocusedOutput { widget: child }
+        | Widget::Bind { widget: child, .. } => for_each_movable(child, f),
+        _ => f(widget),
+    }
+}
+
+// The name of the widget, as used in the config file.
+fn label(widget: &Widget) -> String {
+    let debug = format!("{:?}", widget);
+    let name = debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default();
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+pub struct LayoutEditor {
+    config: Config,
+    path: PathBuf,
+    // The margins of every movable widget, in tree order
+    margins: Vec<Margins>,
+    selected: usize,
+}
+
+impl LayoutEditor {
+    pub fn new(config: Config, path: PathBuf) -> LayoutEditor {
+        LayoutEditor {
+            config,
+            path,
+            margins: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// The widget tree to show, with every movable widget outlined.
+    pub fn widget(&mut self) -> Widget {
+        let mut tree = self.config.widget.clone();
+        let margins = &mut self.margins;
+        margins.clear();
+        for_each_movable(&mut tree, &mut |movable| {
+            let (m, inner) = match std::mem::replace(movable, Widget::VerticalLayout(Vec::new())) {
+                Widget::Margin { margins, widget } => (margins, *widget),
+                widget => ((0, 0, 0, 0), widget),
+            };
+            *movable = Widget::Outline {
+                index: margins.len(),
+                label: label(&inner),
+                margins: m,
+                selected: margins.is_empty(),
+                widget: Box::new(inner),
+            };
+            margins.push(m);
+        });
+        tree
+    }
+
+    fn nudge(&mut self, key: u32, step: u32) -> Option<Action> {
+        let m = self.margins.get_mut(self.selected)?;
+        match key {
+            keysyms::XKB_KEY_Left if m.0 > 0 => {
+                m.0 = m.0.saturating_sub(step);
+                m.1 += step;
+            }
+            keysyms::XKB_KEY_Right => {
+                m.0 += step;
+                m.1 = m.1.saturating_sub(step);
+            }
+            keysyms::XKB_KEY_Up if m.2 > 0 => {
+                m.2 = m.2.saturating_sub(step);
+                m.3 += step;
+            }
+            keysyms::XKB_KEY_Down => {
+                m.2 += step;
+                m.3 = m.3.saturating_sub(step);
+            }
+            _ => return None,
+        }
+        Some(Action::LayoutMargins(self.selected, *m))
+    }
+
+    /// Handles a key press, returning the action to pass on to the widgets.
+    /// Tab selects the next widget, the arrow keys move it and s saves.
+    pub fn keyboard_input(&mut self, key: u32, modifiers: ModifiersState) -> Option<Action> {
+        let count = self.margins.len();
+        if count == 0 {
+            return None;
+        }
+        match key {
+            keysyms::XKB_KEY_Tab => {
+                self.selected = (self.selected + 1) % count;
+                Some(Action::LayoutSelect(self.selected))
+            }
+            keysyms::XKB_KEY_ISO_Left_Tab => {
+                self.selected = (self.selected + count - 1) % count;
+                Some(Action::LayoutSelect(self.selected))
+            }
+            keysyms::XKB_KEY_s => {
+                match self.save() {
+                    Ok(()) => eprintln!("Layout written to {}", self.path.display()),
+                    Err(err) => eprintln!("Unable to write layout: {}", err),
+                }
+                None
+            }
+            key => self.nudge(key, if modifiers.shift { FINE_STEP } else { STEP }),
+        }
+    }
+
+    /// Writes the config with the edited margins to the config file.
+    pub fn save(&self) -> Result<(), Error> {
+        let mut config_margins = self.margins.iter();
+        let mut tree = self.config.widget.clone();
+        for_each_movable(&mut tree, &mut |movable| {
+            let m = *config_margins.next().unwrap();
+            match movable {
+                Widget::Margin { margins, .. } => *margins = m,
+                _ if m != (0, 0, 0, 0) => {
+                    let inner = std::mem::replace(movable, Widget::VerticalLayout(Vec::new()));
+                    *movable = Widget::Margin {
+                        margins: m,
+                        widget: Box::new(inner),
+                    };
+                }
+                _ => (),
+            }
+        });
+
+        let fmt = self
+            .path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(ConfigFmt::new)
+            .unwrap_or_default();
+        let config = Config {
+            widget: tree,
+            ..self.config.clone()
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, fmt.to_string(&config))
+    }
+}
+
+/// Applies the margins of a widget in the layout editor, and outlines it
+/// with its name.
+pub struct Outline<'a> {
+    index: usize,
+    label: String,
+    margins: Margins,
+    selected: bool,
+    font: Font<'a>,
+    child: Box<dyn widget::Widget + Send + 'a>,
+}
+
+impl<'a> Outline<'a> {
+    pub fn new(
+        index: usize,
+        label: String,
+        margins: Margins,
+        selected: bool,
+        font: FontRef<'a>,
+        child: Box<dyn widget::Widget + Send + 'a>,
+    ) -> Box<Outline<'a>> {
+        Box::new(Outline {
+            index,
+            label,
+            margins,
+            selected,
+            font: Font::new(font, LABEL_SIZE),
+            child,
+        })
+    }
+}
+
+impl<'a> widget::Widget for Outline<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.child.wait(ctx)
+    }
+    fn enter(&mut self) {
+        self.child.enter()
+    }
+    fn leave(&mut self) {
+        self.child.leave()
+    }
+    fn size(&self) -> (u32, u32) {
+        let size = self.child.size();
+        (
+            size.0 + self.margins.0 + self.margins.1,
+            size.1 + self.margins.2 + self.margins.3,
+        )
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let expansion = (
+            expansion.0.saturating_sub(self.margins.0 + self.margins.1),
+            expansion.1.saturating_sub(self.margins.2 + self.margins.3),
+        );
+        let pos = (pos.0 + self.margins.0, pos.1 + self.margins.2);
+        let mut report = self.child.draw(ctx, pos, expansion)?;
+
+        // Only outline when the child was drawn, so the label is not drawn
+        // on top of itself.
+        if (ctx.force || report.full_damage || !report.damage.is_empty())
+            && report.width > 0
+            && report.height > 0
+        {
+            let c = if self.selected {
+                Color::new(1.0, 1.0, 0.0, 1.0)
+            } else {
+                Color::new(0.5, 0.5, 0.5, 1.0)
+            };
+            let buf = &mut ctx
+                .buf
+                .subdimensions((pos.0, pos.1, report.width, report.height))?;
+            draw_box(buf, &c, (report.width, report.height))?;
+            self.font.auto_draw_text(
+                &mut buf.offset((2, 2))?,
+                ctx.bg,
+                &c,
+                &format!("{} {:?}", self.label, self.margins),
+            )?;
+            report.damage.push(buf.get_signed_bounds());
+        }
+
+        Ok(DrawReport {
+            width: report.width + self.margins.0 + self.margins.1,
+            height: report.height + self.margins.2 + self.margins.3,
+            damage: report.damage,
+            full_damage: report.full_damage,
+        })
+    }
+
+    fn keyboard_input(
+        &mut self,
+        keysym: u32,
+        modifier_state: ModifiersState,
+        key_state: KeyState,
+        interpreted: Option<String>,
+    ) {
+        self.child
+            .keyboard_input(keysym, modifier_state, key_state, interpreted);
+    }
+
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        let mut constraints = self.child.constraints();
+        constraints.min_size.0 += self.margins.0 + self.margins.1;
+        constraints.min_size.1 += self.margins.2 + self.margins.3;
+        constraints
+    }
+
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        let pos = (
+            pos.0.saturating_sub(self.margins.0),
+            pos.1.saturating_sub(self.margins.2),
+        );
+        self.child.mouse_click(button, pos);
+    }
+
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        let pos = (
+            pos.0.saturating_sub(self.margins.0),
+            pos.1.saturating_sub(self.margins.2),
+        );
+        self.child.mouse_press(button, pos);
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        let pos = (
+            pos.0.saturating_sub(self.margins.0),
+            pos.1.saturating_sub(self.margins.2),
+        );
+        self.child.mouse_scroll(scroll, pos);
+    }
+
+    fn action(&mut self, action: &Action) {
+        match *action {
+            Action::LayoutSelect(index) => self.selected = index == self.index,
+            Action::LayoutMargins(index, margins) if index == self.index => self.margins = margins,
+            _ => self.child.action(action),
+        }
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        let child_pos = (pos.0 + self.margins.0, pos.1 + self.margins.2);
+        TreeNode::new(self, pos)
+            .value("label", StateValue::Text(self.label.clone()))
+            .child(self.child.tree(child_pos))
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        self.child.focused_only(regions);
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
+}
@@ -1,16 +1,27 @@
+use std::collections::VecDeque;
 use std::env;
-use std::fs::File;
+use std::fs;
 use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
 use std::os::unix::io::AsRawFd;
 use std::os::unix::net::{UnixListener, UnixStream};
-use std::{collections::HashMap, sync::mpsc::channel};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
 
-use chrono::{Duration, Local};
+use chrono::{Duration, Local, NaiveDateTime, NaiveTime, TimeZone};
 use nix::poll::{poll, PollFd, PollFlags};
-use os_pipe::pipe;
-use timerfd::{SetTimeFlags, TimerFd, TimerState};
+use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+use nix::sys::stat::{umask, Mode as FileMode};
+use nix::unistd::getuid;
+use os_pipe::{pipe, PipeWriter};
+use timerfd::{ClockId, SetTimeFlags, TimerFd, TimerState};
 
+mod accessibility;
 mod app;
+#[cfg(feature = "dbus-services")]
+mod appearance;
+mod binding;
 mod buffer;
 mod cmd;
 mod color;
@@ -18,36 +29,347 @@ mod config;
 mod configfmt;
 mod data;
 mod desktop;
+mod dirlist;
 mod doublemempool;
 mod draw;
+mod events;
+mod exec;
+mod executables;
 mod fonts;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod help;
+mod ipc;
+mod keybindings;
 mod keyboard;
+mod layout_editor;
+mod matching;
+mod metrics;
+mod motion;
+mod offline;
+mod osd;
+mod pam;
+mod png;
+mod private;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+mod protocol;
+mod scroll;
+mod selection;
+mod session_lock;
+mod shortcodes;
+#[cfg(feature = "dbus-services")]
+mod shortcuts;
+mod vpn;
 mod widget;
 mod widgets;
+mod zoneinfo;
 
 #[macro_use]
 extern crate dlib;
 
-use app::{App, OutputMode};
-use cmd::Cmd;
-use config::Config;
+use app::{App, HotCorner, OutputMode};
+use cmd::{Action, Cmd};
+use config::{DismissPolicy, Widget};
 use configfmt::ConfigFmt;
-use fonts::{FontLoader, FontMap, FontSeeker};
-use widget::WaitContext;
+use keyboard::KeyState;
+use layout_editor::LayoutEditor;
+use widget::{states_to_json, WaitContext};
+use widgets::lock_screen::LockScreen;
 
 enum Mode {
     Start,
     Daemonize,
     StartOrKill,
     ToggleVisible,
+    ToggleStats,
+    Refresh,
+    Clean { dry_run: bool },
+    Offline(Option<bool>),
+    Private(Option<bool>),
+    Accessibility(Option<bool>),
+    Window(Option<bool>),
+    LayoutEditor,
+    PromptOnly,
+    Lock,
+    Export(PathBuf),
+    Query(Option<String>),
+    DumpTree,
+    Fonts,
     PrintConfig(ConfigFmt),
+    Osd { text: String, timeout: Option<u32> },
 }
 
-fn main() {
-    let socket_path = match env::var("XDG_RUNTIME_DIR") {
-        Ok(dir) => dir + "/wldash",
-        Err(_) => "/tmp/wldash".to_string(),
+// Converts a local wall-clock time to the absolute CLOCK_REALTIME value used
+// to arm the timer. Times that do not exist due to DST fire immediately.
+fn realtime(t: NaiveDateTime) -> std::time::Duration {
+    let timestamp = Local
+        .from_local_datetime(&t)
+        .earliest()
+        .map(|t| t.timestamp_nanos())
+        .unwrap_or(1);
+    std::time::Duration::from_nanos(timestamp.max(1) as u64)
+}
+
+// Lists, and unless a dry run removes, the files wldash has written. The
+// config is left alone.
+fn clean(socket_path: &str, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut paths = data::data_dirs()?;
+    paths.push(PathBuf::from(socket_path));
+    for path in paths {
+        let meta = match fs::symlink_metadata(&path) {
+            Ok(meta) => meta,
+            Err(_) => continue,
+        };
+        println!("{}", path.display());
+        if dry_run {
+            continue;
+        }
+        if meta.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+// Creates the directory of the socket in /tmp, where another user could have
+// created it first, so it has to belong to us and be closed to others.
+fn private_dir(dir: &Path) -> Result<(), std::io::Error> {
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(err) if err.kind() != std::io::ErrorKind::AlreadyExists => return Err(err),
+        _ => (),
+    }
+    let meta = fs::symlink_metadata(dir)?;
+    if !meta.is_dir() || meta.uid() != getuid().as_raw() || meta.mode() & 0o077 != 0 {
+        return Err(std::io::Error::other(format!(
+            "{} is not a directory private to this user",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+// Binds the socket with permissions for our own user only, so that there is
+// no window in which others can connect to it.
+fn bind_socket(socket_path: &str) -> Result<UnixListener, std::io::Error> {
+    let _ = fs::remove_file(socket_path);
+    let old = umask(FileMode::from_bits_truncate(0o177));
+    let listener = UnixListener::bind(socket_path);
+    umask(old);
+    listener
+}
+
+// Only commands from our own user are accepted over the socket.
+fn same_user(stream: &UnixStream) -> bool {
+    match getsockopt(stream.as_raw_fd(), PeerCredentials) {
+        Ok(cred) => cred.uid() == getuid().as_raw(),
+        Err(_) => false,
+    }
+}
+
+// Handles the commands of one client of the control socket. Handshakes,
+// queries, tree dumps and unknown commands are answered with a line of JSON.
+fn ipc_client(stream: UnixStream, queue: Arc<Mutex<VecDeque<Cmd>>>, mut pipe: PipeWriter) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let (cmd, reply) = match line.as_str() {
+            v if v == "hello" || v.starts_with("hello ") => {
+                let _ = writeln!(writer, "{}", ipc::hello(&v["hello".len()..]));
+                continue;
+            }
+            "kill" => (Cmd::Kill, None),
+            "toggle_visible" => (Cmd::ToggleVisible, None),
+            "stats" => (Cmd::Action(Action::ToggleStats), None),
+            "refresh" => (Cmd::Action(Action::Refresh), None),
+            "offline" => (Cmd::Offline(None), None),
+            "offline on" => (Cmd::Offline(Some(true)), None),
+            "offline off" => (Cmd::Offline(Some(false)), None),
+            "private" => (Cmd::Private(None), None),
+            "private on" => (Cmd::Private(Some(true)), None),
+            "private off" => (Cmd::Private(Some(false)), None),
+            "accessibility" => (Cmd::Accessibility(None), None),
+            "accessibility on" => (Cmd::Accessibility(Some(true)), None),
+            "accessibility off" => (Cmd::Accessibility(Some(false)), None),
+            "window" => (Cmd::Window(None), None),
+            "window on" => (Cmd::Window(Some(true)), None),
+            "window off" => (Cmd::Window(Some(false)), None),
+            v if v.starts_with("osd ") => {
+                let text = &v["osd ".len()..];
+                let (text, timeout) = match text
+                    .strip_prefix("--timeout ")
+                    .and_then(|rest| rest.split_once(' '))
+                    .and_then(|(ms, text)| Some((text, ms.parse().ok()?)))
+                {
+                    Some((text, timeout)) => (text, Some(timeout)),
+                    None => (text, None),
+                };
+                let text = text.to_string();
+                (Cmd::Osd { text, timeout }, None)
+            }
+            v if v.starts_with("export ") => {
+                let path = PathBuf::from(&v["export ".len()..]);
+                (Cmd::Export { path }, None)
+            }
+            // Answered right away, as fonts keep their own counts
+            "fonts" => {
+                let _ = writeln!(writer, "{}", fonts::faces_to_json());
+                continue;
+            }
+            "dump-tree" => {
+                let (tx, rx) = channel();
+                queue.lock().unwrap().push_back(Cmd::DumpTree { reply: tx });
+                pipe.write_all(&[0x1]).unwrap();
+                match rx.recv() {
+                    Ok(tree) => {
+                        let _ = writeln!(writer, "{}", tree.to_json());
+                    }
+                    Err(_) => return,
+                }
+                continue;
+            }
+            v if v == "query" || v.starts_with("query ") => {
+                let (tx, rx) = channel();
+                let name = v.strip_prefix("query ").map(|name| name.trim().to_string());
+                (Cmd::Query { reply: tx }, Some((name, rx)))
+            }
+            v => {
+                eprintln!("unknown command: {}", v);
+                let _ = writeln!(writer, "{}", ipc::error("unknown-command", v));
+                continue;
+            }
+        };
+        queue.lock().unwrap().push_back(cmd);
+        pipe.write_all(&[0x1]).unwrap();
+        if let Some((name, rx)) = reply {
+            let states = match rx.recv() {
+                Ok(states) => states,
+                Err(_) => return,
+            };
+            let response = match name {
+                Some(name) => states
+                    .iter()
+                    .find(|state| state.name == name)
+                    .map(|state| state.to_json())
+                    .unwrap_or_default(),
+                None => states_to_json(&states),
+            };
+            let _ = writeln!(writer, "{}", response);
+        }
+    }
+}
+
+// Whether the dark theme applies at the given time of day.
+fn is_dark(t: NaiveTime, light_at: NaiveTime, dark_at: NaiveTime) -> bool {
+    if light_at <= dark_at {
+        t < light_at || t >= dark_at
+    } else {
+        t >= dark_at && t < light_at
+    }
+}
+
+// The next time of either theme switch.
+fn next_switch(now: NaiveDateTime, light_at: NaiveTime, dark_at: NaiveTime) -> NaiveDateTime {
+    [light_at, dark_at]
+        .iter()
+        .map(|&at| {
+            let t = now.date().and_time(at);
+            if t > now {
+                t
+            } else {
+                t + Duration::days(1)
+            }
+        })
+        .min()
+        .unwrap()
+}
+
+// Connects to the compositor again after the connection was lost, waiting
+// longer between attempts until it is back.
+fn reconnect<'a>(
+    tx: Sender<Cmd>,
+    queue: Arc<Mutex<VecDeque<Cmd>>>,
+    output_mode: OutputMode,
+    bg: color::Color,
+    scale: u32,
+) -> App<'a> {
+    let mut delay = std::time::Duration::from_millis(250);
+    loop {
+        std::thread::sleep(delay);
+        match App::connect(tx.clone(), queue.clone(), output_mode, bg, scale) {
+            Ok(app) => return app,
+            Err(err) => eprintln!("Unable to reconnect to the compositor: {}", err),
+        }
+        delay = (delay * 2).min(std::time::Duration::from_secs(10));
+    }
+}
+
+// Connects to the running instance, or exits if there is none.
+fn connect(socket_path: &str) -> UnixStream {
+    match UnixStream::connect(socket_path) {
+        Ok(socket) => socket,
+        Err(_) => {
+            eprintln!("wldash is not running");
+            std::process::exit(1);
+        }
+    }
+}
+
+// Sends a command line to the running instance.
+fn send_command(socket_path: &str, line: &str) {
+    writeln!(connect(socket_path), "{}", line).unwrap();
+}
+
+// Parses the optional on or off argument of a sub-command, where no argument
+// toggles.
+fn on_off(name: &str, arg: Option<&str>) -> Option<bool> {
+    match arg {
+        None => None,
+        Some("on") => Some(true),
+        Some("off") => Some(false),
+        Some(arg) => {
+            eprintln!("{} expects on or off, got {}", name, arg);
+            std::process::exit(1);
+        }
+    }
+}
+
+// The command line that switches or toggles a mode of the running instance.
+fn switch_command(name: &str, on: Option<bool>) -> String {
+    match on {
+        Some(true) => format!("{} on", name),
+        Some(false) => format!("{} off", name),
+        None => name.to_string(),
+    }
+}
+
+// Wraps a widget tree for showing, with a warning above it if fonts of the
+// config could not be loaded.
+fn dashboard(widget: Widget, missing_fonts: &[String]) -> Widget {
+    let widget = if missing_fonts.is_empty() {
+        widget
+    } else {
+        Widget::VerticalLayout(vec![
+            Widget::Warning {
+                text: format!("Unable to load fonts: {}", missing_fonts.join(", ")),
+            },
+            widget,
+        ])
     };
+    Widget::Help {
+        widget: Box::new(widget),
+    }
+}
+
+fn main() {
     let config_home = match env::var("XDG_CONFIG_HOME") {
         Ok(dir) => dir + "/wldash",
         Err(_) => match env::var("HOME") {
@@ -56,48 +378,46 @@ fn main() {
         },
     };
 
-    // From all existing files take the first readable one and write it's extension to `ext`
-    let mut ext = [0x0; 8];
-    let file = configfmt::CONFIG_NAMES
-        .iter()
-        .map(|name| std::path::Path::new(&config_home).join(name))
-        .filter_map(|path| {
-            match File::open(&path) {
-                Ok(file) => {
-                    let e = path.extension().and_then(|e| e.to_str())?;
-                    let len = e.len();
-                    let from = len.saturating_sub(8); // the longest possible extension
-                    ext[0..len - from].copy_from_slice(&e.as_bytes()[from..]);
-                    Some(file)
-                }
-                Err(_) => None,
-            }
-        })
-        .next();
-
-    let fmt = std::str::from_utf8(&ext)
-        .ok()
-        .and_then(ConfigFmt::new)
-        .unwrap_or_default();
-
-    let config: Config = file
-        .map(|f| fmt.from_reader(BufReader::new(f)))
+    let (config, config_path) = config::load(std::path::Path::new(&config_home));
+    // The config is printed in the format of the config file of the user
+    let fmt = config_path
+        .as_deref()
+        .and_then(ConfigFmt::from_path)
         .unwrap_or_default();
 
     let scale = config.scale;
+    offline::set_offline(config.offline);
+    accessibility::set_enabled(config.accessibility.enabled);
+    motion::set_configured(config.reduce_motion == config::ReduceMotion::Always);
+    shortcodes::set_configured(&config.shortcodes);
+    matching::set_configured(config.matching);
 
-    let fonts: FontMap = {
-        let load_font = |font_name| {
-            let path = FontSeeker::from_string(font_name);
-            FontLoader::from_path(&path).expect(&format!("Loading {} failed", path.display()))
-        };
+    // Without a runtime dir, the fallback is in a directory in /tmp that is
+    // private to the user, so that several users can run wldash at the same
+    // time.
+    let (socket_path, socket_dir) = match (&config.socket_path, env::var("XDG_RUNTIME_DIR")) {
+        (Some(path), _) => (path.clone(), None),
+        (None, Ok(dir)) => (dir + "/wldash", None),
+        (None, Err(_)) => {
+            let dir = PathBuf::from(format!("/tmp/wldash-{}", getuid()));
+            (dir.join("wldash").to_string_lossy().into_owned(), Some(dir))
+        }
+    };
 
-        config
-            .fonts
-            .iter()
-            .map(|(key, val)| (key.clone(), load_font(val)))
-            .collect::<HashMap<_, _>>()
+    let (fonts, missing_fonts) = match fonts::load_fonts(&config.fonts) {
+        Ok(fonts) => fonts,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
     };
+    for font in missing_fonts.iter() {
+        eprintln!(
+            "Unable to load font {}, using {}",
+            font,
+            fonts::FALLBACK_FONT
+        );
+    }
 
     let mut args = env::args();
     let _ = args.next();
@@ -106,6 +426,60 @@ fn main() {
             "start" => Mode::Daemonize,
             "start-or-kill" => Mode::StartOrKill,
             "toggle-visible" => Mode::ToggleVisible,
+            "stats" => Mode::ToggleStats,
+            "refresh" => Mode::Refresh,
+            "clean" => match args.next().as_deref() {
+                None => Mode::Clean { dry_run: false },
+                Some("--dry-run") => Mode::Clean { dry_run: true },
+                Some(arg) => {
+                    eprintln!("clean expects --dry-run, got {}", arg);
+                    std::process::exit(1);
+                }
+            },
+            "layout-editor" => Mode::LayoutEditor,
+            "prompt-only" => Mode::PromptOnly,
+            "lock" => Mode::Lock,
+            "offline" => Mode::Offline(on_off("offline", args.next().as_deref())),
+            "private" => Mode::Private(on_off("private", args.next().as_deref())),
+            "window" => Mode::Window(on_off("window", args.next().as_deref())),
+            "accessibility" => Mode::Accessibility(on_off("accessibility", args.next().as_deref())),
+            "query" => Mode::Query(args.next()),
+            "dump-tree" => Mode::DumpTree,
+            "fonts" => Mode::Fonts,
+            "osd" => {
+                let mut text = Vec::new();
+                let mut timeout = None;
+                while let Some(arg) = args.next() {
+                    if arg != "--timeout" {
+                        text.push(arg);
+                        continue;
+                    }
+                    match args.next().and_then(|ms| ms.parse().ok()) {
+                        Some(ms) => timeout = Some(ms),
+                        None => {
+                            eprintln!("--timeout expects milliseconds");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                if text.is_empty() {
+                    eprintln!("osd requires a message to show");
+                    std::process::exit(1);
+                }
+                Mode::Osd {
+                    // The message is sent as a single line
+                    text: text.join(" ").replace('\n', " "),
+                    timeout,
+                }
+            }
+            "export" => match args.next() {
+                // The running instance may have a different working directory
+                Some(path) => Mode::Export(env::current_dir().unwrap().join(path)),
+                None => {
+                    eprintln!("export requires a path to write the PNG to");
+                    std::process::exit(1);
+                }
+            },
             "print-config" => Mode::PrintConfig(fmt),
             s => {
                 let p = "print-config-";
@@ -132,15 +506,84 @@ fn main() {
     }
 
     let mut daemon = false;
+    let layout_editing = matches!(mode, Mode::LayoutEditor);
+    let prompt_only = matches!(mode, Mode::PromptOnly);
+    let locking = matches!(mode, Mode::Lock);
+    // The lock screen runs next to the dashboard, with a socket of its own
+    let socket_path = match locking {
+        true => format!("{}-lock", socket_path),
+        false => socket_path,
+    };
 
     match mode {
-        Mode::ToggleVisible => {
-            if let Ok(mut socket) = UnixStream::connect(socket_path) {
-                socket.write_all(b"toggle_visible\n").unwrap();
-                return;
+        Mode::ToggleVisible => return send_command(&socket_path, "toggle_visible"),
+        Mode::Clean { dry_run } => {
+            if UnixStream::connect(&socket_path).is_ok() {
+                eprintln!("wldash is running, stop it before cleaning up");
+                std::process::exit(1);
+            }
+            std::process::exit(match clean(&socket_path, dry_run) {
+                Ok(()) => 0,
+                Err(err) => {
+                    eprintln!("Unable to clean up: {}", err);
+                    1
+                }
+            });
+        }
+        Mode::ToggleStats => return send_command(&socket_path, "stats"),
+        Mode::Refresh => return send_command(&socket_path, "refresh"),
+        Mode::Offline(offline) => {
+            return send_command(&socket_path, &switch_command("offline", offline))
+        }
+        Mode::Private(private) => {
+            return send_command(&socket_path, &switch_command("private", private))
+        }
+        Mode::Accessibility(enabled) => {
+            return send_command(&socket_path, &switch_command("accessibility", enabled))
+        }
+        Mode::Window(windowed) => {
+            return send_command(&socket_path, &switch_command("window", windowed))
+        }
+        Mode::Query(name) => {
+            let mut socket = connect(&socket_path);
+            match name {
+                Some(name) => writeln!(socket, "query {}", name).unwrap(),
+                None => writeln!(socket, "query").unwrap(),
+            }
+            let mut response = String::new();
+            let _ = BufReader::new(socket).read_line(&mut response);
+            if response.trim().is_empty() {
+                eprintln!("no such widget");
+                std::process::exit(1);
+            }
+            print!("{}", response);
+            return;
+        }
+        Mode::DumpTree => {
+            let mut socket = connect(&socket_path);
+            writeln!(socket, "dump-tree").unwrap();
+            let mut response = String::new();
+            let _ = BufReader::new(socket).read_line(&mut response);
+            print!("{}", response);
+            return;
+        }
+        Mode::Fonts => {
+            let mut socket = connect(&socket_path);
+            writeln!(socket, "fonts").unwrap();
+            let mut response = String::new();
+            let _ = BufReader::new(socket).read_line(&mut response);
+            print!("{}", response);
+            return;
+        }
+        Mode::Osd { text, timeout } => {
+            let line = match timeout {
+                Some(timeout) => format!("osd --timeout {} {}", timeout, text),
+                None => format!("osd {}", text),
             };
-            eprintln!("wldash is not running");
-            std::process::exit(1);
+            return send_command(&socket_path, &line);
+        }
+        Mode::Export(path) => {
+            return send_command(&socket_path, &format!("export {}", path.display()))
         }
         Mode::StartOrKill => {
             if let Ok(mut socket) = UnixStream::connect(socket_path.clone()) {
@@ -148,12 +591,18 @@ fn main() {
                 return;
             };
         }
-        Mode::Start => {
+        Mode::Start | Mode::LayoutEditor | Mode::PromptOnly => {
             if UnixStream::connect(socket_path.clone()).is_ok() {
                 eprintln!("wldash is already running");
                 std::process::exit(1);
             };
         }
+        Mode::Lock => {
+            if UnixStream::connect(socket_path.clone()).is_ok() {
+                eprintln!("wldash is already locking the session");
+                std::process::exit(1);
+            };
+        }
         Mode::Daemonize => {
             if UnixStream::connect(socket_path.clone()).is_ok() {
                 eprintln!("wldash is already running");
@@ -167,8 +616,20 @@ fn main() {
         }
     }
 
-    let _ = std::fs::remove_file(socket_path.clone());
-    let listener = UnixListener::bind(socket_path.clone()).unwrap();
+    let listener = match socket_dir
+        .as_deref()
+        .map_or(Ok(()), private_dir)
+        .and_then(|_| bind_socket(&socket_path))
+    {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!(
+                "Unable to create the control socket {}: {}",
+                socket_path, err
+            );
+            std::process::exit(1);
+        }
+    };
 
     let output_mode = match config.output_mode {
         config::OutputMode::All => OutputMode::All,
@@ -176,18 +637,123 @@ fn main() {
     };
 
     let background = config.background;
+    let theme_switch = config.theme_switch.clone();
+    let hot_corner = match (daemon, &config.hot_corner) {
+        (true, Some(hot_corner)) => Some(hot_corner.clone()),
+        _ => None,
+    };
 
     let (tx_draw, rx_draw) = channel();
     let tx_draw_mod = tx_draw.clone();
+    let tx_preset = tx_draw.clone();
 
     // Print, write to a file, or send to an HTTP server.
-    let widget = config
-        .widget
-        .construct(Local::now().naive_local(), tx_draw_mod, &fonts)
-        .expect("no widget configured");
+    let mut layout_editor = if layout_editing {
+        let path = config_path
+            .unwrap_or_else(|| std::path::Path::new(&config_home).join(configfmt::CONFIG_NAMES[0]));
+        Some(LayoutEditor::new(config.clone(), path))
+    } else {
+        None
+    };
+    let widget = match &mut layout_editor {
+        Some(editor) => editor.widget(),
+        None if prompt_only => dashboard(
+            config.accessibility.apply(config.prompt_widget()),
+            &missing_fonts,
+        ),
+        None if locking => dashboard(
+            config.accessibility.apply(config.lock_widget()),
+            &missing_fonts,
+        ),
+        None => dashboard(
+            config.accessibility.apply(config.widget.clone()),
+            &missing_fonts,
+        ),
+    }
+    .construct(Local::now().naive_local(), tx_draw_mod, &fonts)
+    .expect("no widget configured");
+    let widget = match locking {
+        true => LockScreen::new(
+            widget,
+            config.lock.pam_service.clone(),
+            config.lock.font(&fonts),
+            config.lock.font_size,
+            tx_draw.clone(),
+        ),
+        false => widget,
+    };
+
+    #[cfg(feature = "dbus-services")]
+    if daemon && config.global_shortcuts {
+        shortcuts::register_global_shortcuts(tx_draw.clone());
+    }
 
-    let mut app = App::new(tx_draw, output_mode, background, scale);
-    if daemon {
+    // A gamepad could otherwise close the lock screen
+    #[cfg(feature = "gamepad")]
+    if !locking {
+        gamepad::listen_gamepads(tx_draw.clone());
+    }
+
+    // The regular background is the dark theme. The light theme is either
+    // scheduled by time of day here, or follows the portal color scheme.
+    let theme_times = match &theme_switch {
+        Some(theme) => match theme.follow {
+            config::ThemeFollow::Time => Some(theme.times()),
+            config::ThemeFollow::Portal => {
+                #[cfg(feature = "dbus-services")]
+                appearance::follow_color_scheme(tx_draw.clone());
+                None
+            }
+        },
+        None => None,
+    };
+    #[cfg(feature = "dbus-services")]
+    if config.reduce_motion == config::ReduceMotion::Portal {
+        appearance::follow_reduced_motion(tx_draw.clone());
+    }
+    let mut dark = true;
+    // The background for the theme, unless replaced by accessibility mode
+    let themed_background = |dark: bool| match &theme_switch {
+        Some(theme) if !dark => theme.light_background,
+        _ => background,
+    };
+    let current_background = |dark: bool| match accessibility::is_enabled() {
+        true => config.accessibility.background,
+        false => themed_background(dark),
+    };
+
+    let mut app = App::connect(
+        tx_draw.clone(),
+        Arc::new(Mutex::new(VecDeque::new())),
+        output_mode,
+        current_background(dark),
+        scale,
+    )
+    .unwrap_or_else(|err| {
+        eprintln!("Unable to connect to the compositor: {}", err);
+        std::process::exit(1);
+    });
+    // The settings of a new connection, also made again after reconnecting
+    let configure = |app: &mut App| {
+        if let Some(hot_corner) = &hot_corner {
+            app.set_hot_corner(Some(HotCorner {
+                anchor: hot_corner.anchor.to_anchor(),
+                size: hot_corner.size,
+                margin: hot_corner.margin,
+                interactivity: hot_corner.keyboard_interactivity.to_interactivity(),
+            }));
+        }
+        app.set_keyboard_interactivity(config.keyboard_interactivity.to_interactivity());
+        app.set_backdrop(config.backdrop);
+    };
+    configure(&mut app);
+    if locking {
+        if let Err(err) = app.lock_session() {
+            eprintln!("Unable to lock the session: {}", err);
+            let _ = std::fs::remove_file(socket_path);
+            std::process::exit(1);
+        }
+    } else if daemon {
         app.hide();
     } else {
         app.show();
@@ -197,6 +763,11 @@ fn main() {
     let (mut rx_pipe, mut tx_pipe) = pipe().unwrap();
     let ipc_pipe = tx_pipe.try_clone().unwrap();
 
+    #[cfg(feature = "prometheus")]
+    if let Some(port) = config.metrics_port {
+        prometheus::serve(port, app.cmd_queue(), tx_pipe.try_clone().unwrap());
+    }
+
     let worker_queue = app.cmd_queue();
     let _ = std::thread::Builder::new()
         .name("cmd_proxy".to_string())
@@ -206,8 +777,10 @@ fn main() {
             tx_pipe.write_all(&[0x1]).unwrap();
         });
 
-    let mut timer = TimerFd::new().unwrap();
-    let ev_fd = PollFd::new(
+    // An absolute realtime timer keeps ticks on wall-clock boundaries across
+    // suspend, and is cancelled (waking us up) if the clock is changed.
+    let mut timer = TimerFd::new_custom(ClockId::Realtime, true, true).unwrap();
+    let mut ev_fd = PollFd::new(
         app.event_queue().display().get_connection_fd(),
         PollFlags::POLLIN,
     );
@@ -218,6 +791,18 @@ fn main() {
     app.cmd_queue().lock().unwrap().push_back(Cmd::Draw);
 
     let mut visible = !daemon;
+    // The presets replace the regular widget tree depending on the size of
+    // the output. The trees that are not shown are kept, so that switching
+    // back does not start them over.
+    let adaptive =
+        layout_editor.is_none() && !prompt_only && !locking && !config.presets.is_empty();
+    let mut preset = None;
+    let mut inactive = Vec::new();
+    // Whether the dashboard was expanded from the hot corner, and when it
+    // should collapse again after the pointer left it.
+    let mut hot_expanded = false;
+    let mut collapse_at = None;
+    let mut osd = osd::Osd::new(config.osd.font(&fonts), config.osd.clone());
     let mut wait_ctx = WaitContext {
         fds: Vec::new(),
         target_time: None,
@@ -237,14 +822,168 @@ fn main() {
                         q.lock().unwrap().push_back(cmd);
                     }
                 }
+                Cmd::Launch { argv, app_id } => {
+                    let token = app.activation_token();
+                    if let Err(err) = config.exec.spawn(&argv, app_id.as_deref(), token) {
+                        eprintln!("Unable to launch {}: {}", argv[0], err);
+                    }
+                }
                 Cmd::ForceDraw => {
                     app.redraw(true).expect("Failed to draw");
                     app.flush_display();
                 }
+                Cmd::Osd { text, timeout } => {
+                    osd.push(text, timeout);
+                    q.lock().unwrap().push_back(Cmd::OsdDraw);
+                }
+                Cmd::OsdDraw => {
+                    osd.update(Local::now().naive_local());
+                    if let Err(err) = app.draw_osd(&mut osd) {
+                        eprintln!("Unable to draw the on-screen display: {}", err);
+                    }
+                    app.flush_display();
+                }
                 Cmd::MouseClick { btn, pos } => {
+                    if !visible {
+                        // Clicking the collapsed hot corner
+                        q.lock().unwrap().push_back(Cmd::PointerEnter);
+                        continue;
+                    }
                     app.get_widget().mouse_click(btn, pos);
                     q.lock().unwrap().push_back(Cmd::Draw);
                 }
+                Cmd::MousePress { btn, pos } => {
+                    if visible {
+                        app.get_widget().mouse_press(btn, pos);
+                        q.lock().unwrap().push_back(Cmd::Draw);
+                    }
+                }
+                Cmd::MouseMotion { pos } => {
+                    if visible {
+                        app.get_widget().mouse_motion(pos);
+                        q.lock().unwrap().push_back(Cmd::Draw);
+                    }
+                }
+                Cmd::PointerEnter => {
+                    collapse_at = None;
+                    if hot_corner.is_some() && !visible {
+                        visible = true;
+                        hot_expanded = true;
+                        app.get_widget().enter();
+                        app.show();
+                        app.flush_display();
+                    }
+                }
+                Cmd::PointerLeave => {
+                    if let Some(hot_corner) = &hot_corner {
+                        if hot_expanded && visible {
+                            collapse_at = Some(
+                                Local::now().naive_local()
+                                    + Duration::milliseconds(hot_corner.delay.into()),
+                            );
+                        }
+                    }
+                }
+                Cmd::Resize => {
+                    let next = match app.output_size() {
+                        Some(size) if adaptive => config.preset(size),
+                        _ => continue,
+                    };
+                    if next == preset {
+                        continue;
+                    }
+                    let widget = match inactive.iter().position(|(p, _)| *p == next) {
+                        Some(idx) => inactive.swap_remove(idx).1,
+                        None => dashboard(
+                            config.accessibility.apply(config.preset_widget(next)),
+                            &missing_fonts,
+                        )
+                        .construct(Local::now().naive_local(), tx_preset.clone(), &fonts)
+                        .expect("no widget configured"),
+                    };
+                    if let Some(mut old) = app.replace_widget(widget) {
+                        old.leave();
+                        inactive.push((preset, old));
+                    }
+                    preset = next;
+                    if visible {
+                        app.get_widget().enter();
+                    }
+                    q.lock().unwrap().push_back(Cmd::ForceDraw);
+                }
+                Cmd::Offline(offline) => {
+                    offline::set_offline(offline.unwrap_or(!offline::is_offline()));
+                    q.lock().unwrap().push_back(Cmd::ForceDraw);
+                }
+                Cmd::Private(private) => {
+                    private::set_private(private.unwrap_or(!private::is_private()));
+                    q.lock().unwrap().push_back(Cmd::ForceDraw);
+                }
+                Cmd::Accessibility(enabled) => {
+                    let enabled = enabled.unwrap_or(!accessibility::is_enabled());
+                    // The layout editor edits the tree as configured, and the
+                    // lock screen is not rebuilt while locked
+                    if enabled == accessibility::is_enabled() || layout_editor.is_some() || locking
+                    {
+                        continue;
+                    }
+                    accessibility::set_enabled(enabled);
+                    let tree = match prompt_only {
+                        true => config.prompt_widget(),
+                        false => config.preset_widget(preset),
+                    };
+                    let widget = dashboard(config.accessibility.apply(tree), &missing_fonts)
+                        .construct(Local::now().naive_local(), tx_preset.clone(), &fonts)
+                        .expect("no widget configured");
+                    if let Some(mut old) = app.replace_widget(widget) {
+                        old.leave();
+                    }
+                    // The other presets were built for the previous mode
+                    inactive.clear();
+                    if visible {
+                        app.get_widget().enter();
+                    }
+                    app.set_background(current_background(dark));
+                    q.lock().unwrap().push_back(Cmd::ForceDraw);
+                }
+                Cmd::Window(_) if locking => (),
+                Cmd::Window(windowed) => {
+                    let windowed = windowed.unwrap_or(!app.is_windowed());
+                    if let Err(err) = app.set_windowed(windowed) {
+                        eprintln!("Unable to switch how the dashboard is shown: {}", err);
+                    }
+                    app.flush_display();
+                }
+                Cmd::ColorScheme { dark: d } => {
+                    if theme_switch.is_some() && d != dark {
+                        dark = d;
+                        app.set_background(current_background(dark));
+                        q.lock().unwrap().push_back(Cmd::ForceDraw);
+                    }
+                }
+                #[cfg(feature = "dbus-services")]
+                Cmd::ReducedMotion { reduced } => {
+                    motion::set_preferred(reduced);
+                    q.lock().unwrap().push_back(Cmd::ForceDraw);
+                }
+                Cmd::Query { reply } => {
+                    let mut states = Vec::new();
+                    app.get_widget().state(&mut states);
+                    let _ = reply.send(states);
+                }
+                Cmd::DumpTree { reply } => {
+                    let _ = reply.send(app.get_widget().tree((0, 0)));
+                }
+                Cmd::Export { path } => {
+                    if let Err(err) = app.export(&path) {
+                        eprintln!("Unable to export to {}: {}", path.display(), err);
+                    }
+                    q.lock().unwrap().push_back(Cmd::ForceDraw);
+                }
+                Cmd::Action(action) => {
+                    app.get_widget().action(&action);
+                    q.lock().unwrap().push_back(Cmd::Draw);
+                }
                 Cmd::MouseScroll { scroll, pos } => {
                     app.get_widget().mouse_scroll(scroll, pos);
                     q.lock().unwrap().push_back(Cmd::Draw);
@@ -255,12 +994,25 @@ fn main() {
                     modifiers_state,
                     interpreted,
                 } => {
+                    // The layout editor takes over the keyboard
+                    if let Some(editor) = &mut layout_editor {
+                        if key_state == KeyState::Pressed {
+                            if let Some(action) = editor.keyboard_input(key, modifiers_state) {
+                                app.get_widget().action(&action);
+                                q.lock().unwrap().push_back(Cmd::ForceDraw);
+                            }
+                        }
+                        continue;
+                    }
                     app.get_widget()
                         .keyboard_input(key, modifiers_state, key_state, interpreted);
                     q.lock().unwrap().push_back(Cmd::Draw);
                 }
+                Cmd::ToggleVisible if locking => (),
                 Cmd::ToggleVisible => {
                     visible = !visible;
+                    hot_expanded = false;
+                    collapse_at = None;
                     if visible {
                         app.get_widget().enter();
                         app.show();
@@ -270,21 +1022,117 @@ fn main() {
                     }
                     app.flush_display();
                 }
+                // The lock screen cannot be dismissed, only the password
+                // cleared
+                Cmd::Dismiss { .. } if locking => {
+                    app.get_widget().action(&Action::ClearPrompt);
+                    q.lock().unwrap().push_back(Cmd::Draw);
+                }
+                Cmd::Dismiss { focus_lost } => {
+                    let policies = if focus_lost {
+                        config.dismiss.focus_lost
+                    } else {
+                        config.dismiss.escape
+                    };
+                    // Focus is also lost when the dashboard is hidden
+                    if !visible {
+                        continue;
+                    }
+                    match policies.get(daemon) {
+                        DismissPolicy::Exit => {
+                            let _ = std::fs::remove_file(socket_path);
+                            return;
+                        }
+                        DismissPolicy::Hide => q.lock().unwrap().push_back(Cmd::ToggleVisible),
+                        DismissPolicy::ClearPrompt => {
+                            app.get_widget().action(&Action::ClearPrompt);
+                            q.lock().unwrap().push_back(Cmd::Draw);
+                        }
+                        DismissPolicy::Ignore => (),
+                    }
+                }
+                Cmd::Kill if locking => (),
+                Cmd::Kill => q.lock().unwrap().push_back(Cmd::Exit),
+                Cmd::Unlock => {
+                    app.unlock_session();
+                    let _ = std::fs::remove_file(socket_path);
+                    return;
+                }
                 Cmd::Exit => {
                     if daemon {
                         visible = false;
+                        hot_expanded = false;
+                        collapse_at = None;
                         app.hide();
                         app.get_widget().leave();
                         app.flush_display();
                     } else {
+                        app.get_widget().leave();
                         let _ = std::fs::remove_file(socket_path);
                         return;
                     }
                 }
             },
             None => {
+                if let Some((light_at, dark_at)) = theme_times {
+                    let d = is_dark(Local::now().naive_local().time(), light_at, dark_at);
+                    if d != dark {
+                        q.lock().unwrap().push_back(Cmd::ColorScheme { dark: d });
+                        continue;
+                    }
+                }
+
                 app.flush_display();
 
+                if !app.is_connected() {
+                    if !daemon {
+                        eprintln!("Lost the connection to the compositor");
+                        let _ = std::fs::remove_file(socket_path);
+                        std::process::exit(1);
+                    }
+                    // The widgets are kept as they are, and shown again on
+                    // the new connection unless expanded from the hot corner.
+                    let mut widget = app.take_widget();
+                    let windowed = app.is_windowed();
+                    drop(app);
+                    eprintln!("Lost the connection to the compositor, reconnecting");
+                    app = reconnect(
+                        tx_draw.clone(),
+                        q.clone(),
+                        output_mode,
+                        current_background(dark),
+                        scale,
+                    );
+                    configure(&mut app);
+                    // Only a window if asked for, or if the new compositor
+                    // has no layer shell
+                    if windowed {
+                        let _ = app.set_windowed(true);
+                    }
+                    if hot_expanded {
+                        visible = false;
+                        hot_expanded = false;
+                        collapse_at = None;
+                        if let Some(widget) = widget.as_mut() {
+                            widget.leave();
+                        }
+                    }
+                    if visible {
+                        app.show();
+                    } else {
+                        app.hide();
+                    }
+                    if let Some(widget) = widget {
+                        app.set_widget(widget).unwrap();
+                    }
+                    ev_fd = PollFd::new(
+                        app.event_queue().display().get_connection_fd(),
+                        PollFlags::POLLIN,
+                    );
+                    q.lock().unwrap().push_back(Cmd::ForceDraw);
+                    continue;
+                }
+
                 wait_ctx.fds.clear();
                 wait_ctx.fds.push(ev_fd);
                 wait_ctx.fds.push(rx_fd);
@@ -293,42 +1141,31 @@ fn main() {
 
                 app.get_widget().wait(&mut wait_ctx);
                 app.set_keyboard_repeat(&mut wait_ctx);
+                osd.wait(&mut wait_ctx);
+                if let Some(t) = collapse_at {
+                    wait_ctx.set_time(t);
+                }
+                if let Some((light_at, dark_at)) = theme_times {
+                    wait_ctx.set_time(next_switch(Local::now().naive_local(), light_at, dark_at));
+                }
 
                 if let Some(target_time) = wait_ctx.target_time {
-                    let n = Local::now().naive_local();
-                    let sleep = if target_time > n {
-                        target_time - n
-                    } else {
-                        Duration::seconds(0)
-                    };
                     timer.set_state(
-                        TimerState::Oneshot(sleep.to_std().unwrap()),
-                        SetTimeFlags::Default,
+                        TimerState::Oneshot(realtime(target_time)),
+                        SetTimeFlags::TimerCancelOnSet,
                     );
                     wait_ctx.fds.push(tm_fd);
                 }
 
                 poll(&mut wait_ctx.fds, -1).unwrap();
 
+                // A hangup shows up as a failed read
                 if wait_ctx.fds[0]
                     .revents()
                     .unwrap()
-                    .contains(PollFlags::POLLIN)
+                    .intersects(PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR)
                 {
-                    if let Some(guard) = app.event_queue().prepare_read() {
-                        if let Err(e) = guard.read_events() {
-                            if e.kind() != ::std::io::ErrorKind::WouldBlock {
-                                eprintln!(
-                                    "Error while trying to read from the wayland socket: {:?}",
-                                    e
-                                );
-                            }
-                        }
-                    }
-
-                    app.event_queue()
-                        .dispatch_pending(&mut (), |_, _, _| {})
-                        .expect("Failed to dispatch all messages.");
+                    app.dispatch_events();
                 }
 
                 if wait_ctx.fds[1]
@@ -345,30 +1182,17 @@ fn main() {
                     .unwrap()
                     .contains(PollFlags::POLLIN)
                 {
-                    if let Ok((stream, _)) = listener.accept() {
+                    if let Some(stream) = listener
+                        .accept()
+                        .ok()
+                        .map(|(stream, _)| stream)
+                        .filter(same_user)
+                    {
                         let client_queue = q.clone();
-                        let mut client_pipe = ipc_pipe.try_clone().unwrap();
+                        let client_pipe = ipc_pipe.try_clone().unwrap();
                         let _ = std::thread::Builder::new()
                             .name("ipc_client".to_string())
-                            .spawn(move || {
-                                let r = BufReader::new(stream);
-                                for line in r.lines() {
-                                    match line {
-                                        Ok(v) => match v.as_str() {
-                                            "kill" => {
-                                                client_queue.lock().unwrap().push_back(Cmd::Exit)
-                                            }
-                                            "toggle_visible" => client_queue
-                                                .lock()
-                                                .unwrap()
-                                                .push_back(Cmd::ToggleVisible),
-                                            v => eprintln!("unknown command: {}", v),
-                                        },
-                                        Err(_) => return,
-                                    }
-                                    client_pipe.write_all(&[0x1]).unwrap();
-                                }
-                            });
+                            .spawn(move || ipc_client(stream, client_queue, client_pipe));
                     }
                 }
 
@@ -378,10 +1202,18 @@ fn main() {
                         .unwrap()
                         .contains(PollFlags::POLLIN)
                 {
-                    timer.read();
+                    // Fails with ECANCELED if the clock was changed, in which
+                    // case we also want to redraw.
+                    let _ = nix::unistd::read(timer.as_raw_fd(), &mut [0u8; 8]);
                     let mut qq = q.lock().unwrap();
                     qq.push_back(Cmd::KeyboardTest);
                     qq.push_back(Cmd::Draw);
+                    if osd.is_shown() {
+                        qq.push_back(Cmd::OsdDraw);
+                    }
+                    if collapse_at.is_some_and(|t| t <= Local::now().naive_local()) {
+                        qq.push_back(Cmd::Exit);
+                    }
                 }
             }
         }
@@ -0,0 +1,148 @@
+//! Matching input against names
+//!
+//! The launcher, its history search and category browser, and the file
+//! browser all match what was typed through the same algorithm, picked with
+//! `matching` at the top level of the config. Both the input and the names
+//! are expected to be lowercased already.
+
+use std::sync::Mutex;
+
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::{Deserialize, Serialize};
+
+/// A way of matching a pattern against a choice, scoring how well it
+/// matches. Better matches score higher.
+pub trait Matcher {
+    /// The score of the match, and the indices of the matching characters.
+    fn indices(&self, choice: &str, pattern: &str) -> Option<(i64, Vec<usize>)>;
+
+    fn score(&self, choice: &str, pattern: &str) -> Option<i64> {
+        self.indices(choice, pattern).map(|(score, _)| score)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Algorithm {
+    /// Fuzzy matching, where the characters of the pattern appear in order
+    /// with anything in between.
+    #[default]
+    Skim,
+    /// The pattern appears as is anywhere in the choice.
+    Substring,
+    /// The choice starts with the pattern.
+    Prefix,
+    /// The pattern is made of the starts of words of the choice, in order,
+    /// such as "vsc" or "visco" for "visual studio code".
+    WordInitial,
+}
+
+static ALGORITHM: Mutex<Algorithm> = Mutex::new(Algorithm::Skim);
+
+pub fn set_configured(algorithm: Algorithm) {
+    *ALGORITHM.lock().unwrap() = algorithm;
+}
+
+/// The matcher of the configured algorithm.
+pub fn matcher() -> Box<dyn Matcher> {
+    match *ALGORITHM.lock().unwrap() {
+        Algorithm::Skim => Box::new(Skim(SkimMatcherV2::default())),
+        Algorithm::Substring => Box::new(Substring),
+        Algorithm::Prefix => Box::new(Prefix),
+        Algorithm::WordInitial => Box::new(WordInitial),
+    }
+}
+
+struct Skim(SkimMatcherV2);
+
+impl Matcher for Skim {
+    fn indices(&self, choice: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+        self.0.fuzzy_indices(choice, pattern)
+    }
+
+    fn score(&self, choice: &str, pattern: &str) -> Option<i64> {
+        self.0.fuzzy_match(choice, pattern)
+    }
+}
+
+// The score of a match of the given length starting at the character,
+// preferring longer matches that start early.
+fn positional_score(start: usize, len: usize) -> i64 {
+    (len * 16) as i64 - start as i64
+}
+
+struct Substring;
+
+impl Matcher for Substring {
+    fn indices(&self, choice: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+        let offset = choice.find(pattern)?;
+        let start = choice[..offset].chars().count();
+        let len = pattern.chars().count();
+        Some((positional_score(start, len), (start..start + len).collect()))
+    }
+}
+
+struct Prefix;
+
+impl Matcher for Prefix {
+    fn indices(&self, choice: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+        if !choice.starts_with(pattern) {
+            return None;
+        }
+        let len = pattern.chars().count();
+        Some((positional_score(0, len), (0..len).collect()))
+    }
+}
+
+struct WordInitial;
+
+// The words of the text, as the character index they start at and their
+// characters. Words are runs of letters and digits.
+fn words(text: &str) -> Vec<(usize, Vec<char>)> {
+    let mut words: Vec<(usize, Vec<char>)> = Vec::new();
+    let mut in_word = false;
+    for (idx, c) in text.chars().enumerate() {
+        match (c.is_alphanumeric(), in_word) {
+            (true, true) => words.last_mut().unwrap().1.push(c),
+            (true, false) => words.push((idx, vec![c])),
+            _ => (),
+        }
+        in_word = c.is_alphanumeric();
+    }
+    words
+}
+
+// Matches the pattern as starts of the words from the given one on,
+// returning how many words were skipped and the matched characters. Longer
+// starts are tried first, so that "fire" matches "firefox" as a whole.
+fn match_words(words: &[(usize, Vec<char>)], pattern: &[char]) -> Option<(usize, Vec<usize>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    for (skipped, (start, word)) in words.iter().enumerate() {
+        let common = word
+            .iter()
+            .zip(pattern.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        for len in (1..=common).rev() {
+            if let Some((more, rest)) = match_words(&words[skipped + 1..], &pattern[len..]) {
+                let mut indices: Vec<usize> = (*start..start + len).collect();
+                indices.extend(rest);
+                return Some((skipped + more, indices));
+            }
+        }
+    }
+    None
+}
+
+impl Matcher for WordInitial {
+    fn indices(&self, choice: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+        // Spaces in the pattern only separate words, which it already does
+        let pattern: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+        let (skipped, indices) = match_words(&words(choice), &pattern)?;
+        let score = positional_score(indices.first().copied().unwrap_or(0), pattern.len());
+        Some((score - skipped as i64 * 8, indices))
+    }
+}
@@ -0,0 +1,22 @@
+//! Counters describing wldash itself, shown by the metrics widget.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+static FRAME_TIME: AtomicU64 = AtomicU64::new(0); // microseconds
+static POOL_SIZE: AtomicU64 = AtomicU64::new(0); // bytes
+
+/// Records the time it took to render and commit the last frame, and the
+/// total size of the buffer pools.
+pub fn record_frame(duration: Duration, pool_size: u64) {
+    FRAME_TIME.store(duration.as_micros() as u64, Ordering::Relaxed);
+    POOL_SIZE.store(pool_size, Ordering::Relaxed);
+}
+
+pub fn frame_time() -> Duration {
+    Duration::from_micros(FRAME_TIME.load(Ordering::Relaxed))
+}
+
+pub fn pool_size() -> u64 {
+    POOL_SIZE.load(Ordering::Relaxed)
+}
@@ -0,0 +1,27 @@
+//! Reduced motion
+//!
+//! Animations that are only there for show, such as the blinking cursor, the
+//! turning spinners and kinetic scrolling, are left out while motion is
+//! reduced, and what they lead up to is shown at once instead. Motion is
+//! reduced when configured, or when the desktop prefers it through the
+//! settings portal. Widgets check is_reduced before animating.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CONFIGURED: AtomicBool = AtomicBool::new(false);
+static PREFERRED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_reduced() -> bool {
+    CONFIGURED.load(Ordering::Relaxed) || PREFERRED.load(Ordering::Relaxed)
+}
+
+/// Reduces motion regardless of the preference of the desktop.
+pub fn set_configured(reduced: bool) {
+    CONFIGURED.store(reduced, Ordering::Relaxed);
+}
+
+/// Follows the preference of the desktop.
+#[cfg(feature = "dbus-services")]
+pub fn set_preferred(reduced: bool) {
+    PREFERRED.store(reduced, Ordering::Relaxed);
+}
@@ -0,0 +1,16 @@
+//! Offline mode
+//!
+//! While offline, widgets do not fetch anything over the network. They keep
+//! showing the last data they fetched instead, along with how old it is.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
@@ -0,0 +1,105 @@
+//! On-screen messages
+//!
+//! Scripts show short messages with `wldash osd`, such as the volume after
+//! changing it. The messages are shown one after another, each for its
+//! timeout, on a small surface of their own that is there whether the
+//! dashboard is shown or not.
+
+use crate::buffer::Buffer;
+use crate::config::OsdStyle;
+use crate::draw::Font;
+use crate::fonts::FontRef;
+use crate::widget::WaitContext;
+
+use std::collections::VecDeque;
+
+use chrono::{Duration, NaiveDateTime};
+
+pub struct Osd<'a> {
+    font: Font<'a>,
+    style: OsdStyle,
+    // The messages waiting to be shown, with their timeout in milliseconds
+    queue: VecDeque<(String, u32)>,
+    // The message shown, and until when
+    shown: Option<(String, NaiveDateTime)>,
+    // Whether the message shown changed since it was last drawn
+    changed: bool,
+}
+
+impl<'a> Osd<'a> {
+    pub fn new(font: FontRef<'a>, style: OsdStyle) -> Osd<'a> {
+        Osd {
+            font: Font::new(font, style.font_size),
+            style,
+            queue: VecDeque::new(),
+            shown: None,
+            changed: false,
+        }
+    }
+
+    /// Queues a message, to be shown for the timeout or for the configured
+    /// time.
+    pub fn push(&mut self, text: String, timeout: Option<u32>) {
+        self.queue
+            .push_back((text, timeout.unwrap_or(self.style.timeout)));
+    }
+
+    /// Moves on to the next message once the one shown has timed out.
+    pub fn update(&mut self, now: NaiveDateTime) {
+        if self.shown.as_ref().is_some_and(|(_, until)| *until <= now) {
+            self.shown = None;
+            self.changed = true;
+        }
+        if self.shown.is_none() {
+            if let Some((text, timeout)) = self.queue.pop_front() {
+                self.shown = Some((text, now + Duration::milliseconds(timeout.into())));
+                self.changed = true;
+            }
+        }
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.shown.is_some()
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn wait(&self, ctx: &mut WaitContext) {
+        if let Some((_, until)) = &self.shown {
+            ctx.set_time(*until);
+        }
+    }
+
+    pub fn style(&self) -> &OsdStyle {
+        &self.style
+    }
+
+    pub fn size(&mut self) -> (u32, u32) {
+        let width = match &self.shown {
+            Some((text, _)) => self.font.auto_text_width(text),
+            None => 0,
+        };
+        let padding = self.style.padding;
+        (
+            width + 2 * padding,
+            self.style.font_size.ceil() as u32 + 2 * padding,
+        )
+    }
+
+    pub fn draw(&mut self, buf: &mut Buffer) -> Result<(), ::std::io::Error> {
+        self.changed = false;
+        buf.memset(&self.style.background);
+        if let Some((text, _)) = &self.shown {
+            let padding = self.style.padding;
+            self.font.auto_draw_text(
+                &mut buf.offset((padding, padding))?,
+                &self.style.background,
+                &self.style.foreground,
+                text,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+//! Password authentication through PAM
+//!
+//! libpam is loaded when a password is first checked rather than linked, in
+//! the same way as xkbcommon, so that wldash runs without it for everything
+//! but the lock screen.
+
+#![allow(non_camel_case_types)]
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+use nix::libc;
+use nix::unistd::{getuid, User};
+
+const PAM_SUCCESS: c_int = 0;
+const PAM_BUF_ERR: c_int = 5;
+const PAM_CONV_ERR: c_int = 19;
+const PAM_REFRESH_CRED: c_int = 0x0010;
+
+const PAM_PROMPT_ECHO_OFF: c_int = 1;
+const PAM_PROMPT_ECHO_ON: c_int = 2;
+
+pub struct pam_handle_t;
+
+#[repr(C)]
+struct pam_message {
+    msg_style: c_int,
+    msg: *const c_char,
+}
+
+#[repr(C)]
+struct pam_response {
+    resp: *mut c_char,
+    resp_retcode: c_int,
+}
+
+#[repr(C)]
+struct pam_conv {
+    conv:
+        extern "C" fn(c_int, *mut *const pam_message, *mut *mut pam_response, *mut c_void) -> c_int,
+    appdata_ptr: *mut c_void,
+}
+
+dlopen_external_library!(Pam,
+functions:
+    fn pam_start(*const c_char, *const c_char, *const pam_conv, *mut *mut pam_handle_t) -> c_int,
+    fn pam_authenticate(*mut pam_handle_t, c_int) -> c_int,
+    fn pam_setcred(*mut pam_handle_t, c_int) -> c_int,
+    fn pam_end(*mut pam_handle_t, c_int) -> c_int,
+    fn pam_strerror(*mut pam_handle_t, c_int) -> *const c_char,
+);
+
+lazy_static::lazy_static!(
+    static ref PAM_OPTION: Option<Pam> = {
+        unsafe {
+            Pam::open("libpam.so.0")
+            .or_else(|_| Pam::open("libpam.so"))
+            .ok()
+        }
+    };
+);
+
+// Answers the prompts of PAM with the password, which is passed as the
+// application data. Other messages, such as notices, need no answer.
+extern "C" fn converse(
+    num_msg: c_int,
+    msg: *mut *const pam_message,
+    resp: *mut *mut pam_response,
+    appdata_ptr: *mut c_void,
+) -> c_int {
+    let password = appdata_ptr as *const c_char;
+    unsafe {
+        // Both the responses and their text are freed by PAM
+        let responses = libc::calloc(num_msg as usize, std::mem::size_of::<pam_response>())
+            as *mut pam_response;
+        if responses.is_null() {
+            return PAM_BUF_ERR;
+        }
+        for idx in 0..num_msg as usize {
+            let message = &**msg.add(idx);
+            if let PAM_PROMPT_ECHO_OFF | PAM_PROMPT_ECHO_ON = message.msg_style {
+                let text = libc::strdup(password);
+                if text.is_null() {
+                    for done in 0..idx {
+                        libc::free((*responses.add(done)).resp as *mut c_void);
+                    }
+                    libc::free(responses as *mut c_void);
+                    return PAM_CONV_ERR;
+                }
+                (*responses.add(idx)).resp = text;
+            }
+        }
+        *resp = responses;
+    }
+    PAM_SUCCESS
+}
+
+/// Checks the password of the current user against the PAM service, such
+/// as "login" for /etc/pam.d/login. This blocks for as long as PAM takes,
+/// which is seconds after a wrong password.
+pub fn authenticate(service: &str, password: &str) -> Result<(), String> {
+    let pam = PAM_OPTION
+        .as_ref()
+        .ok_or_else(|| "libpam could not be loaded".to_string())?;
+    let user = User::from_uid(getuid())
+        .ok()
+        .flatten()
+        .ok_or_else(|| "unknown user".to_string())?;
+    let service = CString::new(service).map_err(|_| "invalid PAM service".to_string())?;
+    let name = CString::new(user.name).map_err(|_| "invalid user name".to_string())?;
+    let password = CString::new(password).map_err(|_| "invalid password".to_string())?;
+
+    let conv = pam_conv {
+        conv: converse,
+        appdata_ptr: password.as_ptr() as *mut c_void,
+    };
+    let mut handle: *mut pam_handle_t = ptr::null_mut();
+    let result = unsafe {
+        match (pam.pam_start)(service.as_ptr(), name.as_ptr(), &conv, &mut handle) {
+            PAM_SUCCESS => {
+                let mut res = (pam.pam_authenticate)(handle, 0);
+                if res == PAM_SUCCESS {
+                    // Renews credentials such as Kerberos tickets, as a login would
+                    res = (pam.pam_setcred)(handle, PAM_REFRESH_CRED);
+                }
+                let result = match res {
+                    PAM_SUCCESS => Ok(()),
+                    _ => Err(CStr::from_ptr((pam.pam_strerror)(handle, res))
+                        .to_string_lossy()
+                        .into_owned()),
+                };
+                (pam.pam_end)(handle, res);
+                result
+            }
+            _ => Err("unable to start PAM".to_string()),
+        }
+    };
+
+    // The password is overwritten rather than just dropped, and kept alive
+    // until then so that the writes are not left out
+    let mut password = password.into_bytes_with_nul();
+    password.fill(0);
+    std::hint::black_box(&password);
+    result
+}
@@ -0,0 +1,17 @@
+//! Private mode
+//!
+//! While private, nothing about the session is persisted: launches are not
+//! counted towards the launcher ranking. This is meant for screen sharing or
+//! shared machines, and lasts until turned off or wldash exits.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PRIVATE: AtomicBool = AtomicBool::new(false);
+
+pub fn is_private() -> bool {
+    PRIVATE.load(Ordering::Relaxed)
+}
+
+pub fn set_private(private: bool) {
+    PRIVATE.store(private, Ordering::Relaxed);
+}
@@ -0,0 +1,106 @@
+//! Metrics endpoint
+//!
+//! Serves the state of the widgets, as queried with `wldash query`, and the
+//! frame metrics of wldash in the Prometheus text format on a port of
+//! localhost, for scraping into other dashboards.
+
+use crate::cmd::Cmd;
+use crate::metrics;
+use crate::widget::{StateValue, WidgetState};
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use os_pipe::PipeWriter;
+
+// Metric names only allow letters, digits and underscores
+fn metric_name(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn label_value(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Formats the widget states and frame metrics in the Prometheus text
+/// format. Text values become a label of a metric that is always 1.
+pub fn format_metrics(states: &[WidgetState]) -> String {
+    let mut out = String::new();
+    for state in states {
+        for (key, value) in state.values.iter() {
+            let name = format!("wldash_{}_{}", metric_name(&state.name), metric_name(key));
+            let _ = match value {
+                StateValue::Number(n) if n.is_finite() => writeln!(out, "{} {}", name, n),
+                StateValue::Number(_) => Ok(()),
+                StateValue::Bool(b) => writeln!(out, "{} {}", name, *b as u8),
+                StateValue::Text(s) => writeln!(out, "{}{{value=\"{}\"}} 1", name, label_value(s)),
+            };
+        }
+    }
+    let _ = writeln!(
+        out,
+        "wldash_frame_seconds {}",
+        metrics::frame_time().as_secs_f64()
+    );
+    let _ = writeln!(out, "wldash_pool_bytes {}", metrics::pool_size());
+    out
+}
+
+// Answers any request with the metrics, once the main loop has collected
+// the widget states.
+fn respond(
+    stream: TcpStream,
+    queue: &Mutex<VecDeque<Cmd>>,
+    pipe: &mut PipeWriter,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut writer = stream.try_clone()?;
+    // The request is read up to the blank line ending its headers, as
+    // closing the connection with unread data would reset it.
+    for line in BufReader::new(stream).lines() {
+        if line?.is_empty() {
+            break;
+        }
+    }
+
+    let (tx, rx) = channel();
+    queue.lock().unwrap().push_back(Cmd::Query { reply: tx });
+    pipe.write_all(&[0x1])?;
+    let body = format_metrics(&rx.recv().unwrap_or_default());
+    write!(
+        writer,
+        "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+/// Starts serving the metrics on the port of localhost.
+pub fn serve(port: u16, queue: Arc<Mutex<VecDeque<Cmd>>>, mut pipe: PipeWriter) {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Unable to serve metrics on port {}: {}", port, err);
+            return;
+        }
+    };
+    let _ = thread::Builder::new()
+        .name("prometheus".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(err) = respond(stream, &queue, &mut pipe) {
+                    eprintln!("Unable to serve metrics: {}", err);
+                }
+            }
+        });
+}
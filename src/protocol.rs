@@ -0,0 +1,419 @@
+//! Protocol shim
+//!
+//! The values carried by wayland-client events are translated into types of
+//! our own here, and the file descriptors passed to and from the compositor
+//! go through here as well. Major versions of wayland-client have changed
+//! both, wrapping enums in `WEnum` and descriptors in `OwnedFd`, so that an
+//! upgrade should only have to touch this module. Pointer events are turned
+//! into the commands of the widgets here too, a frame at a time.
+
+use crate::cmd::Cmd;
+use crate::scroll::ScrollState;
+
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use wayland_client::protocol::{
+    wl_data_device_manager::DndAction, wl_data_offer, wl_keyboard, wl_output, wl_pointer, wl_shm,
+    wl_shm_pool,
+};
+use wayland_client::Main;
+use wayland_protocols::xdg_shell::client::xdg_toplevel;
+
+/// The interfaces that generated protocol bindings refer to.
+pub mod interfaces {
+    pub(crate) use wayland_client::protocol::{wl_output, wl_surface};
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Released,
+    Pressed,
+}
+
+pub fn key_state(state: wl_keyboard::KeyState) -> KeyState {
+    match state {
+        wl_keyboard::KeyState::Pressed => KeyState::Pressed,
+        _ => KeyState::Released,
+    }
+}
+
+/// The descriptor and size of a keymap, if it is one we understand.
+pub fn keymap(format: wl_keyboard::KeymapFormat, fd: RawFd, size: u32) -> Option<(RawFd, usize)> {
+    match format {
+        wl_keyboard::KeymapFormat::XkbV1 => Some((fd, size as usize)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollAxis {
+    Horizontal,
+    Vertical,
+}
+
+impl ScrollAxis {
+    /// The index of the axis in (x, y) pairs.
+    pub fn index(self) -> usize {
+        match self {
+            ScrollAxis::Horizontal => 0,
+            ScrollAxis::Vertical => 1,
+        }
+    }
+}
+
+pub fn scroll_axis(axis: wl_pointer::Axis) -> Option<ScrollAxis> {
+    match axis {
+        wl_pointer::Axis::HorizontalScroll => Some(ScrollAxis::Horizontal),
+        wl_pointer::Axis::VerticalScroll => Some(ScrollAxis::Vertical),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollSource {
+    /// Fingers on a touchpad, which scroll on kinetically when lifted
+    Finger,
+    Other,
+}
+
+pub fn scroll_source(source: wl_pointer::AxisSource) -> ScrollSource {
+    match source {
+        wl_pointer::AxisSource::Finger => ScrollSource::Finger,
+        _ => ScrollSource::Other,
+    }
+}
+
+pub fn button_released(state: wl_pointer::ButtonState) -> bool {
+    state == wl_pointer::ButtonState::Released
+}
+
+/// The pointer events of a frame, which become commands for the widgets
+/// once the frame ends. Positions are in buffer pixels.
+pub struct PointerFrame {
+    scale: u32,
+    pos: (u32, u32),
+    moved: bool,
+    pressed: Option<u32>,
+    released: Option<u32>,
+    scroll: ScrollState,
+}
+
+impl PointerFrame {
+    pub fn new(scale: u32, scroll: ScrollState) -> PointerFrame {
+        PointerFrame {
+            scale,
+            pos: (0, 0),
+            moved: false,
+            pressed: None,
+            released: None,
+            scroll,
+        }
+    }
+
+    /// The pointer entered a surface, at the given surface position.
+    pub fn enter(&mut self, surface_x: f64, surface_y: f64) {
+        self.pos = (surface_x as u32 * self.scale, surface_y as u32 * self.scale);
+        self.moved = true;
+    }
+
+    /// The pointer left the surface, so nothing is under it anymore.
+    pub fn leave(&mut self) -> Cmd {
+        self.pos = (0, 0);
+        self.moved = false;
+        Cmd::MouseMotion { pos: None }
+    }
+
+    /// Adds an event to the frame, returning the commands of the frame once
+    /// it ends. Entering and leaving surfaces is up to the caller, as only
+    /// it knows the surfaces.
+    pub fn event(&mut self, event: wl_pointer::Event) -> Vec<Cmd> {
+        match event {
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => self.enter(surface_x, surface_y),
+            wl_pointer::Event::AxisSource { axis_source } => {
+                self.scroll.source(scroll_source(axis_source))
+            }
+            wl_pointer::Event::Axis { time, axis, value } => {
+                if let Some(axis) = scroll_axis(axis) {
+                    self.scroll.axis(time, axis, value)
+                }
+            }
+            wl_pointer::Event::AxisDiscrete { axis, discrete } => {
+                if let Some(axis) = scroll_axis(axis) {
+                    self.scroll.discrete(axis, discrete)
+                }
+            }
+            wl_pointer::Event::AxisStop { axis, .. } => {
+                if let Some(axis) = scroll_axis(axis) {
+                    self.scroll.stop(axis)
+                }
+            }
+            wl_pointer::Event::Button { button, state, .. } => {
+                if button_released(state) {
+                    self.released = Some(button);
+                } else {
+                    self.pressed = Some(button);
+                }
+            }
+            wl_pointer::Event::Frame => return self.frame(),
+            _ => (),
+        }
+        Vec::new()
+    }
+
+    fn frame(&mut self) -> Vec<Cmd> {
+        let pos = self.pos;
+        let mut cmds = Vec::new();
+        if std::mem::take(&mut self.moved) {
+            cmds.push(Cmd::MouseMotion { pos: Some(pos) });
+        }
+        if let Some(btn) = self.pressed.take() {
+            cmds.push(Cmd::MousePress { btn, pos });
+        }
+        if let Some(scroll) = self.scroll.frame(pos) {
+            cmds.push(Cmd::MouseScroll { scroll, pos });
+        }
+        if let Some(btn) = self.released.take() {
+            cmds.push(Cmd::MouseClick { btn, pos });
+        }
+        cmds
+    }
+}
+
+/// The states of a window that matter to us, out of those the compositor
+/// configures it with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToplevelStates {
+    pub maximized: bool,
+    /// The window is being resized interactively, which ends with a
+    /// configure without this state
+    pub resizing: bool,
+}
+
+/// The states of a toplevel configure event, which are an array of u32.
+pub fn toplevel_states(states: &[u8]) -> ToplevelStates {
+    let mut result = ToplevelStates::default();
+    for state in states.chunks_exact(4) {
+        let state = u32::from_ne_bytes([state[0], state[1], state[2], state[3]]);
+        match xdg_toplevel::State::from_raw(state) {
+            Some(xdg_toplevel::State::Maximized) => result.maximized = true,
+            Some(xdg_toplevel::State::Resizing) => result.resizing = true,
+            _ => (),
+        }
+    }
+    result
+}
+
+/// Whether an output mode is the one in use.
+pub fn is_current_mode(flags: wl_output::Mode) -> bool {
+    flags.contains(wl_output::Mode::Current)
+}
+
+/// Creates a pool sharing the file with the compositor.
+pub fn create_pool(
+    shm: &Main<wl_shm::WlShm>,
+    file: &File,
+    size: usize,
+) -> Main<wl_shm_pool::WlShmPool> {
+    shm.create_pool(file.as_raw_fd(), size as i32)
+}
+
+/// The mime type of dragged files, a list of uris.
+pub const URI_LIST: &str = "text/uri-list";
+
+/// Accepts the uri list of a drag to be copied from, as the files are
+/// opened rather than moved.
+pub fn accept_uris(offer: &wl_data_offer::WlDataOffer, serial: u32) {
+    offer.accept(serial, Some(URI_LIST.to_string()));
+    if offer.as_ref().version() >= 3 {
+        offer.set_actions(DndAction::Copy, DndAction::Copy);
+    }
+}
+
+/// Asks for the data of an offer to be written to the file.
+pub fn receive(offer: &wl_data_offer::WlDataOffer, mime_type: &str, file: &impl AsRawFd) {
+    offer.receive(mime_type.to_string(), file.as_raw_fd());
+}
+
+/// Tells the source that the dropped data has been read.
+pub fn finish(offer: &wl_data_offer::WlDataOffer) {
+    if offer.as_ref().version() >= 3 {
+        offer.finish();
+    }
+    offer.destroy();
+}
+
+/// The uris of a text/uri-list, which has one per line and comments
+/// starting with #.
+pub fn parse_uri_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The events are built from the raw values a compositor sends, so that
+    // changes in how wayland-client decodes them show up here.
+
+    #[test]
+    fn key_states() {
+        let pressed = wl_keyboard::KeyState::from_raw(1).unwrap();
+        let released = wl_keyboard::KeyState::from_raw(0).unwrap();
+        assert_eq!(key_state(pressed), KeyState::Pressed);
+        assert_eq!(key_state(released), KeyState::Released);
+    }
+
+    #[test]
+    fn keymaps() {
+        let xkb = wl_keyboard::KeymapFormat::from_raw(1).unwrap();
+        let none = wl_keyboard::KeymapFormat::from_raw(0).unwrap();
+        assert_eq!(keymap(xkb, 7, 4096), Some((7, 4096)));
+        assert_eq!(keymap(none, 7, 0), None);
+    }
+
+    #[test]
+    fn scroll_axes() {
+        let vertical = wl_pointer::Axis::from_raw(0).unwrap();
+        let horizontal = wl_pointer::Axis::from_raw(1).unwrap();
+        assert_eq!(scroll_axis(vertical), Some(ScrollAxis::Vertical));
+        assert_eq!(scroll_axis(horizontal), Some(ScrollAxis::Horizontal));
+        assert_eq!(ScrollAxis::Horizontal.index(), 0);
+        assert_eq!(ScrollAxis::Vertical.index(), 1);
+    }
+
+    #[test]
+    fn scroll_sources() {
+        let sources = [
+            (0, ScrollSource::Other),
+            (1, ScrollSource::Finger),
+            (2, ScrollSource::Other),
+        ];
+        for (raw, expected) in sources {
+            let source = wl_pointer::AxisSource::from_raw(raw).unwrap();
+            assert_eq!(scroll_source(source), expected);
+        }
+    }
+
+    #[test]
+    fn buttons() {
+        assert!(button_released(
+            wl_pointer::ButtonState::from_raw(0).unwrap()
+        ));
+        assert!(!button_released(
+            wl_pointer::ButtonState::from_raw(1).unwrap()
+        ));
+    }
+
+    #[test]
+    fn window_states() {
+        let states: Vec<u8> = [4u32, 1]
+            .iter()
+            .flat_map(|state| state.to_ne_bytes())
+            .collect();
+        assert_eq!(
+            toplevel_states(&states),
+            ToplevelStates {
+                maximized: true,
+                resizing: false,
+            }
+        );
+        let states = 3u32.to_ne_bytes();
+        assert!(toplevel_states(&states).resizing);
+        assert_eq!(toplevel_states(&[]), ToplevelStates::default());
+    }
+
+    #[test]
+    fn output_modes() {
+        assert!(is_current_mode(wl_output::Mode::from_raw(0x3).unwrap()));
+        assert!(!is_current_mode(wl_output::Mode::from_raw(0x2).unwrap()));
+    }
+
+    // A pointer frame as a compositor sends it, with the raw values of its
+    // events, turns into the commands the widgets get.
+    #[test]
+    fn pointer_frames() {
+        let (tx, _rx) = std::sync::mpsc::channel();
+        let mut frame = PointerFrame::new(2, ScrollState::new(tx));
+        frame.enter(10.0, 20.0);
+
+        let events = vec![
+            wl_pointer::Event::Motion {
+                time: 1,
+                surface_x: 15.0,
+                surface_y: 25.0,
+            },
+            wl_pointer::Event::Button {
+                serial: 1,
+                time: 1,
+                button: 272,
+                state: wl_pointer::ButtonState::from_raw(1).unwrap(),
+            },
+            wl_pointer::Event::AxisSource {
+                axis_source: wl_pointer::AxisSource::from_raw(0).unwrap(),
+            },
+            wl_pointer::Event::Axis {
+                time: 1,
+                axis: wl_pointer::Axis::from_raw(0).unwrap(),
+                value: 15.0,
+            },
+            wl_pointer::Event::AxisDiscrete {
+                axis: wl_pointer::Axis::from_raw(0).unwrap(),
+                discrete: 1,
+            },
+        ];
+        for event in events {
+            assert!(frame.event(event).is_empty());
+        }
+        let cmds = frame.event(wl_pointer::Event::Frame);
+        assert_eq!(cmds.len(), 3);
+        assert!(matches!(
+            cmds[0],
+            Cmd::MouseMotion {
+                pos: Some((30, 50))
+            }
+        ));
+        assert!(matches!(
+            cmds[1],
+            Cmd::MousePress {
+                btn: 272,
+                pos: (30, 50)
+            }
+        ));
+        match cmds[2] {
+            Cmd::MouseScroll { scroll, pos } => {
+                assert_eq!(pos, (30, 50));
+                assert_eq!(scroll.steps, (0, 1));
+            }
+            _ => panic!("expected a scroll"),
+        }
+
+        // Releasing the button clicks it, and nothing else happened
+        frame.event(wl_pointer::Event::Button {
+            serial: 2,
+            time: 2,
+            button: 272,
+            state: wl_pointer::ButtonState::from_raw(0).unwrap(),
+        });
+        let cmds = frame.event(wl_pointer::Event::Frame);
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(
+            cmds[0],
+            Cmd::MouseClick {
+                btn: 272,
+                pos: (30, 50)
+            }
+        ));
+
+        assert!(matches!(frame.leave(), Cmd::MouseMotion { pos: None }));
+        assert!(frame.event(wl_pointer::Event::Frame).is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! Scroll handling for the pointer
+//!
+//! Axis events are collected per pointer frame and turned into a Scroll,
+//! which carries both the smooth distance and whole wheel steps. Touchpad
+//! scrolls continue with a decaying velocity after the fingers are lifted.
+
+use crate::cmd::Cmd;
+use crate::motion;
+use crate::protocol::{ScrollAxis, ScrollSource};
+use crate::widget::{Scroll, SCROLL_DETENT};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Interval between kinetic scroll events, in ms.
+const KINETIC_INTERVAL: u64 = 16;
+// Fraction of the velocity kept for every kinetic scroll event.
+const KINETIC_DECAY: f64 = 0.92;
+// Velocities below this stop kinetic scrolling, in surface units per ms.
+const KINETIC_MIN_VELOCITY: f64 = 0.05;
+// Frames further apart than this do not give a meaningful velocity, in ms.
+const KINETIC_MAX_FRAME_GAP: u32 = 100;
+
+#[derive(Default)]
+struct Steps {
+    // Smooth scrolling not yet counted as a step
+    remainder: [f64; 2],
+}
+
+impl Steps {
+    fn smooth(&mut self, delta: [f64; 2]) -> Scroll {
+        let mut steps = [0; 2];
+        for idx in 0..2 {
+            self.remainder[idx] += delta[idx];
+            steps[idx] = (self.remainder[idx] / SCROLL_DETENT).trunc() as i32;
+            self.remainder[idx] -= steps[idx] as f64 * SCROLL_DETENT;
+        }
+        Scroll {
+            delta: (delta[0], delta[1]),
+            steps: (steps[0], steps[1]),
+        }
+    }
+
+    fn discrete(&mut self, steps: [i32; 2]) -> Scroll {
+        self.remainder = [0.0; 2];
+        Scroll {
+            delta: (
+                steps[0] as f64 * SCROLL_DETENT,
+                steps[1] as f64 * SCROLL_DETENT,
+            ),
+            steps: (steps[0], steps[1]),
+        }
+    }
+}
+
+/// Collects the axis events of the pointer.
+pub struct ScrollState {
+    tx: Sender<Cmd>,
+    steps: Arc<Mutex<Steps>>,
+    // Bumped to stop a running kinetic scroll.
+    generation: Arc<AtomicUsize>,
+    source: Option<ScrollSource>,
+    delta: [f64; 2],
+    discrete: [i32; 2],
+    stopped: bool,
+    time: Option<u32>,
+    last_frame: Option<u32>,
+    velocity: [f64; 2],
+}
+
+impl ScrollState {
+    pub fn new(tx: Sender<Cmd>) -> ScrollState {
+        ScrollState {
+            tx,
+            steps: Default::default(),
+            generation: Default::default(),
+            source: None,
+            delta: [0.0; 2],
+            discrete: [0; 2],
+            stopped: false,
+            time: None,
+            last_frame: None,
+            velocity: [0.0; 2],
+        }
+    }
+
+    pub fn source(&mut self, source: ScrollSource) {
+        self.source = Some(source);
+    }
+
+    pub fn axis(&mut self, time: u32, axis: ScrollAxis, value: f64) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.delta[axis.index()] += value;
+        self.time = Some(time);
+    }
+
+    pub fn discrete(&mut self, axis: ScrollAxis, steps: i32) {
+        self.discrete[axis.index()] += steps;
+    }
+
+    pub fn stop(&mut self, _: ScrollAxis) {
+        self.stopped = true;
+    }
+
+    /// Finishes a pointer frame, returning the scroll that happened in it.
+    /// Wheel clicks are counted in detents, so they scroll the same distance
+    /// regardless of how the compositor scales the continuous value.
+    pub fn frame(&mut self, pos: (u32, u32)) -> Option<Scroll> {
+        let source = self.source.take();
+        let delta = std::mem::take(&mut self.delta);
+        let discrete = std::mem::take(&mut self.discrete);
+        let time = self.time.take();
+        let stopped = std::mem::take(&mut self.stopped);
+
+        if source == Some(ScrollSource::Finger) {
+            if let Some(time) = time {
+                let gap = self.last_frame.map(|last| time.wrapping_sub(last));
+                self.velocity = match gap {
+                    Some(gap) if gap > 0 && gap < KINETIC_MAX_FRAME_GAP => {
+                        [delta[0] / gap as f64, delta[1] / gap as f64]
+                    }
+                    _ => [0.0; 2],
+                };
+                self.last_frame = Some(time);
+            }
+            if stopped {
+                self.kinetic(pos);
+            }
+        } else if source.is_some() {
+            self.last_frame = None;
+        }
+
+        if discrete != [0; 2] {
+            Some(self.steps.lock().unwrap().discrete(discrete))
+        } else if delta != [0.0; 2] {
+            Some(self.steps.lock().unwrap().smooth(delta))
+        } else {
+            None
+        }
+    }
+
+    // Continues the scroll in the background with a decaying velocity, until
+    // it is slow enough or a new scroll starts. Not while motion is reduced.
+    fn kinetic(&mut self, pos: (u32, u32)) {
+        let mut velocity = std::mem::take(&mut self.velocity);
+        self.last_frame = None;
+        if velocity[0].hypot(velocity[1]) < KINETIC_MIN_VELOCITY || motion::is_reduced() {
+            return;
+        }
+
+        let tx = self.tx.clone();
+        let steps = self.steps.clone();
+        let generation = self.generation.clone();
+        let started = generation.load(Ordering::SeqCst);
+        let _ = thread::Builder::new()
+            .name("kinetic_scroll".to_string())
+            .spawn(move || loop {
+                thread::sleep(Duration::from_millis(KINETIC_INTERVAL));
+                if generation.load(Ordering::SeqCst) != started {
+                    return;
+                }
+                velocity = [velocity[0] * KINETIC_DECAY, velocity[1] * KINETIC_DECAY];
+                if velocity[0].hypot(velocity[1]) < KINETIC_MIN_VELOCITY {
+                    return;
+                }
+                let interval = KINETIC_INTERVAL as f64;
+                let scroll = steps
+                    .lock()
+                    .unwrap()
+                    .smooth([velocity[0] * interval, velocity[1] * interval]);
+                if tx.send(Cmd::MouseScroll { scroll, pos }).is_err() {
+                    return;
+                }
+            });
+    }
+}
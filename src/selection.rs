@@ -0,0 +1,185 @@
+//! Selectable text
+//!
+//! Widgets with output worth copying, such as the calculator result, record
+//! the text they draw as runs, along with where each character starts.
+//! Pressing the left button on a run and dragging across it selects part of
+//! it, which is shaded over the text and copied to the clipboard once the
+//! button is released. A click without dragging clears the selection.
+
+use crate::buffer::Buffer;
+use crate::color::Color;
+use crate::draw::Font;
+use crate::widgets::launcher::wlcopy;
+
+const BTN_LEFT: u32 = 272;
+
+// A line of text as drawn by a widget, relative to the widget.
+struct TextRun {
+    pos: (u32, u32),
+    height: u32,
+    text: String,
+    // Where each character starts, followed by where the text ends
+    offsets: Vec<u32>,
+}
+
+impl TextRun {
+    fn contains(&self, pos: (u32, u32)) -> bool {
+        let end = self.pos.0 + self.offsets.last().copied().unwrap_or(0);
+        pos.0 >= self.pos.0
+            && pos.0 < end
+            && pos.1 >= self.pos.1
+            && pos.1 < self.pos.1 + self.height
+    }
+
+    // The character boundary closest to the horizontal position.
+    fn boundary(&self, x: u32) -> usize {
+        let x = x.saturating_sub(self.pos.0);
+        let mut closest = 0;
+        for (idx, &offset) in self.offsets.iter().enumerate() {
+            if offset.abs_diff(x) < self.offsets[closest].abs_diff(x) {
+                closest = idx;
+            }
+        }
+        closest
+    }
+}
+
+/// The selectable text of a widget, and what of it is selected.
+#[derive(Default)]
+pub struct Selection {
+    runs: Vec<TextRun>,
+    // The run, and the character boundaries where the selection started and
+    // where it is now
+    selected: Option<(usize, usize, usize)>,
+    // The text of the selected run as last drawn
+    selected_run: Option<String>,
+    dragging: bool,
+}
+
+impl Selection {
+    pub fn new() -> Selection {
+        Default::default()
+    }
+
+    /// Forgets the runs, as the widget is about to draw them anew. The
+    /// selection is kept as long as the runs are drawn in the same order.
+    pub fn clear_runs(&mut self) {
+        self.selected_run = self
+            .selected
+            .and_then(|(run, _, _)| self.runs.get(run))
+            .map(|run| run.text.clone());
+        self.runs.clear();
+    }
+
+    /// Records text drawn with the font at the position within the widget.
+    /// A selection of a run whose text changed is dropped.
+    pub fn add_run(&mut self, font: &mut Font, pos: (u32, u32), height: u32, text: &str) {
+        let idx = self.runs.len();
+        if matches!(self.selected, Some((run, _, _)) if run == idx)
+            && self.selected_run.as_deref() != Some(text)
+        {
+            self.selected = None;
+        }
+        self.runs.push(TextRun {
+            pos,
+            height,
+            text: text.to_string(),
+            offsets: font.auto_char_offsets(text),
+        });
+    }
+
+    pub fn is_selected(&self) -> bool {
+        matches!(self.selected, Some((_, from, to)) if from != to)
+    }
+
+    /// Starts selecting if the left button is pressed on a run. Returns
+    /// whether the selection changed.
+    pub fn press(&mut self, button: u32, pos: (u32, u32)) -> bool {
+        if button != BTN_LEFT {
+            return false;
+        }
+        let had_selection = self.is_selected();
+        self.selected = self
+            .runs
+            .iter()
+            .position(|run| run.contains(pos))
+            .map(|idx| {
+                let at = self.runs[idx].boundary(pos.0);
+                (idx, at, at)
+            });
+        self.dragging = self.selected.is_some();
+        had_selection
+    }
+
+    /// Extends the selection while dragging. Returns whether it changed.
+    pub fn motion(&mut self, pos: Option<(u32, u32)>) -> bool {
+        let (run, from, to) = match self.selected {
+            Some(selected) if self.dragging => selected,
+            _ => return false,
+        };
+        let at = match (self.runs.get(run), pos) {
+            (Some(run), Some(pos)) => run.boundary(pos.0),
+            _ => return false,
+        };
+        self.selected = Some((run, from, at));
+        at != to
+    }
+
+    /// Ends the drag, copying what was selected. Returns whether the
+    /// release ended a selection, rather than being a plain click.
+    pub fn release(&mut self, button: u32) -> bool {
+        if button != BTN_LEFT || !self.dragging {
+            return false;
+        }
+        self.dragging = false;
+        match self.selected_text() {
+            Some(text) => {
+                if let Err(err) = wlcopy(&text) {
+                    eprintln!("Unable to copy selection: {}", err);
+                }
+                true
+            }
+            None => {
+                self.selected = None;
+                false
+            }
+        }
+    }
+
+    /// Clears the selection. Returns whether there was one.
+    pub fn clear(&mut self) -> bool {
+        self.dragging = false;
+        self.selected.take().is_some()
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        let (run, from, to) = self.selected?;
+        if from == to {
+            return None;
+        }
+        let text = &self.runs.get(run)?.text;
+        Some(
+            text.chars()
+                .skip(from.min(to))
+                .take(from.abs_diff(to))
+                .collect(),
+        )
+    }
+
+    /// Shades the selection, after the widget has drawn its runs.
+    pub fn draw(&self, buf: &mut Buffer) -> Result<(), ::std::io::Error> {
+        let (run, from, to) = match self.selected {
+            Some(selected) if self.is_selected() => selected,
+            _ => return Ok(()),
+        };
+        let run = match self.runs.get(run) {
+            Some(run) => run,
+            None => return Ok(()),
+        };
+        let start = run.offsets[from.min(to)];
+        let end = run.offsets[from.max(to)];
+        buf.subdimensions((run.pos.0 + start, run.pos.1, end - start, run.height))?
+            .blend(&Color::new(0.3, 0.5, 1.0, 1.0), 0.5);
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+//! Bindings of the ext-session-lock-v1 protocol, which wayland-protocols
+//! 0.29 does not have. They are generated by build.rs from the protocol file
+//! in protocols/, as wayland-protocols generates its own.
+
+#![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+#![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+#![allow(missing_docs, clippy::all)]
+
+pub mod client {
+    pub(crate) use crate::protocol::interfaces::{wl_output, wl_surface};
+    pub(crate) use wayland_client::sys;
+    pub(crate) use wayland_client::{AnonymousObject, Attached, Main, Proxy, ProxyMap};
+    pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+    pub(crate) use wayland_commons::smallvec;
+    pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+    pub(crate) use wayland_commons::{Interface, MessageGroup};
+    include!(concat!(
+        env!("OUT_DIR"),
+        "/ext-session-lock-v1_client_api.rs"
+    ));
+}
@@ -0,0 +1,70 @@
+//! Emoji shortcodes
+//!
+//! A shortcode such as :shrug: right before the cursor in the prompt is
+//! replaced by its emoji or kaomoji with Tab. The shortcodes of the config
+//! come first, and add to or replace the built-in ones.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("shrug", "¯\\_(ツ)_/¯"),
+    ("tableflip", "(╯°□°)╯︵ ┻━┻"),
+    ("unflip", "┬─┬ノ( º _ ºノ)"),
+    ("lenny", "( ͡° ͜ʖ ͡°)"),
+    ("disapproval", "ಠ_ಠ"),
+    ("smile", "😄"),
+    ("joy", "😂"),
+    ("wink", "😉"),
+    ("thinking", "🤔"),
+    ("sob", "😭"),
+    ("eyes", "👀"),
+    ("wave", "👋"),
+    ("pray", "🙏"),
+    ("ok_hand", "👌"),
+    ("+1", "👍"),
+    ("thumbsup", "👍"),
+    ("-1", "👎"),
+    ("thumbsdown", "👎"),
+    ("heart", "❤️"),
+    ("fire", "🔥"),
+    ("sparkles", "✨"),
+    ("tada", "🎉"),
+    ("rocket", "🚀"),
+    ("coffee", "☕"),
+    ("100", "💯"),
+    ("check", "✅"),
+    ("x", "❌"),
+    ("warning", "⚠️"),
+];
+
+static CONFIGURED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+pub fn set_configured(shortcodes: &HashMap<String, String>) {
+    *CONFIGURED.lock().unwrap() = shortcodes
+        .iter()
+        .map(|(code, text)| (code.clone(), text.clone()))
+        .collect();
+}
+
+fn lookup(code: &str) -> Option<String> {
+    if let Some((_, text)) = CONFIGURED.lock().unwrap().iter().find(|(c, _)| c == code) {
+        return Some(text.clone());
+    }
+    BUILTIN
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, text)| text.to_string())
+}
+
+/// Finds a known shortcode ending at the byte offset of the text, and
+/// returns the byte offset it starts at along with what it expands to.
+pub fn expand(text: &str, end: usize) -> Option<(usize, String)> {
+    let rest = text.get(..end)?.strip_suffix(':')?;
+    let start = rest.rfind(':')?;
+    let code = &rest[start + 1..];
+    if code.is_empty() || code.contains(char::is_whitespace) {
+        return None;
+    }
+    lookup(code).map(|expansion| (start, expansion))
+}
@@ -0,0 +1,209 @@
+//! Global shortcuts through the xdg-desktop-portal GlobalShortcuts interface
+//!
+//! This lets wldash register shortcuts to show and hide the dashboard, as well
+//! as media keys forwarded to the widgets, without depending on compositor
+//! specific configuration. The user assigns the actual key combinations
+//! through the portal.
+
+use crate::cmd::Cmd;
+use crate::keyboard::{keysyms, KeyState, ModifiersState};
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use dbus::arg::{RefArg, Variant};
+use dbus::{BusType, Connection, ConnectionItem, Message, Path};
+
+const PORTAL_BUS: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SHORTCUTS_INTERFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+// id, description, preferred trigger and the keysym forwarded to the widgets.
+// A keysym of 0 toggles the visibility of the dashboard.
+const SHORTCUTS: &[(&str, &str, &str, u32)] = &[
+    ("toggle-visible", "Show or hide the dashboard", "LOGO+d", 0),
+    (
+        "volume-up",
+        "Raise volume",
+        "XF86AudioRaiseVolume",
+        keysyms::XKB_KEY_XF86AudioRaiseVolume,
+    ),
+    (
+        "volume-down",
+        "Lower volume",
+        "XF86AudioLowerVolume",
+        keysyms::XKB_KEY_XF86AudioLowerVolume,
+    ),
+    (
+        "volume-mute",
+        "Mute volume",
+        "XF86AudioMute",
+        keysyms::XKB_KEY_XF86AudioMute,
+    ),
+    (
+        "brightness-up",
+        "Raise brightness",
+        "XF86MonBrightnessUp",
+        keysyms::XKB_KEY_XF86MonBrightnessUp,
+    ),
+    (
+        "brightness-down",
+        "Lower brightness",
+        "XF86MonBrightnessDown",
+        keysyms::XKB_KEY_XF86MonBrightnessDown,
+    ),
+];
+
+type VarDict = HashMap<String, Variant<Box<dyn RefArg>>>;
+
+fn var(s: &str) -> Variant<Box<dyn RefArg>> {
+    Variant(Box::new(s.to_string()))
+}
+
+fn dbus_error(desc: &str) -> Error {
+    Error::new(ErrorKind::Other, desc.to_string())
+}
+
+// Portal methods return a request object, whose Response signal carries the
+// actual result. The object path is predictable from the handle token, so we
+// subscribe before making the call to avoid missing the signal.
+fn portal_request(con: &Connection, token: &str, msg: Message) -> Result<VarDict, Error> {
+    let sender = con.unique_name();
+    let path = format!(
+        "{}/request/{}/{}",
+        PORTAL_PATH,
+        sender.trim_start_matches(':').replace('.', "_"),
+        token
+    );
+    let rule = format!(
+        "type='signal',\
+         interface='org.freedesktop.portal.Request',\
+         member='Response',\
+         path='{}'",
+        path
+    );
+    con.add_match(&rule)
+        .map_err(|_| dbus_error("unable to add match rule to dbus connection"))?;
+    con.send_with_reply_and_block(msg, 5000)
+        .map_err(|_| dbus_error("portal request failed"))?;
+
+    for item in con.iter(60_000) {
+        match item {
+            ConnectionItem::Signal(m) if m.path().is_some_and(|p| *p == *path) => {
+                let _ = con.remove_match(&rule);
+                let (response, results) = m
+                    .read2::<u32, VarDict>()
+                    .map_err(|_| dbus_error("invalid portal response"))?;
+                if response != 0 {
+                    return Err(dbus_error("portal request was denied"));
+                }
+                return Ok(results);
+            }
+            ConnectionItem::Nothing => {
+                let _ = con.remove_match(&rule);
+                return Err(dbus_error("portal request timed out"));
+            }
+            _ => (),
+        }
+    }
+    Err(dbus_error("dbus connection closed"))
+}
+
+fn bind_shortcuts(con: &Connection) -> Result<(), Error> {
+    let mut options = VarDict::new();
+    options.insert("handle_token".to_string(), var("wldash_session"));
+    options.insert("session_handle_token".to_string(), var("wldash"));
+    let msg = Message::new_method_call(
+        PORTAL_BUS,
+        PORTAL_PATH,
+        SHORTCUTS_INTERFACE,
+        "CreateSession",
+    )
+    .map_err(|_| dbus_error("could not make dbus method call"))?
+    .append1(options);
+    let results = portal_request(con, "wldash_session", msg)?;
+    let session = results
+        .get("session_handle")
+        .and_then(|v| v.0.as_str())
+        .ok_or_else(|| dbus_error("portal did not return a session"))?
+        .to_string();
+
+    let shortcuts: Vec<(String, VarDict)> = SHORTCUTS
+        .iter()
+        .map(|(id, description, trigger, _)| {
+            let mut props = VarDict::new();
+            props.insert("description".to_string(), var(description));
+            props.insert("preferred_trigger".to_string(), var(trigger));
+            (id.to_string(), props)
+        })
+        .collect();
+    let mut options = VarDict::new();
+    options.insert("handle_token".to_string(), var("wldash_bind"));
+    let msg = Message::new_method_call(
+        PORTAL_BUS,
+        PORTAL_PATH,
+        SHORTCUTS_INTERFACE,
+        "BindShortcuts",
+    )
+    .map_err(|_| dbus_error("could not make dbus method call"))?
+    .append3(Path::from(session), shortcuts, "")
+    .append1(options);
+    portal_request(con, "wldash_bind", msg)?;
+    Ok(())
+}
+
+fn listen(tx: Sender<Cmd>) -> Result<(), Error> {
+    let con =
+        Connection::get_private(BusType::Session).map_err(|_| dbus_error("unable to open dbus"))?;
+    bind_shortcuts(&con)?;
+
+    let rule = format!(
+        "type='signal',interface='{}',member='Activated'",
+        SHORTCUTS_INTERFACE
+    );
+    con.add_match(&rule)
+        .map_err(|_| dbus_error("unable to add match rule to dbus connection"))?;
+
+    for item in con.iter(60_000) {
+        let m = match item {
+            ConnectionItem::Signal(m) => m,
+            _ => continue,
+        };
+        let id = match m.read2::<Path, String>() {
+            Ok((_, id)) => id,
+            Err(_) => continue,
+        };
+        let keysym = match SHORTCUTS.iter().find(|s| s.0 == id) {
+            Some(s) => s.3,
+            None => continue,
+        };
+        let cmd = if keysym == 0 {
+            Cmd::ToggleVisible
+        } else {
+            Cmd::Keyboard {
+                key: keysym,
+                key_state: KeyState::Pressed,
+                modifiers_state: ModifiersState::default(),
+                interpreted: None,
+            }
+        };
+        if tx.send(cmd).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Registers the global shortcuts with the portal in a background thread,
+/// forwarding activations as commands.
+pub fn register_global_shortcuts(tx: Sender<Cmd>) {
+    let _ = thread::Builder::new()
+        .name("global_shortcuts".to_string())
+        .spawn(move || {
+            if let Err(err) = listen(tx) {
+                eprintln!("Unable to register global shortcuts: {}", err);
+            }
+        });
+}
@@ -0,0 +1,263 @@
+//! NetworkManager VPN connections
+//!
+//! The VPN and WireGuard connections configured in NetworkManager are listed
+//! in the launcher, as "Connect VPN: Work" while down and "Disconnect VPN:
+//! Work" while up. They are read over the system bus in a background thread
+//! when the launcher is created, so a missing NetworkManager only means no
+//! such entries. Built without the dbus-services feature, there are none.
+
+use crate::cmd::Cmd;
+use crate::desktop::Desktop;
+
+use std::io::Error;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The entry type of the launcher entries of VPN connections, whose id is
+/// the uuid of the connection.
+pub const ENTRY_TYPE: &str = "VPN";
+
+fn dbus_error(desc: &str) -> Error {
+    Error::other(desc.to_string())
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "dbus-services"), allow(dead_code))]
+struct Vpn {
+    uuid: String,
+    name: String,
+    path: String,
+    // The active connection while connected
+    active: Option<String>,
+}
+
+impl Vpn {
+    fn entry(&self) -> Desktop {
+        let name = match self.active {
+            Some(_) => format!("Disconnect VPN: {} (connected)", self.name),
+            None => format!("Connect VPN: {}", self.name),
+        };
+        Desktop {
+            path: String::new(),
+            id: self.uuid.clone(),
+            entry_type: ENTRY_TYPE.to_string(),
+            name,
+            localized_name: None,
+            comment: None,
+            no_display: false,
+            hidden: false,
+            exec: None,
+            url: None,
+            term: false,
+            keywords: vec!["vpn".to_string(), self.name.clone()],
+            categories: vec!["Network".to_string()],
+        }
+    }
+}
+
+#[cfg(feature = "dbus-services")]
+mod nm {
+    use super::{dbus_error, Vpn};
+
+    use std::collections::HashMap;
+    use std::io::Error;
+
+    use dbus::arg::{RefArg, Variant};
+    use dbus::{BusType, Connection, Message, Path};
+
+    const NM_BUS: &str = "org.freedesktop.NetworkManager";
+    const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+    const NM_INTERFACE: &str = "org.freedesktop.NetworkManager";
+    const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+    const SETTINGS_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings";
+    const CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings.Connection";
+    const ACTIVE_INTERFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+    const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+    const TIMEOUT: i32 = 5000;
+
+    type Settings = HashMap<String, HashMap<String, Variant<Box<dyn RefArg>>>>;
+
+    fn call(path: &str, interface: &str, method: &str) -> Result<Message, Error> {
+        Message::new_method_call(NM_BUS, path, interface, method)
+            .map_err(|_| dbus_error("could not make dbus method call"))
+    }
+
+    fn property<T>(con: &Connection, path: &str, interface: &str, name: &str) -> Result<T, Error>
+    where
+        T: for<'a> dbus::arg::Get<'a>,
+    {
+        let msg = call(path, PROPERTIES_INTERFACE, "Get")?.append2(interface, name);
+        let reply = con
+            .send_with_reply_and_block(msg, TIMEOUT)
+            .map_err(|_| dbus_error("unable to read property"))?;
+        reply
+            .read1::<Variant<T>>()
+            .map(|v| v.0)
+            .map_err(|_| dbus_error("invalid property"))
+    }
+
+    // The id, uuid and type of a connection.
+    fn setting<'a>(settings: &'a Settings, key: &str) -> Option<&'a str> {
+        settings.get("connection")?.get(key)?.as_str()
+    }
+
+    pub fn list() -> Result<Vec<Vpn>, Error> {
+        let con = &Connection::get_private(BusType::System)
+            .map_err(|_| dbus_error("unable to open dbus"))?;
+        let reply = con
+            .send_with_reply_and_block(
+                call(SETTINGS_PATH, SETTINGS_INTERFACE, "ListConnections")?,
+                TIMEOUT,
+            )
+            .map_err(|_| dbus_error("unable to list connections"))?;
+        let paths: Vec<Path<'static>> = reply
+            .read1()
+            .map_err(|_| dbus_error("invalid connection list"))?;
+
+        // The uuids of the connections that are up
+        let mut active = HashMap::new();
+        let active_paths: Vec<Path<'static>> =
+            property(con, NM_PATH, NM_INTERFACE, "ActiveConnections")?;
+        for path in active_paths {
+            if let Ok(uuid) = property::<String>(con, &path, ACTIVE_INTERFACE, "Uuid") {
+                active.insert(uuid, path);
+            }
+        }
+
+        let mut vpns = Vec::new();
+        for path in paths {
+            let reply = match con.send_with_reply_and_block(
+                call(&path, CONNECTION_INTERFACE, "GetSettings")?,
+                TIMEOUT,
+            ) {
+                Ok(reply) => reply,
+                Err(_) => continue,
+            };
+            let settings: Settings = match reply.read1() {
+                Ok(settings) => settings,
+                Err(_) => continue,
+            };
+            if !matches!(setting(&settings, "type"), Some("vpn") | Some("wireguard")) {
+                continue;
+            }
+            let (uuid, name) = match (setting(&settings, "uuid"), setting(&settings, "id")) {
+                (Some(uuid), Some(name)) => (uuid.to_string(), name.to_string()),
+                _ => continue,
+            };
+            vpns.push(Vpn {
+                active: active.remove(&uuid).map(|path| path.to_string()),
+                uuid,
+                name,
+                path: path.to_string(),
+            });
+        }
+        vpns.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(vpns)
+    }
+
+    /// Brings the connection up if it is down, or down if it is up.
+    /// NetworkManager replies as soon as it has started doing so.
+    pub fn toggle(vpn: Vpn) -> Result<(), Error> {
+        let con = Connection::get_private(BusType::System)
+            .map_err(|_| dbus_error("unable to open dbus"))?;
+        let msg = match vpn.active {
+            Some(active) => {
+                call(NM_PATH, NM_INTERFACE, "DeactivateConnection")?.append1(Path::from(active))
+            }
+            // Any device will do, and there is no specific object
+            None => call(NM_PATH, NM_INTERFACE, "ActivateConnection")?.append3(
+                Path::from(vpn.path),
+                Path::from("/"),
+                Path::from("/"),
+            ),
+        };
+        con.send_with_reply_and_block(msg, TIMEOUT)
+            .map_err(|err| dbus_error(err.message().unwrap_or("unable to toggle connection")))?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "dbus-services"))]
+mod nm {
+    use super::{dbus_error, Vpn};
+
+    use std::io::Error;
+
+    pub fn list() -> Result<Vec<Vpn>, Error> {
+        Ok(Vec::new())
+    }
+
+    pub fn toggle(_: Vpn) -> Result<(), Error> {
+        Err(dbus_error("built without dbus-services"))
+    }
+}
+
+struct Listing {
+    vpns: Vec<Vpn>,
+    updated: bool,
+}
+
+pub struct Vpns {
+    listing: Arc<Mutex<Listing>>,
+}
+
+impl Vpns {
+    /// Starts reading the VPN connections in the background, asking for a
+    /// redraw once they are known.
+    pub fn new(tx: Sender<Cmd>) -> Vpns {
+        let listing = Arc::new(Mutex::new(Listing {
+            vpns: Vec::new(),
+            updated: false,
+        }));
+        let shared = listing.clone();
+        let _ = thread::Builder::new()
+            .name("vpn".to_string())
+            .spawn(move || match nm::list() {
+                Ok(listed) if !listed.is_empty() => {
+                    let mut listing = shared.lock().unwrap();
+                    listing.vpns = listed;
+                    listing.updated = true;
+                    drop(listing);
+                    let _ = tx.send(Cmd::Draw);
+                }
+                Ok(_) => (),
+                Err(err) => eprintln!("Unable to list VPN connections: {}", err),
+            });
+        Vpns { listing }
+    }
+
+    /// The launcher entries of the VPN connections.
+    pub fn entries(&self) -> Vec<Desktop> {
+        self.listing
+            .lock()
+            .unwrap()
+            .vpns
+            .iter()
+            .map(Vpn::entry)
+            .collect()
+    }
+
+    /// Returns true once if the connections have been read since the last
+    /// call.
+    pub fn take_updated(&self) -> bool {
+        std::mem::take(&mut self.listing.lock().unwrap().updated)
+    }
+
+    /// Brings the connection with the uuid up if it is down, or down if it
+    /// is up. NetworkManager replies as soon as it has started doing so.
+    pub fn toggle(&self, uuid: &str) -> Result<(), Error> {
+        let vpn = match self
+            .listing
+            .lock()
+            .unwrap()
+            .vpns
+            .iter()
+            .find(|v| v.uuid == uuid)
+        {
+            Some(vpn) => vpn.clone(),
+            None => return Err(dbus_error("unknown connection")),
+        };
+        nm::toggle(vpn)
+    }
+}
@@ -1,11 +1,15 @@
+use crate::binding::Binding;
 use crate::buffer::Buffer;
+use crate::cmd::{Action, Cmd};
 use crate::color::Color;
+use crate::draw::{draw_rounded_box, draw_rounded_outline};
 pub use crate::keyboard::{KeyState, ModifiersState};
 use chrono::NaiveDateTime;
 use nix::poll::PollFd;
+use std::sync::mpsc::Sender;
 
-pub struct DrawContext<'a> {
-    pub buf: &'a mut Buffer<'a>,
+pub struct DrawContext<'a, 'b> {
+    pub buf: &'a mut Buffer<'b>,
     pub bg: &'a Color,
     pub time: NaiveDateTime,
     pub force: bool,
@@ -30,6 +34,119 @@ impl DrawReport {
     }
 }
 
+/// A value in a widget state snapshot.
+#[derive(Clone, Debug)]
+pub enum StateValue {
+    Number(f64),
+    Bool(bool),
+    Text(String),
+}
+
+/// A snapshot of the state of a widget, such as the battery level, to let
+/// scripts reuse the backends of wldash.
+#[derive(Clone, Debug)]
+pub struct WidgetState {
+    pub name: String,
+    pub values: Vec<(&'static str, StateValue)>,
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_values(values: &[(&'static str, StateValue)]) -> String {
+    let values: Vec<String> = values
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                StateValue::Number(n) if n.is_finite() => n.to_string(),
+                StateValue::Number(_) => "null".to_string(),
+                StateValue::Bool(b) => b.to_string(),
+                StateValue::Text(s) => json_string(s),
+            };
+            format!("{}:{}", json_string(key), value)
+        })
+        .collect();
+    format!("{{{}}}", values.join(","))
+}
+
+impl WidgetState {
+    pub fn to_json(&self) -> String {
+        json_values(&self.values)
+    }
+}
+
+/// Formats the states as a JSON object keyed by widget name.
+pub fn states_to_json(states: &[WidgetState]) -> String {
+    let states: Vec<String> = states
+        .iter()
+        .map(|state| format!("{}:{}", json_string(&state.name), state.to_json()))
+        .collect();
+    format!("{{{}}}", states.join(","))
+}
+
+/// A widget in the tree dumped by `wldash dump-tree`, at its position in the
+/// dashboard as of the last draw and with the size it asks for. Values hold
+/// details such as fonts, colors and whether a redraw is pending.
+#[derive(Clone, Debug)]
+pub struct TreeNode {
+    pub kind: String,
+    pub pos: (u32, u32),
+    pub size: (u32, u32),
+    pub values: Vec<(&'static str, StateValue)>,
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// A node named after the type of the widget, without children.
+    pub fn new<W: Widget + ?Sized>(widget: &W, pos: (u32, u32)) -> TreeNode {
+        let name = std::any::type_name::<W>();
+        let name = name.split('<').next().unwrap_or(name);
+        TreeNode {
+            kind: name.rsplit("::").next().unwrap_or(name).to_string(),
+            pos,
+            size: widget.size(),
+            values: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn value(mut self, key: &'static str, value: StateValue) -> TreeNode {
+        self.values.push((key, value));
+        self
+    }
+
+    pub fn child(mut self, child: TreeNode) -> TreeNode {
+        self.children.push(child);
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        let children: Vec<String> = self.children.iter().map(|c| c.to_json()).collect();
+        format!(
+            "{{\"kind\":{},\"x\":{},\"y\":{},\"width\":{},\"height\":{},\"values\":{},\"children\":[{}]}}",
+            json_string(&self.kind),
+            self.pos.0,
+            self.pos.1,
+            self.size.0,
+            self.size.1,
+            json_values(&self.values),
+            children.join(",")
+        )
+    }
+}
+
 pub struct WaitContext {
     pub fds: Vec<PollFd>,
     pub target_time: Option<NaiveDateTime>,
@@ -47,6 +164,47 @@ impl WaitContext {
     }
 }
 
+/// What a layout does with a widget that does not fit in the remaining space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Draw the widget, discarding whatever falls outside its allocation.
+    Clip,
+    /// Give the widget the remaining space, as long as it is at least its
+    /// minimum size. Otherwise, hide it.
+    Shrink,
+    /// Do not draw the widget at all.
+    Hide,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Constraints {
+    pub min_size: (u32, u32),
+    pub priority: i32,
+    pub overflow: Overflow,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Constraints {
+            min_size: (0, 0),
+            priority: 0,
+            overflow: Overflow::Clip,
+        }
+    }
+}
+
+/// The scroll distance of one mouse wheel detent, in surface units.
+pub const SCROLL_DETENT: f64 = 15.0;
+
+/// A horizontal and vertical scroll. The delta is the smooth scroll distance,
+/// while steps counts whole wheel detents for widgets that move in fixed
+/// increments. Smooth scrolling produces a step for every SCROLL_DETENT.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Scroll {
+    pub delta: (f64, f64),
+    pub steps: (i32, i32),
+}
+
 pub trait Widget {
     fn wait(&mut self, ctx: &mut WaitContext);
     fn enter(&mut self);
@@ -67,17 +225,120 @@ pub trait Widget {
         interpreted: Option<String>,
     );
     fn mouse_click(&mut self, button: u32, pos: (u32, u32));
-    fn mouse_scroll(&mut self, scroll: (f64, f64), pos: (u32, u32));
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32));
+
+    /// Called when a button is pressed over the widget, ahead of
+    /// mouse_click once it is released. Motion in between is a drag.
+    fn mouse_press(&mut self, _button: u32, _pos: (u32, u32)) {}
+
+    /// Called when the pointer moves over the widget, or with None when it
+    /// is no longer over the widget.
+    fn mouse_motion(&mut self, _pos: Option<(u32, u32)>) {}
+
+    /// Called with actions emitted by other widgets through Cmd::Action.
+    /// Layouts pass them on to all of their children.
+    fn action(&mut self, _action: &Action) {}
+
+    /// Adds a snapshot of the widget state for `wldash query`. Layouts
+    /// collect the states of all of their children.
+    fn state(&self, _states: &mut Vec<WidgetState>) {}
+
+    /// Describes the widget, and the children of layouts, for `wldash
+    /// dump-tree`. The position is where the widget was last drawn. By
+    /// default the node holds the values of the widget state.
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        let mut node = TreeNode::new(self, pos);
+        let mut states = Vec::new();
+        self.state(&mut states);
+        for state in states {
+            node.values.extend(state.values);
+        }
+        node
+    }
+
+    /// Adds the regions that are only shown on the focused output, which
+    /// are left blank on the other outputs. Layouts collect the regions of
+    /// all of their children.
+    fn focused_only(&self, _regions: &mut Vec<(i32, i32, i32, i32)>) {}
+
+    /// Why the backend of the widget failed, if it currently has, such as a
+    /// sound server that cannot be reached. Wrapped in an error badge, the
+    /// widget is then marked with a warning that shows this when clicked.
+    fn error(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the widget has something to draw above the other widgets,
+    /// such as a list dropping down from it.
+    fn has_overlay(&self) -> bool {
+        false
+    }
+
+    /// Draws above the other widgets, after all of them have been drawn.
+    /// This may draw anywhere on the surface, which is fully redrawn while
+    /// any widget has an overlay.
+    fn draw_overlay(&mut self, _ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        Ok(())
+    }
+
+    /// Whether the widget wants all keyboard input for itself, such as while
+    /// a text field is being edited.
+    fn has_focus(&self) -> bool {
+        false
+    }
+
+    /// How the widget should be treated by layouts when space is scarce.
+    fn constraints(&self) -> Constraints {
+        Default::default()
+    }
+}
+
+// Distributes the available space along the axis of a layout. Children are
+// served in order of priority, and children that do not fit are clipped,
+// shrunk or hidden according to their overflow policy. Hidden children get
+// None.
+fn allocate<'a>(
+    children: &[Box<dyn Widget + Send + 'a>],
+    available: u32,
+    axis: fn((u32, u32)) -> u32,
+) -> Vec<Option<u32>> {
+    let mut order: Vec<usize> = (0..children.len()).collect();
+    order.sort_by_key(|&idx| -children[idx].constraints().priority);
+
+    let mut allocation = vec![None; children.len()];
+    let mut remaining = available;
+    for idx in order {
+        let wanted = axis(children[idx].size());
+        let constraints = children[idx].constraints();
+        let size = if wanted <= remaining {
+            Some(wanted)
+        } else {
+            match constraints.overflow {
+                Overflow::Clip if remaining > 0 => Some(remaining),
+                Overflow::Shrink if remaining >= axis(constraints.min_size) => Some(remaining),
+                _ => None,
+            }
+        };
+        if let Some(size) = size {
+            remaining -= size;
+        }
+        allocation[idx] = size;
+    }
+    allocation
 }
 
 pub struct VerticalLayout<'a> {
     pub children: Vec<Box<dyn Widget + Send + 'a>>,
+    extents: Vec<u32>,
 }
 
 #[allow(dead_code)]
 impl<'a> VerticalLayout<'a> {
     pub fn new(children: Vec<Box<dyn Widget + Send + 'a>>) -> Box<VerticalLayout> {
-        Box::new(VerticalLayout { children })
+        Box::new(VerticalLayout {
+            children,
+            extents: Vec::new(),
+        })
     }
 }
 
@@ -118,20 +379,49 @@ impl<'a> Widget for VerticalLayout<'a> {
         pos: (u32, u32),
         expansion: (u32, u32),
     ) -> Result<DrawReport, ::std::io::Error> {
+        let allocation = allocate(&self.children, expansion.1, |s| s.1);
         let mut offset = 0;
         let mut width = 0;
         let mut damage = Vec::new();
         let mut full_damage = false;
-        for child in &mut self.children {
-            let mut report = child.draw(
-                ctx,
-                (pos.0, offset + pos.1),
-                (expansion.0, expansion.1 - offset),
-            )?;
+        self.extents.clear();
+        for (child, allocated) in self.children.iter_mut().zip(allocation) {
+            let child_pos = (pos.0, offset + pos.1);
+            let mut report = match allocated {
+                None => {
+                    self.extents.push(0);
+                    continue;
+                }
+                Some(allocated) if allocated >= child.size().1 => child.draw(
+                    ctx,
+                    child_pos,
+                    (expansion.0, expansion.1.saturating_sub(offset)),
+                )?,
+                Some(allocated) => {
+                    let mut report = child.draw(
+                        &mut DrawContext {
+                            buf: &mut ctx.buf.clip((
+                                child_pos.0,
+                                child_pos.1,
+                                expansion.0,
+                                allocated,
+                            )),
+                            bg: ctx.bg,
+                            time: ctx.time,
+                            force: ctx.force,
+                        },
+                        child_pos,
+                        (expansion.0, allocated),
+                    )?;
+                    report.height = report.height.min(allocated);
+                    report
+                }
+            };
             if report.width > width {
                 width = report.width
             }
             offset += report.height;
+            self.extents.push(report.height);
             full_damage |= report.full_damage;
             damage.append(&mut report.damage);
         }
@@ -151,48 +441,132 @@ impl<'a> Widget for VerticalLayout<'a> {
         key_state: KeyState,
         interpreted: Option<String>,
     ) {
+        if let Some(child) = self.children.iter_mut().find(|c| c.has_focus()) {
+            child.keyboard_input(keysym, modifier_state, key_state, interpreted);
+            return;
+        }
         for child in &mut self.children {
             child.keyboard_input(keysym, modifier_state, key_state, interpreted.clone());
         }
     }
 
+    fn has_focus(&self) -> bool {
+        self.children.iter().any(|c| c.has_focus())
+    }
+
     fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
         let mut height = 0;
 
-        for child in &mut self.children {
-            let size = child.size();
-            if pos.1 >= height && pos.1 < height + size.1 {
+        for (child, extent) in self.children.iter_mut().zip(&self.extents) {
+            if pos.1 >= height && pos.1 < height + extent {
                 let pos = (pos.0, pos.1 - height);
                 child.mouse_click(button, pos);
                 return;
             }
-            height += size.1;
+            height += extent;
         }
     }
 
-    fn mouse_scroll(&mut self, scroll: (f64, f64), pos: (u32, u32)) {
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
         let mut height = 0;
 
-        for child in &mut self.children {
-            let size = child.size();
-            if pos.1 >= height && pos.1 < height + size.1 {
+        for (child, extent) in self.children.iter_mut().zip(&self.extents) {
+            if pos.1 >= height && pos.1 < height + extent {
+                let pos = (pos.0, pos.1 - height);
+                child.mouse_press(button, pos);
+                return;
+            }
+            height += extent;
+        }
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        let mut height = 0;
+
+        for (child, extent) in self.children.iter_mut().zip(&self.extents) {
+            if pos.1 >= height && pos.1 < height + extent {
                 let pos = (pos.0, pos.1 - height);
                 child.mouse_scroll(scroll, pos);
                 return;
             }
-            height += size.1;
+            height += extent;
+        }
+    }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        let mut height = 0;
+
+        for (child, extent) in self.children.iter_mut().zip(&self.extents) {
+            child.mouse_motion(match pos {
+                Some(pos) if pos.1 >= height && pos.1 < height + extent => {
+                    Some((pos.0, pos.1 - height))
+                }
+                _ => None,
+            });
+            height += extent;
+        }
+    }
+
+    fn action(&mut self, action: &Action) {
+        for child in self.children.iter_mut() {
+            child.action(action);
+        }
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        for child in self.children.iter() {
+            child.state(states);
         }
     }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        let mut node = TreeNode::new(self, pos);
+        let mut offset = 0;
+        for (idx, child) in self.children.iter().enumerate() {
+            // Children that did not fit were given an extent of 0
+            let extent = self.extents.get(idx).copied().unwrap_or(0);
+            node.children.push(
+                child
+                    .tree((pos.0, pos.1 + offset))
+                    .value("extent", StateValue::Number(extent as f64)),
+            );
+            offset += extent;
+        }
+        node
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        for child in self.children.iter() {
+            child.focused_only(regions);
+        }
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.children.iter().any(|c| c.has_overlay())
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        for child in self.children.iter_mut() {
+            if child.has_overlay() {
+                child.draw_overlay(ctx)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 pub struct HorizontalLayout<'a> {
     pub children: Vec<Box<dyn Widget + Send + 'a>>,
+    extents: Vec<u32>,
 }
 
 #[allow(dead_code)]
 impl<'a> HorizontalLayout<'a> {
     pub fn new(children: Vec<Box<dyn Widget + Send + 'a>>) -> Box<HorizontalLayout> {
-        Box::new(HorizontalLayout { children })
+        Box::new(HorizontalLayout {
+            children,
+            extents: Vec::new(),
+        })
     }
 
     fn height(&self) -> u32 {
@@ -246,20 +620,49 @@ impl<'a> Widget for HorizontalLayout<'a> {
         pos: (u32, u32),
         expansion: (u32, u32),
     ) -> Result<DrawReport, ::std::io::Error> {
+        let allocation = allocate(&self.children, expansion.0, |s| s.0);
         let mut offset = 0;
         let mut height = 0;
         let mut damage = Vec::new();
         let mut full_damage = false;
-        for child in &mut self.children {
-            let mut report = child.draw(
-                ctx,
-                (offset + pos.0, pos.1),
-                (expansion.0 - offset, expansion.1),
-            )?;
+        self.extents.clear();
+        for (child, allocated) in self.children.iter_mut().zip(allocation) {
+            let child_pos = (offset + pos.0, pos.1);
+            let mut report = match allocated {
+                None => {
+                    self.extents.push(0);
+                    continue;
+                }
+                Some(allocated) if allocated >= child.size().0 => child.draw(
+                    ctx,
+                    child_pos,
+                    (expansion.0.saturating_sub(offset), expansion.1),
+                )?,
+                Some(allocated) => {
+                    let mut report = child.draw(
+                        &mut DrawContext {
+                            buf: &mut ctx.buf.clip((
+                                child_pos.0,
+                                child_pos.1,
+                                allocated,
+                                expansion.1,
+                            )),
+                            bg: ctx.bg,
+                            time: ctx.time,
+                            force: ctx.force,
+                        },
+                        child_pos,
+                        (allocated, expansion.1),
+                    )?;
+                    report.width = report.width.min(allocated);
+                    report
+                }
+            };
             if report.height > height {
                 height = report.height
             }
             offset += report.width;
+            self.extents.push(report.width);
             full_damage |= report.full_damage;
             damage.append(&mut report.damage);
         }
@@ -279,39 +682,120 @@ impl<'a> Widget for HorizontalLayout<'a> {
         key_state: KeyState,
         interpreted: Option<String>,
     ) {
+        if let Some(child) = self.children.iter_mut().find(|c| c.has_focus()) {
+            child.keyboard_input(keysym, modifier_state, key_state, interpreted);
+            return;
+        }
         for child in &mut self.children {
             child.keyboard_input(keysym, modifier_state, key_state, interpreted.clone());
         }
     }
 
+    fn has_focus(&self) -> bool {
+        self.children.iter().any(|c| c.has_focus())
+    }
+
     fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
         let mut width = 0;
 
-        for child in &mut self.children {
-            let size = child.size();
-            if pos.0 >= width && pos.0 < width + size.0 {
+        for (child, extent) in self.children.iter_mut().zip(&self.extents) {
+            if pos.0 >= width && pos.0 < width + extent {
                 let pos = (pos.0 - width, pos.1);
                 child.mouse_click(button, pos);
                 return;
             }
 
-            width += size.0;
+            width += extent;
         }
     }
 
-    fn mouse_scroll(&mut self, scroll: (f64, f64), pos: (u32, u32)) {
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
         let mut width = 0;
 
-        for child in &mut self.children {
-            let size = child.size();
-            if pos.0 >= width && pos.0 < width + size.0 {
+        for (child, extent) in self.children.iter_mut().zip(&self.extents) {
+            if pos.0 >= width && pos.0 < width + extent {
+                let pos = (pos.0 - width, pos.1);
+                child.mouse_press(button, pos);
+                return;
+            }
+
+            width += extent;
+        }
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        let mut width = 0;
+
+        for (child, extent) in self.children.iter_mut().zip(&self.extents) {
+            if pos.0 >= width && pos.0 < width + extent {
                 let pos = (pos.0 - width, pos.1);
                 child.mouse_scroll(scroll, pos);
                 return;
             }
 
-            width += size.0;
+            width += extent;
+        }
+    }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        let mut width = 0;
+
+        for (child, extent) in self.children.iter_mut().zip(&self.extents) {
+            child.mouse_motion(match pos {
+                Some(pos) if pos.0 >= width && pos.0 < width + extent => {
+                    Some((pos.0 - width, pos.1))
+                }
+                _ => None,
+            });
+            width += extent;
+        }
+    }
+
+    fn action(&mut self, action: &Action) {
+        for child in self.children.iter_mut() {
+            child.action(action);
+        }
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        for child in self.children.iter() {
+            child.state(states);
+        }
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        let mut node = TreeNode::new(self, pos);
+        let mut offset = 0;
+        for (idx, child) in self.children.iter().enumerate() {
+            // Children that did not fit were given an extent of 0
+            let extent = self.extents.get(idx).copied().unwrap_or(0);
+            node.children.push(
+                child
+                    .tree((pos.0 + offset, pos.1))
+                    .value("extent", StateValue::Number(extent as f64)),
+            );
+            offset += extent;
         }
+        node
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        for child in self.children.iter() {
+            child.focused_only(regions);
+        }
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.children.iter().any(|c| c.has_overlay())
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        for child in self.children.iter_mut() {
+            if child.has_overlay() {
+                child.draw_overlay(ctx)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -379,6 +863,17 @@ impl<'a> Widget for Margin<'a> {
             .keyboard_input(keysym, modifier_state, key_state, interpreted);
     }
 
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        let mut constraints = self.child.constraints();
+        constraints.min_size.0 += self.margins.0 + self.margins.1;
+        constraints.min_size.1 += self.margins.2 + self.margins.3;
+        constraints
+    }
+
     fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
         let pos = (
             pos.0.saturating_sub(self.margins.0),
@@ -387,13 +882,58 @@ impl<'a> Widget for Margin<'a> {
         self.child.mouse_click(button, pos);
     }
 
-    fn mouse_scroll(&mut self, scroll: (f64, f64), pos: (u32, u32)) {
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        let pos = (
+            pos.0.saturating_sub(self.margins.0),
+            pos.1.saturating_sub(self.margins.2),
+        );
+        self.child.mouse_press(button, pos);
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
         let pos = (
             pos.0.saturating_sub(self.margins.0),
             pos.1.saturating_sub(self.margins.2),
         );
         self.child.mouse_scroll(scroll, pos);
     }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        let pos = pos.map(|pos| {
+            (
+                pos.0.saturating_sub(self.margins.0),
+                pos.1.saturating_sub(self.margins.2),
+            )
+        });
+        self.child.mouse_motion(pos);
+    }
+
+    fn action(&mut self, action: &Action) {
+        self.child.action(action);
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        let child_pos = (pos.0 + self.margins.0, pos.1 + self.margins.2);
+        TreeNode::new(self, pos)
+            .value("margins", StateValue::Text(format!("{:?}", self.margins)))
+            .child(self.child.tree(child_pos))
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        self.child.focused_only(regions);
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
 }
 
 pub struct Fixed<'a> {
@@ -448,11 +988,649 @@ impl<'a> Widget for Fixed<'a> {
             .keyboard_input(keysym, modifier_state, key_state, interpreted);
     }
 
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        self.child.constraints()
+    }
+
     fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
         self.child.mouse_click(button, pos);
     }
 
-    fn mouse_scroll(&mut self, scroll: (f64, f64), pos: (u32, u32)) {
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        self.child.mouse_press(button, pos);
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
         self.child.mouse_scroll(scroll, pos);
     }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        self.child.mouse_motion(pos);
+    }
+
+    fn action(&mut self, action: &Action) {
+        self.child.action(action);
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos).child(self.child.tree(pos))
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        self.child.focused_only(regions);
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
+}
+
+pub struct Styled<'a> {
+    pub child: Box<dyn Widget + Send + 'a>,
+    pub background: Option<Color>,
+    pub padding: (u32, u32, u32, u32), // left, right, top, bottom
+    pub border: Option<(u32, Color)>,
+    pub radius: u32,
+    // The size the decorations were last drawn at, which is what the child
+    // took of the space given to it
+    drawn: (u32, u32),
+}
+
+#[allow(dead_code)]
+impl<'a> Styled<'a> {
+    pub fn new(
+        background: Option<Color>,
+        padding: (u32, u32, u32, u32),
+        border: Option<(u32, Color)>,
+        radius: u32,
+        child: Box<dyn Widget + Send + 'a>,
+    ) -> Box<Styled> {
+        Box::new(Styled {
+            child,
+            background,
+            padding,
+            border,
+            radius,
+            drawn: (0, 0),
+        })
+    }
+
+    fn insets(&self) -> (u32, u32, u32, u32) {
+        let border = self.border.map(|(width, _)| width).unwrap_or(0);
+        (
+            self.padding.0 + border,
+            self.padding.1 + border,
+            self.padding.2 + border,
+            self.padding.3 + border,
+        )
+    }
+
+    fn decorate(
+        &self,
+        buf: &mut Buffer,
+        bg: &Color,
+        pos: (u32, u32),
+        (width, height): (u32, u32),
+    ) -> Result<(i32, i32, i32, i32), ::std::io::Error> {
+        let buf = &mut buf.subdimensions((pos.0, pos.1, width, height))?;
+        if let Some(fill) = &self.background {
+            draw_rounded_box(buf, bg, fill, (width, height), self.radius)?;
+        }
+        if let Some((border, c)) = &self.border {
+            let bg = self.background.as_ref().unwrap_or(bg);
+            draw_rounded_outline(buf, bg, c, (width, height), self.radius, *border)?;
+        }
+        Ok(buf.get_signed_bounds())
+    }
+
+    fn draw_child(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+        force: bool,
+    ) -> Result<DrawReport, ::std::io::Error> {
+        self.child.draw(
+            &mut DrawContext {
+                buf: ctx.buf,
+                bg: self.background.as_ref().unwrap_or(ctx.bg),
+                time: ctx.time,
+                force,
+            },
+            pos,
+            expansion,
+        )
+    }
+}
+
+impl<'a> Widget for Styled<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.child.wait(ctx)
+    }
+    fn enter(&mut self) {
+        self.child.enter()
+    }
+    fn leave(&mut self) {
+        self.child.leave()
+    }
+    fn size(&self) -> (u32, u32) {
+        let size = self.child.size();
+        let insets = self.insets();
+        (size.0 + insets.0 + insets.1, size.1 + insets.2 + insets.3)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let insets = self.insets();
+        let mut damage = Vec::new();
+
+        // The decorations only change on a full redraw or when the child
+        // takes another size, as the child is responsible for clearing its
+        // own area with our background. They are drawn before the child, at
+        // the size it took last time.
+        if ctx.force && self.drawn != (0, 0) {
+            damage.push(self.decorate(ctx.buf, ctx.bg, pos, self.drawn)?);
+        }
+
+        let expansion = (
+            expansion.0.saturating_sub(insets.0 + insets.1),
+            expansion.1.saturating_sub(insets.2 + insets.3),
+        );
+        let child_pos = (pos.0 + insets.0, pos.1 + insets.2);
+        let mut report = self.draw_child(ctx, child_pos, expansion, ctx.force)?;
+        let size = (
+            report.width + insets.0 + insets.1,
+            report.height + insets.2 + insets.3,
+        );
+        if size != self.drawn {
+            // The decorations are drawn at the new size, and the child again
+            // over them
+            self.drawn = size;
+            damage.push(self.decorate(ctx.buf, ctx.bg, pos, size)?);
+            report = self.draw_child(ctx, child_pos, expansion, true)?;
+        }
+        damage.append(&mut report.damage);
+
+        Ok(DrawReport {
+            width: size.0,
+            height: size.1,
+            damage,
+            full_damage: report.full_damage,
+        })
+    }
+
+    fn keyboard_input(
+        &mut self,
+        keysym: u32,
+        modifier_state: ModifiersState,
+        key_state: KeyState,
+        interpreted: Option<String>,
+    ) {
+        self.child
+            .keyboard_input(keysym, modifier_state, key_state, interpreted);
+    }
+
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        let insets = self.insets();
+        let mut constraints = self.child.constraints();
+        constraints.min_size.0 += insets.0 + insets.1;
+        constraints.min_size.1 += insets.2 + insets.3;
+        constraints
+    }
+
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        let insets = self.insets();
+        let pos = (
+            pos.0.saturating_sub(insets.0),
+            pos.1.saturating_sub(insets.2),
+        );
+        self.child.mouse_click(button, pos);
+    }
+
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        let insets = self.insets();
+        let pos = (
+            pos.0.saturating_sub(insets.0),
+            pos.1.saturating_sub(insets.2),
+        );
+        self.child.mouse_press(button, pos);
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        let insets = self.insets();
+        let pos = (
+            pos.0.saturating_sub(insets.0),
+            pos.1.saturating_sub(insets.2),
+        );
+        self.child.mouse_scroll(scroll, pos);
+    }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        let insets = self.insets();
+        let pos = pos.map(|pos| {
+            (
+                pos.0.saturating_sub(insets.0),
+                pos.1.saturating_sub(insets.2),
+            )
+        });
+        self.child.mouse_motion(pos);
+    }
+
+    fn action(&mut self, action: &Action) {
+        self.child.action(action);
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        let insets = self.insets();
+        let mut node = TreeNode::new(self, pos);
+        if let Some(bg) = &self.background {
+            node = node.value("background", StateValue::Text(bg.as_hex_rgb()));
+        }
+        if let Some((width, c)) = &self.border {
+            node = node
+                .value("border", StateValue::Number(*width as f64))
+                .value("border_color", StateValue::Text(c.as_hex_rgb()));
+        }
+        node.child(self.child.tree((pos.0 + insets.0, pos.1 + insets.2)))
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        self.child.focused_only(regions);
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
+}
+
+pub struct Constrained<'a> {
+    pub child: Box<dyn Widget + Send + 'a>,
+    pub min_size: (u32, u32),
+    pub max_size: (u32, u32), // 0 for unconstrained
+    pub priority: i32,
+    pub overflow: Overflow,
+}
+
+#[allow(dead_code)]
+impl<'a> Constrained<'a> {
+    pub fn new(
+        min_size: (u32, u32),
+        max_size: (u32, u32),
+        priority: i32,
+        overflow: Overflow,
+        child: Box<dyn Widget + Send + 'a>,
+    ) -> Box<Constrained> {
+        Box::new(Constrained {
+            child,
+            min_size,
+            max_size,
+            priority,
+            overflow,
+        })
+    }
+
+    fn limit(&self, size: (u32, u32)) -> (u32, u32) {
+        let limit = |v: u32, max: u32| if max == 0 { v } else { v.min(max) };
+        (
+            limit(size.0, self.max_size.0),
+            limit(size.1, self.max_size.1),
+        )
+    }
+}
+
+impl<'a> Widget for Constrained<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.child.wait(ctx)
+    }
+    fn enter(&mut self) {
+        self.child.enter()
+    }
+    fn leave(&mut self) {
+        self.child.leave()
+    }
+    fn size(&self) -> (u32, u32) {
+        let size = self.limit(self.child.size());
+        (size.0.max(self.min_size.0), size.1.max(self.min_size.1))
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let size = self.child.size();
+        let limited = self.limit(size);
+        let expansion = self.limit(expansion);
+        let mut report = if limited == size {
+            self.child.draw(ctx, pos, expansion)?
+        } else {
+            self.child.draw(
+                &mut DrawContext {
+                    buf: &mut ctx.buf.clip((pos.0, pos.1, limited.0, limited.1)),
+                    bg: ctx.bg,
+                    time: ctx.time,
+                    force: ctx.force,
+                },
+                pos,
+                expansion,
+            )?
+        };
+        let size = self.limit((report.width, report.height));
+        report.width = size.0.max(self.min_size.0);
+        report.height = size.1.max(self.min_size.1);
+        Ok(report)
+    }
+
+    fn keyboard_input(
+        &mut self,
+        keysym: u32,
+        modifier_state: ModifiersState,
+        key_state: KeyState,
+        interpreted: Option<String>,
+    ) {
+        self.child
+            .keyboard_input(keysym, modifier_state, key_state, interpreted);
+    }
+
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        Constraints {
+            min_size: self.min_size,
+            priority: self.priority,
+            overflow: self.overflow,
+        }
+    }
+
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        self.child.mouse_click(button, pos);
+    }
+
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        self.child.mouse_press(button, pos);
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        self.child.mouse_scroll(scroll, pos);
+    }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        self.child.mouse_motion(pos);
+    }
+
+    fn action(&mut self, action: &Action) {
+        self.child.action(action);
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos).child(self.child.tree(pos))
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        self.child.focused_only(regions);
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
+}
+
+/// Shows the child only on the focused output when the dashboard is shown on
+/// all outputs. The space of the child is kept, so the layout is the same on
+/// every output.
+pub struct FocusedOutput<'a> {
+    pub child: Box<dyn Widget + Send + 'a>,
+    bounds: (i32, i32, i32, i32),
+}
+
+impl<'a> FocusedOutput<'a> {
+    pub fn new(child: Box<dyn Widget + Send + 'a>) -> Box<FocusedOutput<'a>> {
+        Box::new(FocusedOutput {
+            child,
+            bounds: (0, 0, 0, 0),
+        })
+    }
+}
+
+impl<'a> Widget for FocusedOutput<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.child.wait(ctx)
+    }
+    fn enter(&mut self) {
+        self.child.enter()
+    }
+    fn leave(&mut self) {
+        self.child.leave()
+    }
+    fn size(&self) -> (u32, u32) {
+        self.child.size()
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let report = self.child.draw(ctx, pos, expansion)?;
+        self.bounds = (
+            pos.0 as i32,
+            pos.1 as i32,
+            report.width as i32,
+            report.height as i32,
+        );
+        Ok(report)
+    }
+
+    fn keyboard_input(
+        &mut self,
+        keysym: u32,
+        modifier_state: ModifiersState,
+        key_state: KeyState,
+        interpreted: Option<String>,
+    ) {
+        self.child
+            .keyboard_input(keysym, modifier_state, key_state, interpreted);
+    }
+
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        self.child.constraints()
+    }
+
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        self.child.mouse_click(button, pos);
+    }
+
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        self.child.mouse_press(button, pos);
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        self.child.mouse_scroll(scroll, pos);
+    }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        self.child.mouse_motion(pos);
+    }
+
+    fn action(&mut self, action: &Action) {
+        self.child.action(action);
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos).child(self.child.tree(pos))
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        regions.push(self.bounds);
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
+}
+
+/// Carries out the configured bindings for clicks on the child, instead of
+/// passing those clicks on to it.
+pub struct Bound<'a> {
+    pub child: Box<dyn Widget + Send + 'a>,
+    bindings: Vec<(u32, Binding)>,
+    tx: Sender<Cmd>,
+}
+
+impl<'a> Bound<'a> {
+    pub fn new(
+        bindings: Vec<(u32, Binding)>,
+        tx: Sender<Cmd>,
+        child: Box<dyn Widget + Send + 'a>,
+    ) -> Box<Bound<'a>> {
+        Box::new(Bound {
+            child,
+            bindings,
+            tx,
+        })
+    }
+}
+
+impl<'a> Widget for Bound<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.child.wait(ctx)
+    }
+    fn enter(&mut self) {
+        self.child.enter()
+    }
+    fn leave(&mut self) {
+        self.child.leave()
+    }
+    fn size(&self) -> (u32, u32) {
+        self.child.size()
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        self.child.draw(ctx, pos, expansion)
+    }
+
+    fn keyboard_input(
+        &mut self,
+        keysym: u32,
+        modifier_state: ModifiersState,
+        key_state: KeyState,
+        interpreted: Option<String>,
+    ) {
+        self.child
+            .keyboard_input(keysym, modifier_state, key_state, interpreted);
+    }
+
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        self.child.constraints()
+    }
+
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        match self.bindings.iter().find(|(b, _)| *b == button) {
+            Some((_, Binding::Refresh)) => {
+                self.child.action(&Action::Refresh);
+                self.tx.send(Cmd::Draw).unwrap();
+            }
+            Some((_, binding)) => binding.execute(&self.tx),
+            None => self.child.mouse_click(button, pos),
+        }
+    }
+
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        if !self.bindings.iter().any(|(b, _)| *b == button) {
+            self.child.mouse_press(button, pos);
+        }
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        self.child.mouse_scroll(scroll, pos);
+    }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        self.child.mouse_motion(pos);
+    }
+
+    fn action(&mut self, action: &Action) {
+        self.child.action(action);
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos).child(self.child.tree(pos))
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        self.child.focused_only(regions);
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
 }
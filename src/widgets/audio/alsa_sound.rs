@@ -1,4 +1,5 @@
 use crate::color::Color;
+use crate::keyboard::keysyms;
 use crate::widget::WaitContext;
 use crate::{
     fonts::FontRef,
@@ -112,4 +113,11 @@ impl BarWidgetImpl for Alsa {
         }
     }
     fn toggle(&mut self) {}
+    fn keysyms(&self) -> Option<(u32, u32, u32)> {
+        Some((
+            keysyms::XKB_KEY_XF86AudioRaiseVolume,
+            keysyms::XKB_KEY_XF86AudioLowerVolume,
+            keysyms::XKB_KEY_XF86AudioMute,
+        ))
+    }
 }
@@ -6,4 +6,4 @@ pub use alsa_sound::Alsa;
 #[cfg(feature = "pulseaudio-widget")]
 mod pulse_sound;
 #[cfg(feature = "pulseaudio-widget")]
-pub use pulse_sound::PulseAudio;
+pub use pulse_sound::{Direction, PulseAudio};
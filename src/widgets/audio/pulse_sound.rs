@@ -1,6 +1,7 @@
 use crate::cmd::Cmd;
 use crate::color::Color;
-use crate::widget::WaitContext;
+use crate::keyboard::keysyms;
+use crate::widget::{StateValue, WaitContext, WidgetState};
 use crate::{
     fonts::FontRef,
     widgets::bar_widget::{BarWidget, BarWidgetImpl},
@@ -14,10 +15,12 @@ use std::sync::mpsc::{channel, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
+use chrono::{Duration, Local, NaiveDateTime};
 use libpulse_binding::callbacks::ListResult;
 use libpulse_binding::context::{
-    introspect::ServerInfo, introspect::SinkInfo, subscribe::Facility, subscribe::InterestMaskSet,
-    subscribe::Operation as SubscribeOperation, Context, FlagSet, State as PulseState,
+    introspect::ServerInfo, introspect::SinkInfo, introspect::SourceInfo, subscribe::Facility,
+    subscribe::InterestMaskSet, subscribe::Operation as SubscribeOperation, Context, FlagSet,
+    State as PulseState,
 };
 use libpulse_binding::mainloop::standard::IterateResult;
 use libpulse_binding::mainloop::standard::Mainloop;
@@ -29,24 +32,105 @@ struct PulseAudioConnection {
     context: Rc<RefCell<Context>>,
 }
 
+/// Whether a device plays sound, like speakers, or records it, like a
+/// microphone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sink,
+    Source,
+}
+
+impl Direction {
+    fn index(self) -> usize {
+        match self {
+            Direction::Sink => 0,
+            Direction::Source => 1,
+        }
+    }
+
+    fn default_name(self) -> &'static str {
+        match self {
+            Direction::Sink => "@DEFAULT_SINK@",
+            Direction::Source => "@DEFAULT_SOURCE@",
+        }
+    }
+}
+
 #[derive(Clone)]
-struct PulseAudioSinkInfo {
+struct PulseAudioDeviceInfo {
+    index: u32,
+    description: String,
     volume: ChannelVolumes,
     mute: bool,
+    // Whether this is a source recording the output of a sink
+    monitor: bool,
+}
+
+impl PulseAudioDeviceInfo {
+    fn from_sink(info: &SinkInfo) -> Option<(String, Self)> {
+        let name = info.name.as_ref()?.to_string();
+        let description = match &info.description {
+            Some(description) => description.to_string(),
+            None => name.clone(),
+        };
+        Some((
+            name,
+            PulseAudioDeviceInfo {
+                index: info.index,
+                description,
+                volume: info.volume,
+                mute: info.mute,
+                monitor: false,
+            },
+        ))
+    }
+
+    fn from_source(info: &SourceInfo) -> Option<(String, Self)> {
+        let name = info.name.as_ref()?.to_string();
+        let description = match &info.description {
+            Some(description) => description.to_string(),
+            None => name.clone(),
+        };
+        Some((
+            name,
+            PulseAudioDeviceInfo {
+                index: info.index,
+                description,
+                volume: info.volume,
+                mute: info.mute,
+                monitor: info.monitor_of_sink.is_some(),
+            },
+        ))
+    }
+}
+
+// Converts the result of an introspection request into device info.
+fn device_result<T>(
+    result: ListResult<&T>,
+    f: fn(&T) -> Option<(String, PulseAudioDeviceInfo)>,
+) -> ListResult<Option<(String, PulseAudioDeviceInfo)>> {
+    match result {
+        ListResult::Item(item) => ListResult::Item(f(item)),
+        ListResult::End => ListResult::End,
+        ListResult::Error => ListResult::Error,
+    }
 }
 
 struct PulseAudioClient {
     sender: Sender<PulseAudioClientRequest>,
-    default_sink: String,
-    sinks: HashMap<String, PulseAudioSinkInfo>,
+    // The default device and the known devices, by direction
+    defaults: [String; 2],
+    devices: [HashMap<String, PulseAudioDeviceInfo>; 2],
 }
 
 enum PulseAudioClientRequest {
     GetDefaultDevice(Option<Sender<bool>>),
-    GetSinkInfoByIndex(Option<Sender<bool>>, u32),
-    GetSinkInfoByName(Option<Sender<bool>>, String),
-    SetSinkVolumeByName(Option<Sender<bool>>, String, ChannelVolumes),
-    SetSinkMuteByName(Option<Sender<bool>>, String, bool),
+    GetInfoByIndex(Option<Sender<bool>>, Direction, u32),
+    GetInfoByName(Option<Sender<bool>>, Direction, String),
+    SetVolumeByName(Option<Sender<bool>>, Direction, String, ChannelVolumes),
+    SetMuteByName(Option<Sender<bool>>, Direction, String, bool),
+    List(Option<Sender<bool>>, Direction),
+    SetDefault(Option<Sender<bool>>, Direction, String),
 }
 
 #[derive(Debug)]
@@ -55,12 +139,15 @@ struct PulseAudioSoundDeviceInner {
     volume: Option<ChannelVolumes>,
     volume_avg: f32,
     muted: bool,
-    default_sink: String,
+    default_device: String,
+    error: Option<String>,
 }
 
 struct PulseAudioSoundDevice {
+    direction: Direction,
     client: Arc<Mutex<PulseAudioClient>>,
     inner: Arc<Mutex<PulseAudioSoundDeviceInner>>,
+    listener: Arc<dyn Fn() + Send + Sync>,
 }
 
 impl PulseAudioConnection {
@@ -96,7 +183,12 @@ impl PulseAudioConnection {
                 PulseState::Ready => {
                     break;
                 }
-                PulseState::Failed | PulseState::Terminated => {}
+                PulseState::Failed | PulseState::Terminated => {
+                    return Err(::std::io::Error::new(
+                        ::std::io::ErrorKind::Other,
+                        "unable to connect to pulseaudio context",
+                    ))
+                }
                 _ => {}
             }
         }
@@ -116,20 +208,38 @@ impl PulseAudioConnection {
 }
 
 impl PulseAudioClient {
-    fn new<F>(listener: F) -> Result<Arc<Mutex<Self>>, ::std::io::Error>
+    fn new() -> Arc<Mutex<Self>> {
+        // Requests fail until the client is started
+        let (tx, _) = channel();
+        Arc::new(Mutex::new(PulseAudioClient {
+            sender: tx,
+            defaults: [
+                Direction::Sink.default_name().to_string(),
+                Direction::Source.default_name().to_string(),
+            ],
+            devices: [HashMap::new(), HashMap::new()],
+        }))
+    }
+
+    /// Starts the threads talking to the server. on_exit is called if they
+    /// stop because the connection failed. Starting again replaces the
+    /// request sender, which stops the writer of the previous connection.
+    fn start<F, E>(
+        client: &Arc<Mutex<Self>>,
+        listener: F,
+        on_exit: E,
+    ) -> Result<(), ::std::io::Error>
     where
         F: Fn(Arc<Mutex<Self>>) -> (),
         F: Send + 'static + Clone,
+        E: Fn() -> (),
+        E: Send + 'static + Clone,
     {
         let (tx, rx) = channel();
-
-        let client = Arc::new(Mutex::new(PulseAudioClient {
-            sender: tx,
-            default_sink: "@DEFAULT_SINK@".to_string(),
-            sinks: HashMap::new(),
-        }));
+        client.lock().unwrap().sender = tx;
 
         let loop_client = client.clone();
+        let writer_exit = on_exit.clone();
         let (tx1, rx1) = channel();
         let _ = thread::Builder::new()
             .name("pa_writer".to_string())
@@ -147,7 +257,10 @@ impl PulseAudioClient {
 
                 // make sure mainloop dispatched everything
                 for _ in 0..10 {
-                    conn.iterate(false).unwrap();
+                    if conn.iterate(false).is_err() {
+                        writer_exit();
+                        return;
+                    }
                 }
 
                 loop {
@@ -172,46 +285,132 @@ impl PulseAudioClient {
                                     });
                                     s
                                 }
-                                PulseAudioClientRequest::GetSinkInfoByIndex(s, index) => {
-                                    introspector.get_sink_info_by_index(index, move |res| {
-                                        *done.lock().unwrap() = true;
-                                        PulseAudioClient::sink_info_callback(
-                                            cl.clone(),
-                                            l.clone(),
-                                            res,
-                                        );
-                                    });
+                                PulseAudioClientRequest::GetInfoByIndex(s, d, index) => {
+                                    let mut callback =
+                                        PulseAudioClient::info_callback(cl, l, d, done);
+                                    match d {
+                                        Direction::Sink => {
+                                            introspector.get_sink_info_by_index(
+                                                index,
+                                                move |res| {
+                                                    callback(device_result(
+                                                        res,
+                                                        PulseAudioDeviceInfo::from_sink,
+                                                    ))
+                                                },
+                                            );
+                                        }
+                                        Direction::Source => {
+                                            introspector.get_source_info_by_index(
+                                                index,
+                                                move |res| {
+                                                    callback(device_result(
+                                                        res,
+                                                        PulseAudioDeviceInfo::from_source,
+                                                    ))
+                                                },
+                                            );
+                                        }
+                                    }
+                                    s
+                                }
+                                PulseAudioClientRequest::GetInfoByName(s, d, name) => {
+                                    let mut callback =
+                                        PulseAudioClient::info_callback(cl, l, d, done);
+                                    match d {
+                                        Direction::Sink => {
+                                            introspector.get_sink_info_by_name(&name, move |res| {
+                                                callback(device_result(
+                                                    res,
+                                                    PulseAudioDeviceInfo::from_sink,
+                                                ))
+                                            });
+                                        }
+                                        Direction::Source => {
+                                            introspector.get_source_info_by_name(
+                                                &name,
+                                                move |res| {
+                                                    callback(device_result(
+                                                        res,
+                                                        PulseAudioDeviceInfo::from_source,
+                                                    ))
+                                                },
+                                            );
+                                        }
+                                    }
                                     s
                                 }
-                                PulseAudioClientRequest::GetSinkInfoByName(s, name) => {
-                                    introspector.get_sink_info_by_name(&name, move |res| {
+                                PulseAudioClientRequest::List(s, d) => {
+                                    let mut callback =
+                                        PulseAudioClient::info_callback(cl, l, d, done);
+                                    match d {
+                                        Direction::Sink => {
+                                            introspector.get_sink_info_list(move |res| {
+                                                callback(device_result(
+                                                    res,
+                                                    PulseAudioDeviceInfo::from_sink,
+                                                ))
+                                            });
+                                        }
+                                        Direction::Source => {
+                                            introspector.get_source_info_list(move |res| {
+                                                callback(device_result(
+                                                    res,
+                                                    PulseAudioDeviceInfo::from_source,
+                                                ))
+                                            });
+                                        }
+                                    }
+                                    s
+                                }
+                                PulseAudioClientRequest::SetVolumeByName(s, d, name, volumes) => {
+                                    let callback = Some(Box::new(move |_| {
                                         *done.lock().unwrap() = true;
-                                        PulseAudioClient::sink_info_callback(
-                                            cl.clone(),
-                                            l.clone(),
-                                            res,
-                                        );
-                                    });
+                                    })
+                                        as Box<dyn FnMut(bool)>);
+                                    match d {
+                                        Direction::Sink => {
+                                            introspector
+                                                .set_sink_volume_by_name(&name, &volumes, callback);
+                                        }
+                                        Direction::Source => {
+                                            introspector.set_source_volume_by_name(
+                                                &name, &volumes, callback,
+                                            );
+                                        }
+                                    }
                                     s
                                 }
-                                PulseAudioClientRequest::SetSinkVolumeByName(s, name, volumes) => {
-                                    introspector.set_sink_volume_by_name(
-                                        &name,
-                                        &volumes,
-                                        Some(Box::new(move |_| {
-                                            *done.lock().unwrap() = true;
-                                        })),
-                                    );
+                                PulseAudioClientRequest::SetMuteByName(s, d, name, mute) => {
+                                    let callback = Some(Box::new(move |_| {
+                                        *done.lock().unwrap() = true;
+                                    })
+                                        as Box<dyn FnMut(bool)>);
+                                    match d {
+                                        Direction::Sink => {
+                                            introspector
+                                                .set_sink_mute_by_name(&name, mute, callback);
+                                        }
+                                        Direction::Source => {
+                                            introspector
+                                                .set_source_mute_by_name(&name, mute, callback);
+                                        }
+                                    }
                                     s
                                 }
-                                PulseAudioClientRequest::SetSinkMuteByName(s, name, mute) => {
-                                    introspector.set_sink_mute_by_name(
-                                        &name,
-                                        mute,
-                                        Some(Box::new(move |_| {
-                                            *done.lock().unwrap() = true;
-                                        })),
-                                    );
+                                PulseAudioClientRequest::SetDefault(s, d, name) => {
+                                    let callback = move |_| {
+                                        *done.lock().unwrap() = true;
+                                    };
+                                    let mut context = conn.context.borrow_mut();
+                                    match d {
+                                        Direction::Sink => {
+                                            context.set_default_sink(&name, callback);
+                                        }
+                                        Direction::Source => {
+                                            context.set_default_source(&name, callback);
+                                        }
+                                    }
                                     s
                                 }
                             };
@@ -221,7 +420,10 @@ impl PulseAudioClient {
                                 if *done_reader.lock().unwrap() {
                                     break;
                                 }
-                                conn.iterate(true).unwrap();
+                                if conn.iterate(true).is_err() {
+                                    writer_exit();
+                                    return;
+                                }
                             }
 
                             if let Some(s) = s {
@@ -234,6 +436,7 @@ impl PulseAudioClient {
 
         // subscribe
         let cl2 = client.clone();
+        let reader_exit = on_exit;
         let (tx2, rx2) = channel();
         let _ = thread::Builder::new()
             .name("pa_reader".to_string())
@@ -256,11 +459,14 @@ impl PulseAudioClient {
                             .unwrap()
                             .subscribe_callback(facility, operation, index)
                     })));
-                conn.context
-                    .borrow_mut()
-                    .subscribe(InterestMaskSet::SERVER | InterestMaskSet::SINK, |_| {});
-
-                conn.mainloop.borrow_mut().run().unwrap();
+                conn.context.borrow_mut().subscribe(
+                    InterestMaskSet::SERVER | InterestMaskSet::SINK | InterestMaskSet::SOURCE,
+                    |_| {},
+                );
+
+                // Only returns if the connection fails
+                let _ = conn.mainloop.borrow_mut().run();
+                reader_exit();
             });
 
         let pa_writer_res = rx1.recv().unwrap();
@@ -273,7 +479,7 @@ impl PulseAudioClient {
             ));
         }
 
-        Ok(client)
+        Ok(())
     }
 
     fn send(&self, request: PulseAudioClientRequest) -> Result<(), ::std::io::Error> {
@@ -290,112 +496,191 @@ impl PulseAudioClient {
         F: Fn(Arc<Mutex<Self>>) -> (),
         F: Send + 'static,
     {
-        match server_info.default_sink_name.clone() {
-            None => {}
-            Some(default_sink) => {
-                (*s.lock().unwrap()).default_sink = default_sink.into();
-                listener(s);
+        let defaults = [
+            (Direction::Sink, &server_info.default_sink_name),
+            (Direction::Source, &server_info.default_source_name),
+        ];
+        {
+            let mut client = s.lock().unwrap();
+            for (direction, name) in defaults {
+                let name = match name {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                };
+                // A device that has just been added may not be known yet
+                if !client.devices[direction.index()].contains_key(&name) {
+                    let _ = client.send(PulseAudioClientRequest::GetInfoByName(
+                        None,
+                        direction,
+                        name.clone(),
+                    ));
+                }
+                client.defaults[direction.index()] = name;
             }
         }
+        listener(s);
     }
 
-    fn sink_info_callback<F>(s: Arc<Mutex<Self>>, listener: F, result: ListResult<&SinkInfo>)
+    /// Makes the callback for requests of device info, storing the info
+    /// and marking the request done at the end of the results.
+    fn info_callback<F>(
+        s: Arc<Mutex<Self>>,
+        listener: F,
+        direction: Direction,
+        done: Arc<Mutex<bool>>,
+    ) -> impl FnMut(ListResult<Option<(String, PulseAudioDeviceInfo)>>)
     where
         F: Fn(Arc<Mutex<Self>>) -> (),
         F: Send + 'static,
     {
-        match result {
-            ListResult::End | ListResult::Error => {}
-            ListResult::Item(sink_info) => match sink_info.name.clone() {
-                None => {}
-                Some(name) => {
-                    let info = PulseAudioSinkInfo {
-                        volume: sink_info.volume,
-                        mute: sink_info.mute,
-                    };
-                    s.lock().unwrap().sinks.insert(name.into(), info);
-                    listener(s);
-                }
-            },
+        move |result| match result {
+            ListResult::End | ListResult::Error => *done.lock().unwrap() = true,
+            ListResult::Item(None) => {}
+            ListResult::Item(Some((name, info))) => {
+                s.lock().unwrap().devices[direction.index()].insert(name, info);
+                listener(s.clone());
+            }
         }
     }
 
     fn subscribe_callback(
-        &self,
+        &mut self,
         facility: Option<Facility>,
-        _operation: Option<SubscribeOperation>,
+        operation: Option<SubscribeOperation>,
         index: u32,
     ) {
-        match facility {
-            None => {}
-            Some(facility) => match facility {
-                Facility::Server => {
-                    let _ = self.send(PulseAudioClientRequest::GetDefaultDevice(None));
-                }
-                Facility::Sink => {
-                    let _ = self.send(PulseAudioClientRequest::GetSinkInfoByIndex(None, index));
-                }
-                _ => {}
-            },
+        let direction = match facility {
+            Some(Facility::Server) => {
+                let _ = self.send(PulseAudioClientRequest::GetDefaultDevice(None));
+                return;
+            }
+            Some(Facility::Sink) => Direction::Sink,
+            Some(Facility::Source) => Direction::Source,
+            _ => return,
+        };
+        if operation == Some(SubscribeOperation::Removed) {
+            self.devices[direction.index()].retain(|_, device| device.index != index);
+        } else {
+            let _ = self.send(PulseAudioClientRequest::GetInfoByIndex(
+                None, direction, index,
+            ));
         }
     }
 }
 
 impl PulseAudioSoundDevice {
-    fn new<F>(listener: F) -> Result<Self, ::std::io::Error>
-    where
-        F: Fn() -> (),
-        F: Send + 'static + Clone,
-    {
-        let inner = Arc::new(Mutex::new(PulseAudioSoundDeviceInner {
-            name: None,
-            volume: None,
-            volume_avg: 0.0,
-            muted: false,
-            default_sink: "@DEFAULT_SINK@".to_string(),
-        }));
-
-        let cb_inner = inner.clone();
-        let client = PulseAudioClient::new(move |client| {
-            let mut inner = cb_inner.lock().unwrap();
-            inner.default_sink = client.lock().unwrap().default_sink.clone();
-            let name = inner
-                .name
-                .clone()
-                .unwrap_or_else(|| inner.default_sink.clone());
-            let sink_info = match client.lock().unwrap().sinks.get(&name) {
-                None => return,
-                Some(sink_info) => (*sink_info).clone(),
-            };
-
-            inner.volume = Some(sink_info.volume);
-            inner.volume_avg = sink_info.volume.avg().0 as f32 / Volume::NORMAL.0 as f32;
-            inner.muted = sink_info.mute;
-
-            listener();
-        })?;
-
-        let cl = client.clone();
+    /// Creates a device controlling the named sink or source, or the default
+    /// one if None, following it as the default changes.
+    fn new(
+        direction: Direction,
+        name: Option<String>,
+        listener: Arc<dyn Fn() + Send + Sync>,
+    ) -> Result<Self, ::std::io::Error> {
+        let device = PulseAudioSoundDevice {
+            direction,
+            client: PulseAudioClient::new(),
+            inner: Arc::new(Mutex::new(PulseAudioSoundDeviceInner {
+                name,
+                volume: None,
+                volume_avg: 0.0,
+                muted: false,
+                default_device: direction.default_name().to_string(),
+                error: None,
+            })),
+            listener,
+        };
+        device.connect()?;
+        Ok(device)
+    }
+
+    // Connects to the server and fetches the default device and the list of
+    // devices. Should the connection fail later on, the error is recorded and
+    // the listener notified.
+    fn connect(&self) -> Result<(), ::std::io::Error> {
+        let cb_inner = self.inner.clone();
+        let listener = self.listener.clone();
+        let exit_inner = self.inner.clone();
+        let exit_listener = self.listener.clone();
+        let idx = self.direction.index();
+        PulseAudioClient::start(
+            &self.client,
+            move |client| {
+                let mut inner = cb_inner.lock().unwrap();
+                let client = client.lock().unwrap();
+                inner.default_device = client.defaults[idx].clone();
+                let name = inner
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| inner.default_device.clone());
+                let info = match client.devices[idx].get(&name) {
+                    None => return,
+                    Some(info) => (*info).clone(),
+                };
+
+                inner.volume = Some(info.volume);
+                inner.volume_avg = info.volume.avg().0 as f32 / Volume::NORMAL.0 as f32;
+                inner.muted = info.mute;
+
+                listener();
+            },
+            move || {
+                exit_inner.lock().unwrap().error = Some("disconnected".to_string());
+                exit_listener();
+            },
+        )?;
+
+        let disconnected =
+            |_| ::std::io::Error::new(::std::io::ErrorKind::Other, "pulseaudio connection lost");
         let (tx, rx) = channel();
         {
-            let cl = client.lock().unwrap();
+            let cl = self.client.lock().unwrap();
             cl.send(PulseAudioClientRequest::GetDefaultDevice(Some(tx)))?;
         }
-        rx.recv().unwrap();
-        let name = {
-            let cl = client.lock().unwrap();
-            cl.default_sink.to_string()
-        };
-        (*inner.lock().unwrap()).name = Some(name.clone());
-        let device = PulseAudioSoundDevice { client: cl, inner };
+        rx.recv().map_err(disconnected)?;
         let (tx, rx) = channel();
         {
-            let cl = client.lock().unwrap();
-            cl.send(PulseAudioClientRequest::GetSinkInfoByName(Some(tx), name))?;
+            let cl = self.client.lock().unwrap();
+            cl.send(PulseAudioClientRequest::List(Some(tx), self.direction))?;
         }
-        rx.recv().unwrap();
+        rx.recv().map_err(disconnected)?;
 
-        Ok(device)
+        self.inner.lock().unwrap().error = None;
+        (self.listener)();
+        Ok(())
+    }
+
+    fn error(&self) -> Option<String> {
+        self.inner.lock().unwrap().error.clone()
+    }
+
+    /// The names and descriptions of the devices, sorted by description,
+    /// and the index of the default device among them. Monitors of sinks are
+    /// left out.
+    fn devices(&self) -> (Vec<(String, String)>, usize) {
+        let client = self.client.lock().unwrap();
+        let idx = self.direction.index();
+        let mut devices: Vec<(String, String)> = client.devices[idx]
+            .iter()
+            .filter(|(_, info)| !info.monitor)
+            .map(|(name, info)| (name.clone(), info.description.clone()))
+            .collect();
+        devices.sort_by(|a, b| a.1.cmp(&b.1));
+        let default = devices
+            .iter()
+            .position(|(name, _)| *name == client.defaults[idx])
+            .unwrap_or(0);
+        (devices, default)
+    }
+
+    fn set_default(&self, name: String) -> Result<(), ::std::io::Error> {
+        self.client
+            .lock()
+            .unwrap()
+            .send(PulseAudioClientRequest::SetDefault(
+                None,
+                self.direction,
+                name,
+            ))
     }
 
     fn muted(&self) -> bool {
@@ -431,7 +716,7 @@ impl PulseAudioSoundDevice {
         let name = inner
             .name
             .clone()
-            .unwrap_or_else(|| inner.default_sink.clone());
+            .unwrap_or_else(|| inner.default_device.clone());
 
         // update volumes
         inner.volume = Some(volume);
@@ -439,8 +724,11 @@ impl PulseAudioSoundDevice {
         self.client
             .lock()
             .unwrap()
-            .send(PulseAudioClientRequest::SetSinkVolumeByName(
-                None, name, volume,
+            .send(PulseAudioClientRequest::SetVolumeByName(
+                None,
+                self.direction,
+                name,
+                volume,
             ))?;
         Ok(())
     }
@@ -463,7 +751,7 @@ impl PulseAudioSoundDevice {
         let name = inner
             .name
             .clone()
-            .unwrap_or_else(|| inner.default_sink.clone());
+            .unwrap_or_else(|| inner.default_device.clone());
 
         // update volumes
         inner.volume = Some(volume);
@@ -471,8 +759,11 @@ impl PulseAudioSoundDevice {
         self.client
             .lock()
             .unwrap()
-            .send(PulseAudioClientRequest::SetSinkVolumeByName(
-                None, name, volume,
+            .send(PulseAudioClientRequest::SetVolumeByName(
+                None,
+                self.direction,
+                name,
+                volume,
             ))?;
         Ok(())
     }
@@ -483,43 +774,95 @@ impl PulseAudioSoundDevice {
         self.client
             .lock()
             .unwrap()
-            .send(PulseAudioClientRequest::SetSinkMuteByName(
+            .send(PulseAudioClientRequest::SetMuteByName(
                 None,
+                self.direction,
                 inner
                     .name
                     .clone()
-                    .unwrap_or_else(|| inner.default_sink.clone()),
+                    .unwrap_or_else(|| inner.default_device.clone()),
                 inner.muted,
             ))?;
         Ok(())
     }
 }
 
+// Delay before reconnecting after losing the connection, doubled for every
+// failed attempt, in seconds.
+const RECONNECT_DELAY: i64 = 1;
+const RECONNECT_MAX_DELAY: i64 = 60;
+
 pub struct PulseAudio {
     device: PulseAudioSoundDevice,
+    // The names of the devices last offered in the menu
+    choices: Vec<String>,
+    // When to reconnect, and the delay used for it
+    reconnect: Option<(NaiveDateTime, i64)>,
 }
 
 impl PulseAudio {
+    /// Creates a bar for the volume of the named device of the direction,
+    /// or of the default one if None.
     pub fn new(
         font: FontRef,
         font_size: f32,
         length: u32,
+        direction: Direction,
+        device: Option<String>,
         sender: Sender<Cmd>,
     ) -> Result<Box<BarWidget>, ::std::io::Error> {
         BarWidget::new(font, font_size, length, move |dirty| {
-            let device = PulseAudioSoundDevice::new(move || {
-                *dirty.lock().unwrap() = true;
-                sender.send(Cmd::Draw).unwrap();
-            })?;
-            Ok(Box::new(PulseAudio { device }))
+            let device = PulseAudioSoundDevice::new(
+                direction,
+                device,
+                Arc::new(move || {
+                    *dirty.lock().unwrap() = true;
+                    let _ = sender.send(Cmd::Draw);
+                }),
+            )?;
+            Ok(Box::new(PulseAudio {
+                device,
+                choices: Vec::new(),
+                reconnect: None,
+            }))
         })
     }
 }
 
 impl BarWidgetImpl for PulseAudio {
-    fn wait(&mut self, _: &mut WaitContext) {}
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        if self.device.error().is_none() {
+            self.reconnect = None;
+            return;
+        }
+        let now = Local::now().naive_local();
+        let (at, delay) = *self
+            .reconnect
+            .get_or_insert((now + Duration::seconds(RECONNECT_DELAY), RECONNECT_DELAY));
+        if now < at {
+            ctx.set_time(at);
+            return;
+        }
+        if let Err(err) = self.device.connect() {
+            eprintln!("Unable to reconnect to pulseaudio: {}", err);
+            let delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+            let at = now + Duration::seconds(delay);
+            self.reconnect = Some((at, delay));
+            ctx.set_time(at);
+        }
+    }
+    // The values follow pulseaudio by themselves, so this only skips the
+    // wait before reconnecting.
+    fn refresh(&mut self) {
+        if self.device.error().is_some() {
+            self.reconnect = Some((Local::now().naive_local(), RECONNECT_DELAY));
+        }
+    }
     fn name(&self) -> &str {
-        "volume"
+        match self.device.direction {
+            Direction::Sink => "volume",
+            Direction::Source => "mic",
+        }
     }
     fn value(&self) -> f32 {
         self.device.volume()
@@ -533,12 +876,57 @@ impl BarWidgetImpl for PulseAudio {
         }
     }
     fn inc(&mut self, inc: f32) {
-        self.device.inc_volume(inc).unwrap();
+        if let Err(err) = self.device.inc_volume(inc) {
+            eprintln!("Unable to change volume: {}", err);
+        }
     }
     fn set(&mut self, val: f32) {
-        self.device.set_volume(val).unwrap();
+        if let Err(err) = self.device.set_volume(val) {
+            eprintln!("Unable to change volume: {}", err);
+        }
     }
     fn toggle(&mut self) {
-        self.device.toggle().unwrap();
+        if let Err(err) = self.device.toggle() {
+            eprintln!("Unable to toggle mute: {}", err);
+        }
+    }
+    fn error(&self) -> Option<String> {
+        self.device.error()
+    }
+    fn choices(&mut self) -> Option<(Vec<String>, usize)> {
+        let (devices, default) = self.device.devices();
+        let (names, descriptions) = devices.into_iter().unzip();
+        self.choices = names;
+        Some((descriptions, default))
+    }
+    fn choose(&mut self, idx: usize) {
+        if let Some(name) = self.choices.get(idx) {
+            if let Err(err) = self.device.set_default(name.clone()) {
+                eprintln!("Unable to change default device: {}", err);
+            }
+        }
+    }
+    fn keysyms(&self) -> Option<(u32, u32, u32)> {
+        Some(match self.device.direction {
+            Direction::Sink => (
+                keysyms::XKB_KEY_XF86AudioRaiseVolume,
+                keysyms::XKB_KEY_XF86AudioLowerVolume,
+                keysyms::XKB_KEY_XF86AudioMute,
+            ),
+            Direction::Source => (0, 0, keysyms::XKB_KEY_XF86AudioMicMute),
+        })
+    }
+    fn state(&self) -> WidgetState {
+        let mut values = vec![
+            ("value", StateValue::Number(self.device.volume() as f64)),
+            ("muted", StateValue::Bool(self.device.muted())),
+        ];
+        if let Some(error) = self.device.error() {
+            values.push(("error", StateValue::Text(error)));
+        }
+        WidgetState {
+            name: self.name().to_string(),
+            values,
+        }
     }
 }
@@ -1,4 +1,6 @@
+use crate::cmd::Cmd;
 use crate::color::Color;
+use crate::keyboard::keysyms;
 use crate::widget::WaitContext;
 use crate::{
     fonts::FontRef,
@@ -9,12 +11,72 @@ use std::fs;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::io::{Error, ErrorKind};
+use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+use nix::poll::{PollFd, PollFlags};
+use nix::sys::socket::{
+    bind, recv, socket, AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType,
+};
+
+// The multicast group of the uevents sent by the kernel
+const KERNEL_UEVENTS: u32 = 1;
+
+// A netlink socket receiving the uevents of the kernel, which tell of
+// brightness changes made outside of wldash, such as by brightness keys.
+struct Uevents(RawFd);
+
+impl Uevents {
+    fn open() -> Result<Uevents, Error> {
+        let fd = socket(
+            AddressFamily::Netlink,
+            SockType::Datagram,
+            SockFlag::SOCK_NONBLOCK | SockFlag::SOCK_CLOEXEC,
+            SockProtocol::NetlinkKObjectUEvent,
+        )?;
+        let uevents = Uevents(fd);
+        bind(fd, &NetlinkAddr::new(0, KERNEL_UEVENTS))?;
+        Ok(uevents)
+    }
+
+    // Reads the pending uevents, returning true if any of them was about the
+    // backlight device of the given name.
+    fn changed(&self, device: &str) -> bool {
+        let mut buf = [0u8; 4096];
+        let mut changed = false;
+        while let Ok(len) = recv(self.0, &mut buf, MsgFlags::empty()) {
+            let mut subsystem = None;
+            let mut devpath = None;
+            for field in buf[..len].split(|b| *b == 0) {
+                let field = std::str::from_utf8(field).unwrap_or_default();
+                if let Some(value) = field.strip_prefix("SUBSYSTEM=") {
+                    subsystem = Some(value);
+                } else if let Some(value) = field.strip_prefix("DEVPATH=") {
+                    devpath = Some(value);
+                }
+            }
+            changed |= subsystem == Some("backlight")
+                && devpath.is_some_and(|path| path.rsplit('/').next() == Some(device));
+        }
+        changed
+    }
+}
+
+impl Drop for Uevents {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.0);
+    }
+}
 
 pub struct Backlight {
     device_path: PathBuf,
     cur_brightness: u64,
     max_brightness: u64,
+    uevents: Option<Uevents>,
+    dirty: Arc<Mutex<bool>>,
+    sender: Sender<Cmd>,
 }
 
 fn read_file_as_u64(path: &Path) -> Result<u64, Error> {
@@ -78,26 +140,56 @@ impl Backlight {
         font: FontRef<'a>,
         font_size: f32,
         length: u32,
+        sender: Sender<Cmd>,
     ) -> Result<Box<BarWidget<'a>>, Error> {
-        let mut dev = Backlight {
-            device_path: Path::new("/sys/class/backlight").to_path_buf().join(path),
-            cur_brightness: 0,
-            max_brightness: 0,
-        };
-
-        dev.update()?;
-
-        Ok(BarWidget::new_simple(
-            font,
-            font_size,
-            length,
-            Box::new(dev),
-        ))
+        let device_path = Path::new("/sys/class/backlight").join(path);
+        BarWidget::new(font, font_size, length, move |dirty| {
+            let uevents = match Uevents::open() {
+                Ok(uevents) => Some(uevents),
+                Err(err) => {
+                    eprintln!("Unable to watch for brightness changes: {}", err);
+                    None
+                }
+            };
+            let mut dev = Backlight {
+                device_path,
+                cur_brightness: 0,
+                max_brightness: 0,
+                uevents,
+                dirty,
+                sender,
+            };
+
+            dev.update()?;
+
+            Ok(Box::new(dev))
+        })
     }
 }
 
 impl BarWidgetImpl for Backlight {
-    fn wait(&mut self, _: &mut WaitContext) {}
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        let device = self.device_path.file_name().unwrap_or_default();
+        let (fd, changed) = match &self.uevents {
+            Some(uevents) => (uevents.0, uevents.changed(&device.to_string_lossy())),
+            None => return,
+        };
+        if changed {
+            match self.update() {
+                Ok(()) => {
+                    *self.dirty.lock().unwrap() = true;
+                    self.sender.send(Cmd::Draw).unwrap();
+                }
+                Err(err) => eprintln!("Error while trying to read brightness: {}", err),
+            }
+        }
+        ctx.fds.push(PollFd::new(fd, PollFlags::POLLIN));
+    }
+    fn refresh(&mut self) {
+        if let Err(err) = self.update() {
+            eprintln!("Error while trying to read brightness: {}", err);
+        }
+    }
     fn name(&self) -> &str {
         "backlight"
     }
@@ -138,4 +230,11 @@ impl BarWidgetImpl for Backlight {
             }
         }
     }
+    fn keysyms(&self) -> Option<(u32, u32, u32)> {
+        Some((
+            keysyms::XKB_KEY_XF86MonBrightnessUp,
+            keysyms::XKB_KEY_XF86MonBrightnessDown,
+            0,
+        ))
+    }
 }
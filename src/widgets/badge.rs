@@ -0,0 +1,266 @@
+use crate::buffer::Buffer;
+use crate::cmd::{Action, Cmd};
+use crate::color::Color;
+use crate::draw::Font;
+use crate::{
+    fonts::FontRef,
+    widget::{
+        Constraints, DrawContext, DrawReport, KeyState, ModifiersState, Scroll, StateValue,
+        TreeNode, WaitContext, Widget, WidgetState,
+    },
+};
+
+use std::sync::mpsc::Sender;
+
+const BTN_LEFT: u32 = 272;
+// The largest the badge gets, which is smaller for shorter widgets.
+const BADGE_SIZE: u32 = 16;
+
+// Draws a warning triangle with an exclamation mark, filling a square of
+// the given size.
+fn draw_badge(buf: &mut Buffer, size: u32) -> Result<(), ::std::io::Error> {
+    let fill = Color::new(1.0, 0.5, 0.0, 1.0);
+    let mark = Color::new(0.1, 0.1, 0.1, 1.0);
+    let half = size as f32 / 2.0;
+    for y in 0..size {
+        // The triangle widens by a pixel on each side every other row
+        let span = ((y as f32 + 1.0) / 2.0).min(half);
+        let x0 = (half - span).round() as u32;
+        let x1 = (half + span).round() as u32;
+        buf.subdimensions((x0, y, x1 - x0, 1))?.memset(&fill);
+    }
+    let stroke = (size / 8).max(1);
+    let x = (size - stroke) / 2;
+    buf.subdimensions((x, size * 3 / 8, stroke, size * 3 / 8))?
+        .memset(&mark);
+    buf.subdimensions((x, size * 13 / 16, stroke, stroke))?
+        .memset(&mark);
+    Ok(())
+}
+
+/// Marks the child with a warning badge in its top right corner while its
+/// backend has failed, as told by its error. Clicking the badge shows the
+/// error on the on-screen display, while other clicks go to the child.
+pub struct ErrorBadge<'a> {
+    child: Box<dyn Widget + Send + 'a>,
+    // The error the badge was last drawn for
+    shown: Option<String>,
+    // The size of the child as last drawn
+    drawn: (u32, u32),
+    tx: Sender<Cmd>,
+}
+
+impl<'a> ErrorBadge<'a> {
+    pub fn new(tx: Sender<Cmd>, child: Box<dyn Widget + Send + 'a>) -> Box<ErrorBadge<'a>> {
+        Box::new(ErrorBadge {
+            child,
+            shown: None,
+            drawn: (0, 0),
+            tx,
+        })
+    }
+
+    fn badge_size(&self) -> u32 {
+        self.drawn.0.min(self.drawn.1).min(BADGE_SIZE)
+    }
+}
+
+impl<'a> Widget for ErrorBadge<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.child.wait(ctx)
+    }
+    fn enter(&mut self) {
+        self.child.enter()
+    }
+    fn leave(&mut self) {
+        self.child.leave()
+    }
+    fn size(&self) -> (u32, u32) {
+        self.child.size()
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let error = self.child.error();
+        let changed = error != self.shown;
+        // The child is drawn anew when the badge comes or goes, to clear
+        // what was below it
+        let mut report = self.child.draw(
+            &mut DrawContext {
+                buf: ctx.buf,
+                bg: ctx.bg,
+                time: ctx.time,
+                force: ctx.force || changed,
+            },
+            pos,
+            expansion,
+        )?;
+        self.drawn = (report.width, report.height);
+        self.shown = error;
+
+        let redrawn = changed || ctx.force || report.full_damage || !report.damage.is_empty();
+        if self.shown.is_some() && redrawn {
+            let size = self.badge_size();
+            let buf =
+                &mut ctx
+                    .buf
+                    .subdimensions((pos.0 + report.width - size, pos.1, size, size))?;
+            draw_badge(buf, size)?;
+            report.damage.push(buf.get_signed_bounds());
+        }
+        Ok(report)
+    }
+
+    fn keyboard_input(
+        &mut self,
+        keysym: u32,
+        modifier_state: ModifiersState,
+        key_state: KeyState,
+        interpreted: Option<String>,
+    ) {
+        self.child
+            .keyboard_input(keysym, modifier_state, key_state, interpreted);
+    }
+
+    fn has_focus(&self) -> bool {
+        self.child.has_focus()
+    }
+
+    fn constraints(&self) -> Constraints {
+        self.child.constraints()
+    }
+
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        let size = self.badge_size();
+        let on_badge = pos.0 + size >= self.drawn.0 && pos.1 < size;
+        match &self.shown {
+            Some(error) if on_badge && button == BTN_LEFT => {
+                let _ = self.tx.send(Cmd::Osd {
+                    text: error.clone(),
+                    timeout: None,
+                });
+            }
+            _ => self.child.mouse_click(button, pos),
+        }
+    }
+
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        self.child.mouse_press(button, pos);
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, pos: (u32, u32)) {
+        self.child.mouse_scroll(scroll, pos);
+    }
+
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        self.child.mouse_motion(pos);
+    }
+
+    fn action(&mut self, action: &Action) {
+        self.child.action(action);
+    }
+
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        self.child.state(states);
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        let mut node = self.child.tree(pos);
+        if let Some(error) = &self.shown {
+            node = node.value("error", StateValue::Text(error.clone()));
+        }
+        node
+    }
+
+    fn error(&self) -> Option<String> {
+        self.child.error()
+    }
+
+    fn focused_only(&self, regions: &mut Vec<(i32, i32, i32, i32)>) {
+        self.child.focused_only(regions);
+    }
+
+    fn has_overlay(&self) -> bool {
+        self.child.has_overlay()
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        self.child.draw_overlay(ctx)
+    }
+}
+
+/// Stands in for a widget whose backend could not be set up at all, such as
+/// an audio widget without a sound server, so that it does not just vanish.
+pub struct Unavailable<'a> {
+    name: String,
+    error: String,
+    font: Font<'a>,
+    font_size: u32,
+    length: u32,
+}
+
+impl<'a> Unavailable<'a> {
+    pub fn new(
+        name: &str,
+        error: String,
+        font: FontRef<'a>,
+        font_size: f32,
+        length: u32,
+    ) -> Box<Unavailable<'a>> {
+        Box::new(Unavailable {
+            name: format!("{}: unavailable", name),
+            error,
+            font: Font::new(font, font_size),
+            font_size: font_size as u32,
+            length,
+        })
+    }
+}
+
+impl<'a> Widget for Unavailable<'a> {
+    fn wait(&mut self, _: &mut WaitContext) {}
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (self.length, self.font_size)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+        if !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        self.font
+            .auto_draw_text(buf, ctx.bg, &Color::new(0.5, 0.5, 0.5, 1.0), &self.name)?;
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+
+    fn error(&self) -> Option<String> {
+        Some(self.error.clone())
+    }
+}
@@ -1,12 +1,22 @@
+use crate::buffer::Buffer;
+use crate::cmd::Action;
 use crate::color::Color;
-use crate::draw::{draw_bar, draw_box, Font};
+use crate::draw::{
+    draw_arc, draw_bar, draw_box, draw_gradient_bar, draw_line, gradient_color, Font,
+};
 use crate::{
     fonts::FontRef,
-    widget::{DrawContext, DrawReport, KeyState, ModifiersState, WaitContext, Widget},
+    widget::{
+        DrawContext, DrawReport, KeyState, ModifiersState, Scroll, StateValue, TreeNode,
+        WaitContext, Widget, WidgetState, SCROLL_DETENT,
+    },
 };
 
+use std::f32::consts::TAU;
 use std::sync::{Arc, Mutex};
 
+use serde::{Deserialize, Serialize};
+
 pub trait BarWidgetImpl {
     fn wait(&mut self, ctx: &mut WaitContext);
     fn name(&self) -> &str;
@@ -15,6 +25,82 @@ pub trait BarWidgetImpl {
     fn inc(&mut self, inc: f32);
     fn set(&mut self, val: f32);
     fn toggle(&mut self);
+    fn marker(&self) -> Option<f32> {
+        None
+    }
+    /// Keysyms for increasing, decreasing and toggling the value. A keysym
+    /// of 0 is unbound.
+    fn keysyms(&self) -> Option<(u32, u32, u32)> {
+        None
+    }
+    /// An error to show instead of the value, such as a lost connection.
+    /// Input is ignored while there is an error.
+    fn error(&self) -> Option<String> {
+        None
+    }
+    /// Choices offered in a menu opened by clicking the name, such as the
+    /// devices to use, and the index of the current one.
+    fn choices(&mut self) -> Option<(Vec<String>, usize)> {
+        None
+    }
+    /// Picks the choice at the index of those last offered.
+    fn choose(&mut self, _idx: usize) {}
+    /// Reads the value again right away rather than waiting for a change.
+    fn refresh(&mut self) {}
+    fn state(&self) -> WidgetState {
+        WidgetState {
+            name: self.name().to_string(),
+            values: vec![("value", StateValue::Number(self.value() as f64))],
+        }
+    }
+}
+
+/// The change in value per wheel detent.
+pub const DEFAULT_SCROLL_STEP: f32 = 0.02;
+
+/// Colors of a bar by its value, replacing the colors of the widget.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BarColors {
+    /// Values and their colors, in increasing order of value. Values above
+    /// 1.0 color the part of the bar that wraps around, such as the volume
+    /// above 100%.
+    pub stops: Vec<(f32, Color)>,
+    /// Whether to blend between the stops along the bar, instead of coloring
+    /// the whole bar with the color of the first stop at or above the value.
+    #[serde(default)]
+    pub gradient: bool,
+}
+
+impl BarColors {
+    fn threshold_color(&self, value: f32) -> Color {
+        self.stops
+            .iter()
+            .find(|(stop, _)| value <= *stop)
+            .or_else(|| self.stops.last())
+            .map_or(Color::new(1.0, 1.0, 1.0, 1.0), |(_, color)| *color)
+    }
+
+    // The stops for the part of the bar that has wrapped around the given
+    // number of times.
+    fn wrapped(&self, wraps: f32) -> Vec<(f32, Color)> {
+        self.stops
+            .iter()
+            .map(|(stop, color)| (stop - wraps, *color))
+            .collect()
+    }
+}
+
+/// How the value of a bar widget is shown.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum BarStyle {
+    /// A bar after the name, as long as the widget.
+    #[default]
+    Bar,
+    /// A ring around the value in percent, with the name below it, for
+    /// square grid layouts.
+    Ring,
 }
 
 pub struct BarWidget<'a> {
@@ -22,10 +108,16 @@ pub struct BarWidget<'a> {
     font: Font<'a>,
     font_size: u32,
     length: u32,
+    scroll_step: f32,
+    colors: Option<BarColors>,
+    style: BarStyle,
+    // The choices and the selected one while the menu is open
+    menu: Option<(Vec<String>, usize)>,
     dirty: Arc<Mutex<bool>>,
 }
 
 impl<'a> BarWidget<'a> {
+    #[cfg_attr(not(feature = "alsa-widget"), allow(dead_code))]
     pub fn new_simple(
         font: FontRef,
         font_size: f32,
@@ -34,6 +126,7 @@ impl<'a> BarWidget<'a> {
     ) -> Box<BarWidget> {
         let mut font = Font::new(font, font_size);
         font.add_str_to_cache(w.name());
+        font.add_str_to_cache("0123456789%");
 
         Box::new(BarWidget {
             bar_impl: w,
@@ -41,6 +134,10 @@ impl<'a> BarWidget<'a> {
             font,
             font_size: font_size as u32,
             length,
+            scroll_step: DEFAULT_SCROLL_STEP,
+            colors: None,
+            style: BarStyle::Bar,
+            menu: None,
         })
     }
 
@@ -59,6 +156,7 @@ impl<'a> BarWidget<'a> {
 
         let mut font = Font::new(font, font_size);
         font.add_str_to_cache(im.name());
+        font.add_str_to_cache("0123456789%");
 
         Ok(Box::new(BarWidget {
             bar_impl: im,
@@ -66,18 +164,245 @@ impl<'a> BarWidget<'a> {
             font,
             font_size: font_size as u32,
             length,
+            scroll_step: DEFAULT_SCROLL_STEP,
+            colors: None,
+            style: BarStyle::Bar,
+            menu: None,
         }))
     }
+
+    /// Sets the change in value per wheel detent. A negative step inverts the
+    /// scroll direction.
+    pub fn set_scroll_step(&mut self, scroll_step: f32) {
+        self.scroll_step = scroll_step;
+    }
+
+    /// Sets the colors of the bar by value, or None for those of the widget.
+    pub fn set_colors(&mut self, colors: Option<BarColors>) {
+        self.colors = colors;
+    }
+
+    pub fn set_style(&mut self, style: BarStyle) {
+        self.style = style;
+    }
+
+    fn ring_diameter(&self) -> u32 {
+        self.font_size * 4
+    }
+
+    // The color of the bar after wrapping around the given number of times,
+    // which is that of the value at its end.
+    fn fill_color(&self, value: f32, wraps: f32) -> Color {
+        match &self.colors {
+            Some(colors) if colors.gradient => {
+                gradient_color(&colors.stops, value.min(wraps + 1.0))
+            }
+            Some(colors) => colors.threshold_color(value.min(wraps + 1.0)),
+            None if wraps == 0.0 => self.bar_impl.color(),
+            None => Color::new(0.75 / wraps, 0.25 / wraps, 0.25 / wraps, 1.0),
+        }
+    }
+
+    // The text of the open menu, if any.
+    fn menu_text(&self) -> Option<String> {
+        let (choices, selected) = self.menu.as_ref()?;
+        Some(format!(
+            "< {}/{} {} >",
+            selected + 1,
+            choices.len(),
+            choices[*selected]
+        ))
+    }
+
+    fn draw_as_bar(
+        &mut self,
+        buf: &mut Buffer,
+        bg: &Color,
+        width: u32,
+    ) -> Result<(), ::std::io::Error> {
+        let c = Color::new(1.0, 1.0, 1.0, 1.0);
+        self.font
+            .auto_draw_text(buf, bg, &c, self.bar_impl.name())?;
+
+        let bar_off = 5 * self.font_size;
+        if let Some(text) = self.menu_text() {
+            self.font
+                .auto_draw_text(&mut buf.offset((bar_off, 0))?, bg, &c, &text)?;
+        } else if let Some(error) = self.bar_impl.error() {
+            self.font.auto_draw_text(
+                &mut buf.offset((bar_off, 0))?,
+                bg,
+                &Color::new(0.75, 0.25, 0.25, 1.0),
+                &error,
+            )?;
+        } else {
+            let value = self.bar_impl.value();
+            let c = self.fill_color(value, 0.0);
+            let mut val = value;
+            self.draw_fill(
+                &mut buf.offset((bar_off, 0))?,
+                &c,
+                width - bar_off,
+                0.0,
+                val,
+            )?;
+            let mut iter = 1.0;
+            while val > 1.0 {
+                val -= 1.0;
+                self.draw_fill(
+                    &mut buf.offset((bar_off, 0))?,
+                    &self.fill_color(value, iter),
+                    width - bar_off,
+                    iter,
+                    val,
+                )?;
+                iter += 1.0;
+            }
+            draw_box(
+                &mut buf.offset((bar_off, 0))?,
+                &c,
+                (width - bar_off, self.font_size),
+            )?;
+            if let Some(marker) = self.bar_impl.marker() {
+                let x = bar_off as f32 + (width - bar_off) as f32 * marker.clamp(0.0, 1.0);
+                draw_line(
+                    buf,
+                    bg,
+                    &Color::new(0.5, 0.5, 0.5, 1.0),
+                    (x, 0.0),
+                    (x, self.font_size as f32),
+                    2.0,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_as_ring(&mut self, buf: &mut Buffer, bg: &Color) -> Result<(), ::std::io::Error> {
+        let diameter = self.ring_diameter();
+        let thickness = (diameter / 10).max(2);
+
+        // The name goes below the ring, or the menu or error in its place
+        let error = self.bar_impl.error();
+        let (label, label_color) = match (self.menu_text(), &error) {
+            (Some(text), _) => (text, Color::new(1.0, 1.0, 1.0, 1.0)),
+            (None, Some(error)) => (error.clone(), Color::new(0.75, 0.25, 0.25, 1.0)),
+            (None, None) => (
+                self.bar_impl.name().to_string(),
+                Color::new(1.0, 1.0, 1.0, 1.0),
+            ),
+        };
+        let label_width = self.font.auto_text_width(&label).min(diameter);
+        self.font.auto_draw_text(
+            &mut buf.subdimensions((
+                (diameter - label_width) / 2,
+                diameter,
+                label_width,
+                self.font_size,
+            ))?,
+            bg,
+            &label_color,
+            &label,
+        )?;
+
+        let ring = &mut buf.subdimensions((0, 0, diameter, diameter))?;
+        draw_arc(
+            ring,
+            bg,
+            &Color::new(0.25, 0.25, 0.25, 1.0),
+            diameter,
+            thickness,
+            0.0,
+            TAU,
+        )?;
+        if self.menu.is_some() || error.is_some() {
+            return Ok(());
+        }
+
+        let value = self.bar_impl.value();
+        let mut val = value;
+        let mut wraps = 0.0;
+        loop {
+            draw_arc(
+                ring,
+                bg,
+                &self.fill_color(value, wraps),
+                diameter,
+                thickness,
+                0.0,
+                val.min(1.0) * TAU,
+            )?;
+            if val <= 1.0 {
+                break;
+            }
+            val -= 1.0;
+            wraps += 1.0;
+        }
+        if let Some(marker) = self.bar_impl.marker() {
+            let angle = marker.clamp(0.0, 1.0) * TAU;
+            let (dx, dy) = (angle.sin(), -angle.cos());
+            let center = diameter as f32 / 2.0;
+            let inner = center - thickness as f32 * 1.5;
+            draw_line(
+                ring,
+                bg,
+                &Color::new(0.5, 0.5, 0.5, 1.0),
+                (center + dx * inner, center + dy * inner),
+                (center + dx * center, center + dy * center),
+                2.0,
+            )?;
+        }
+
+        let text = format!("{:.0}%", value * 100.0);
+        let text_width = self.font.auto_text_width(&text).min(diameter);
+        self.font.auto_draw_text(
+            &mut ring.subdimensions((
+                (diameter - text_width) / 2,
+                (diameter - self.font_size) / 2,
+                text_width,
+                self.font_size,
+            ))?,
+            bg,
+            &Color::new(1.0, 1.0, 1.0, 1.0),
+            &text,
+        )?;
+        Ok(())
+    }
+
+    fn draw_fill(
+        &self,
+        buf: &mut Buffer,
+        color: &Color,
+        length: u32,
+        wraps: f32,
+        fill: f32,
+    ) -> Result<(), ::std::io::Error> {
+        match &self.colors {
+            Some(colors) if colors.gradient => {
+                draw_gradient_bar(buf, &colors.wrapped(wraps), length, self.font_size, fill)
+            }
+            _ => draw_bar(buf, color, length, self.font_size, fill),
+        }
+    }
 }
 
 impl<'a> Widget for BarWidget<'a> {
     fn wait(&mut self, ctx: &mut WaitContext) {
         self.bar_impl.wait(ctx);
     }
+    fn action(&mut self, action: &Action) {
+        if let Action::Refresh = action {
+            self.bar_impl.refresh();
+            *self.dirty.lock().unwrap() = true;
+        }
+    }
     fn enter(&mut self) {}
     fn leave(&mut self) {}
     fn size(&self) -> (u32, u32) {
-        (self.length, self.font_size)
+        match self.style {
+            BarStyle::Bar => (self.length, self.font_size),
+            BarStyle::Ring => (self.ring_diameter(), self.ring_diameter() + self.font_size),
+        }
     }
     fn draw(
         &mut self,
@@ -88,7 +413,7 @@ impl<'a> Widget for BarWidget<'a> {
         if self.length == 0 {
             self.length = expansion.0;
         }
-        let (width, height) = (self.length, self.font_size);
+        let (width, height) = self.size();
         {
             let mut d = self.dirty.lock().unwrap();
             if !*d && !ctx.force {
@@ -99,38 +424,10 @@ impl<'a> Widget for BarWidget<'a> {
 
         let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
         buf.memset(ctx.bg);
-
-        let c = Color::new(1.0, 1.0, 1.0, 1.0);
-        self.font.draw_text(buf, ctx.bg, &c, self.bar_impl.name())?;
-
-        let c = self.bar_impl.color();
-        let bar_off = 5 * self.font_size;
-        let mut val = self.bar_impl.value();
-        draw_bar(
-            &mut buf.offset((bar_off, 0))?,
-            &c,
-            width - bar_off,
-            self.font_size,
-            val,
-        )?;
-        let mut iter = 1.0;
-        while val > 1.0 {
-            let c = &Color::new(0.75 / iter, 0.25 / iter, 0.25 / iter, 1.0);
-            val -= 1.0;
-            iter += 1.0;
-            draw_bar(
-                &mut buf.offset((bar_off, 0))?,
-                &c,
-                width - bar_off,
-                self.font_size,
-                val,
-            )?;
+        match self.style {
+            BarStyle::Bar => self.draw_as_bar(buf, ctx.bg, width)?,
+            BarStyle::Ring => self.draw_as_ring(buf, ctx.bg)?,
         }
-        draw_box(
-            &mut buf.offset((bar_off, 0))?,
-            &c,
-            (width - bar_off, self.font_size),
-        )?;
         Ok(DrawReport {
             width,
             height,
@@ -139,15 +436,65 @@ impl<'a> Widget for BarWidget<'a> {
         })
     }
 
-    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
-    fn mouse_click(&mut self, button: u32, (x, _): (u32, u32)) {
+    fn keyboard_input(&mut self, key: u32, _: ModifiersState, state: KeyState, _: Option<String>) {
+        let (up, down, toggle) = match self.bar_impl.keysyms() {
+            Some(keysyms) => keysyms,
+            None => return,
+        };
+        if state != KeyState::Pressed || key == 0 || self.bar_impl.error().is_some() {
+            return;
+        }
+        if key == up {
+            self.bar_impl.inc(0.05);
+        } else if key == down {
+            self.bar_impl.inc(-0.05);
+        } else if key == toggle {
+            self.bar_impl.toggle();
+        } else {
+            return;
+        }
+        *self.dirty.lock().unwrap() = true;
+    }
+    fn mouse_click(&mut self, button: u32, (x, y): (u32, u32)) {
+        if self.bar_impl.error().is_some() {
+            self.menu = None;
+            return;
+        }
         *self.dirty.lock().unwrap() = true;
+        // Whether the name was clicked, and the value at where it was
+        // clicked, if any
+        let (on_name, value) = match self.style {
+            BarStyle::Bar => {
+                let offset = 5 * self.font_size;
+                let value =
+                    (x > offset).then(|| ((x - offset) + 1) as f32 / (self.length - offset) as f32);
+                (x < offset, value)
+            }
+            BarStyle::Ring => {
+                let center = self.ring_diameter() as f32 / 2.0;
+                let (dx, dy) = (x as f32 + 0.5 - center, y as f32 + 0.5 - center);
+                let angle = dx.atan2(-dy).rem_euclid(TAU);
+                (y >= self.ring_diameter(), Some(angle / TAU))
+            }
+        };
+        if button == 272 && on_name {
+            // The name opens and closes the menu
+            self.menu = match self.menu {
+                Some(_) => None,
+                None => self.bar_impl.choices().filter(|(c, _)| !c.is_empty()),
+            };
+            return;
+        }
+        if let Some((_, selected)) = self.menu.take() {
+            if button == 272 {
+                self.bar_impl.choose(selected);
+            }
+            return;
+        }
         match button {
             272 => {
-                let offset = 5 * self.font_size;
-                if x > offset {
-                    self.bar_impl
-                        .set(((x - offset) + 1) as f32 / (self.length - offset) as f32);
+                if let Some(value) = value {
+                    self.bar_impl.set(value);
                 }
             }
             273 => self.bar_impl.toggle(),
@@ -156,8 +503,38 @@ impl<'a> Widget for BarWidget<'a> {
             }
         }
     }
-    fn mouse_scroll(&mut self, (_, y): (f64, f64), _: (u32, u32)) {
+    fn mouse_scroll(&mut self, scroll: Scroll, _: (u32, u32)) {
+        if self.bar_impl.error().is_some() {
+            return;
+        }
         *self.dirty.lock().unwrap() = true;
-        self.bar_impl.inc(y as f32 / -800.0);
+        if let Some((choices, selected)) = &mut self.menu {
+            // Scrolling down moves to the next choice
+            let steps = (scroll.delta.1 / SCROLL_DETENT) as i64;
+            let len = choices.len() as i64;
+            *selected = (*selected as i64 + steps).rem_euclid(len) as usize;
+            return;
+        }
+        // Scrolling down decreases the value
+        self.bar_impl
+            .inc((scroll.delta.1 / SCROLL_DETENT) as f32 * -self.scroll_step);
+    }
+    fn state(&self, states: &mut Vec<WidgetState>) {
+        states.push(self.bar_impl.state());
+    }
+    fn error(&self) -> Option<String> {
+        self.bar_impl.error()
+    }
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        let mut node = TreeNode::new(self, pos)
+            .value("name", StateValue::Text(self.bar_impl.name().to_string()))
+            .value("font_size", StateValue::Number(self.font_size as f64))
+            .value(
+                "color",
+                StateValue::Text(self.bar_impl.color().as_hex_rgb()),
+            )
+            .value("dirty", StateValue::Bool(*self.dirty.lock().unwrap()));
+        node.values.extend(self.bar_impl.state().values);
+        node
     }
 }
@@ -1,13 +1,17 @@
 use crate::cmd::Cmd;
 use crate::color::Color;
-use crate::widget::WaitContext;
+use crate::widget::{StateValue, WaitContext, WidgetState};
 use crate::{
     fonts::FontRef,
     widgets::bar_widget::{BarWidget, BarWidgetImpl},
 };
 
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use std::thread;
 
 use nix::poll::{PollFd, PollFlags};
 
@@ -38,6 +42,95 @@ fn get_upower_property(
     })
 }
 
+fn read_file_as_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse::<u64>().ok()
+}
+
+fn find_sysfs_battery() -> Option<PathBuf> {
+    fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            fs::read_to_string(path.join("type"))
+                .map(|t| t.trim() == "Battery")
+                .unwrap_or(false)
+        })
+}
+
+// The charge cycles of the battery as UPower reports them for the device of
+// the sysfs battery, which is unknown where it reports -1.
+fn upower_charge_cycles(con: &dbus::Connection, battery: &Path) -> Option<u64> {
+    let name = battery.file_name()?.to_str()?;
+    let device_path = format!("/org/freedesktop/UPower/devices/battery_{}", name);
+    let cycles = get_upower_property(con, &device_path, "ChargeCycles")
+        .ok()?
+        .get1::<dbus::arg::Variant<i32>>()?
+        .0;
+    u64::try_from(cycles).ok()
+}
+
+// Health information is not exposed for the UPower DisplayDevice, so it is
+// read directly from the first battery in sysfs, and from the UPower device
+// of that battery.
+struct BatteryHealth {
+    path: Option<PathBuf>,
+    health: Option<f64>,
+    end_threshold: Option<u64>,
+    cycles: Option<u64>,
+}
+
+impl BatteryHealth {
+    fn new(con: &dbus::Connection) -> BatteryHealth {
+        let mut health = BatteryHealth {
+            path: find_sysfs_battery(),
+            health: None,
+            end_threshold: None,
+            cycles: None,
+        };
+        health.update(con);
+        health
+    }
+
+    fn update(&mut self, con: &dbus::Connection) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let full = read_file_as_u64(&path.join("energy_full"))
+            .or_else(|| read_file_as_u64(&path.join("charge_full")));
+        let design = read_file_as_u64(&path.join("energy_full_design"))
+            .or_else(|| read_file_as_u64(&path.join("charge_full_design")));
+        self.health = match (full, design) {
+            (Some(full), Some(design)) if design > 0 => Some(100.0 * full as f64 / design as f64),
+            _ => None,
+        };
+        self.end_threshold = read_file_as_u64(&path.join("charge_control_end_threshold"));
+        self.cycles =
+            upower_charge_cycles(con, path).or_else(|| read_file_as_u64(&path.join("cycle_count")));
+    }
+
+    // A summary for the on-screen display, such as "health 94%, 312 cycles".
+    fn summary(&self) -> Option<String> {
+        let health = format!("health {:.0}%", self.health?);
+        Some(match self.cycles {
+            Some(cycles) => format!("{}, {} cycles", health, cycles),
+            None => health,
+        })
+    }
+}
+
+// The threshold command runs in the background, as it may wait for a polkit
+// prompt to be answered.
+#[derive(PartialEq)]
+enum ThresholdJob {
+    Idle,
+    Running,
+    // The command succeeded, and the threshold is to be read again
+    Done,
+}
+
 // The widget is created on a different thread than where it will be used, so
 // we need it to be Send. However, dbus::Connection has a void*, so it's not
 // auto-derived. So, we make a small wrapper where we make it Send.
@@ -59,6 +152,10 @@ pub struct UpowerBattery {
     capacity: f64,
     con: DbusConnection,
     watch: dbus::Watch,
+    health: BatteryHealth,
+    show_health: bool,
+    threshold_command: String,
+    threshold_job: Arc<Mutex<ThresholdJob>>,
 }
 
 enum UpowerBatteryState {
@@ -75,6 +172,7 @@ impl UpowerBattery {
         dirty: Arc<Mutex<bool>>,
         sender: Sender<Cmd>,
         device: &str,
+        threshold_command: String,
     ) -> Result<Self, ::std::io::Error> {
         let con = dbus::Connection::get_private(dbus::BusType::System).map_err(|_| {
             ::std::io::Error::new(::std::io::ErrorKind::Other, "unable to open dbus")
@@ -108,7 +206,7 @@ impl UpowerBattery {
         // https://upower.freedesktop.org/docs/Device.html#Device:Type
         if upower_type.0 != 2 {
             return Err(::std::io::Error::new(
-                ::std::io::ErrorKind::Other,
+                ::std::io::ErrorKind::NotFound,
                 "UPower device is not a battery.",
             ));
         }
@@ -152,6 +250,7 @@ impl UpowerBattery {
             ));
         }
 
+        let health = BatteryHealth::new(&con);
         Ok(UpowerBattery {
             device_path,
             con: DbusConnection(con),
@@ -160,6 +259,10 @@ impl UpowerBattery {
             capacity,
             state,
             watch: fds[0],
+            health,
+            show_health: false,
+            threshold_command,
+            threshold_job: Arc::new(Mutex::new(ThresholdJob::Idle)),
         })
     }
 
@@ -168,42 +271,62 @@ impl UpowerBattery {
         font_size: f32,
         length: u32,
         sender: Sender<Cmd>,
+        threshold_command: String,
     ) -> Result<Box<BarWidget>, ::std::io::Error> {
         BarWidget::new(font, font_size, length, move |dirty| {
-            let d = UpowerBattery::from_device(dirty, sender, "DisplayDevice")?;
+            let d = UpowerBattery::from_device(dirty, sender, "DisplayDevice", threshold_command)?;
             Ok(Box::new(d))
         })
     }
 }
 
+impl UpowerBattery {
+    // Reads the percentage and state from UPower, and the health from sysfs.
+    fn update(&mut self) {
+        let capacity = get_upower_property(self.con.as_ref(), &self.device_path, "Percentage")
+            .unwrap()
+            .get1::<dbus::arg::Variant<f64>>()
+            .unwrap()
+            .0;
+        let state = match get_upower_property(self.con.as_ref(), &self.device_path, "State")
+            .unwrap()
+            .get1::<dbus::arg::Variant<u32>>()
+            .unwrap()
+            .0
+        {
+            1 => UpowerBatteryState::Charging,
+            2 => UpowerBatteryState::Discharging,
+            3 => UpowerBatteryState::Empty,
+            4 => UpowerBatteryState::Full,
+            5 => UpowerBatteryState::NotCharging,
+            6 => UpowerBatteryState::Discharging,
+            _ => UpowerBatteryState::Unknown,
+        };
+        self.state = state;
+        self.capacity = capacity;
+        self.health.update(self.con.as_ref());
+    }
+}
+
 impl BarWidgetImpl for UpowerBattery {
     fn wait(&mut self, ctx: &mut WaitContext) {
-        for _ in self
+        let changed = self
             .con
             .as_ref()
             .watch_handle(self.watch.fd(), dbus::WatchEvent::Readable as u32)
-        {
-            let capacity = get_upower_property(self.con.as_ref(), &self.device_path, "Percentage")
-                .unwrap()
-                .get1::<dbus::arg::Variant<f64>>()
-                .unwrap()
-                .0;
-            let state = match get_upower_property(self.con.as_ref(), &self.device_path, "State")
-                .unwrap()
-                .get1::<dbus::arg::Variant<u32>>()
-                .unwrap()
-                .0
-            {
-                1 => UpowerBatteryState::Charging,
-                2 => UpowerBatteryState::Discharging,
-                3 => UpowerBatteryState::Empty,
-                4 => UpowerBatteryState::Full,
-                5 => UpowerBatteryState::NotCharging,
-                6 => UpowerBatteryState::Discharging,
-                _ => UpowerBatteryState::Unknown,
-            };
-            self.state = state;
-            self.capacity = capacity;
+            .count()
+            > 0;
+        let mut job = self.threshold_job.lock().unwrap();
+        let threshold_set = *job == ThresholdJob::Done;
+        if threshold_set {
+            *job = ThresholdJob::Idle;
+            self.health.update(self.con.as_ref());
+        }
+        drop(job);
+        if changed {
+            self.update();
+        }
+        if changed || threshold_set {
             *self.dirty.lock().unwrap() = true;
             self.sender.send(Cmd::Draw).unwrap();
         }
@@ -211,13 +334,27 @@ impl BarWidgetImpl for UpowerBattery {
         ctx.fds
             .push(PollFd::new(self.watch.fd(), PollFlags::POLLIN));
     }
+    fn refresh(&mut self) {
+        self.update();
+    }
     fn name(&self) -> &str {
-        "battery"
+        if self.show_health {
+            "health"
+        } else {
+            "battery"
+        }
     }
     fn value(&self) -> f32 {
-        (self.capacity as f32) / 100.0
+        if self.show_health {
+            (self.health.health.unwrap_or(0.0) as f32) / 100.0
+        } else {
+            (self.capacity as f32) / 100.0
+        }
     }
     fn color(&self) -> Color {
+        if self.show_health {
+            return Color::new(1.0, 1.0, 1.0, 1.0);
+        }
         match self.state {
             UpowerBatteryState::Discharging | UpowerBatteryState::Unknown => {
                 if self.capacity > 10.0 {
@@ -235,6 +372,94 @@ impl BarWidgetImpl for UpowerBattery {
         }
     }
     fn inc(&mut self, _: f32) {}
-    fn set(&mut self, _: f32) {}
-    fn toggle(&mut self) {}
+    fn set(&mut self, val: f32) {
+        if self.show_health || self.threshold_command.is_empty() {
+            return;
+        }
+        let threshold = ((val * 100.0).round() as u64).clamp(1, 100);
+        let mut lexed = match shlex::split(&self.threshold_command) {
+            Some(v) if !v.is_empty() => v,
+            _ => return,
+        };
+        lexed.push(threshold.to_string());
+
+        let mut job = self.threshold_job.lock().unwrap();
+        if *job == ThresholdJob::Running {
+            return;
+        }
+        *job = ThresholdJob::Running;
+        drop(job);
+        let job = self.threshold_job.clone();
+        let sender = self.sender.clone();
+        let _ = thread::Builder::new()
+            .name("battery_threshold".to_string())
+            .spawn(move || {
+                let done = match Command::new(&lexed[0]).args(&lexed[1..]).status() {
+                    Ok(status) if status.success() => true,
+                    Ok(status) => {
+                        eprintln!("Battery threshold command failed: {}", status);
+                        false
+                    }
+                    Err(err) => {
+                        eprintln!("Error while trying to set battery threshold: {}", err);
+                        false
+                    }
+                };
+                *job.lock().unwrap() = if done {
+                    ThresholdJob::Done
+                } else {
+                    ThresholdJob::Idle
+                };
+                // Wakes up the event loop, so that the threshold is read again
+                let _ = sender.send(Cmd::Draw);
+            });
+    }
+    fn toggle(&mut self) {
+        if self.health.health.is_none() {
+            return;
+        }
+        self.show_health = !self.show_health;
+        if let Some(text) = self.health.summary().filter(|_| self.show_health) {
+            let _ = self.sender.send(Cmd::Osd {
+                text,
+                timeout: None,
+            });
+        }
+    }
+    fn marker(&self) -> Option<f32> {
+        if self.show_health {
+            return None;
+        }
+        self.health.end_threshold.map(|t| t as f32 / 100.0)
+    }
+    fn state(&self) -> WidgetState {
+        let state = match self.state {
+            UpowerBatteryState::Charging => "charging",
+            UpowerBatteryState::Discharging => "discharging",
+            UpowerBatteryState::Empty => "empty",
+            UpowerBatteryState::Full => "full",
+            UpowerBatteryState::NotCharging => "not charging",
+            UpowerBatteryState::Unknown => "unknown",
+        };
+        let mut values = vec![
+            ("value", StateValue::Number(self.capacity / 100.0)),
+            ("state", StateValue::Text(state.to_string())),
+        ];
+        if let Some(health) = self.health.health {
+            values.push(("health", StateValue::Number(health / 100.0)));
+        }
+        if let Some(cycles) = self.health.cycles {
+            values.push(("cycles", StateValue::Number(cycles as f64)));
+        }
+        if let Some(threshold) = self.health.end_threshold {
+            values.push((
+                "end_threshold",
+                StateValue::Number(threshold as f64 / 100.0),
+            ));
+        }
+        WidgetState {
+            name: "battery".to_string(),
+            values,
+        }
+    }
 }
@@ -1,18 +1,158 @@
 use crate::buffer::Buffer;
+use crate::cmd::Action;
 use crate::color::Color;
 use crate::draw::Font;
 use crate::{
     fonts::FontRef,
-    widget::{DrawContext, DrawReport, KeyState, ModifiersState, WaitContext, Widget},
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
 };
 
 use chrono::{Datelike, NaiveDate, NaiveDateTime};
 
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Holiday {
+    Date(NaiveDate),
+    Yearly(u32, u32), // month, day
+}
+
+impl Holiday {
+    // Accepts YYYY-MM-DD for a single date, or MM-DD for a date that repeats
+    // every year.
+    fn parse(s: &str) -> Option<Holiday> {
+        let s = s.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Some(Holiday::Date(date));
+        }
+        let (month, day) = s.split_once('-')?;
+        let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+        NaiveDate::from_ymd_opt(2000, month, day)?;
+        Some(Holiday::Yearly(month, day))
+    }
+
+    fn matches(&self, date: NaiveDate) -> bool {
+        match *self {
+            Holiday::Date(d) => d == date,
+            Holiday::Yearly(month, day) => date.month() == month && date.day() == day,
+        }
+    }
+}
+
+// Reads the all-day events of an iCalendar file, which start at a date
+// without a time. Events with a yearly recurrence rule are treated as yearly
+// holidays.
+fn parse_ics(content: &str) -> Vec<Holiday> {
+    let mut holidays = Vec::new();
+    let mut start = None;
+    let mut yearly = false;
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line == "BEGIN:VEVENT" {
+            start = None;
+            yearly = false;
+        } else if line.starts_with("DTSTART") {
+            start = line
+                .rsplit(':')
+                .next()
+                .filter(|v| v.len() == 8)
+                .and_then(|v| NaiveDate::parse_from_str(v, "%Y%m%d").ok());
+        } else if line.starts_with("RRULE:") && line.contains("FREQ=YEARLY") {
+            yearly = true;
+        } else if line == "END:VEVENT" {
+            match start {
+                Some(d) if yearly => holidays.push(Holiday::Yearly(d.month(), d.day())),
+                Some(d) => holidays.push(Holiday::Date(d)),
+                None => (),
+            }
+        }
+    }
+    holidays
+}
+
+fn parse_holidays(path: &Path, content: &str) -> Vec<Holiday> {
+    if path.extension().is_some_and(|ext| ext == "ics") {
+        return parse_ics(content);
+    }
+    #[cfg(feature = "yaml-cfg")]
+    let dates = serde_yaml::from_str::<Vec<String>>(content).map_err(|err| err.to_string());
+    #[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+    let dates = serde_json::from_str::<Vec<String>>(content).map_err(|err| err.to_string());
+    match dates {
+        Ok(dates) => dates.iter().filter_map(|d| Holiday::parse(d)).collect(),
+        Err(err) => {
+            eprintln!("Unable to parse holiday file: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+/// The holidays of the config, along with those of a holiday file, which is
+/// read again when it changes.
+pub struct Holidays {
+    dates: Vec<Holiday>,
+    file: Option<PathBuf>,
+    file_modified: Option<SystemTime>,
+    file_dates: Vec<Holiday>,
+}
+
+impl Holidays {
+    pub fn new(dates: Vec<String>, file: Option<PathBuf>) -> Holidays {
+        let dates = dates
+            .iter()
+            .filter_map(|d| {
+                let holiday = Holiday::parse(d);
+                if holiday.is_none() {
+                    eprintln!("Invalid holiday date: {}", d);
+                }
+                holiday
+            })
+            .collect();
+        let mut holidays = Holidays {
+            dates,
+            file,
+            file_modified: None,
+            file_dates: Vec::new(),
+        };
+        holidays.reload();
+        holidays
+    }
+
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.dates
+            .iter()
+            .chain(self.file_dates.iter())
+            .any(|h| h.matches(date))
+    }
+
+    /// Returns true if the holiday file changed since it was last read.
+    pub fn reload(&mut self) -> bool {
+        let path = match &self.file {
+            Some(path) => path,
+            None => return false,
+        };
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified == self.file_modified {
+            return false;
+        }
+        self.file_modified = modified;
+        self.file_dates = match fs::read_to_string(path) {
+            Ok(content) => parse_holidays(path, &content),
+            Err(_) => Vec::new(),
+        };
+        true
+    }
+}
+
 pub struct Calendar<'a> {
     cur_date: NaiveDate,
     dirty: bool,
     offset: f64,
     sections: u32,
+    holidays: Holidays,
+    week_parity: bool,
     font_size: u32,
     calendar_cache: Font<'a>,
     month_cache: Font<'a>,
@@ -112,10 +252,15 @@ impl<'a> Calendar<'a> {
             // Draw the week number
             //
             let wk = time.iso_week();
+            let c = if self.week_parity && wk.week() & 1 == 0 {
+                Color::new(0.4, 0.6, 0.9, 1.0)
+            } else {
+                Color::new(0.75, 0.75, 0.75, 1.0)
+            };
             self.calendar_cache.draw_text(
                 &mut buf.offset((0, y_off * self.font_size * 2 + self.font_size * 4))?,
                 background_color,
-                &Color::new(0.75, 0.75, 0.75, 1.0),
+                &c,
                 &format!("{:02}", wk.week()),
             )?;
             x_pos += 1;
@@ -126,6 +271,8 @@ impl<'a> Calendar<'a> {
             while x_pos < 8 {
                 let c = if time.day() == orig.day() && time.month() == orig.month() {
                     Color::new(1.0, 1.0, 1.0, 1.0)
+                } else if self.holidays.is_holiday(time) {
+                    Color::new(0.8, 0.35, 0.35, 1.0)
                 } else {
                     Color::new(0.5, 0.5, 0.5, 1.0)
                 };
@@ -162,6 +309,9 @@ impl<'a> Calendar<'a> {
         font_secondary: FontRef<'a>,
         font_size: f32,
         sections: u32,
+        holidays: Vec<String>,
+        holiday_file: Option<PathBuf>,
+        week_parity: bool,
     ) -> Box<Calendar<'a>> {
         let mut calendar_cache = Font::new(font_secondary, font_size * 2.0);
         calendar_cache.add_str_to_cache("0123456789");
@@ -176,6 +326,8 @@ impl<'a> Calendar<'a> {
             dirty: true,
             offset: 0.0,
             sections,
+            holidays: Holidays::new(holidays, holiday_file),
+            week_parity,
             font_size: font_size as u32,
             calendar_cache,
             month_cache,
@@ -206,6 +358,9 @@ impl<'a> Widget for Calendar<'a> {
         _expansion: (u32, u32),
     ) -> Result<DrawReport, ::std::io::Error> {
         let (width, height) = self.size();
+        if self.holidays.reload() {
+            self.dirty = true;
+        }
         if ctx.time.date() == self.cur_date && !ctx.force && !self.dirty {
             return Ok(DrawReport::empty(width, height));
         }
@@ -273,8 +428,48 @@ impl<'a> Widget for Calendar<'a> {
         }
         self.dirty = true;
     }
-    fn mouse_scroll(&mut self, (_, y): (f64, f64), _: (u32, u32)) {
-        self.offset += y;
+    fn mouse_scroll(&mut self, scroll: Scroll, _: (u32, u32)) {
+        self.offset += scroll.delta.1;
         self.dirty = true;
     }
+    fn action(&mut self, action: &Action) {
+        match action {
+            Action::CalendarToday => self.offset = 0.0,
+            Action::CalendarScroll(y) => self.offset += y,
+            _ => return,
+        }
+        self.dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ics_all_day_events() {
+        let content = "BEGIN:VCALENDAR\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20241225\r
+RRULE:FREQ=YEARLY\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20240501\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;TZID=Europe/Oslo:20240510T090000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20240511T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+        assert_eq!(
+            parse_ics(content),
+            vec![
+                Holiday::Yearly(12, 25),
+                Holiday::Date(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()),
+            ]
+        );
+    }
 }
@@ -2,7 +2,10 @@ use crate::color::Color;
 use crate::draw::Font;
 use crate::{
     fonts::FontRef,
-    widget::{DrawContext, DrawReport, KeyState, ModifiersState, WaitContext, Widget},
+    widget::{
+        DrawContext, DrawReport, KeyState, ModifiersState, Scroll, StateValue, TreeNode,
+        WaitContext, Widget,
+    },
 };
 
 use chrono::{Duration, NaiveDateTime, Timelike};
@@ -13,6 +16,8 @@ pub struct Clock<'a> {
     size: f32,
     digit: u32,
     colon: u32,
+    // The time as last drawn
+    shown: String,
 }
 
 impl<'a> Clock<'a> {
@@ -29,6 +34,7 @@ impl<'a> Clock<'a> {
             size,
             digit,
             colon,
+            shown: String::new(),
         }))
     }
 }
@@ -65,28 +71,52 @@ impl<'a> Widget for Clock<'a> {
 
         self.cur_time = ctx.time;
 
+        let text = format!("{:02}:{:02}", ctx.time.hour(), ctx.time.minute());
+        let distances = [self.digit, self.digit, self.colon, self.digit, self.digit];
         let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
-        buf.memset(ctx.bg);
 
-        let digit = self.digit;
-        let colon = self.colon;
-        self.clock_cache.draw_text_fixed_width(
-            buf,
-            ctx.bg,
-            &Color::new(1.0, 1.0, 1.0, 1.0),
-            &[digit, digit, colon, digit, digit],
-            &format!("{:02}:{:02}", ctx.time.hour(), ctx.time.minute()),
-        )?;
+        // Only the characters that changed are drawn again, each damaging
+        // just its own cell, as most minutes change a single digit.
+        let shown: Vec<char> = self.shown.chars().collect();
+        let mut damage = Vec::new();
+        let mut x = 0;
+        for (idx, ch) in text.chars().enumerate() {
+            let w = distances[idx];
+            if ctx.force || shown.get(idx) != Some(&ch) {
+                buf.subdimensions((x, 0, w, height))?.memset(ctx.bg);
+                // The whole text is drawn clipped to the cell, to keep the
+                // baseline of the full text.
+                self.clock_cache.draw_text_fixed_width(
+                    &mut buf.clip((x, 0, w, height)),
+                    ctx.bg,
+                    &Color::new(1.0, 1.0, 1.0, 1.0),
+                    &distances,
+                    &text,
+                )?;
+                damage.push(((pos.0 + x) as i32, pos.1 as i32, w as i32, height as i32));
+            }
+            x += w;
+        }
+        self.shown = text;
 
         Ok(DrawReport {
             width,
             height,
-            damage: vec![(pos.0 as i32, pos.1 as i32, width as i32, height as i32)],
+            damage,
             full_damage: false,
         })
     }
 
     fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
     fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
-    fn mouse_scroll(&mut self, _: (f64, f64), _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos)
+            .value("font_size", StateValue::Number(self.size as f64))
+            .value(
+                "shown",
+                StateValue::Text(self.cur_time.format("%H:%M").to_string()),
+            )
+    }
 }
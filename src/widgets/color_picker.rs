@@ -0,0 +1,247 @@
+use crate::cmd::Cmd;
+use crate::color::Color;
+use crate::draw::{draw_box, draw_spinner, next_spinner_frame, Font};
+use crate::motion;
+use crate::widgets::launcher::wlcopy;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::Local;
+
+const HUES: u32 = 12;
+const LIGHTNESS: [f32; 5] = [0.8, 0.65, 0.5, 0.35, 0.2];
+// The rows of hues, followed by a row of grays.
+const ROWS: u32 = LIGHTNESS.len() as u32 + 1;
+
+fn grid_color(col: u32, row: u32) -> Color {
+    match LIGHTNESS.get(row as usize) {
+        Some(&lightness) => Color::from_hsl(col as f32 * 360.0 / HUES as f32, 0.8, lightness),
+        None => {
+            let v = col as f32 / (HUES - 1) as f32;
+            Color::new(v, v, v, 1.0)
+        }
+    }
+}
+
+// Picks a point with slurp, and captures that single pixel with grim, which
+// reads the screen through the wlr-screencopy protocol.
+fn pick_from_screen() -> Result<Color, String> {
+    let point = Command::new("slurp")
+        .arg("-p")
+        .output()
+        .map_err(|_| "slurp not available".to_string())?;
+    if !point.status.success() {
+        return Err("no point selected".to_string());
+    }
+    let geometry = String::from_utf8_lossy(&point.stdout).trim().to_string();
+
+    let capture = Command::new("grim")
+        .args(["-g", &geometry, "-t", "ppm", "-"])
+        .output()
+        .map_err(|_| "grim not available".to_string())?;
+    parse_ppm_pixel(&capture.stdout).ok_or_else(|| "unable to read captured pixel".to_string())
+}
+
+// Reads the first pixel of a binary PPM image, which has a header of four
+// whitespace separated fields: "P6", width, height and maximum value.
+fn parse_ppm_pixel(data: &[u8]) -> Option<Color> {
+    let mut fields = 0;
+    let mut in_field = false;
+    for (idx, &b) in data.iter().enumerate() {
+        if b.is_ascii_whitespace() {
+            if in_field {
+                fields += 1;
+                in_field = false;
+            }
+            if fields == 4 {
+                let pixel = data.get(idx + 1..idx + 4)?;
+                return Some(Color::from_rgba8(pixel[0], pixel[1], pixel[2], 255));
+            }
+        } else {
+            in_field = true;
+        }
+    }
+    None
+}
+
+struct Picked {
+    color: Option<Color>,
+    picking: bool,
+    dirty: bool,
+}
+
+pub struct ColorPicker<'a> {
+    picked: Arc<Mutex<Picked>>,
+    sender: Sender<Cmd>,
+    font: Font<'a>,
+    font_size: u32,
+    button_x: u32,
+}
+
+impl<'a> ColorPicker<'a> {
+    pub fn new(font: FontRef, font_size: f32, sender: Sender<Cmd>) -> Box<ColorPicker> {
+        let mut font = Font::new(font, font_size);
+        font.add_str_to_cache("#0123456789abcdefpick.");
+        Box::new(ColorPicker {
+            picked: Arc::new(Mutex::new(Picked {
+                color: None,
+                picking: false,
+                dirty: true,
+            })),
+            sender,
+            font,
+            font_size: font_size as u32,
+            button_x: u32::MAX,
+        })
+    }
+
+    fn select(picked: &Mutex<Picked>, color: Color) {
+        if let Err(err) = wlcopy(&color.as_hex_rgb()) {
+            eprintln!("Unable to copy color: {}", err);
+        }
+        let mut picked = picked.lock().unwrap();
+        picked.color = Some(color);
+        picked.dirty = true;
+    }
+
+    fn eyedropper(&self) {
+        let mut picked = self.picked.lock().unwrap();
+        if picked.picking {
+            return;
+        }
+        picked.picking = true;
+        picked.dirty = true;
+        drop(picked);
+
+        let picked = self.picked.clone();
+        let sender = self.sender.clone();
+        let _ = thread::Builder::new()
+            .name("eyedropper".to_string())
+            .spawn(move || {
+                match pick_from_screen() {
+                    Ok(color) => ColorPicker::select(&picked, color),
+                    Err(err) => eprintln!("Unable to pick color: {}", err),
+                }
+                let mut p = picked.lock().unwrap();
+                p.picking = false;
+                p.dirty = true;
+                drop(p);
+                let _ = sender.send(Cmd::Draw);
+            });
+    }
+}
+
+impl<'a> Widget for ColorPicker<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        if self.picked.lock().unwrap().picking && !motion::is_reduced() {
+            ctx.set_time(next_spinner_frame(Local::now().naive_local()));
+        }
+    }
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (HUES * self.font_size, (ROWS + 1) * self.font_size)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        _expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let (width, height) = self.size();
+        let mut picked = self.picked.lock().unwrap();
+        if !picked.dirty && !picked.picking && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+        picked.dirty = false;
+        let (color, picking) = (picked.color, picked.picking);
+        drop(picked);
+
+        let cell = self.font_size;
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        for row in 0..ROWS {
+            for col in 0..HUES {
+                buf.subdimensions((col * cell, row * cell, cell, cell))?
+                    .memset(&grid_color(col, row));
+            }
+        }
+
+        let line = &mut buf.subdimensions((0, ROWS * cell, width, cell))?;
+        let mut x_off = 0;
+        if let Some(color) = color {
+            line.subdimensions((0, 0, cell, cell))?.memset(&color);
+            x_off += cell + cell / 4;
+            x_off += self
+                .font
+                .auto_draw_text(
+                    &mut line.offset((x_off, 0))?,
+                    ctx.bg,
+                    &Color::new(1.0, 1.0, 1.0, 1.0),
+                    &color.as_hex_rgb(),
+                )?
+                .0;
+        }
+
+        let c = if picking {
+            Color::new(0.5, 0.5, 0.5, 1.0)
+        } else {
+            Color::new(0.75, 0.75, 0.75, 1.0)
+        };
+        let label = "pick";
+        let label_width = self.font.auto_widest(label)? * label.len() as u32;
+        let button_width = label_width + cell / 2;
+        self.button_x = width.saturating_sub(button_width).max(x_off);
+        if self.button_x + button_width <= width {
+            let button = &mut line.offset((self.button_x, 0))?;
+            if picking {
+                let size = cell * 3 / 4;
+                draw_spinner(
+                    &mut button
+                        .offset((button_width.saturating_sub(size) / 2, (cell - size) / 2))?,
+                    ctx.bg,
+                    &c,
+                    size,
+                    ctx.time,
+                )?;
+            } else {
+                self.font
+                    .auto_draw_text(&mut button.offset((cell / 4, 0))?, ctx.bg, &c, label)?;
+            }
+            draw_box(button, &c, (button_width, cell))?;
+        }
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+
+    fn mouse_click(&mut self, button: u32, (x, y): (u32, u32)) {
+        if button != 272 {
+            return;
+        }
+        let cell = self.font_size;
+        if y < ROWS * cell {
+            if x < HUES * cell {
+                ColorPicker::select(&self.picked, grid_color(x / cell, y / cell));
+            }
+        } else if x >= self.button_x {
+            self.eyedropper();
+        }
+    }
+
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+}
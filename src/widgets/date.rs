@@ -1,10 +1,16 @@
+use crate::cmd::{Action, Cmd};
 use crate::color::Color;
 use crate::draw::Font;
 use crate::{
     fonts::FontRef,
-    widget::{DrawContext, DrawReport, KeyState, ModifiersState, WaitContext, Widget},
+    widget::{
+        DrawContext, DrawReport, KeyState, ModifiersState, Scroll, StateValue, TreeNode,
+        WaitContext, Widget,
+    },
 };
 
+use std::sync::mpsc::Sender;
+
 use chrono::{Datelike, NaiveDateTime};
 
 pub struct Date<'a> {
@@ -14,10 +20,16 @@ pub struct Date<'a> {
     ch_width: u32,
     digit_width: u32,
     spacing_width: u32,
+    sender: Sender<Cmd>,
 }
 
 impl<'a> Date<'a> {
-    pub fn new(time: NaiveDateTime, font: FontRef, size: f32) -> ::std::io::Result<Box<Date>> {
+    pub fn new(
+        time: NaiveDateTime,
+        font: FontRef,
+        size: f32,
+        sender: Sender<Cmd>,
+    ) -> ::std::io::Result<Box<Date>> {
         let mut date_cache = Font::new(font, size);
         let chs = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
         let digits = "0123456789,/ ";
@@ -37,6 +49,7 @@ impl<'a> Date<'a> {
             ch_width,
             digit_width,
             spacing_width,
+            sender,
         }))
     }
 }
@@ -90,6 +103,21 @@ impl<'a> Widget for Date<'a> {
     }
 
     fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
-    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
-    fn mouse_scroll(&mut self, _: (f64, f64), _: (u32, u32)) {}
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {
+        let _ = self.sender.send(Cmd::Action(Action::CalendarToday));
+    }
+    fn mouse_scroll(&mut self, scroll: Scroll, _: (u32, u32)) {
+        let _ = self
+            .sender
+            .send(Cmd::Action(Action::CalendarScroll(scroll.delta.1)));
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos)
+            .value("font_size", StateValue::Number(self.size as f64))
+            .value(
+                "shown",
+                StateValue::Text(self.cur_time.format("%Y-%m-%d").to_string()),
+            )
+    }
 }
@@ -0,0 +1,649 @@
+use crate::cmd::{Action, Cmd};
+use crate::color::Color;
+use crate::data::state_file;
+use crate::draw::Font;
+use crate::offline;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "yaml-cfg")]
+const CACHE_FILE: &str = "feeds.yaml";
+#[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+const CACHE_FILE: &str = "feeds.json";
+// Only this many headlines are kept for every feed.
+const MAX_HEADLINES: usize = 50;
+// Seconds before giving up on fetching a feed.
+const FETCH_TIMEOUT: &str = "30";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Headline {
+    pub title: String,
+    pub link: String,
+    // Unix timestamp of when the entry was published, if the feed says
+    pub published: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct CachedFeed {
+    // Local time of the last successful fetch, as a timestamp
+    fetched: i64,
+    headlines: Vec<Headline>,
+}
+
+/// A feed to show headlines from, and how often to fetch it.
+pub struct Source {
+    pub url: String,
+    pub refresh: Duration,
+}
+
+/// The feeds to show, how many headlines at a time and how often they
+/// cycle, and the command that opens a clicked headline.
+pub struct Settings {
+    pub sources: Vec<Source>,
+    pub count: u32,
+    pub cycle: Option<Duration>,
+    pub opener: String,
+}
+
+// Finds the elements with the given name, returning the attributes and the
+// content of each. This is just enough XML to read RSS and Atom feeds, which
+// do not nest the elements looked for.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<(&'a str, &'a str)> {
+    let mut found = Vec::new();
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut pos = 0;
+    while let Some(idx) = xml[pos..].find(&open) {
+        let start = pos + idx + open.len();
+        pos = start;
+        match xml[start..].chars().next() {
+            Some(c) if c == '>' || c == '/' || c.is_whitespace() => (),
+            _ => continue,
+        }
+        let end = match xml[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let attrs = &xml[start..end];
+        if let Some(attrs) = attrs.strip_suffix('/') {
+            found.push((attrs, ""));
+            pos = end + 1;
+            continue;
+        }
+        match xml[end + 1..].find(&close) {
+            Some(len) => {
+                found.push((attrs, &xml[end + 1..end + 1 + len]));
+                pos = end + 1 + len + close.len();
+            }
+            None => break,
+        }
+    }
+    found
+}
+
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    let mut rest = attrs;
+    while let Some(idx) = rest.find(name) {
+        let before = rest[..idx].chars().next_back();
+        let after = rest[idx + name.len()..].trim_start();
+        rest = &rest[idx + name.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let value = match after.strip_prefix('=') {
+            Some(value) => value.trim_start(),
+            None => continue,
+        };
+        let quote = value.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| unescape(&value[..end]));
+    }
+    None
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let num = name.strip_prefix('#')?;
+            let code = match num.strip_prefix('x').or_else(|| num.strip_prefix('X')) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => num.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = rest.find('&') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        match rest
+            .find(';')
+            .and_then(|end| Some((end, entity(&rest[1..end])?)))
+        {
+            Some((end, c)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// The text of an element, with CDATA sections taken as is, markup removed
+// and whitespace collapsed.
+fn text(content: &str) -> String {
+    let mut out = String::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").unwrap_or(cdata.len());
+            out.push_str(&cdata[..end]);
+            rest = cdata.get(end + 3..).unwrap_or_default();
+        } else if rest.starts_with('<') {
+            let end = rest.find('>').unwrap_or(rest.len() - 1);
+            rest = &rest[end + 1..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            out.push_str(&unescape(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// RSS has the link as text, while Atom has any number of links as
+// attributes, of which the alternate one points to the article.
+fn link(item: &str) -> String {
+    let mut fallback = String::new();
+    for (attrs, content) in elements(item, "link") {
+        match attribute(attrs, "href") {
+            Some(href) => match attribute(attrs, "rel") {
+                None => return href,
+                Some(rel) if rel == "alternate" => return href,
+                Some(_) if fallback.is_empty() => fallback = href,
+                Some(_) => (),
+            },
+            None => {
+                let link = text(content);
+                if !link.is_empty() {
+                    return link;
+                }
+            }
+        }
+    }
+    fallback
+}
+
+// Whether a link may be handed to the opener. The feed decides the links,
+// so only web pages are opened, rather than local files or anything the
+// opener could take as an option.
+fn web_link(link: &str) -> bool {
+    match link.split_once("://") {
+        Some((scheme, _)) => {
+            scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+        }
+        None => false,
+    }
+}
+
+fn published(item: &str) -> Option<i64> {
+    let date = |name| {
+        elements(item, name)
+            .first()
+            .map(|(_, content)| text(content))
+    };
+    if let Some(date) = date("pubDate") {
+        return DateTime::parse_from_rfc2822(&date)
+            .ok()
+            .map(|d| d.timestamp());
+    }
+    let date = date("published").or_else(|| date("updated"))?;
+    DateTime::parse_from_rfc3339(&date)
+        .ok()
+        .map(|d| d.timestamp())
+}
+
+/// Reads the headlines of an RSS or Atom feed, in the order of the feed.
+pub fn parse_feed(xml: &str) -> Vec<Headline> {
+    let mut items = elements(xml, "item");
+    if items.is_empty() {
+        items = elements(xml, "entry");
+    }
+    items
+        .into_iter()
+        .filter_map(|(_, item)| {
+            let title = text(elements(item, "title").first()?.1);
+            if title.is_empty() {
+                return None;
+            }
+            Some(Headline {
+                title,
+                link: link(item),
+                published: published(item),
+            })
+        })
+        .take(MAX_HEADLINES)
+        .collect()
+}
+
+// There is no HTTP client in wldash, so the feed is fetched with curl.
+fn fetch(url: &str) -> Result<Vec<Headline>, String> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", FETCH_TIMEOUT])
+        .arg(url)
+        .output()
+        .map_err(|_| "curl not available".to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(parse_feed(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn load_cache() -> Result<HashMap<String, CachedFeed>, Box<dyn Error>> {
+    let file = File::open(state_file(CACHE_FILE)?)?;
+    #[cfg(feature = "yaml-cfg")]
+    let cache = serde_yaml::from_reader(file)?;
+    #[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+    let cache = serde_json::from_reader(file)?;
+    Ok(cache)
+}
+
+fn save_cache(cache: &HashMap<String, CachedFeed>) -> Result<(), Box<dyn Error>> {
+    let file = File::create(state_file(CACHE_FILE)?)?;
+    #[cfg(feature = "yaml-cfg")]
+    serde_yaml::to_writer(file, cache)?;
+    #[cfg(all(feature = "json-cfg", not(feature = "yaml-cfg")))]
+    serde_json::to_writer(file, cache)?;
+    Ok(())
+}
+
+struct Fetched {
+    feeds: HashMap<String, CachedFeed>,
+    dirty: bool,
+}
+
+pub struct Feeds<'a> {
+    sources: Vec<Source>,
+    // When each feed is due to be fetched, None if it is due now
+    next_fetch: Vec<Option<NaiveDateTime>>,
+    fetched: Arc<Mutex<Fetched>>,
+    // The headlines of all feeds, newest first
+    headlines: Vec<Headline>,
+    first: usize,
+    cycle: Option<Duration>,
+    next_cycle: Option<NaiveDateTime>,
+    count: u32,
+    opener: String,
+    sender: Sender<Cmd>,
+    font: Font<'a>,
+    font_size: u32,
+    length: u32,
+}
+
+impl<'a> Feeds<'a> {
+    pub fn new(
+        font: FontRef,
+        font_size: f32,
+        length: u32,
+        settings: Settings,
+        sender: Sender<Cmd>,
+    ) -> Box<Feeds> {
+        let Settings {
+            sources,
+            count,
+            cycle,
+            opener,
+        } = settings;
+        let mut feeds = load_cache().unwrap_or_default();
+        feeds.retain(|url, _| sources.iter().any(|source| &source.url == url));
+        // Feeds fetched recently enough are not fetched again on start
+        let next_fetch = sources
+            .iter()
+            .map(|source| {
+                let fetched =
+                    NaiveDateTime::from_timestamp_opt(feeds.get(&source.url)?.fetched, 0)?;
+                Some(fetched + source.refresh)
+            })
+            .collect();
+        Box::new(Feeds {
+            sources,
+            next_fetch,
+            fetched: Arc::new(Mutex::new(Fetched { feeds, dirty: true })),
+            headlines: Vec::new(),
+            first: 0,
+            cycle,
+            next_cycle: None,
+            count,
+            opener,
+            sender,
+            font: Font::new(font, font_size),
+            font_size: font_size as u32,
+            length,
+        })
+    }
+
+    // Fetching can take a long time, so it runs in the background and asks
+    // for a redraw when it is done.
+    fn fetch(&self, urls: Vec<String>) {
+        let fetched = self.fetched.clone();
+        let sender = self.sender.clone();
+        let _ = thread::Builder::new()
+            .name("feeds".to_string())
+            .spawn(move || {
+                for url in urls {
+                    let headlines = match fetch(&url) {
+                        Ok(headlines) => headlines,
+                        Err(err) => {
+                            eprintln!("Unable to fetch feed {}: {}", url, err);
+                            continue;
+                        }
+                    };
+                    let mut fetched = fetched.lock().unwrap();
+                    fetched.feeds.insert(
+                        url,
+                        CachedFeed {
+                            fetched: Local::now().naive_local().timestamp(),
+                            headlines,
+                        },
+                    );
+                    fetched.dirty = true;
+                    if let Err(err) = save_cache(&fetched.feeds) {
+                        eprintln!("Unable to save feeds: {}", err);
+                    }
+                    drop(fetched);
+                    let _ = sender.send(Cmd::Draw);
+                }
+            });
+    }
+
+    fn merge(&mut self) {
+        let fetched = self.fetched.lock().unwrap();
+        self.headlines = self
+            .sources
+            .iter()
+            .filter_map(|source| fetched.feeds.get(&source.url))
+            .flat_map(|feed| feed.headlines.iter().cloned())
+            .collect();
+        // The sort is stable, so headlines without a date keep the order of
+        // the feeds after the dated ones.
+        self.headlines
+            .sort_by_key(|headline| std::cmp::Reverse(headline.published));
+        if self.first >= self.headlines.len() {
+            self.first = 0;
+        }
+    }
+
+    fn visible(&self) -> usize {
+        self.headlines.len().min(self.count as usize)
+    }
+}
+
+impl<'a> Widget for Feeds<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        // Fetching is put off while offline, and picked up again by the
+        // redraw that follows reconnecting.
+        if !offline::is_offline() {
+            for next in self.next_fetch.iter() {
+                match next {
+                    Some(next) => ctx.set_time(*next),
+                    None => ctx.set_time(Local::now().naive_local()),
+                }
+            }
+        }
+        if let Some(next_cycle) = self.next_cycle {
+            ctx.set_time(next_cycle);
+        }
+    }
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (self.length, self.count * self.font_size)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+
+        if !offline::is_offline() {
+            let mut due = Vec::new();
+            for (source, next) in self.sources.iter().zip(self.next_fetch.iter_mut()) {
+                if matches!(next, Some(next) if ctx.time < *next) {
+                    continue;
+                }
+                *next = Some(ctx.time + source.refresh);
+                due.push(source.url.clone());
+            }
+            if !due.is_empty() {
+                self.fetch(due);
+            }
+        }
+
+        let dirty = std::mem::take(&mut self.fetched.lock().unwrap().dirty);
+        if dirty {
+            self.merge();
+        }
+        let cycled = match (self.cycle, self.next_cycle) {
+            (Some(cycle), Some(next)) if ctx.time >= next => {
+                self.next_cycle = Some(ctx.time + cycle);
+                // Only cycle when there are more headlines than fit
+                let cycled = self.headlines.len() > self.count as usize;
+                if cycled {
+                    self.first = (self.first + 1) % self.headlines.len();
+                }
+                cycled
+            }
+            (Some(cycle), None) => {
+                self.next_cycle = Some(ctx.time + cycle);
+                false
+            }
+            _ => false,
+        };
+        if !dirty && !cycled && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        if self.headlines.is_empty() {
+            let status = if offline::is_offline() {
+                "offline"
+            } else {
+                "no headlines"
+            };
+            self.font
+                .auto_draw_text(buf, ctx.bg, &Color::new(0.75, 0.75, 0.75, 1.0), status)?;
+        }
+        for row in 0..self.visible() {
+            let idx = (self.first + row) % self.headlines.len();
+            let line =
+                &mut buf.subdimensions((0, row as u32 * self.font_size, width, self.font_size))?;
+            self.font.auto_draw_text(
+                line,
+                ctx.bg,
+                &Color::new(1.0, 1.0, 1.0, 1.0),
+                &self.headlines[idx].title,
+            )?;
+        }
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+
+    fn mouse_click(&mut self, button: u32, (_, y): (u32, u32)) {
+        if button != 272 || self.opener.is_empty() {
+            return;
+        }
+        let row = (y / self.font_size) as usize;
+        if row >= self.visible() {
+            return;
+        }
+        let headline = &self.headlines[(self.first + row) % self.headlines.len()];
+        if !web_link(&headline.link) {
+            return;
+        }
+        let mut argv = match shlex::split(&self.opener) {
+            Some(argv) if !argv.is_empty() => argv,
+            _ => {
+                eprintln!("Invalid feed opener: {}", self.opener);
+                return;
+            }
+        };
+        argv.push(headline.link.clone());
+        self.sender
+            .send(Cmd::Launch { argv, app_id: None })
+            .unwrap();
+        self.sender.send(Cmd::Exit).unwrap();
+    }
+
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+
+    fn action(&mut self, action: &Action) {
+        // All feeds are due on the next draw
+        if let Action::Refresh = action {
+            for next in self.next_fetch.iter_mut() {
+                *next = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headline(title: &str, link: &str, published: Option<i64>) -> (String, String, Option<i64>) {
+        (title.to_string(), link.to_string(), published)
+    }
+
+    fn parse(xml: &str) -> Vec<(String, String, Option<i64>)> {
+        parse_feed(xml)
+            .into_iter()
+            .map(|h| (h.title, h.link, h.published))
+            .collect()
+    }
+
+    #[test]
+    fn rss() {
+        let xml = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+<title>Channel</title><link>https://example.com/</link>
+<item>
+  <title>First &amp; foremost</title>
+  <link>https://example.com/1</link>
+  <pubDate>Tue, 10 Jun 2003 04:00:00 GMT</pubDate>
+</item>
+<item>
+  <title><![CDATA[Second <b>bold</b> & raw]]></title>
+  <link>
+    https://example.com/2
+  </link>
+</item>
+<item><title></title><link>https://example.com/untitled</link></item>
+<item><title>No link &bogus; here</title><link/></item>
+</channel></rss>"#;
+        assert_eq!(
+            parse(xml),
+            vec![
+                headline(
+                    "First & foremost",
+                    "https://example.com/1",
+                    Some(1055217600)
+                ),
+                headline("Second <b>bold</b> & raw", "https://example.com/2", None),
+                headline("No link &bogus; here", "", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn atom() {
+        let xml = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+<title>Feed</title>
+<link rel="self" href="https://example.com/feed.xml"/>
+<entry>
+  <title type="html">Caf&#233; &#x2014; news</title>
+  <link rel="self" href="https://example.com/self"/>
+  <link rel="alternate" type="text/html" href="https://example.com/a?x=1&amp;y=2"/>
+  <updated>2003-12-13T18:30:02Z</updated>
+</entry>
+<entry>
+  <title>Other links only</title>
+  <link rel="enclosure" href="https://example.com/a.mp3"/>
+  <link rel="related" href="https://example.com/related"/>
+  <published>2003-12-13T18:30:02+01:00</published>
+</entry>
+<entry><title>Without rel</title><link href='https://example.com/plain' /></entry>
+</feed>"#;
+        assert_eq!(
+            parse(xml),
+            vec![
+                headline(
+                    "Caf\u{e9} \u{2014} news",
+                    "https://example.com/a?x=1&y=2",
+                    Some(1071340202)
+                ),
+                headline(
+                    "Other links only",
+                    "https://example.com/a.mp3",
+                    Some(1071336602)
+                ),
+                headline("Without rel", "https://example.com/plain", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn opened_links() {
+        assert!(web_link("https://example.com/1"));
+        assert!(web_link("HTTP://example.com/"));
+        assert!(!web_link(""));
+        assert!(!web_link("file:///etc/passwd"));
+        assert!(!web_link("--help"));
+        assert!(!web_link("-a https://example.com/"));
+        assert!(!web_link("javascript:alert(1)"));
+        assert!(!web_link("/relative/path"));
+        assert!(!web_link("ftp://example.com/"));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::cmd::Cmd;
+use crate::color::Color;
+use crate::draw::{draw_spinner, next_spinner_frame, Font};
+use crate::motion;
+use crate::offline;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::env;
+use std::fs;
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::{Duration, Local, NaiveDateTime, Timelike};
+use nix::unistd::gethostname;
+
+fn salutation(hour: u32) -> &'static str {
+    match hour {
+        5..=11 => "Good morning",
+        12..=17 => "Good afternoon",
+        _ => "Good evening",
+    }
+}
+
+fn uptime() -> Option<String> {
+    let uptime = fs::read_to_string("/proc/uptime").ok()?;
+    let secs = uptime.split_whitespace().next()?.parse::<f64>().ok()? as u64;
+    let (days, hours) = (secs / 86400, secs / 3600 % 24);
+    Some(match (days, hours) {
+        (0, 0) => "up less than an hour".to_string(),
+        (0, 1) => "up 1 hour".to_string(),
+        (0, h) => format!("up {} hours", h),
+        (1, h) => format!("up 1 day, {} h", h),
+        (d, h) => format!("up {} days, {} h", d, h),
+    })
+}
+
+fn age(age: Duration) -> String {
+    match (age.num_days(), age.num_hours(), age.num_minutes()) {
+        (0, 0, m) => format!("{} min ago", m.max(1)),
+        (0, h, _) => format!("{} h ago", h),
+        (d, _, _) => format!("{} d ago", d),
+    }
+}
+
+// The command either prints the number of pending updates, or lists them
+// one per line like checkupdates does.
+fn count_updates(command: &str) -> Option<usize> {
+    let lexed = shlex::split(command)?;
+    if lexed.is_empty() {
+        return None;
+    }
+    let output = Command::new(&lexed[0]).args(&lexed[1..]).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = stdout.trim();
+    match stdout.parse::<usize>() {
+        Ok(count) => Some(count),
+        Err(_) => Some(stdout.lines().filter(|l| !l.trim().is_empty()).count()),
+    }
+}
+
+#[derive(Default)]
+struct Updates {
+    count: Option<usize>,
+    // When the count was last updated
+    checked: Option<NaiveDateTime>,
+    running: bool,
+    dirty: bool,
+}
+
+pub struct Greeting<'a> {
+    next_update: NaiveDateTime,
+    user: String,
+    hostname: String,
+    updates_command: String,
+    updates: Arc<Mutex<Updates>>,
+    offline: bool,
+    sender: Sender<Cmd>,
+    title_font: Font<'a>,
+    detail_font: Font<'a>,
+    font_size: u32,
+    length: u32,
+}
+
+impl<'a> Greeting<'a> {
+    pub fn new(
+        time: NaiveDateTime,
+        font: FontRef,
+        font_size: f32,
+        length: u32,
+        updates_command: String,
+        sender: Sender<Cmd>,
+    ) -> Box<Greeting> {
+        let hostname = gethostname()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_default();
+        Box::new(Greeting {
+            next_update: time,
+            user: env::var("USER").unwrap_or_default(),
+            hostname,
+            updates_command,
+            updates: Arc::new(Mutex::new(Default::default())),
+            offline: offline::is_offline(),
+            sender,
+            title_font: Font::new(font, font_size),
+            detail_font: Font::new(font, font_size / 2.0),
+            font_size: font_size as u32,
+            length,
+        })
+    }
+
+    // The update check can take a long time, so it runs in the background
+    // and asks for a redraw when it is done. It usually needs the network,
+    // so it is skipped while offline.
+    fn check_updates(&self) {
+        if self.updates_command.is_empty() || offline::is_offline() {
+            return;
+        }
+        let mut updates = self.updates.lock().unwrap();
+        if updates.running {
+            return;
+        }
+        updates.running = true;
+        drop(updates);
+
+        let command = self.updates_command.clone();
+        let updates = self.updates.clone();
+        let sender = self.sender.clone();
+        let _ = thread::Builder::new()
+            .name("greeting_updates".to_string())
+            .spawn(move || {
+                let count = count_updates(&command);
+                let mut updates = updates.lock().unwrap();
+                if count.is_some() {
+                    updates.count = count;
+                    updates.checked = Some(Local::now().naive_local());
+                }
+                updates.running = false;
+                updates.dirty = true;
+                drop(updates);
+                let _ = sender.send(Cmd::Draw);
+            });
+    }
+
+    fn details(&self, now: NaiveDateTime) -> String {
+        let mut details = vec![];
+        if !self.hostname.is_empty() {
+            details.push(self.hostname.clone());
+        }
+        if let Some(uptime) = uptime() {
+            details.push(uptime);
+        }
+        let updates = self.updates.lock().unwrap();
+        let count = match updates.count {
+            Some(0) => Some("up to date".to_string()),
+            Some(1) => Some("1 update".to_string()),
+            Some(n) => Some(format!("{} updates", n)),
+            None => None,
+        };
+        // While offline, the count is shown with its age as it may be stale
+        match (count, updates.checked) {
+            (Some(count), Some(checked)) if self.offline => {
+                details.push(format!("{} ({})", count, age(now - checked)))
+            }
+            (Some(count), _) => details.push(count),
+            (None, _) if self.offline && !self.updates_command.is_empty() => {
+                details.push("offline".to_string())
+            }
+            (None, _) => (),
+        }
+        details.join(" · ")
+    }
+}
+
+impl<'a> Widget for Greeting<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        ctx.set_time(self.next_update);
+        if self.updates.lock().unwrap().running && !motion::is_reduced() {
+            ctx.set_time(next_spinner_frame(Local::now().naive_local()));
+        }
+    }
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (self.length, self.font_size + self.font_size / 2)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+        let update = ctx.time >= self.next_update;
+        let (updates_dirty, checking) = {
+            let mut updates = self.updates.lock().unwrap();
+            (std::mem::take(&mut updates.dirty), updates.running)
+        };
+        if !update && !updates_dirty && !checking && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+        let offline = offline::is_offline();
+        let reconnected = self.offline && !offline;
+        self.offline = offline;
+        if update {
+            // Refresh on the hour, which is also when the salutation changes
+            let hour = ctx.time.date().and_hms_opt(ctx.time.hour(), 0, 0).unwrap();
+            self.next_update = hour + Duration::hours(1);
+        }
+        if update || reconnected {
+            self.check_updates();
+        }
+
+        let title = if self.user.is_empty() {
+            salutation(ctx.time.hour()).to_string()
+        } else {
+            format!("{}, {}", salutation(ctx.time.hour()), self.user)
+        };
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        self.title_font
+            .auto_draw_text(buf, ctx.bg, &Color::new(1.0, 1.0, 1.0, 1.0), &title)?;
+        let details = self.details(ctx.time);
+        let c = Color::new(0.75, 0.75, 0.75, 1.0);
+        let (details_width, _) = self.detail_font.auto_draw_text(
+            &mut buf.offset((0, self.font_size))?,
+            ctx.bg,
+            &c,
+            &details,
+        )?;
+        let size = self.font_size / 2;
+        if checking && details_width + size * 3 / 2 <= width {
+            draw_spinner(
+                &mut buf.offset((details_width + size / 2, self.font_size))?,
+                ctx.bg,
+                &c,
+                size,
+                ctx.time,
+            )?;
+        }
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+}
@@ -0,0 +1,98 @@
+use crate::binding::Binding;
+use crate::cmd::Cmd;
+use crate::color::Color;
+use crate::draw::Font;
+use crate::{
+    fonts::FontRef,
+    widget::{
+        DrawContext, DrawReport, KeyState, ModifiersState, Scroll, StateValue, TreeNode,
+        WaitContext, Widget,
+    },
+};
+
+use std::sync::mpsc::Sender;
+
+const BTN_LEFT: u32 = 272;
+
+/// A line of static text, such as a section header or an icon font glyph,
+/// carrying out its binding when clicked.
+pub struct Label<'a> {
+    text: String,
+    font: Font<'a>,
+    size: f32,
+    width: u32,
+    on_click: Option<Binding>,
+    tx: Sender<Cmd>,
+}
+
+impl<'a> Label<'a> {
+    pub fn new(
+        text: String,
+        font: FontRef<'a>,
+        size: f32,
+        on_click: Option<Binding>,
+        tx: Sender<Cmd>,
+    ) -> Box<Label<'a>> {
+        let mut font = Font::new(font, size);
+        font.add_str_to_cache(&text);
+        let width = font.auto_text_width(&text);
+        Box::new(Label {
+            text,
+            font,
+            size,
+            width,
+            on_click,
+            tx,
+        })
+    }
+}
+
+impl<'a> Widget for Label<'a> {
+    fn wait(&mut self, _: &mut WaitContext) {}
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.size.ceil() as u32)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        _expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let (width, height) = self.size();
+        if !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        self.font
+            .auto_draw_text(buf, ctx.bg, &Color::new(1.0, 1.0, 1.0, 1.0), &self.text)?;
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, button: u32, _: (u32, u32)) {
+        if button != BTN_LEFT {
+            return;
+        }
+        if let Some(binding) = &self.on_click {
+            binding.execute(&self.tx);
+        }
+    }
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos)
+            .value("font_size", StateValue::Number(self.size as f64))
+            .value("text", StateValue::Text(self.text.clone()))
+    }
+}
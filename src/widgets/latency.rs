@@ -0,0 +1,263 @@
+use crate::cmd::{Action, Cmd};
+use crate::color::Color;
+use crate::draw::Font;
+use crate::offline;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::io::ErrorKind;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use chrono::{Duration, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+const BTN_LEFT: u32 = 272;
+
+/// How the latency to the host is measured.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Probe {
+    /// The time taken to connect to a TCP port, which needs no privileges.
+    #[default]
+    Tcp,
+    /// An ICMP echo with ping, which uses unprivileged ICMP sockets where
+    /// the system allows them.
+    Icmp,
+}
+
+/// What to probe, and how.
+#[derive(Clone)]
+pub struct Target {
+    pub host: String,
+    pub probe: Probe,
+    // The port connected to with the TCP probe
+    pub port: u16,
+    // Seconds before giving up on a probe
+    pub timeout: u32,
+}
+
+// The round trip time to the host in milliseconds, or why it could not be
+// measured.
+fn measure(target: &Target) -> Result<f64, String> {
+    let (host, port) = (target.host.as_str(), target.port);
+    let timeout = std::time::Duration::from_secs(target.timeout as u64);
+    match target.probe {
+        Probe::Tcp => {
+            // The name is resolved first, so that only connecting is timed
+            let addr = (host, port)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .ok_or_else(|| "unknown host".to_string())?;
+            let start = Instant::now();
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(_) => Ok(start.elapsed().as_secs_f64() * 1000.0),
+                Err(err) if err.kind() == ErrorKind::TimedOut => Err("timed out".to_string()),
+                Err(_) => Err("unreachable".to_string()),
+            }
+        }
+        Probe::Icmp => {
+            let output = Command::new("ping")
+                .args(["-n", "-c", "1", "-W"])
+                .arg(timeout.as_secs().to_string())
+                .arg(host)
+                .output()
+                .map_err(|_| "ping not available".to_string())?;
+            // The reply is reported as "... time=12.3 ms"
+            String::from_utf8_lossy(&output.stdout)
+                .split_once("time=")
+                .and_then(|(_, rest)| rest.split_whitespace().next()?.parse().ok())
+                .ok_or_else(|| "unreachable".to_string())
+        }
+    }
+}
+
+struct Measured {
+    latency: Option<Result<f64, String>>,
+    probing: bool,
+}
+
+/// The latency to a host, measured in the background every so often and
+/// colored by how it compares to the thresholds. Clicking pauses and
+/// resumes probing, and nothing is probed while offline.
+pub struct Latency<'a> {
+    target: Target,
+    interval: Duration,
+    // The thresholds in milliseconds above which the latency is degraded and
+    // bad
+    warn: u32,
+    bad: u32,
+    // When to probe next, None if it is due now
+    next_probe: Option<NaiveDateTime>,
+    paused: bool,
+    measured: Arc<Mutex<Measured>>,
+    shown: String,
+    sender: Sender<Cmd>,
+    font: Font<'a>,
+    font_size: u32,
+    length: u32,
+}
+
+impl<'a> Latency<'a> {
+    pub fn new(
+        font: FontRef,
+        font_size: f32,
+        length: u32,
+        target: Target,
+        interval: Duration,
+        (warn, bad): (u32, u32),
+        sender: Sender<Cmd>,
+    ) -> Box<Latency> {
+        let mut font = Font::new(font, font_size);
+        font.add_str_to_cache(&target.host);
+        font.add_str_to_cache("0123456789 ms:");
+        Box::new(Latency {
+            target,
+            interval,
+            warn,
+            bad,
+            next_probe: None,
+            paused: false,
+            measured: Arc::new(Mutex::new(Measured {
+                latency: None,
+                probing: false,
+            })),
+            shown: String::new(),
+            sender,
+            font,
+            font_size: font_size as u32,
+            length,
+        })
+    }
+
+    // Probing takes up to the timeout, so it runs in the background and asks
+    // for a redraw when it is done. Only one probe runs at a time.
+    fn start_probe(&self) {
+        let mut measured = self.measured.lock().unwrap();
+        if measured.probing {
+            return;
+        }
+        measured.probing = true;
+        drop(measured);
+
+        let measured = self.measured.clone();
+        let sender = self.sender.clone();
+        let target = self.target.clone();
+        let _ = thread::Builder::new()
+            .name("latency".to_string())
+            .spawn(move || {
+                let latency = measure(&target);
+                let mut measured = measured.lock().unwrap();
+                measured.latency = Some(latency);
+                measured.probing = false;
+                drop(measured);
+                let _ = sender.send(Cmd::Draw);
+            });
+    }
+
+    fn status(&self) -> (String, Color) {
+        let gray = Color::new(0.75, 0.75, 0.75, 1.0);
+        if self.paused {
+            return (format!("{}: paused", self.target.host), gray);
+        }
+        if offline::is_offline() {
+            return (format!("{}: offline", self.target.host), gray);
+        }
+        match &self.measured.lock().unwrap().latency {
+            None => (format!("{}: ...", self.target.host), gray),
+            Some(Err(err)) => (
+                format!("{}: {}", self.target.host, err),
+                Color::new(0.8, 0.35, 0.35, 1.0),
+            ),
+            Some(Ok(ms)) => {
+                let c = if *ms >= self.bad as f64 {
+                    Color::new(0.8, 0.35, 0.35, 1.0)
+                } else if *ms >= self.warn as f64 {
+                    Color::new(0.9, 0.8, 0.3, 1.0)
+                } else {
+                    Color::new(0.4, 0.8, 0.4, 1.0)
+                };
+                (format!("{}: {:.0} ms", self.target.host, ms), c)
+            }
+        }
+    }
+}
+
+impl<'a> Widget for Latency<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        // Probing is put off while offline or paused, and picked up again by
+        // the redraw that follows.
+        if self.paused || offline::is_offline() {
+            return;
+        }
+        match self.next_probe {
+            Some(next) => ctx.set_time(next),
+            None => ctx.set_time(Local::now().naive_local()),
+        }
+    }
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (self.length, self.font_size)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+
+        let due = !matches!(self.next_probe, Some(next) if ctx.time < next);
+        if due && !self.paused && !offline::is_offline() {
+            self.next_probe = Some(ctx.time + self.interval);
+            self.start_probe();
+        }
+
+        let (text, c) = self.status();
+        if text == self.shown && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        self.font.auto_draw_text(buf, ctx.bg, &c, &text)?;
+        self.shown = text;
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, button: u32, _: (u32, u32)) {
+        if button != BTN_LEFT {
+            return;
+        }
+        self.paused = !self.paused;
+        // Resuming probes right away
+        self.next_probe = None;
+        self.shown.clear();
+    }
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+
+    fn action(&mut self, action: &Action) {
+        if let Action::Refresh = action {
+            self.next_probe = None;
+        }
+    }
+}
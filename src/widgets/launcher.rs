@@ -1,78 +1,798 @@
 use crate::buffer::Buffer;
-use crate::cmd::Cmd;
+use crate::cmd::{Action, Cmd};
 use crate::color::Color;
-use crate::desktop::{load_desktop_files, Desktop};
-use crate::draw::Font;
+use crate::desktop::{self, load_desktop_files, Desktop, DesktopFiles};
+use crate::dirlist::{expand_home, split_path, DirReader, Entry};
+use crate::draw::{Cursor, Font};
+use crate::executables::Executables;
 use crate::{
     fonts::FontRef,
-    widget::{DrawContext, DrawReport, KeyState, ModifiersState, WaitContext, Widget},
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
 };
 
 use std::cell::RefCell;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashMap;
-use std::process::Command;
+use std::error::Error;
+use std::fs;
 use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 
-use crate::data::Data;
+use crate::data::{state_file, Data};
+use crate::keybindings::{self, LauncherKey};
 use crate::keyboard::keysyms;
-use fuzzy_matcher::skim::SkimMatcherV2;
-use fuzzy_matcher::FuzzyMatcher;
+use crate::matching::{self, Matcher};
+use crate::motion;
+use crate::private;
+use crate::selection::Selection;
+use crate::shortcodes;
+use crate::vpn::{self, Vpns};
+use crate::widgets::notes::append_note;
+use chrono::{Local, NaiveDateTime};
 use unicode_segmentation::UnicodeSegmentation;
 
+// The Linux input event code of the middle mouse button
+const BTN_MIDDLE: u32 = 274;
+
+// The results listed at most at a time in overlay mode
+const OVERLAY_ROWS: usize = 8;
+
+// A font and size used for one region of the launcher line.
+struct Region<'a> {
+    font: RefCell<Font<'a>>,
+    size: u32,
+}
+
+impl<'a> Region<'a> {
+    fn new(font: FontRef<'a>, font_size: f32) -> Region<'a> {
+        Region {
+            font: RefCell::new(Font::new(font, font_size)),
+            size: font_size as u32,
+        }
+    }
+
+    // Regions with a smaller font are aligned to the bottom of the line, so
+    // that they share a baseline with the larger ones.
+    fn at<'b>(
+        &self,
+        buf: &'b mut Buffer,
+        x: u32,
+        height: u32,
+    ) -> Result<Buffer<'b>, ::std::io::Error> {
+        buf.offset((x, height.saturating_sub(self.size)))
+    }
+}
+
+// The rectangle a result was drawn in, relative to the launcher, as recorded
+// while drawing so that pointer input hits exactly what is shown.
+#[derive(Clone, Copy)]
+struct Hit {
+    idx: usize,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Hit {
+    fn contains(&self, (x, y): (u32, u32)) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+// A search back through the history of a mode, started with Ctrl+R as in a
+// shell. The input is left as it was until an entry is picked.
+struct HistorySearch {
+    // The mode character the entries are entered with, if any
+    prefix: &'static str,
+    // The entries, most recent first
+    entries: Vec<String>,
+    query: String,
+    // The entries matching the query, and the one selected among them
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl HistorySearch {
+    fn new(prefix: &'static str, entries: Vec<String>) -> HistorySearch {
+        let mut search = HistorySearch {
+            prefix,
+            matches: (0..entries.len()).collect(),
+            entries,
+            query: String::new(),
+            selected: 0,
+        };
+        search.filter();
+        search
+    }
+
+    fn filter(&mut self) {
+        let matcher = matching::matcher();
+        let query = self.query.to_lowercase();
+        self.matches = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                query.is_empty() || matcher.score(&entry.to_lowercase(), &query).is_some()
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        self.selected = 0;
+    }
+
+    fn selection(&self) -> Option<&str> {
+        self.matches
+            .get(self.selected)
+            .map(|&idx| self.entries[idx].as_str())
+    }
+}
+
 pub struct Launcher<'a> {
     cursor: usize,
     options: Vec<Desktop>,
     term_opener: String,
     app_opener: String,
     url_opener: String,
+    file_opener: String,
+    // The command shell commands are run through split into its arguments,
+    // empty if there is none, or None if it is set but cannot be split
+    shell_sandbox: Option<Vec<String>>,
     matches: Vec<Desktop>,
+    category: Option<String>,
+    category_matches: Vec<String>,
+    dir_reader: DirReader,
+    // The commands found in PATH, which commands in shell mode are checked
+    // against
+    executables: Executables,
+    vpns: Vpns,
+    path_matches: Vec<Entry>,
     input: String,
     result: Option<String>,
     offset: usize,
-    font: RefCell<Font<'a>>,
-    font_size: u32,
+    hover: Option<usize>,
+    last_click: Option<(usize, Instant)>,
+    hits: RefCell<Vec<Hit>>,
+    // The calculator result, which can be selected and copied
+    selection: RefCell<Selection>,
+    prompt: Region<'a>,
+    results: Region<'a>,
+    calc: Region<'a>,
+    hints: Region<'a>,
+    // Whether Alt is held, showing the hints for quick activation
+    alt: bool,
+    // Whether Shift is held, bypassing the shell sandbox
+    shift: bool,
+    height: u32,
     length: u32,
     dirty: bool,
     tx: Sender<Cmd>,
     counter: Data,
+    // The commands run in shell mode, oldest first
+    shell_history: Vec<String>,
+    // The expressions evaluated in calculator mode, oldest first
+    calc_history: Vec<String>,
+    // The mode and index of the entry scrolled to in the history of the mode
+    history_pos: Option<(char, usize)>,
+    search: Option<HistorySearch>,
+    // Whether the results are sorted by launches rather than by match
+    most_used: bool,
+    cursor_style: Cursor,
+    // The cursor blinks from when it last moved, if set
+    blink: Option<chrono::Duration>,
+    blink_start: Option<NaiveDateTime>,
+    cursor_shown: bool,
+    // Whether the results are listed above the other widgets, below the
+    // prompt, and where the launcher was last drawn for that
+    overlay: bool,
+    pos: (u32, u32),
+    // The language of the names of applications, read again on refresh
+    language: Option<String>,
+    desktop_files: DesktopFiles,
 }
 
 impl<'a> Launcher<'a> {
     pub fn new(
-        font: FontRef,
-        font_size: f32,
+        prompt: (FontRef<'a>, f32),
+        results: (FontRef<'a>, f32),
+        calc: (FontRef<'a>, f32),
         length: u32,
         listener: Sender<Cmd>,
         app: String,
         term: String,
         url: String,
-    ) -> Box<Launcher> {
+        file: String,
+        shell_sandbox: String,
+        language: Option<String>,
+        cursor_style: Cursor,
+        blink: u32,
+        overlay: bool,
+    ) -> Box<Launcher<'a>> {
+        let height = prompt.1.max(results.1).max(calc.1) as u32;
+        let (desktop_files, options) = DesktopFiles::load(language.clone(), listener.clone());
+        let sandbox = sandbox_argv(&shell_sandbox);
+        if sandbox.is_none() {
+            eprintln!(
+                "Invalid shell_sandbox, shell commands will only run with Shift+Return: {}",
+                shell_sandbox
+            );
+        }
         Box::new(Launcher {
             cursor: 0,
-            options: load_desktop_files(),
+            options,
+            language,
+            desktop_files,
             term_opener: term,
             app_opener: app,
             url_opener: url,
+            file_opener: file,
+            shell_sandbox: sandbox,
             matches: vec![],
+            category: None,
+            category_matches: vec![],
+            dir_reader: DirReader::new(listener.clone()),
+            executables: Executables::new(listener.clone()),
+            vpns: Vpns::new(listener.clone()),
+            path_matches: vec![],
             input: "".to_string(),
             result: None,
             offset: 0,
-            font: RefCell::new(Font::new(font, font_size)),
-            font_size: font_size as u32,
+            hover: None,
+            last_click: None,
+            hits: RefCell::new(Vec::new()),
+            selection: RefCell::new(Selection::new()),
+            prompt: Region::new(prompt.0, prompt.1),
+            results: Region::new(results.0, results.1),
+            calc: Region::new(calc.0, calc.1),
+            hints: Region::new(results.0, results.1 / 2.0),
+            alt: false,
+            shift: false,
+            height,
             length,
             dirty: true,
             tx: listener,
             counter: Data::load().unwrap_or_default(),
+            shell_history: load_history(SHELL_HISTORY_FILE),
+            history_pos: None,
+            calc_history: load_history(CALC_HISTORY_FILE),
+            search: None,
+            most_used: false,
+            cursor_style,
+            blink: match blink {
+                0 => None,
+                ms => Some(chrono::Duration::milliseconds(ms.into())),
+            },
+            blink_start: None,
+            cursor_shown: true,
+            overlay,
+            pos: (0, 0),
         })
     }
 
+    // How long the cursor is shown and hidden for, unless it keeps still.
+    fn blink(&self) -> Option<chrono::Duration> {
+        self.blink.filter(|_| !motion::is_reduced())
+    }
+
+    // Whether shell commands are run through a sandbox command, or are
+    // refused for the one set being invalid.
+    fn sandboxed(&self) -> bool {
+        self.shell_sandbox
+            .as_ref()
+            .is_none_or(|sandbox| !sandbox.is_empty())
+    }
+
+    // Runs the shell command, through the sandbox command if there is one
+    // and it is not bypassed. An invalid sandbox command runs nothing,
+    // rather than the command outside of it.
+    fn run_shell(&mut self, sandboxed: bool) {
+        let command = self.input.chars().skip(1).collect::<String>();
+        let mut argv = match (&self.shell_sandbox, sandboxed) {
+            (_, false) => Vec::new(),
+            (Some(sandbox), true) => sandbox.clone(),
+            (None, true) => {
+                eprintln!("Not running {}: shell_sandbox is invalid", command);
+                return;
+            }
+        };
+        self.cursor = 0;
+        self.remember_command(&command);
+        argv.extend(["sh".to_string(), "-c".to_string(), command]);
+        self.tx.send(Cmd::Launch { argv, app_id: None }).unwrap();
+        self.tx.send(Cmd::Exit).unwrap();
+    }
+
+    // Remembers a command run in shell mode, unless in private mode.
+    fn remember_command(&mut self, command: &str) {
+        self.history_pos = None;
+        remember(&mut self.shell_history, SHELL_HISTORY_FILE, command);
+    }
+
+    // The applications launched, most recently launched first. Those only
+    // counted from before the launch times were kept come last.
+    fn launch_history(&self) -> Vec<String> {
+        let mut usage: Vec<(&String, i64)> = self
+            .counter
+            .usage
+            .iter()
+            .map(|(name, usage)| (name, usage.last))
+            .collect();
+        usage.sort_by_key(|&(_, key)| Reverse(key));
+        let mut counted: Vec<(&String, i64)> = self
+            .counter
+            .entries
+            .iter()
+            .filter(|(name, _)| !self.counter.usage.contains_key(*name))
+            .map(|(name, count)| (name, *count))
+            .collect();
+        counted.sort_by_key(|&(_, key)| Reverse(key));
+        usage
+            .into_iter()
+            .chain(counted)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    // Starts searching the history of the mode, or selects the next older
+    // match when already searching.
+    fn search_history(&mut self) {
+        if let Some(search) = &mut self.search {
+            if search.selected + 1 < search.matches.len() {
+                search.selected += 1;
+            }
+            self.dirty = true;
+            return;
+        }
+        let (prefix, entries) = match self.input.chars().next() {
+            Some('=') => ("=", self.calc_history.iter().rev().cloned().collect()),
+            Some('!') => ("!", self.shell_history.iter().rev().cloned().collect()),
+            // Notes, paths and categories have no history
+            Some('#' | '/' | '~' | '@') => return,
+            _ => ("", self.launch_history()),
+        };
+        self.search = Some(HistorySearch::new(prefix, entries));
+        self.dirty = true;
+    }
+
+    // Handles a key while searching the history.
+    fn search_input(&mut self, key: u32, modifiers: ModifiersState, interpreted: Option<String>) {
+        self.dirty = true;
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return,
+        };
+        match keybindings::lookup(keybindings::LAUNCHER, key, modifiers) {
+            Some(LauncherKey::Search) => self.search_history(),
+            Some(LauncherKey::Next) => {
+                if search.selected + 1 < search.matches.len() {
+                    search.selected += 1;
+                }
+            }
+            Some(LauncherKey::Previous) => search.selected = search.selected.saturating_sub(1),
+            Some(LauncherKey::CancelSearch) => self.search = None,
+            Some(LauncherKey::Activate) => {
+                if let Some(entry) = search.selection() {
+                    self.input = format!("{}{}", search.prefix, entry);
+                    self.cursor = self.input.graphemes(true).count();
+                    self.offset = 0;
+                    self.result = None;
+                    self.history_pos = None;
+                }
+                self.search = None;
+            }
+            Some(LauncherKey::DeleteLeft) => {
+                let last = search.query.graphemes(true).count().saturating_sub(1);
+                remove_range(&mut search.query, last, last + 1);
+                search.filter();
+            }
+            Some(LauncherKey::Clear) => {
+                search.query.clear();
+                search.filter();
+            }
+            Some(LauncherKey::Help) | None => {
+                if let Some(v) = interpreted {
+                    search.query.push_str(&v);
+                    search.filter();
+                }
+            }
+            Some(_) => (),
+        }
+    }
+
+    // The history of the mode of the input, if it keeps one, with the mode
+    // character.
+    fn mode_history(&self) -> Option<(char, &[String])> {
+        match self.input.chars().next() {
+            Some('!') => Some(('!', &self.shell_history)),
+            Some('=') => Some(('=', &self.calc_history)),
+            _ => None,
+        }
+    }
+
+    // Replaces the command or calculation with an older one when scrolling
+    // up, or a newer one when scrolling down, down to an empty one. The
+    // position is kept while editing the entry, so scrolling on goes on
+    // from there.
+    fn scroll_history(&mut self, steps: i32) {
+        let (mode, history) = match self.mode_history() {
+            Some(history) => history,
+            None => return,
+        };
+        let len = history.len() as i64;
+        // A position in the history of another mode starts over
+        let pos = match self.history_pos {
+            Some((m, pos)) if m == mode => pos as i64,
+            _ => len,
+        };
+        let pos = (pos + steps as i64).clamp(0, len);
+        let pos = if pos == len { None } else { Some(pos as usize) };
+        if pos == self.history_pos.map(|(_, pos)| pos) {
+            return;
+        }
+        let input = match pos {
+            Some(pos) => format!("{}{}", mode, history[pos]),
+            None => mode.to_string(),
+        };
+        self.history_pos = pos.map(|pos| (mode, pos));
+        self.input = input;
+        self.result = None;
+        self.cursor = self.input.graphemes(true).count();
+        self.dirty = true;
+    }
+
+    // Expands the shortcode right before the cursor, if there is one, and
+    // returns whether there was.
+    fn expand_shortcode(&mut self) -> bool {
+        let end = grapheme_offset(&self.input, self.cursor);
+        let (start, expansion) = match shortcodes::expand(&self.input, end) {
+            Some(expanded) => expanded,
+            None => return false,
+        };
+        self.input.replace_range(start..end, &expansion);
+        self.cursor = self.input[..start + expansion.len()]
+            .graphemes(true)
+            .count();
+        self.offset = 0;
+        self.result = None;
+        self.dirty = true;
+        true
+    }
+
+    // Inserts text at the cursor, after the mode character if there is one.
+    fn paste(&mut self, text: &str) {
+        let text = text.replace('\n', " ");
+        let cursor = self.cursor.max(1);
+        self.cursor = insert_at(&mut self.input, cursor, &text);
+        self.result = None;
+        self.dirty = true;
+    }
+
+    fn match_count(&self) -> usize {
+        match self.input.chars().next() {
+            Some('@') => self.category_matches.len(),
+            Some('/') | Some('~') => self.path_matches.len(),
+            _ => self.matches.len(),
+        }
+    }
+
+    // Records where a result was drawn, clipped to the visible width.
+    fn record_hit(&self, idx: usize, x: u32, width: u32, visible: u32) {
+        self.hits.borrow_mut().push(Hit {
+            idx,
+            x,
+            y: self.height.saturating_sub(self.results.size),
+            width: width.min(visible.saturating_sub(x)),
+            height: self.results.size,
+        });
+    }
+
+    fn hit(&self, pos: (u32, u32)) -> Option<usize> {
+        self.hits
+            .borrow()
+            .iter()
+            .find(|hit| hit.contains(pos))
+            .map(|hit| hit.idx)
+    }
+
+    // The width draw_hint takes for the result.
+    fn hint_width(&self, idx: usize) -> u32 {
+        if !self.alt || idx >= 9 {
+            return 0;
+        }
+        let width = self
+            .hints
+            .font
+            .borrow_mut()
+            .auto_text_width(&(idx + 1).to_string());
+        width + self.hints.size / 4
+    }
+
+    // Draws the number to activate the result with while Alt is held, and
+    // returns the width it took.
+    fn draw_hint(
+        &self,
+        buf: &mut Buffer,
+        bg: &Color,
+        idx: usize,
+        x: u32,
+    ) -> Result<u32, ::std::io::Error> {
+        if !self.alt || idx >= 9 {
+            return Ok(0);
+        }
+        let (width, _) = self.hints.font.borrow_mut().auto_draw_text(
+            &mut self.hints.at(buf, x, self.height)?,
+            bg,
+            &Color::new(1.0, 1.0, 0.0, 1.0),
+            &(idx + 1).to_string(),
+        )?;
+        Ok(width + self.hints.size / 4)
+    }
+
+    // How many times the result was launched, if at all.
+    fn launches(&self, m: &Desktop) -> Option<i64> {
+        self.counter
+            .entries
+            .get(&m.name)
+            .copied()
+            .filter(|&n| n > 0)
+    }
+
+    // The width draw_badge takes for the result.
+    fn badge_width(&self, m: &Desktop) -> u32 {
+        match self.launches(m) {
+            Some(launches) => {
+                self.hints.size / 4
+                    + self
+                        .hints
+                        .font
+                        .borrow_mut()
+                        .auto_text_width(&launches.to_string())
+            }
+            None => 0,
+        }
+    }
+
+    // Draws how many times the result was launched after its name, and
+    // returns the width it took.
+    fn draw_badge(
+        &self,
+        buf: &mut Buffer,
+        bg: &Color,
+        m: &Desktop,
+        x: u32,
+    ) -> Result<u32, ::std::io::Error> {
+        let launches = match self.launches(m) {
+            Some(launches) => launches,
+            None => return Ok(0),
+        };
+        let gap = self.hints.size / 4;
+        let (width, _) = self.hints.font.borrow_mut().auto_draw_text(
+            &mut self.hints.at(buf, x + gap, self.height)?,
+            bg,
+            &Color::new(0.4, 0.4, 0.4, 1.0),
+            &launches.to_string(),
+        )?;
+        Ok(gap + width)
+    }
+
+    // Activates a visible result, as Alt and its number does.
+    fn activate_visible(&mut self, idx: usize) {
+        if self.input.starts_with(['=', '!', '#', '@'])
+            || !self.hits.borrow().iter().any(|hit| hit.idx == idx)
+        {
+            return;
+        }
+        if self.input.starts_with(['/', '~']) {
+            self.open_path(idx);
+        } else {
+            self.launch(idx);
+        }
+    }
+
+    // Removes the graphemes between the two indices from the input.
+    fn delete(&mut self, from: usize, to: usize) {
+        if from >= to {
+            return;
+        }
+        remove_range(&mut self.input, from, to);
+        self.offset = 0;
+        self.result = None;
+        self.dirty = true;
+    }
+
+    fn categories(&self) -> Vec<String> {
+        let mut categories: Vec<String> = self
+            .options
+            .iter()
+            .flat_map(|d| d.categories.iter().cloned())
+            .collect();
+        categories.sort();
+        categories.dedup();
+        categories
+    }
+
+    // Draws what applies to the search in front of the input, which is
+    // private mode and the category searched within. Returns their width.
+    fn draw_chips(&self, buf: &mut Buffer, bg: &Color) -> Result<u32, ::std::io::Error> {
+        let mut x_off = 0;
+        if private::is_private() {
+            let dim = self.prompt.font.borrow_mut().auto_draw_text(
+                &mut self.prompt.at(buf, x_off, self.height)?,
+                bg,
+                &Color::new(0.5, 0.5, 0.5, 1.0),
+                "private",
+            )?;
+            x_off += dim.0 + self.prompt.size / 2;
+        }
+        if self.most_used {
+            let dim = self.prompt.font.borrow_mut().auto_draw_text(
+                &mut self.prompt.at(buf, x_off, self.height)?,
+                bg,
+                &Color::new(0.5, 0.5, 0.5, 1.0),
+                "most used",
+            )?;
+            x_off += dim.0 + self.prompt.size / 2;
+        }
+        if let Some(category) = &self.category {
+            let dim = self.prompt.font.borrow_mut().auto_draw_text(
+                &mut self.prompt.at(buf, x_off, self.height)?,
+                bg,
+                &Color::new(1.0, 1.0, 0.0, 1.0),
+                &format!("@{}", category),
+            )?;
+            x_off += dim.0 + self.prompt.size / 2;
+        }
+        Ok(x_off)
+    }
+
+    fn draw_categories(
+        &self,
+        buf: &mut Buffer,
+        bg: &Color,
+        width: u32,
+    ) -> Result<(), ::std::io::Error> {
+        let x_off = self
+            .prompt
+            .font
+            .borrow_mut()
+            .auto_draw_text(
+                &mut self.prompt.at(buf, 0, self.height)?,
+                bg,
+                &Color::new(1.0, 1.0, 0.0, 1.0),
+                "@",
+            )?
+            .0;
+        let mut x_off = x_off
+            + self
+                .prompt
+                .font
+                .borrow_mut()
+                .auto_draw_text_with_cursor(
+                    &mut self.prompt.at(buf, x_off, self.height)?,
+                    bg,
+                    &Color::new(1.0, 1.0, 1.0, 1.0),
+                    &self.input[1..],
+                    char_index(&self.input[1..], self.cursor.saturating_sub(1)),
+                )?
+                .0
+            + self.prompt.size / 4;
+
+        for (idx, category) in self.category_matches.iter().enumerate() {
+            if x_off >= width {
+                break;
+            }
+            let c = if idx == self.offset {
+                Color::new(1.0, 1.0, 1.0, 1.0)
+            } else {
+                Color::new(0.5, 0.5, 0.5, 1.0)
+            };
+            let size = self.results.font.borrow_mut().auto_draw_text(
+                &mut self.results.at(buf, x_off, self.height)?,
+                bg,
+                &c,
+                category,
+            )?;
+            x_off += size.0 + self.results.size / 2;
+        }
+
+        Ok(())
+    }
+
+    fn draw_paths(&self, buf: &mut Buffer, bg: &Color, width: u32) -> Result<(), ::std::io::Error> {
+        let mut x_off = self
+            .prompt
+            .font
+            .borrow_mut()
+            .auto_draw_text_with_cursor(
+                &mut self.prompt.at(buf, 0, self.height)?,
+                bg,
+                &Color::new(1.0, 1.0, 1.0, 1.0),
+                &self.input,
+                char_index(&self.input, self.cursor),
+            )?
+            .0
+            + self.prompt.size / 4;
+
+        for (idx, entry) in self.path_matches.iter().enumerate() {
+            if x_off >= width {
+                break;
+            }
+            x_off += self.draw_hint(buf, bg, idx, x_off)?;
+            let c = if idx == self.offset {
+                Color::new(1.0, 1.0, 1.0, 1.0)
+            } else if self.hover == Some(idx) {
+                Color::new(0.75, 0.75, 0.75, 1.0)
+            } else {
+                Color::new(0.5, 0.5, 0.5, 1.0)
+            };
+            // Directories are marked like they are entered
+            let name = if entry.dir {
+                format!("{}/", entry.name)
+            } else {
+                entry.name.clone()
+            };
+            let size = self.results.font.borrow_mut().auto_draw_text(
+                &mut self.results.at(buf, x_off, self.height)?,
+                bg,
+                &c,
+                &name,
+            )?;
+            self.record_hit(idx, x_off, size.0, width);
+            x_off += size.0 + self.results.size / 2;
+        }
+
+        Ok(())
+    }
+
+    // Draws the name of a match, with the letters matching the input
+    // highlighted if it is the selected one.
+    fn draw_match(
+        &self,
+        buf: &mut Buffer,
+        bg: &Color,
+        idx: usize,
+        m: &Desktop,
+    ) -> Result<(u32, u32), ::std::io::Error> {
+        let name = m.display_name();
+        if idx == self.offset {
+            let (_, indices) = matching::matcher()
+                .indices(&name.to_lowercase(), &self.input.to_lowercase())
+                .unwrap_or((0, vec![]));
+
+            let mut colors = Vec::with_capacity(name.len());
+            for pos in 0..name.len() {
+                if indices.contains(&pos) {
+                    colors.push(Color::new(1.0, 1.0, 1.0, 1.0));
+                } else {
+                    colors.push(Color::new(0.75, 0.75, 0.75, 1.0));
+                }
+            }
+            self.results
+                .font
+                .borrow_mut()
+                .auto_draw_text_individual_colors(buf, bg, &colors, name)
+        } else {
+            let c = if self.hover == Some(idx) {
+                Color::new(0.75, 0.75, 0.75, 1.0)
+            } else {
+                Color::new(0.5, 0.5, 0.5, 1.0)
+            };
+            self.results
+                .font
+                .borrow_mut()
+                .auto_draw_text(buf, bg, &c, name)
+        }
+    }
+
     fn draw_launcher(
         &self,
         buf: &mut Buffer,
         bg: &Color,
         width: u32,
     ) -> Result<(), ::std::io::Error> {
+        let chip = self.draw_chips(buf, bg)?;
+        let buf = &mut buf.offset((chip, 0))?;
+        let width = width.saturating_sub(chip);
         let mut x_off = if !self.input.is_empty() {
             let c = if self.matches.is_empty() {
                 Color::new(1.0, 0.5, 0.5, 1.0)
@@ -80,53 +800,62 @@ impl<'a> Launcher<'a> {
                 Color::new(1.0, 1.0, 1.0, 1.0)
             };
 
-            let dim = self.font.borrow_mut().auto_draw_text_with_cursor(
-                buf,
+            let dim = self.prompt.font.borrow_mut().auto_draw_text_with_cursor(
+                &mut self.prompt.at(buf, 0, self.height)?,
                 bg,
                 &c,
                 &self.input,
-                self.cursor,
+                char_index(&self.input, self.cursor),
             )?;
 
-            dim.0 + self.font_size / 4
+            dim.0 + self.prompt.size / 4
         } else {
             0
         };
 
-        let mut width_remaining: i32 = (width - x_off) as i32;
-        let fuzzy_matcher = SkimMatcherV2::default();
-        for (idx, m) in self.matches.iter().enumerate() {
-            let mut b = match buf.offset((x_off, 0)) {
+        // The results are drawn above the other widgets instead
+        if self.overlay {
+            return Ok(());
+        }
+
+        // The results scroll along to keep the selection in view
+        let widths: Vec<u32> = self
+            .matches
+            .iter()
+            .take(self.offset + 1)
+            .enumerate()
+            .map(|(idx, m)| {
+                self.hint_width(idx)
+                    + self
+                        .results
+                        .font
+                        .borrow_mut()
+                        .auto_text_width(m.display_name())
+                    + self.badge_width(m)
+                    + self.results.size / 2
+            })
+            .collect();
+        let available = width.saturating_sub(x_off);
+        let mut first = 0;
+        while first < self.offset && widths[first..].iter().sum::<u32>() > available {
+            first += 1;
+        }
+
+        let mut width_remaining: i32 = width as i32 - x_off as i32;
+        for (idx, m) in self.matches.iter().enumerate().skip(first) {
+            let hint = self.draw_hint(buf, bg, idx, x_off)?;
+            x_off += hint;
+            width_remaining -= hint as i32;
+            let mut b = match self.results.at(buf, x_off, self.height) {
                 Ok(b) => b,
                 Err(_) => break,
             };
-            let size = if idx == self.offset {
-                let (_, indices) = fuzzy_matcher
-                    .fuzzy_indices(&m.name.to_lowercase(), &self.input.to_lowercase())
-                    .unwrap_or((0, vec![]));
-
-                let mut colors = Vec::with_capacity(m.name.len());
-                for pos in 0..m.name.len() {
-                    if indices.contains(&pos) {
-                        colors.push(Color::new(1.0, 1.0, 1.0, 1.0));
-                    } else {
-                        colors.push(Color::new(0.75, 0.75, 0.75, 1.0));
-                    }
-                }
-                self.font
-                    .borrow_mut()
-                    .auto_draw_text_individual_colors(&mut b, bg, &colors, &m.name)?
-            } else {
-                self.font.borrow_mut().auto_draw_text(
-                    &mut b,
-                    bg,
-                    &Color::new(0.5, 0.5, 0.5, 1.0),
-                    &m.name,
-                )?
-            };
+            let size = self.draw_match(&mut b, bg, idx, m)?;
+            let badge = self.draw_badge(buf, bg, m, x_off + size.0)?;
 
-            x_off += size.0 + self.font_size / 2;
-            width_remaining -= (size.0 + self.font_size / 2) as i32;
+            self.record_hit(idx, chip + x_off, size.0 + badge, chip + width);
+            x_off += size.0 + badge + self.results.size / 2;
+            width_remaining -= (size.0 + badge + self.results.size / 2) as i32;
 
             if width_remaining < 0 {
                 break;
@@ -136,60 +865,351 @@ impl<'a> Launcher<'a> {
         Ok(())
     }
 
+    fn launch(&mut self, idx: usize) {
+        self.launch_with(idx, &[])
+    }
+
+    // Launches the result with the uris in place of its field codes.
+    fn launch_with(&mut self, idx: usize, uris: &[String]) {
+        if self.matches.len() > idx {
+            let d = &self.matches[idx];
+            if d.entry_type == vpn::ENTRY_TYPE {
+                if let Err(err) = self.vpns.toggle(&d.id) {
+                    eprintln!("Unable to toggle VPN connection {}: {}", d.name, err);
+                }
+                self.tx.send(Cmd::Exit).unwrap();
+                return;
+            }
+            if let Some(exec) = &d.exec {
+                let prefix = if d.term {
+                    &self.term_opener
+                } else {
+                    &self.app_opener
+                };
+
+                // Without a command of its own, only the opener would run
+                let mut lexed = match desktop::exec_argv(exec, uris) {
+                    Some(argv) if !argv.is_empty() => argv,
+                    _ => {
+                        eprintln!("Unable to launch {}: invalid Exec {}", d.name, exec);
+                        return;
+                    }
+                };
+                let lexed = if !prefix.is_empty() {
+                    let mut prefix = shlex::split(prefix).unwrap();
+                    prefix.append(&mut lexed);
+                    prefix
+                } else {
+                    lexed
+                };
+
+                if !private::is_private() {
+                    self.counter.record(&d.name, Local::now().timestamp());
+                    self.counter.save().expect("Unable to save data");
+                }
+
+                if !lexed.is_empty() {
+                    self.tx
+                        .send(Cmd::Launch {
+                            argv: lexed,
+                            app_id: Some(d.id.clone()),
+                        })
+                        .unwrap();
+                    self.tx.send(Cmd::Exit).unwrap();
+                }
+            }
+            if let Some(url) = &d.url {
+                if !self.url_opener.is_empty() {
+                    let mut lexed = shlex::split(&self.url_opener).unwrap();
+                    lexed.push(url.to_string());
+                    if !lexed.is_empty() {
+                        self.tx
+                            .send(Cmd::Launch {
+                                argv: lexed,
+                                app_id: None,
+                            })
+                            .unwrap();
+                        self.tx.send(Cmd::Exit).unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    // Copies what would be run for the selected result, or with details the
+    // path of its desktop file.
+    fn copy_details(&self, path: bool) {
+        if self.input.starts_with(['=', '!', '#', '@', '/', '~']) {
+            return;
+        }
+        let d = match self.matches.get(self.offset) {
+            Some(d) => d,
+            None => return,
+        };
+        if d.entry_type == vpn::ENTRY_TYPE {
+            return;
+        }
+        let details = if path {
+            Some(&d.path)
+        } else {
+            d.exec.as_ref().or(d.url.as_ref())
+        };
+        if let Some(details) = details {
+            if let Err(err) = wlcopy(details) {
+                eprintln!("Unable to copy launcher entry: {}", err);
+            }
+        }
+    }
+
+    // Enters the directory, or opens the file, of the result.
+    fn open_path(&mut self, idx: usize) {
+        let entry = match self.path_matches.get(idx) {
+            Some(entry) => entry.clone(),
+            None => return,
+        };
+        let dir = match self.input.rfind('/') {
+            Some(idx) => self.input[..idx + 1].to_string(),
+            None => "~/".to_string(),
+        };
+        let path = format!("{}{}", dir, entry.name);
+        if entry.dir {
+            self.input = path + "/";
+            self.cursor = self.input.graphemes(true).count();
+            self.offset = 0;
+            self.hover = None;
+            self.dirty = true;
+        } else if !self.file_opener.is_empty() {
+            let mut argv = shlex::split(&self.file_opener).unwrap_or_default();
+            argv.push(expand_home(&path).to_string_lossy().into_owned());
+            self.tx.send(Cmd::Launch { argv, app_id: None }).unwrap();
+            self.tx.send(Cmd::Exit).unwrap();
+        }
+    }
+
     fn draw_calc(&self, buf: &mut Buffer, bg: &Color) -> Result<(), ::std::io::Error> {
         let x_off = self
+            .prompt
             .font
             .borrow_mut()
-            .auto_draw_text(buf, bg, &Color::new(1.0, 1.0, 0.0, 1.0), "=")?
+            .auto_draw_text(
+                &mut self.prompt.at(buf, 0, self.height)?,
+                bg,
+                &Color::new(1.0, 1.0, 0.0, 1.0),
+                "=",
+            )?
             .0
-            + self.font_size / 4;
+            + self.prompt.size / 4;
 
         let x_off = if !self.input.is_empty() {
-            let dim = self.font.borrow_mut().auto_draw_text_with_cursor(
-                &mut buf.offset((x_off, 0))?,
+            let dim = self.prompt.font.borrow_mut().auto_draw_text_with_cursor(
+                &mut self.prompt.at(buf, x_off, self.height)?,
                 bg,
                 &Color::new(1.0, 1.0, 1.0, 1.0),
                 &self.input[1..],
-                self.cursor - 1,
+                char_index(&self.input[1..], self.cursor.saturating_sub(1)),
             )?;
 
-            x_off + dim.0 + self.font_size / 4
+            x_off + dim.0 + self.prompt.size / 4
         } else {
             0
         };
 
-        if let Some(result) = &self.result {
-            self.font.borrow_mut().auto_draw_text(
-                &mut buf.offset((x_off, 0))?,
+        let x_off = match &self.result {
+            Some(result) => {
+                let mut font = self.calc.font.borrow_mut();
+                let (width, _) = font.auto_draw_text(
+                    &mut self.calc.at(buf, x_off, self.height)?,
+                    bg,
+                    &Color::new(0.75, 0.75, 0.75, 1.0),
+                    &format!(" = {:}", result),
+                )?;
+                // Only the result itself is selected
+                let result_x = x_off + font.auto_text_width(" = ");
+                let y = self.height.saturating_sub(self.calc.size);
+                self.selection.borrow_mut().add_run(
+                    &mut font,
+                    (result_x, y),
+                    self.calc.size,
+                    result,
+                );
+                x_off + width + self.calc.size / 4
+            }
+            None => x_off,
+        };
+        self.draw_history_pos(buf, bg, x_off)?;
+
+        Ok(())
+    }
+
+    // Draws how far back in the history the entry scrolled to is, as in
+    // 3/12.
+    fn draw_history_pos(
+        &self,
+        buf: &mut Buffer,
+        bg: &Color,
+        x: u32,
+    ) -> Result<(), ::std::io::Error> {
+        let (len, pos) = match (self.mode_history(), self.history_pos) {
+            (Some((mode, history)), Some((m, pos))) if m == mode => (history.len(), pos),
+            _ => return Ok(()),
+        };
+        self.hints.font.borrow_mut().auto_draw_text(
+            &mut self.hints.at(buf, x, self.height)?,
+            bg,
+            &Color::new(0.4, 0.4, 0.4, 1.0),
+            &format!("{}/{}", len - pos, len),
+        )?;
+        Ok(())
+    }
+
+    // Draws the history search in place of the input, followed by the
+    // matching entries from the selected one on.
+    fn draw_search(
+        &self,
+        buf: &mut Buffer,
+        bg: &Color,
+        width: u32,
+    ) -> Result<(), ::std::io::Error> {
+        let search = match &self.search {
+            Some(search) => search,
+            None => return Ok(()),
+        };
+        let mut x_off = self
+            .prompt
+            .font
+            .borrow_mut()
+            .auto_draw_text(
+                &mut self.prompt.at(buf, 0, self.height)?,
                 bg,
-                &Color::new(0.75, 0.75, 0.75, 1.0),
-                &format!(" = {:}", result),
+                &Color::new(1.0, 1.0, 0.0, 1.0),
+                &format!("{}history", search.prefix),
+            )?
+            .0
+            + self.prompt.size / 2;
+
+        let c = if search.matches.is_empty() {
+            Color::new(1.0, 0.5, 0.5, 1.0)
+        } else {
+            Color::new(1.0, 1.0, 1.0, 1.0)
+        };
+        x_off += self
+            .prompt
+            .font
+            .borrow_mut()
+            .auto_draw_text_with_cursor(
+                &mut self.prompt.at(buf, x_off, self.height)?,
+                bg,
+                &c,
+                &search.query,
+                search.query.chars().count(),
+            )?
+            .0
+            + self.prompt.size / 4;
+
+        for (n, &idx) in search.matches.iter().enumerate().skip(search.selected) {
+            if x_off >= width {
+                break;
+            }
+            let c = if n == search.selected {
+                Color::new(1.0, 1.0, 1.0, 1.0)
+            } else {
+                Color::new(0.5, 0.5, 0.5, 1.0)
+            };
+            let mut b = match self.results.at(buf, x_off, self.height) {
+                Ok(b) => b,
+                Err(_) => break,
+            };
+            let size = self.results.font.borrow_mut().auto_draw_text(
+                &mut b,
+                bg,
+                &c,
+                &search.entries[idx],
             )?;
+            x_off += size.0 + self.results.size / 2;
         }
 
         Ok(())
     }
 
-    fn draw_shell(&self, buf: &mut Buffer, bg: &Color) -> Result<(), ::std::io::Error> {
-        let x_off = self
+    fn draw_shell(
+        &self,
+        buf: &mut Buffer,
+        bg: &Color,
+        prefix: &str,
+    ) -> Result<(), ::std::io::Error> {
+        let mut x_off = self
+            .prompt
             .font
             .borrow_mut()
             .auto_draw_text(
-                &mut buf.offset((0, 0))?,
+                &mut self.prompt.at(buf, 0, self.height)?,
                 bg,
                 &Color::new(1.0, 1.0, 0.0, 1.0),
-                "!",
+                prefix,
             )?
             .0
-            + self.font_size / 4;
+            + self.prompt.size / 4;
+
+        // Shell commands run through the sandbox are marked as such, unless
+        // Shift is held to run them outside of it.
+        if prefix == "!" && self.sandboxed() {
+            let (text, c) = match (self.shift, &self.shell_sandbox) {
+                (true, _) => ("unsandboxed", Color::new(1.0, 0.4, 0.4, 1.0)),
+                (false, Some(_)) => ("sandboxed", Color::new(0.4, 0.8, 0.4, 1.0)),
+                (false, None) => ("invalid sandbox", Color::new(1.0, 0.4, 0.4, 1.0)),
+            };
+            let (width, _) = self.hints.font.borrow_mut().auto_draw_text(
+                &mut self.hints.at(buf, x_off, self.height)?,
+                bg,
+                &c,
+                text,
+            )?;
+            x_off += width + self.prompt.size / 4;
+        }
 
         if !self.input.is_empty() {
-            self.font.borrow_mut().auto_draw_text_with_cursor(
-                &mut buf.offset((x_off, 0))?,
+            let command = &self.input[1..];
+            let cursor = char_index(command, self.cursor.saturating_sub(1));
+            // A command that cannot be found is tinted up to the end of its
+            // first word, as it would fail once the dashboard is gone
+            let unknown = prefix == "!" && self.executables.resolves(command) == Some(false);
+            let split = if unknown {
+                command.len() - command.trim_start().len()
+                    + command
+                        .trim_start()
+                        .find(char::is_whitespace)
+                        .unwrap_or(command.trim_start().len())
+            } else {
+                0
+            };
+            let (head, tail) = command.split_at(split);
+            let head_chars = head.chars().count();
+
+            let mut font = self.prompt.font.borrow_mut();
+            let (head_width, _) = font.auto_draw_text_with_cursor(
+                &mut self.prompt.at(buf, x_off, self.height)?,
+                bg,
+                &Color::new(1.0, 0.4, 0.4, 1.0),
+                head,
+                // Past the end, so that the cursor is not drawn here
+                if cursor < head_chars {
+                    cursor
+                } else {
+                    usize::MAX
+                },
+            )?;
+            let (tail_width, _) = font.auto_draw_text_with_cursor(
+                &mut self.prompt.at(buf, x_off + head_width, self.height)?,
                 bg,
                 &Color::new(1.0, 1.0, 1.0, 1.0),
-                &self.input[1..],
-                self.cursor - 1,
+                tail,
+                cursor.saturating_sub(head_chars),
+            )?;
+            drop(font);
+            self.draw_history_pos(
+                buf,
+                bg,
+                x_off + head_width + tail_width + self.prompt.size / 4,
             )?;
         };
 
@@ -197,13 +1217,135 @@ impl<'a> Launcher<'a> {
     }
 }
 
+// The byte offset of the grapheme at the given index.
+// The sandbox command split into its arguments, empty if there is none.
+// None if it cannot be split, such as for an unbalanced quote, or if it has
+// no program to run, so that shell commands are never run outside of it by
+// mistake.
+fn sandbox_argv(sandbox: &str) -> Option<Vec<String>> {
+    if sandbox.trim().is_empty() {
+        return Some(Vec::new());
+    }
+    shlex::split(sandbox).filter(|argv| argv.first().is_some_and(|program| !program.is_empty()))
+}
+
+fn grapheme_offset(s: &str, idx: usize) -> usize {
+    s.grapheme_indices(true)
+        .nth(idx)
+        .map(|(offset, _)| offset)
+        .unwrap_or(s.len())
+}
+
+// The char index of the grapheme at the given index, which is where the
+// cursor is drawn.
+fn char_index(s: &str, idx: usize) -> usize {
+    s[..grapheme_offset(s, idx)].chars().count()
+}
+
+// Inserts text before the grapheme at the given index, and returns the
+// index right after it. Text such as a combining accent joins the grapheme
+// before it, so this is not always the index plus the length of the text.
+fn insert_at(s: &mut String, idx: usize, text: &str) -> usize {
+    let offset = grapheme_offset(s, idx);
+    s.insert_str(offset, text);
+    s[..offset + text.len()].graphemes(true).count()
+}
+
+// Removes the graphemes from one index up to another.
+fn remove_range(s: &mut String, from: usize, to: usize) {
+    let range = grapheme_offset(s, from)..grapheme_offset(s, to);
+    s.replace_range(range, "");
+}
+
+// The words of the string as byte ranges, leaving out whitespace and
+// punctuation between them.
+fn words(s: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    s.split_word_bound_indices()
+        .filter(|(_, word)| word.chars().any(char::is_alphanumeric))
+        .map(|(start, word)| (start, start + word.len()))
+}
+
+// The grapheme index of the start of the word before the cursor.
+fn prev_word(s: &str, cursor: usize) -> usize {
+    let offset = grapheme_offset(s, cursor);
+    let start = words(s)
+        .take_while(|&(start, _)| start < offset)
+        .last()
+        .map_or(0, |(start, _)| start);
+    s[..start].graphemes(true).count()
+}
+
+// The grapheme index of the end of the word after the cursor.
+fn next_word(s: &str, cursor: usize) -> usize {
+    let offset = grapheme_offset(s, cursor);
+    let end = words(s)
+        .find(|&(_, end)| end > offset)
+        .map_or(s.len(), |(_, end)| end);
+    s[..end].graphemes(true).count()
+}
+
+#[cfg(feature = "calc")]
 fn calc(s: &str) -> Result<String, String> {
     rcalc_lib::parse::eval(s, &mut rcalc_lib::parse::CalcState::new())
         .map(|x| format!("{}", x))
         .map_err(|x| format!("{}", x))
 }
 
-fn wlcopy(s: &str) -> Result<(), String> {
+#[cfg(not(feature = "calc"))]
+fn calc(_: &str) -> Result<String, String> {
+    Err("compiled without calculator support".to_string())
+}
+
+/// Reads the primary selection through wl-paste.
+pub fn wlpaste_primary() -> Result<String, String> {
+    let output = std::process::Command::new("wl-paste")
+        .args(["--primary", "--no-newline"])
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .map_err(|_| "wl-paste not available".to_string())?;
+    if !output.status.success() {
+        return Err("no primary selection".to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+const SHELL_HISTORY_FILE: &str = "shell_history";
+const CALC_HISTORY_FILE: &str = "calc_history";
+const HISTORY_LENGTH: usize = 100;
+
+fn load_history(file: &str) -> Vec<String> {
+    state_file(file)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|history| history.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn save_history(file: &str, history: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut content = history.join("\n");
+    content.push('\n');
+    fs::write(state_file(file)?, content)?;
+    Ok(())
+}
+
+// Adds the entry to the end of the history, and saves it, unless in private
+// mode. An entry already in the history is moved to the end.
+fn remember(history: &mut Vec<String>, file: &str, entry: &str) {
+    if private::is_private() || entry.trim().is_empty() {
+        return;
+    }
+    history.retain(|e| e != entry);
+    history.push(entry.to_string());
+    let excess = history.len().saturating_sub(HISTORY_LENGTH);
+    history.drain(..excess);
+    if let Err(err) = save_history(file, history) {
+        eprintln!("Unable to save {}: {}", file.replace('_', " "), err);
+    }
+}
+
+/// Copies the string to the clipboard through wl-copy.
+pub fn wlcopy(s: &str) -> Result<(), String> {
     let mut child = std::process::Command::new("wl-copy")
         .stdin(std::process::Stdio::null())
         .stdout(std::process::Stdio::null())
@@ -217,22 +1359,36 @@ fn wlcopy(s: &str) -> Result<(), String> {
     Ok(())
 }
 
-struct Matcher {
+struct Ranking {
+    matcher: Box<dyn Matcher>,
     matches: HashMap<Desktop, i64>,
     counter: Data,
+    most_used: bool,
 }
 
-impl Matcher {
-    fn new(counter: Data) -> Self {
+impl Ranking {
+    fn new(counter: Data, most_used: bool) -> Self {
         Self {
+            matcher: matching::matcher(),
             matches: HashMap::new(),
             counter,
+            most_used,
+        }
+    }
+
+    // Best first. The launches count towards how well a result matches, or
+    // sort the results by themselves when sorting by the most used.
+    fn score(&self, ma: i64, d: &Desktop) -> (i64, i64) {
+        let count = *self.counter.entries.get(&d.name).unwrap_or(&0);
+        if self.most_used {
+            (count, ma)
+        } else {
+            (ma + count, 0)
         }
     }
 
     fn try_match(&mut self, dtop: Desktop, val: &str, input: &str, prio: f32) {
-        let fuzzy_matcher = SkimMatcherV2::default();
-        if let Some(ma) = fuzzy_matcher.fuzzy_match(val, input) {
+        if let Some(ma) = self.matcher.score(val, input) {
             let ma = ((ma as f32) * prio) as i64;
             if let Some(ma_old) = self.matches.get(&dtop) {
                 // Skip over new matches for the same program that are worse
@@ -254,16 +1410,16 @@ impl Matcher {
             .collect::<Vec<(i64, Desktop)>>();
 
         m.sort_by(|(ma1, d1), (ma2, d2)| {
-            let count1 = self.counter.entries.get(&d1.name).unwrap_or(&0);
-            let count2 = self.counter.entries.get(&d2.name).unwrap_or(&0);
+            let score1 = self.score(*ma1, d1);
+            let score2 = self.score(*ma2, d2);
 
-            if ma1 + count1 > ma2 + count2 {
+            if score1 > score2 {
                 Ordering::Less
-            } else if ma1 + count1 < ma2 + count2 {
+            } else if score1 < score2 {
                 Ordering::Greater
-            } else if d1.name.len() < d2.name.len() {
+            } else if d1.display_name().len() < d2.display_name().len() {
                 Ordering::Less
-            } else if d1.name.len() > d2.name.len() {
+            } else if d1.display_name().len() > d2.display_name().len() {
                 Ordering::Greater
             } else {
                 d1.cmp(d2)
@@ -275,18 +1431,31 @@ impl Matcher {
 }
 
 impl<'a> Widget for Launcher<'a> {
-    fn wait(&mut self, _: &mut WaitContext) {}
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        // The cursor is only drawn, and so only blinks, with input
+        if let (Some(blink), Some(start)) = (self.blink(), self.blink_start) {
+            if !self.input.is_empty() {
+                let now = Local::now().naive_local();
+                let phases = (now - start).num_milliseconds() / blink.num_milliseconds();
+                ctx.set_time(start + blink * (phases as i32 + 1));
+            }
+        }
+    }
     fn enter(&mut self) {}
     fn leave(&mut self) {
+        self.search = None;
         self.input = "".to_string();
         self.cursor = 0;
         self.offset = 0;
         self.result = None;
+        self.category = None;
+        self.hover = None;
+        self.selection.get_mut().clear();
         self.dirty = true;
     }
 
     fn size(&self) -> (u32, u32) {
-        (self.length, self.font_size)
+        (self.length, self.height)
     }
 
     fn draw(
@@ -298,11 +1467,39 @@ impl<'a> Widget for Launcher<'a> {
         if self.length == 0 {
             self.length = expansion.0;
         }
-        let (width, height) = (self.length, self.font_size);
-        if !self.dirty && !ctx.force {
+        self.pos = pos;
+        let (width, height) = (self.length, self.height);
+        if let Some(blink) = self.blink() {
+            let start = *self.blink_start.get_or_insert(ctx.time);
+            let phase = (ctx.time - start).num_milliseconds() / blink.num_milliseconds();
+            if (phase % 2 == 0) != self.cursor_shown {
+                self.cursor_shown = !self.cursor_shown;
+                self.dirty = true;
+            }
+        } else if !self.cursor_shown {
+            // Motion was reduced while the cursor was blinked away
+            self.cursor_shown = true;
+            self.dirty = true;
+        }
+        if let Some(options) = self.desktop_files.take_reloaded() {
+            self.options = options;
+            self.dirty = true;
+        }
+        let listed = self.dir_reader.take_updated()
+            | self.vpns.take_updated()
+            | self.executables.take_updated();
+        if !self.dirty && !listed && !ctx.force {
             return Ok(DrawReport::empty(width, height));
         }
         self.dirty = false;
+        self.prompt
+            .font
+            .borrow_mut()
+            .set_cursor(if self.cursor_shown {
+                self.cursor_style
+            } else {
+                Cursor::Hidden
+            });
 
         match self.input.chars().next() {
             Some('=') => {
@@ -313,17 +1510,49 @@ impl<'a> Widget for Launcher<'a> {
                     }
                 }
             }
-            Some('!') => (),
+            Some('!') | Some('#') => (),
+            Some('/') | Some('~') => {
+                let (dir, filter) = split_path(&self.input);
+                self.path_matches = self.dir_reader.matches(&dir, filter).unwrap_or_default();
+            }
+            Some('@') => {
+                let input = self.input[1..].to_lowercase();
+                let matcher = matching::matcher();
+                let mut matches: Vec<(i64, String)> = self
+                    .categories()
+                    .into_iter()
+                    .filter_map(|c| matcher.score(&c.to_lowercase(), &input).map(|ma| (ma, c)))
+                    .collect();
+                matches.sort_by(|(ma1, c1), (ma2, c2)| ma2.cmp(ma1).then(c1.cmp(c2)));
+                self.category_matches = matches.into_iter().map(|(_, c)| c).collect();
+            }
             _ => {
-                let mut matcher = Matcher::new(self.counter.clone());
+                let mut matcher = Ranking::new(self.counter.clone(), self.most_used);
+                let vpns = self.vpns.entries();
 
-                for desktop in self.options.iter() {
+                for desktop in
+                    self.options
+                        .iter()
+                        .chain(vpns.iter())
+                        .filter(|d| match &self.category {
+                            Some(category) => d.categories.contains(category),
+                            None => true,
+                        })
+                {
                     matcher.try_match(
                         desktop.clone(),
                         &desktop.name.to_lowercase(),
                         &self.input.to_lowercase(),
                         1.0,
                     );
+                    if let Some(name) = &desktop.localized_name {
+                        matcher.try_match(
+                            desktop.clone(),
+                            &name.to_lowercase(),
+                            &self.input.to_lowercase(),
+                            1.0,
+                        );
+                    }
                     for keyword in desktop.keywords.iter() {
                         matcher.try_match(
                             desktop.clone(),
@@ -332,6 +1561,14 @@ impl<'a> Widget for Launcher<'a> {
                             0.5,
                         );
                     }
+                    if let Some(comment) = &desktop.comment {
+                        matcher.try_match(
+                            desktop.clone(),
+                            &comment.to_lowercase(),
+                            &self.input.to_lowercase(),
+                            0.25,
+                        );
+                    }
                 }
 
                 self.matches = matcher.matches();
@@ -341,11 +1578,19 @@ impl<'a> Widget for Launcher<'a> {
         let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
         buf.memset(ctx.bg);
 
+        // Every mode records the results it draws again
+        self.hits.borrow_mut().clear();
+        self.selection.borrow_mut().clear_runs();
         match self.input.chars().next() {
+            _ if self.search.is_some() => self.draw_search(buf, ctx.bg, width),
             Some('=') => self.draw_calc(buf, ctx.bg),
-            Some('!') => self.draw_shell(buf, ctx.bg),
+            Some('!') => self.draw_shell(buf, ctx.bg, "!"),
+            Some('#') => self.draw_shell(buf, ctx.bg, "#"),
+            Some('@') => self.draw_categories(buf, ctx.bg, width),
+            Some('/') | Some('~') => self.draw_paths(buf, ctx.bg, width),
             _ => self.draw_launcher(buf, ctx.bg, width),
         }?;
+        self.selection.borrow().draw(buf)?;
 
         Ok(DrawReport {
             width,
@@ -362,141 +1607,134 @@ impl<'a> Widget for Launcher<'a> {
         _: KeyState,
         interpreted: Option<String>,
     ) {
-        match key {
-            keysyms::XKB_KEY_u if modifiers.ctrl => self.leave(),
-            keysyms::XKB_KEY_a if modifiers.ctrl => {
+        // The cursor stays shown while typing
+        self.blink_start = None;
+        if self.search.is_some() {
+            self.search_input(key, modifiers, interpreted);
+            return;
+        }
+        // Alt and a number activates that result without selecting it
+        if modifiers.alt && (keysyms::XKB_KEY_1..=keysyms::XKB_KEY_9).contains(&key) {
+            self.activate_visible((key - keysyms::XKB_KEY_1) as usize);
+            return;
+        }
+        match keybindings::lookup(keybindings::LAUNCHER, key, modifiers) {
+            Some(LauncherKey::Clear) => self.leave(),
+            Some(LauncherKey::CopyPath) => self.copy_details(true),
+            Some(LauncherKey::CopyCommand) => self.copy_details(false),
+            Some(LauncherKey::Private) => {
+                self.tx.send(Cmd::Private(None)).unwrap();
+            }
+            Some(LauncherKey::Search) => self.search_history(),
+            Some(LauncherKey::Sort) => {
+                self.most_used = !self.most_used;
+                self.offset = 0;
+                self.dirty = true;
+            }
+            Some(LauncherKey::Help) if self.input.is_empty() => {
+                self.tx.send(Cmd::Action(Action::ToggleHelp)).unwrap();
+            }
+            Some(LauncherKey::Start) => {
                 self.cursor = 0;
                 self.dirty = true;
             }
-            keysyms::XKB_KEY_e if modifiers.ctrl => {
-                self.cursor = self.input.len();
+            Some(LauncherKey::End) => {
+                self.cursor = self.input.graphemes(true).count();
                 self.dirty = true;
             }
-            keysyms::XKB_KEY_Home => {
-                self.cursor = 0;
+            Some(LauncherKey::WordLeft) => {
+                self.cursor = prev_word(&self.input, self.cursor);
                 self.dirty = true;
             }
-            keysyms::XKB_KEY_End => {
-                self.cursor = self.input.len();
+            Some(LauncherKey::WordRight) => {
+                self.cursor = next_word(&self.input, self.cursor);
                 self.dirty = true;
             }
-            keysyms::XKB_KEY_BackSpace => {
-                let mut indices: Vec<(usize, &str)> = self.input.grapheme_indices(true).collect();
-                if !indices.is_empty() && self.cursor > 0 {
+            Some(LauncherKey::DeleteWordLeft) => {
+                let start = prev_word(&self.input, self.cursor);
+                self.delete(start, self.cursor);
+                self.cursor = start;
+            }
+            Some(LauncherKey::DeleteWordRight) => {
+                let end = next_word(&self.input, self.cursor);
+                self.delete(self.cursor, end);
+            }
+            Some(LauncherKey::DeleteLeft) => {
+                if self.input.is_empty() && self.category.is_some() {
+                    self.category = None;
+                    self.dirty = true;
+                } else if self.cursor > 0 {
                     self.cursor -= 1;
-                    indices.remove(self.cursor);
-                    self.input = indices.iter().fold("".into(), |acc, el| acc + el.1);
-                    self.offset = 0;
-                    self.result = None;
-                    self.dirty = true
+                    self.delete(self.cursor, self.cursor + 1);
                 }
             }
-            keysyms::XKB_KEY_Delete => {
-                let mut indices: Vec<(usize, &str)> = self.input.grapheme_indices(true).collect();
-                if !indices.is_empty() && self.cursor < indices.len() {
-                    indices.remove(self.cursor);
-                    self.input = indices.iter().fold("".into(), |acc, el| acc + el.1);
-                    self.dirty = true;
+            Some(LauncherKey::DeleteRight) => {
+                if self.cursor < self.input.graphemes(true).count() {
+                    self.delete(self.cursor, self.cursor + 1);
                 }
             }
-            keysyms::XKB_KEY_Return => {
+            Some(LauncherKey::ActivateUnsandboxed) if self.input.starts_with('!') => {
+                self.run_shell(false)
+            }
+            Some(LauncherKey::Activate) | Some(LauncherKey::ActivateUnsandboxed) => {
                 match self.input.chars().next() {
                     Some('=') => {
                         if let Some(ref v) = self.result {
                             let _ = wlcopy(&v);
+                            let expression = self.input[1..].to_string();
+                            remember(&mut self.calc_history, CALC_HISTORY_FILE, &expression);
                         }
                     }
-                    Some('!') => {
-                        self.cursor = 0;
-                        let _ = Command::new("sh")
-                            .arg("-c")
-                            .arg(self.input.chars().skip(1).collect::<String>())
-                            .spawn();
-                        self.tx.send(Cmd::Exit).unwrap();
+                    Some('!') => self.run_shell(self.sandboxed()),
+                    Some('#') => {
+                        let note = self.input.chars().skip(1).collect::<String>();
+                        if let Err(err) = append_note(note.trim()) {
+                            eprintln!("Error while trying to save note: {}", err);
+                        }
+                        self.leave();
                     }
-                    _ => {
-                        if self.matches.len() > self.offset {
-                            let d = &self.matches[self.offset];
-                            if let Some(exec) = &d.exec {
-                                let exec = exec
-                                    .replace("%f", "")
-                                    .replace("%F", "")
-                                    .replace("%u", "")
-                                    .replace("%U", "");
-                                let prefix = if d.term {
-                                    &self.term_opener
-                                } else {
-                                    &self.app_opener
-                                };
-
-                                let mut lexed = shlex::split(&exec).unwrap();
-                                let lexed = if !prefix.is_empty() {
-                                    let mut prefix = shlex::split(prefix).unwrap();
-                                    prefix.append(&mut lexed);
-                                    prefix
-                                } else {
-                                    lexed
-                                };
-
-                                *self.counter.entries.entry(d.name.clone()).or_insert(0) += 1;
-                                self.counter.save().expect("Unable to save data");
-
-                                if !lexed.is_empty() {
-                                    let _ =
-                                        Command::new(lexed[0].clone()).args(&lexed[1..]).spawn();
-                                    self.tx.send(Cmd::Exit).unwrap();
-                                }
-                            }
-                            if let Some(url) = &d.url {
-                                if !self.url_opener.is_empty() {
-                                    let mut lexed = shlex::split(&self.url_opener).unwrap();
-                                    lexed.push(url.to_string());
-                                    if !lexed.is_empty() {
-                                        let _ = Command::new(lexed[0].clone())
-                                            .args(&lexed[1..])
-                                            .spawn();
-                                        self.tx.send(Cmd::Exit).unwrap();
-                                    }
-                                }
-                            }
+                    Some('/') | Some('~') => self.open_path(self.offset),
+                    Some('@') => {
+                        if let Some(category) = self.category_matches.get(self.offset) {
+                            self.category = Some(category.clone());
+                            self.input = "".to_string();
+                            self.cursor = 0;
+                            self.offset = 0;
+                            self.dirty = true;
                         }
                     }
+                    _ => self.launch(self.offset),
                 };
             }
-            keysyms::XKB_KEY_Tab => {
-                if !self.matches.is_empty() && self.offset < self.matches.len() - 1 {
+            Some(LauncherKey::Next) => {
+                if !self.expand_shortcode() && self.offset + 1 < self.match_count() {
                     self.offset += 1;
                     self.dirty = true;
                 }
             }
-            keysyms::XKB_KEY_ISO_Left_Tab => {
-                if !self.matches.is_empty() && self.offset > 0 {
+            Some(LauncherKey::Previous) => {
+                if self.match_count() > 0 && self.offset > 0 {
                     self.offset -= 1;
                     self.dirty = true;
                 }
             }
-            keysyms::XKB_KEY_Left => {
+            Some(LauncherKey::OlderEntry) => self.scroll_history(-1),
+            Some(LauncherKey::NewerEntry) => self.scroll_history(1),
+            Some(LauncherKey::Left) => {
                 if self.cursor > 0 {
                     self.cursor -= 1;
                     self.dirty = true;
                 }
             }
-            keysyms::XKB_KEY_Right => {
-                if self.cursor < self.input.len() {
+            Some(LauncherKey::Right) => {
+                if self.cursor < self.input.graphemes(true).count() {
                     self.cursor += 1;
                     self.dirty = true;
                 }
             }
             _ => {
                 if let Some(v) = interpreted {
-                    let indices: Vec<(usize, &str)> = self.input.grapheme_indices(true).collect();
-                    if self.cursor == indices.len() {
-                        self.input += &v;
-                    } else {
-                        let index_at = indices[self.cursor].0;
-                        self.input.insert_str(index_at, &v);
-                    }
-                    self.cursor += 1;
+                    self.cursor = insert_at(&mut self.input, self.cursor, &v);
                     self.offset = 0;
                     self.result = None;
                     self.dirty = true;
@@ -504,6 +1742,374 @@ impl<'a> Widget for Launcher<'a> {
             }
         }
     }
-    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
-    fn mouse_scroll(&mut self, _: (f64, f64), _: (u32, u32)) {}
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        if self.selection.get_mut().press(button, pos) {
+            self.dirty = true;
+        }
+    }
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        // Dragging across the calculator result selects and copies it
+        if self.selection.get_mut().release(button) {
+            self.dirty = true;
+            return;
+        }
+        // Middle-click pastes the primary selection into a shell command
+        if button == BTN_MIDDLE && self.input.starts_with('!') {
+            match wlpaste_primary() {
+                Ok(text) => self.paste(&text),
+                Err(err) => eprintln!("Unable to paste: {}", err),
+            }
+            return;
+        }
+        if button != 272 || self.input.starts_with(['=', '!', '#', '@']) {
+            return;
+        }
+        let idx = match self.hit(pos) {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        // Clicking selects, and clicking the selection again shortly after
+        // launches it.
+        let now = Instant::now();
+        let double_click = match self.last_click {
+            Some((last, time)) => last == idx && now - time < Duration::from_millis(400),
+            None => false,
+        };
+        self.offset = idx;
+        self.dirty = true;
+        if double_click {
+            self.last_click = None;
+            if self.input.starts_with(['/', '~']) {
+                self.open_path(idx);
+            } else {
+                self.launch(idx);
+            }
+        } else {
+            self.last_click = Some((idx, now));
+        }
+    }
+    fn mouse_scroll(&mut self, scroll: Scroll, _: (u32, u32)) {
+        if self.search.is_some() {
+            return;
+        }
+        // Scrolling up goes back through the shell or calc history
+        if self.input.starts_with(['!', '=']) {
+            self.scroll_history(scroll.steps.1);
+            return;
+        }
+        if self.input.starts_with(['=', '!', '#', '@']) || self.match_count() == 0 {
+            return;
+        }
+        // Scrolling down or right moves the selection forward
+        let steps = scroll.steps.0 + scroll.steps.1;
+        if steps == 0 {
+            return;
+        }
+        let last = self.match_count() as i64 - 1;
+        let offset = (self.offset as i64 + steps as i64).clamp(0, last) as usize;
+        if offset != self.offset {
+            self.offset = offset;
+            self.dirty = true;
+        }
+    }
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        if self.selection.get_mut().motion(pos) {
+            self.dirty = true;
+        }
+        let hover = pos.and_then(|pos| self.hit(pos));
+        if hover != self.hover {
+            self.hover = hover;
+            self.dirty = true;
+        }
+    }
+
+    fn action(&mut self, action: &Action) {
+        match action {
+            Action::Modifiers(modifiers)
+                if modifiers.alt != self.alt || modifiers.shift != self.shift =>
+            {
+                self.alt = modifiers.alt;
+                self.shift = modifiers.shift;
+                self.dirty = true;
+            }
+            Action::ClearPrompt => self.leave(),
+            // Dropping files on a result opens them with it
+            Action::Drop(uris) if !self.input.starts_with(['=', '!', '#', '@', '/', '~']) => {
+                let idx = match self.hover {
+                    Some(idx) => idx,
+                    None => return,
+                };
+                match self.matches.get(idx) {
+                    Some(d) if d.exec.as_deref().is_some_and(desktop::takes_uris) => {
+                        self.launch_with(idx, uris)
+                    }
+                    Some(d) => eprintln!("{} does not open files", d.name),
+                    None => (),
+                }
+            }
+            Action::Refresh => {
+                self.options = load_desktop_files(self.language.as_deref());
+                self.vpns = Vpns::new(self.tx.clone());
+                self.dirty = true;
+            }
+            _ => (),
+        }
+    }
+
+    fn has_overlay(&self) -> bool {
+        // Only the search of applications is listed, the other modes keep
+        // to the prompt line.
+        self.overlay
+            && self.search.is_none()
+            && !self.matches.is_empty()
+            && !self.input.is_empty()
+            && !self.input.starts_with(['=', '!', '#', '@', '/', '~'])
+    }
+
+    fn draw_overlay(&mut self, ctx: &mut DrawContext) -> Result<(), ::std::io::Error> {
+        let bounds = ctx.buf.get_bounds();
+        let line = self.height;
+        // The selected result is kept in view
+        let first = self.offset.saturating_sub(OVERLAY_ROWS - 1);
+        let shown = self.matches.len().saturating_sub(first).min(OVERLAY_ROWS);
+        let list_height = line * shown as u32;
+
+        // The list drops down from the prompt, or up from it when there is
+        // no room below.
+        let below = self.pos.1 + self.height;
+        let y = if below + list_height <= bounds.3 {
+            below
+        } else {
+            self.pos.1.saturating_sub(list_height)
+        };
+        let width = self.length.min(bounds.2.saturating_sub(self.pos.0));
+        let list_height = list_height.min(bounds.3.saturating_sub(y));
+
+        // The widgets behind are shaded for the list to stand out, after
+        // which the prompt line and the list are drawn on top.
+        ctx.buf.blend(&Color::new(0.0, 0.0, 0.0, 1.0), 0.5);
+        let buf = &mut ctx
+            .buf
+            .subdimensions((self.pos.0, self.pos.1, width, self.height))?;
+        buf.memset(ctx.bg);
+        self.draw_launcher(buf, ctx.bg, width)?;
+
+        let buf = &mut ctx.buf.subdimensions((self.pos.0, y, width, list_height))?;
+        buf.memset(ctx.bg);
+        for (row, (idx, m)) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(shown)
+            .enumerate()
+        {
+            let row_y = row as u32 * line;
+            if row_y + line > list_height {
+                break;
+            }
+            let row = &mut buf.offset((0, row_y))?;
+            let hint = self.draw_hint(row, ctx.bg, idx, 0)?;
+            let mut b = self.results.at(row, hint, self.height)?;
+            let size = self.draw_match(&mut b, ctx.bg, idx, m)?;
+            let badge = self.draw_badge(row, ctx.bg, m, hint + size.0)?;
+            // Recorded for Alt and a number to activate the results listed
+            // here, as the pointer does not reach past the launcher line.
+            self.hits.borrow_mut().push(Hit {
+                idx,
+                x: hint,
+                y: self.height + row_y,
+                width: size.0 + badge,
+                height: line,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Types the text one grapheme at a time at the cursor, the way the
+    // prompt does, and returns the new cursor.
+    fn type_text(s: &mut String, mut cursor: usize, text: &str) -> usize {
+        for g in text.graphemes(true) {
+            cursor = insert_at(s, cursor, g);
+        }
+        cursor
+    }
+
+    fn backspace(s: &mut String, cursor: usize) -> usize {
+        if cursor == 0 {
+            return 0;
+        }
+        remove_range(s, cursor - 1, cursor);
+        cursor - 1
+    }
+
+    fn end(s: &str) -> usize {
+        s.graphemes(true).count()
+    }
+
+    #[test]
+    fn sandbox_commands() {
+        assert_eq!(sandbox_argv(""), Some(vec![]));
+        assert_eq!(sandbox_argv("  "), Some(vec![]));
+        assert_eq!(
+            sandbox_argv("systemd-run --user --wait -p 'ProtectHome=read-only'"),
+            Some(
+                [
+                    "systemd-run",
+                    "--user",
+                    "--wait",
+                    "-p",
+                    "ProtectHome=read-only"
+                ]
+                .map(String::from)
+                .to_vec()
+            )
+        );
+        // Unbalanced quotes, only a comment, or no program at all
+        assert_eq!(sandbox_argv("bwrap --bind '/ /"), None);
+        assert_eq!(sandbox_argv("bwrap \\"), None);
+        assert_eq!(sandbox_argv("# bwrap"), None);
+        assert_eq!(sandbox_argv("'' --unshare-all"), None);
+    }
+
+    #[test]
+    fn append() {
+        let mut s = String::new();
+        let cursor = type_text(&mut s, 0, "fïrefox 🦊");
+        assert_eq!(s, "fïrefox 🦊");
+        assert_eq!(cursor, 9);
+        assert_eq!(cursor, end(&s));
+    }
+
+    #[test]
+    fn insert_between_multibyte() {
+        let mut s = "äö".to_string();
+        assert_eq!(insert_at(&mut s, 1, "x"), 2);
+        assert_eq!(s, "äxö");
+        assert_eq!(insert_at(&mut s, 0, "🎉"), 1);
+        assert_eq!(s, "🎉äxö");
+        assert_eq!(insert_at(&mut s, 2, "ß"), 3);
+        assert_eq!(s, "🎉äßxö");
+    }
+
+    #[test]
+    fn insert_past_end_appends() {
+        let mut s = "ab".to_string();
+        assert_eq!(insert_at(&mut s, 10, "ü"), 3);
+        assert_eq!(s, "abü");
+    }
+
+    #[test]
+    fn insert_pasted_text() {
+        let mut s = "!ls ".to_string();
+        assert_eq!(insert_at(&mut s, 4, "~/Bilder/Fotos 📷"), 20);
+        assert_eq!(s, "!ls ~/Bilder/Fotos 📷");
+        assert_eq!(end(&s), 20);
+    }
+
+    #[test]
+    fn combining_accent_joins_grapheme() {
+        let mut s = String::new();
+        let cursor = type_text(&mut s, 0, "e");
+        let cursor = insert_at(&mut s, cursor, "\u{301}");
+        assert_eq!(s, "e\u{301}");
+        assert_eq!(cursor, 1);
+        assert_eq!(end(&s), 1);
+        // The cursor is drawn after both chars
+        assert_eq!(char_index(&s, cursor), 2);
+    }
+
+    #[test]
+    fn backspace_removes_whole_grapheme() {
+        let mut s = "a👨‍👩‍👧e\u{301}".to_string();
+        let mut cursor = end(&s);
+        assert_eq!(cursor, 3);
+        cursor = backspace(&mut s, cursor);
+        assert_eq!(s, "a👨‍👩‍👧");
+        cursor = backspace(&mut s, cursor);
+        assert_eq!(s, "a");
+        cursor = backspace(&mut s, cursor);
+        assert_eq!(s, "");
+        assert_eq!(backspace(&mut s, cursor), 0);
+        assert_eq!(s, "");
+    }
+
+    #[test]
+    fn backspace_in_middle() {
+        let mut s = "häll😀".to_string();
+        let cursor = backspace(&mut s, 2);
+        assert_eq!(cursor, 1);
+        assert_eq!(s, "hll😀");
+    }
+
+    #[test]
+    fn delete_forward() {
+        let mut s = "ñandú🐍".to_string();
+        remove_range(&mut s, 0, 1);
+        assert_eq!(s, "andú🐍");
+        remove_range(&mut s, 3, 4);
+        assert_eq!(s, "and🐍");
+        remove_range(&mut s, 3, 4);
+        assert_eq!(s, "and");
+        // Nothing to delete at the end
+        remove_range(&mut s, 3, 4);
+        assert_eq!(s, "and");
+    }
+
+    #[test]
+    fn home_and_end() {
+        let s = "¡olé 🇳🇴!";
+        assert_eq!(end(s), 7);
+        assert_eq!(grapheme_offset(s, 0), 0);
+        assert_eq!(grapheme_offset(s, end(s)), s.len());
+        assert_eq!(char_index(s, 0), 0);
+        assert_eq!(char_index(s, end(s)), s.chars().count());
+    }
+
+    #[test]
+    fn grapheme_offsets() {
+        let s = "aä🦀b";
+        assert_eq!(grapheme_offset(s, 1), 1);
+        assert_eq!(grapheme_offset(s, 2), 3);
+        assert_eq!(grapheme_offset(s, 3), 7);
+        assert_eq!(grapheme_offset(s, 4), 8);
+        assert_eq!(grapheme_offset(s, 5), 8);
+    }
+
+    #[test]
+    fn word_movement() {
+        let s = "grüße an 🌍 welt";
+        assert_eq!(next_word(s, 0), 5);
+        assert_eq!(next_word(s, 5), 8);
+        // The emoji is not a word, so it is skipped
+        assert_eq!(next_word(s, 8), 15);
+        assert_eq!(next_word(s, 15), 15);
+        assert_eq!(prev_word(s, 15), 11);
+        assert_eq!(prev_word(s, 11), 6);
+        assert_eq!(prev_word(s, 3), 0);
+        assert_eq!(prev_word(s, 0), 0);
+    }
+
+    #[test]
+    fn delete_words() {
+        let mut s = "öffne datei.txt jetzt".to_string();
+        let cursor = end(&s);
+        let start = prev_word(&s, cursor);
+        remove_range(&mut s, start, cursor);
+        assert_eq!(s, "öffne datei.txt ");
+        // The dot does not split the file name
+        let cursor = end(&s);
+        let start = prev_word(&s, cursor);
+        remove_range(&mut s, start, cursor);
+        assert_eq!(s, "öffne ");
+        let stop = next_word(&s, 0);
+        remove_range(&mut s, 0, stop);
+        assert_eq!(s, " ");
+    }
 }
@@ -0,0 +1,251 @@
+use crate::cmd::{Action, Cmd};
+use crate::color::Color;
+use crate::draw::Font;
+use crate::keyboard::keysyms;
+use crate::pam;
+use crate::{
+    fonts::FontRef,
+    widget::{
+        DrawContext, DrawReport, KeyState, ModifiersState, Scroll, TreeNode, WaitContext, Widget,
+    },
+};
+
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// The width of the prompt, in characters of the font size
+const PROMPT_CHARS: u32 = 12;
+
+enum Auth {
+    Idle,
+    Checking,
+    Failed(String),
+}
+
+/// The widgets shown while the session is locked, centered on the output,
+/// with a password prompt below them. Input goes to the prompt alone, so
+/// that nothing can be run from the lock screen. The password is checked
+/// with PAM in the background, and the session is unlocked once it is
+/// accepted.
+pub struct LockScreen<'a> {
+    child: Box<dyn Widget + Send + 'a>,
+    password: String,
+    auth: Arc<Mutex<Auth>>,
+    pam_service: String,
+    // Whether the prompt changed since it was last drawn
+    dirty: bool,
+    // The space last drawn in, which the widgets are centered in
+    expansion: (u32, u32),
+    tx: Sender<Cmd>,
+    font: Font<'a>,
+    font_size: u32,
+}
+
+impl<'a> LockScreen<'a> {
+    pub fn new(
+        child: Box<dyn Widget + Send + 'a>,
+        pam_service: String,
+        font: FontRef<'a>,
+        font_size: f32,
+        tx: Sender<Cmd>,
+    ) -> Box<LockScreen<'a>> {
+        let mut font = Font::new(font, font_size);
+        font.add_str_to_cache("*Password.");
+        Box::new(LockScreen {
+            child,
+            password: String::new(),
+            auth: Arc::new(Mutex::new(Auth::Idle)),
+            pam_service,
+            dirty: true,
+            expansion: (0, 0),
+            tx,
+            font,
+            font_size: font_size as u32,
+        })
+    }
+
+    fn prompt_size(&self) -> (u32, u32) {
+        (self.font_size * PROMPT_CHARS, self.font_size)
+    }
+
+    fn clear(&mut self) {
+        // The password is overwritten rather than just dropped
+        unsafe { self.password.as_bytes_mut().fill(0) };
+        self.password.clear();
+        self.dirty = true;
+    }
+
+    fn submit(&mut self) {
+        let mut auth = self.auth.lock().unwrap();
+        if matches!(*auth, Auth::Checking) || self.password.is_empty() {
+            return;
+        }
+        *auth = Auth::Checking;
+        drop(auth);
+
+        let mut password = std::mem::take(&mut self.password);
+        let service = self.pam_service.clone();
+        let auth = self.auth.clone();
+        let tx = self.tx.clone();
+        let _ = thread::Builder::new()
+            .name("pam".to_string())
+            .spawn(move || {
+                let result = pam::authenticate(&service, &password);
+                unsafe { password.as_bytes_mut().fill(0) };
+                match result {
+                    Ok(()) => {
+                        *auth.lock().unwrap() = Auth::Idle;
+                        let _ = tx.send(Cmd::Unlock);
+                    }
+                    Err(err) => {
+                        *auth.lock().unwrap() = Auth::Failed(err);
+                        let _ = tx.send(Cmd::Draw);
+                    }
+                }
+            });
+        self.dirty = true;
+    }
+
+    fn prompt(&self) -> (String, Color) {
+        match &*self.auth.lock().unwrap() {
+            Auth::Checking => ("...".to_string(), Color::new(0.5, 0.5, 0.5, 1.0)),
+            Auth::Failed(err) if self.password.is_empty() => {
+                (err.clone(), Color::new(0.8, 0.35, 0.35, 1.0))
+            }
+            _ if self.password.is_empty() => {
+                ("Password".to_string(), Color::new(0.5, 0.5, 0.5, 1.0))
+            }
+            _ => (
+                "*".repeat(self.password.chars().count()),
+                Color::new(1.0, 1.0, 1.0, 1.0),
+            ),
+        }
+    }
+}
+
+impl<'a> Widget for LockScreen<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.child.wait(ctx)
+    }
+    fn enter(&mut self) {
+        self.child.enter()
+    }
+    fn leave(&mut self) {
+        self.clear();
+        self.child.leave()
+    }
+    fn size(&self) -> (u32, u32) {
+        let child = self.child.size();
+        let prompt = self.prompt_size();
+        (child.0.max(prompt.0), child.1 + prompt.1 * 2)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let force = ctx.force || self.expansion != expansion;
+        self.expansion = expansion;
+        let (width, height) = self.size();
+        let child_size = self.child.size();
+        let (prompt_width, prompt_height) = self.prompt_size();
+
+        // Centered in what is left of the output, or at the position if the
+        // output is too small
+        let x = pos.0 + expansion.0.saturating_sub(width) / 2;
+        let y = pos.1 + expansion.1.saturating_sub(height) / 2;
+        let report = self.child.draw(
+            &mut DrawContext {
+                buf: ctx.buf,
+                bg: ctx.bg,
+                time: ctx.time,
+                force,
+            },
+            (x + (width - child_size.0) / 2, y),
+            child_size,
+        )?;
+        let mut damage = report.damage;
+
+        if force || self.dirty {
+            self.dirty = false;
+            let (text, c) = self.prompt();
+            let buf = &mut ctx.buf.subdimensions((
+                x + (width - prompt_width) / 2,
+                y + child_size.1 + prompt_height,
+                prompt_width,
+                prompt_height,
+            ))?;
+            buf.memset(ctx.bg);
+            let text_width = self.font.auto_text_width(&text).min(prompt_width);
+            self.font.auto_draw_text(
+                &mut buf.subdimensions((
+                    (prompt_width - text_width) / 2,
+                    0,
+                    text_width,
+                    prompt_height,
+                ))?,
+                ctx.bg,
+                &c,
+                &text,
+            )?;
+            damage.push(buf.get_signed_bounds());
+        }
+
+        Ok(DrawReport {
+            width: expansion.0.max(width),
+            height: expansion.1.max(height),
+            damage,
+            full_damage: report.full_damage,
+        })
+    }
+
+    fn keyboard_input(
+        &mut self,
+        key: u32,
+        modifiers: ModifiersState,
+        _: KeyState,
+        interpreted: Option<String>,
+    ) {
+        if matches!(*self.auth.lock().unwrap(), Auth::Checking) {
+            return;
+        }
+        match key {
+            keysyms::XKB_KEY_Return => self.submit(),
+            keysyms::XKB_KEY_BackSpace if modifiers.ctrl => self.clear(),
+            keysyms::XKB_KEY_BackSpace => {
+                self.password.pop();
+                self.dirty = true;
+            }
+            keysyms::XKB_KEY_u if modifiers.ctrl => self.clear(),
+            _ => {
+                if let Some(text) = interpreted {
+                    self.password
+                        .extend(text.chars().filter(|c| !c.is_control()));
+                    self.dirty = true;
+                }
+            }
+        }
+    }
+
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+
+    fn action(&mut self, action: &Action) {
+        match action {
+            // Escape, as the lock screen cannot be dismissed
+            Action::ClearPrompt => self.clear(),
+            action => self.child.action(action),
+        }
+    }
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos).child(self.child.tree(pos))
+    }
+
+    fn has_focus(&self) -> bool {
+        true
+    }
+}
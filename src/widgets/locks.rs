@@ -0,0 +1,122 @@
+use crate::cmd::Action;
+use crate::color::Color;
+use crate::draw::Font;
+use crate::{
+    fonts::FontRef,
+    widget::{
+        DrawContext, DrawReport, KeyState, ModifiersState, Scroll, StateValue, TreeNode,
+        WaitContext, Widget,
+    },
+};
+
+const LABELS: &str = "CAPSNUM";
+
+pub struct Locks<'a> {
+    caps_lock: bool,
+    num_lock: bool,
+    active_color: Color,
+    inactive_color: Color,
+    only_active: bool,
+    dirty: bool,
+    font: Font<'a>,
+    font_size: u32,
+    ch_width: u32,
+}
+
+impl<'a> Locks<'a> {
+    pub fn new(
+        font: FontRef,
+        font_size: f32,
+        active_color: Color,
+        inactive_color: Color,
+        only_active: bool,
+    ) -> ::std::io::Result<Box<Locks>> {
+        let mut font = Font::new(font, font_size);
+        let ch_width = font.auto_widest(LABELS)?;
+        Ok(Box::new(Locks {
+            caps_lock: false,
+            num_lock: false,
+            active_color,
+            inactive_color,
+            only_active,
+            dirty: true,
+            font,
+            font_size: font_size as u32,
+            ch_width,
+        }))
+    }
+}
+
+impl<'a> Widget for Locks<'a> {
+    fn wait(&mut self, _: &mut WaitContext) {}
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        // Room for "CAPS NUM", each in its own place so that neither moves
+        (self.ch_width * 8, self.font_size)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        _expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let (width, height) = self.size();
+        if !self.dirty && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+        self.dirty = false;
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        let locks = [
+            ("CAPS", self.caps_lock, 0),
+            ("NUM", self.num_lock, self.ch_width * 5),
+        ];
+        for (label, active, x) in locks {
+            let color = match (active, self.only_active) {
+                (true, _) => &self.active_color,
+                (false, false) => &self.inactive_color,
+                (false, true) => continue,
+            };
+            self.font
+                .draw_text(&mut buf.offset((x, 0))?, ctx.bg, color, label)?;
+        }
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+
+    fn tree(&self, pos: (u32, u32)) -> TreeNode {
+        TreeNode::new(self, pos)
+            .value("font_size", StateValue::Number(self.font_size as f64))
+            .value(
+                "active_color",
+                StateValue::Text(self.active_color.as_hex_rgb()),
+            )
+            .value(
+                "inactive_color",
+                StateValue::Text(self.inactive_color.as_hex_rgb()),
+            )
+            .value("dirty", StateValue::Bool(self.dirty))
+    }
+
+    fn action(&mut self, action: &Action) {
+        if let Action::Modifiers(modifiers) = action {
+            if (modifiers.caps_lock, modifiers.num_lock) != (self.caps_lock, self.num_lock) {
+                self.caps_lock = modifiers.caps_lock;
+                self.num_lock = modifiers.num_lock;
+                self.dirty = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::color::Color;
+use crate::draw::Font;
+use crate::metrics;
+use crate::selection::Selection;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::fs;
+use std::time::Instant;
+
+use chrono::{Duration, NaiveDateTime};
+use nix::unistd::{sysconf, SysconfVar};
+
+const INTERVAL: i64 = 2;
+
+struct ProcStat {
+    cpu_ticks: u64,
+    rss_pages: u64,
+    threads: u64,
+}
+
+fn read_proc_stat() -> Option<ProcStat> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces, so start after its closing paren.
+    // The first field after it is the state, which is field 3 in proc(5).
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+    Some(ProcStat {
+        cpu_ticks: field(14)? + field(15)?,
+        threads: field(20)?,
+        rss_pages: field(24)?,
+    })
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let mib = bytes as f64 / (1024.0 * 1024.0);
+    if mib >= 1.0 {
+        format!("{:.1} MiB", mib)
+    } else {
+        format!("{} KiB", bytes / 1024)
+    }
+}
+
+pub struct Metrics<'a> {
+    next_update: NaiveDateTime,
+    last_sample: Option<(Instant, u64)>,
+    text: String,
+    selection: Selection,
+    dirty: bool,
+    font: Font<'a>,
+    font_size: u32,
+    length: u32,
+    page_size: u64,
+    ticks_per_second: u64,
+}
+
+impl<'a> Metrics<'a> {
+    pub fn new(time: NaiveDateTime, font: FontRef, font_size: f32, length: u32) -> Box<Metrics> {
+        let mut font = Font::new(font, font_size);
+        font.add_str_to_cache("0123456789.% RSSCPUpoolframethreadsKMiBms");
+        let sysconf_or = |var, default| match sysconf(var) {
+            Ok(Some(v)) if v > 0 => v as u64,
+            _ => default,
+        };
+        Box::new(Metrics {
+            next_update: time,
+            last_sample: None,
+            text: String::new(),
+            selection: Selection::new(),
+            dirty: false,
+            font,
+            font_size: font_size as u32,
+            length,
+            page_size: sysconf_or(SysconfVar::PAGE_SIZE, 4096),
+            ticks_per_second: sysconf_or(SysconfVar::CLK_TCK, 100),
+        })
+    }
+
+    fn sample(&mut self) {
+        let stat = match read_proc_stat() {
+            Some(stat) => stat,
+            None => {
+                self.text = "metrics unavailable".to_string();
+                return;
+            }
+        };
+
+        let now = Instant::now();
+        let cpu = match self.last_sample {
+            Some((then, ticks)) => {
+                let elapsed = now.duration_since(then).as_secs_f64();
+                let used =
+                    stat.cpu_ticks.saturating_sub(ticks) as f64 / self.ticks_per_second as f64;
+                if elapsed > 0.0 {
+                    used / elapsed * 100.0
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        };
+        self.last_sample = Some((now, stat.cpu_ticks));
+
+        self.text = format!(
+            "RSS {}  CPU {:.1}%  pool {}  frame {:.1} ms  threads {}",
+            format_bytes(stat.rss_pages * self.page_size),
+            cpu,
+            format_bytes(metrics::pool_size()),
+            metrics::frame_time().as_secs_f64() * 1000.0,
+            stat.threads,
+        );
+    }
+}
+
+impl<'a> Widget for Metrics<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        ctx.set_time(self.next_update);
+    }
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (self.length, self.font_size)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+        let update = ctx.time >= self.next_update;
+        if !update && !self.dirty && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+        if update {
+            self.sample();
+            self.next_update = ctx.time + Duration::seconds(INTERVAL);
+        }
+        self.dirty = false;
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        self.font
+            .auto_draw_text(buf, ctx.bg, &Color::new(0.75, 0.75, 0.75, 1.0), &self.text)?;
+        self.selection.clear_runs();
+        self.selection
+            .add_run(&mut self.font, (0, 0), self.font_size, &self.text);
+        self.selection.draw(buf)?;
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_press(&mut self, button: u32, pos: (u32, u32)) {
+        self.dirty |= self.selection.press(button, pos);
+    }
+    fn mouse_click(&mut self, button: u32, _: (u32, u32)) {
+        self.dirty |= self.selection.release(button);
+    }
+    fn mouse_motion(&mut self, pos: Option<(u32, u32)>) {
+        self.dirty |= self.selection.motion(pos);
+    }
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+}
@@ -1,10 +1,26 @@
 pub mod backlight;
+pub mod badge;
 pub mod bar_widget;
+#[cfg(feature = "battery-widget")]
 pub mod battery;
 pub mod calendar;
 pub mod clock;
+pub mod color_picker;
 pub mod date;
+pub mod feeds;
+pub mod greeting;
+pub mod label;
+pub mod latency;
 pub mod launcher;
+pub mod lock_screen;
+pub mod locks;
+pub mod metrics;
+pub mod next_event;
+pub mod notes;
+pub mod processes;
+pub mod warning;
+pub mod week_strip;
+pub mod world_clock;
 
 #[cfg(any(feature = "alsa-widget", feature = "pulseaudio-widget"))]
 pub mod audio;
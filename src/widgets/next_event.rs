@@ -0,0 +1,154 @@
+use crate::cmd::Cmd;
+use crate::color::Color;
+use crate::draw::Font;
+use crate::events::EventFile;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+use chrono::{Duration, Local, NaiveDateTime};
+
+fn countdown(left: Duration) -> String {
+    let secs = left.num_seconds();
+    match secs {
+        0..=59 => format!("in {}s", secs),
+        60..=3599 => format!("in {}m", secs / 60),
+        3600..=86399 => format!("in {}h {}m", secs / 3600, secs / 60 % 60),
+        _ => format!("in {}d", secs / 86400),
+    }
+}
+
+// When the countdown changes next: every second in the last minute, and
+// every minute before that.
+fn next_tick(now: NaiveDateTime, start: NaiveDateTime) -> NaiveDateTime {
+    let secs = (start - now).num_seconds();
+    if secs < 60 {
+        start - Duration::seconds(secs)
+    } else {
+        start - Duration::minutes(secs / 60)
+    }
+}
+
+pub struct NextEvent<'a> {
+    events: EventFile,
+    command: String,
+    notify_before: Duration,
+    // The start of the event the command was last run for
+    notified: Option<NaiveDateTime>,
+    shown: String,
+    sender: Sender<Cmd>,
+    font: Font<'a>,
+    font_size: u32,
+    length: u32,
+}
+
+impl<'a> NextEvent<'a> {
+    pub fn new(
+        font: FontRef,
+        font_size: f32,
+        length: u32,
+        event_file: PathBuf,
+        command: String,
+        notify_before: Duration,
+        sender: Sender<Cmd>,
+    ) -> Box<NextEvent> {
+        Box::new(NextEvent {
+            events: EventFile::new(event_file),
+            command,
+            notify_before,
+            notified: None,
+            shown: String::new(),
+            sender,
+            font: Font::new(font, font_size),
+            font_size: font_size as u32,
+            length,
+        })
+    }
+
+    // Runs the command once for an event about to start, with the summary of
+    // the event as its last argument.
+    fn notify(&mut self, start: NaiveDateTime, summary: &str) {
+        if self.command.is_empty() || self.notified == Some(start) {
+            return;
+        }
+        self.notified = Some(start);
+        match shlex::split(&self.command) {
+            Some(mut argv) if !argv.is_empty() => {
+                argv.push(summary.to_string());
+                self.sender
+                    .send(Cmd::Launch { argv, app_id: None })
+                    .unwrap();
+            }
+            _ => eprintln!("Unable to parse command: {}", self.command),
+        }
+    }
+}
+
+impl<'a> Widget for NextEvent<'a> {
+    // The command is run from here rather than when drawing, so that it is
+    // also run while the dashboard is hidden.
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        self.events.reload();
+        let now = Local::now().naive_local();
+        let (start, summary) = match self.events.next(now) {
+            Some((start, event)) => (start, event.summary.clone()),
+            None => return,
+        };
+        ctx.set_time(next_tick(now, start));
+        let notify_at = start - self.notify_before;
+        if notify_at > now {
+            ctx.set_time(notify_at);
+        } else {
+            self.notify(start, &summary);
+        }
+    }
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (self.length, self.font_size)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+        let text = match self.events.next(ctx.time) {
+            Some((start, event)) => format!(
+                "next event: {} {}",
+                event.summary,
+                countdown(start - ctx.time)
+            ),
+            None => "no upcoming events".to_string(),
+        };
+        if text == self.shown && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        self.font
+            .auto_draw_text(buf, ctx.bg, &Color::new(1.0, 1.0, 1.0, 1.0), &text)?;
+        self.shown = text;
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+}
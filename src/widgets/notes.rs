@@ -0,0 +1,337 @@
+use crate::color::Color;
+use crate::data::state_file;
+use crate::draw::{draw_box, Font};
+use crate::keyboard::keysyms;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use chrono::{Duration, Local, NaiveDateTime};
+use unicode_segmentation::UnicodeSegmentation;
+
+const NOTES_FILE: &str = "notes.txt";
+
+// How long after the last edit the notes are saved, unless editing stops
+// before that.
+const SAVE_DELAY_MS: i64 = 1000;
+
+/// Appends a line to the scratchpad, as used by the launcher quick capture.
+pub fn append_note(note: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(state_file(NOTES_FILE)?)?;
+    writeln!(file, "{}", note)?;
+    Ok(())
+}
+
+fn grapheme_len(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+fn grapheme_offset(s: &str, idx: usize) -> usize {
+    s.grapheme_indices(true)
+        .nth(idx)
+        .map(|(offset, _)| offset)
+        .unwrap_or(s.len())
+}
+
+pub struct Notes<'a> {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    lines: Vec<String>,
+    cursor: (usize, usize), // line, grapheme
+    scroll: usize,
+    editing: bool,
+    dirty: bool,
+    // When the unsaved edits are to be saved
+    save_at: Option<NaiveDateTime>,
+    font: Font<'a>,
+    font_size: u32,
+    length: u32,
+    height: u32,
+}
+
+impl<'a> Notes<'a> {
+    pub fn new(font: FontRef, font_size: f32, length: u32, height: u32) -> Box<Notes> {
+        let mut notes = Notes {
+            path: state_file(NOTES_FILE).ok(),
+            modified: None,
+            lines: vec![String::new()],
+            cursor: (0, 0),
+            scroll: 0,
+            editing: false,
+            dirty: true,
+            save_at: None,
+            font: Font::new(font, font_size),
+            font_size: font_size as u32,
+            length,
+            height,
+        };
+        notes.reload();
+        Box::new(notes)
+    }
+
+    fn mtime(&self) -> Option<SystemTime> {
+        let path = self.path.as_ref()?;
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    fn reload(&mut self) {
+        let modified = self.mtime();
+        if modified == self.modified || self.save_at.is_some() {
+            return;
+        }
+        self.modified = modified;
+
+        let content = match &self.path {
+            Some(path) => fs::read_to_string(path).unwrap_or_default(),
+            None => String::new(),
+        };
+        self.lines = content.split('\n').map(|l| l.to_string()).collect();
+        if self.lines.len() > 1 && matches!(self.lines.last(), Some(l) if l.is_empty()) {
+            self.lines.pop();
+        }
+        let line = self.cursor.0.min(self.lines.len() - 1);
+        self.cursor = (line, self.cursor.1.min(grapheme_len(&self.lines[line])));
+        self.dirty = true;
+    }
+
+    fn save(&mut self) {
+        if self.save_at.take().is_none() {
+            return;
+        }
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let mut content = self.lines.join("\n");
+        content.push('\n');
+        if let Err(err) = fs::write(path, content) {
+            eprintln!("Error while trying to save notes: {}", err);
+        }
+        self.modified = self.mtime();
+    }
+
+    fn save_later(&mut self) {
+        self.save_at = Some(Local::now().naive_local() + Duration::milliseconds(SAVE_DELAY_MS));
+    }
+
+    fn visible_lines(&self) -> usize {
+        (self.height / self.font_size).max(1) as usize
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        let visible = self.visible_lines();
+        if self.cursor.0 < self.scroll {
+            self.scroll = self.cursor.0;
+        } else if self.cursor.0 >= self.scroll + visible {
+            self.scroll = self.cursor.0 + 1 - visible;
+        }
+    }
+
+    fn edit(&mut self, key: u32, modifiers: ModifiersState, interpreted: Option<String>) {
+        let (line, col) = self.cursor;
+        let line_len = grapheme_len(&self.lines[line]);
+        match key {
+            keysyms::XKB_KEY_Left => {
+                if col > 0 {
+                    self.cursor.1 -= 1;
+                } else if line > 0 {
+                    self.cursor = (line - 1, grapheme_len(&self.lines[line - 1]));
+                }
+            }
+            keysyms::XKB_KEY_Right => {
+                if col < line_len {
+                    self.cursor.1 += 1;
+                } else if line + 1 < self.lines.len() {
+                    self.cursor = (line + 1, 0);
+                }
+            }
+            keysyms::XKB_KEY_Up => {
+                if line > 0 {
+                    self.cursor = (line - 1, col.min(grapheme_len(&self.lines[line - 1])));
+                }
+            }
+            keysyms::XKB_KEY_Down => {
+                if line + 1 < self.lines.len() {
+                    self.cursor = (line + 1, col.min(grapheme_len(&self.lines[line + 1])));
+                }
+            }
+            keysyms::XKB_KEY_Home => self.cursor.1 = 0,
+            keysyms::XKB_KEY_End => self.cursor.1 = line_len,
+            keysyms::XKB_KEY_a if modifiers.ctrl => self.cursor.1 = 0,
+            keysyms::XKB_KEY_e if modifiers.ctrl => self.cursor.1 = line_len,
+            keysyms::XKB_KEY_Return => {
+                let offset = grapheme_offset(&self.lines[line], col);
+                let rest = self.lines[line].split_off(offset);
+                self.lines.insert(line + 1, rest);
+                self.cursor = (line + 1, 0);
+                self.save_later();
+            }
+            keysyms::XKB_KEY_BackSpace => {
+                if col > 0 {
+                    let start = grapheme_offset(&self.lines[line], col - 1);
+                    let end = grapheme_offset(&self.lines[line], col);
+                    self.lines[line].replace_range(start..end, "");
+                    self.cursor.1 -= 1;
+                } else if line > 0 {
+                    let current = self.lines.remove(line);
+                    let prev_len = grapheme_len(&self.lines[line - 1]);
+                    self.lines[line - 1].push_str(&current);
+                    self.cursor = (line - 1, prev_len);
+                }
+                self.save_later();
+            }
+            keysyms::XKB_KEY_Delete => {
+                if col < line_len {
+                    let start = grapheme_offset(&self.lines[line], col);
+                    let end = grapheme_offset(&self.lines[line], col + 1);
+                    self.lines[line].replace_range(start..end, "");
+                } else if line + 1 < self.lines.len() {
+                    let next = self.lines.remove(line + 1);
+                    self.lines[line].push_str(&next);
+                }
+                self.save_later();
+            }
+            _ => match interpreted {
+                Some(v) if !v.chars().any(char::is_control) => {
+                    let offset = grapheme_offset(&self.lines[line], col);
+                    self.lines[line].insert_str(offset, &v);
+                    self.cursor.1 += grapheme_len(&v);
+                    self.save_later();
+                }
+                _ => return,
+            },
+        }
+        self.scroll_to_cursor();
+        self.dirty = true;
+    }
+}
+
+impl<'a> Widget for Notes<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        if let Some(save_at) = self.save_at {
+            ctx.set_time(save_at);
+        }
+    }
+    fn enter(&mut self) {
+        self.reload();
+    }
+    fn leave(&mut self) {
+        self.save();
+        self.editing = false;
+        self.dirty = true;
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.length, self.height)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        if matches!(self.save_at, Some(save_at) if ctx.time >= save_at) {
+            self.save();
+        }
+        self.reload();
+        let (width, height) = (self.length, self.height);
+        if !self.dirty && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+        self.dirty = false;
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+
+        let c = if self.editing {
+            Color::new(1.0, 1.0, 1.0, 1.0)
+        } else {
+            Color::new(0.75, 0.75, 0.75, 1.0)
+        };
+        let visible = self.visible_lines();
+        for (idx, line) in self
+            .lines
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(visible)
+        {
+            let y = (idx - self.scroll) as u32 * self.font_size;
+            let line_buf = &mut buf.subdimensions((0, y, width, self.font_size))?;
+            if self.editing && idx == self.cursor.0 {
+                let cursor = line
+                    .graphemes(true)
+                    .take(self.cursor.1)
+                    .map(|g| g.chars().count())
+                    .sum();
+                self.font
+                    .auto_draw_text_with_cursor(line_buf, ctx.bg, &c, line, cursor)?;
+            } else {
+                self.font.auto_draw_text(line_buf, ctx.bg, &c, line)?;
+            }
+        }
+        if self.editing {
+            draw_box(buf, &Color::new(0.5, 0.5, 0.5, 1.0), (width, height))?;
+        }
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(
+        &mut self,
+        key: u32,
+        modifiers: ModifiersState,
+        _: KeyState,
+        interpreted: Option<String>,
+    ) {
+        if self.editing {
+            self.edit(key, modifiers, interpreted);
+        }
+    }
+
+    fn mouse_click(&mut self, button: u32, _: (u32, u32)) {
+        if button == 272 {
+            self.editing = !self.editing;
+            if !self.editing {
+                self.save();
+            }
+            self.dirty = true;
+        }
+    }
+
+    fn mouse_scroll(&mut self, scroll: Scroll, _: (u32, u32)) {
+        let max = self.lines.len().saturating_sub(self.visible_lines());
+        let lines = scroll.steps.1.unsigned_abs() as usize;
+        if scroll.steps.1 > 0 {
+            self.scroll = (self.scroll + lines).min(max);
+        } else if scroll.steps.1 < 0 {
+            self.scroll = self.scroll.saturating_sub(lines);
+        } else {
+            return;
+        }
+        self.dirty = true;
+    }
+
+    fn has_focus(&self) -> bool {
+        self.editing
+    }
+}
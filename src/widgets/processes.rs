@@ -0,0 +1,403 @@
+use crate::cmd::Action;
+use crate::color::Color;
+use crate::draw::{draw_box, Font};
+use crate::keyboard::keysyms;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::fs;
+use std::time::Instant;
+
+use chrono::{Duration, Local, NaiveDateTime};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::{sysconf, Pid, SysconfVar};
+use serde::{Deserialize, Serialize};
+
+const INTERVAL: i64 = 3;
+const BTN_LEFT: u32 = 272;
+
+/// What the processes are ordered by, the largest first.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SortBy {
+    #[default]
+    Cpu,
+    Memory,
+}
+
+struct Process {
+    pid: i32,
+    name: String,
+    // Clock ticks after boot when the process started, which tells it apart
+    // from a later process that reuses its pid
+    start: u64,
+    // Percent of one CPU since the last sample
+    cpu: f64,
+    rss: u64,
+}
+
+struct Stat {
+    name: String,
+    cpu_ticks: u64,
+    rss_pages: u64,
+    start: u64,
+}
+
+// The name, CPU ticks, resident pages and start time of a process from its
+// stat file.
+fn read_stat(pid: i32) -> Option<Stat> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces and parens, so it is everything
+    // between the first open paren and the last close paren. The field
+    // after it is the state, which is field 3 in proc(5).
+    let close = stat.rfind(')')?;
+    let name = stat.get(stat.find('(')? + 1..close)?.to_string();
+    let fields: Vec<&str> = stat.get(close + 1..)?.split_whitespace().collect();
+    let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+    Some(Stat {
+        name,
+        cpu_ticks: field(14)? + field(15)?,
+        rss_pages: field(24)?,
+        start: field(22)?,
+    })
+}
+
+// Whether the pid of a sampled process still belongs to that process, as
+// pids are reused once a process exits.
+fn same_process(process: &Process) -> bool {
+    read_stat(process.pid)
+        .is_some_and(|stat| stat.name == process.name && stat.start == process.start)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let mib = bytes as f64 / (1024.0 * 1024.0);
+    if mib >= 1024.0 {
+        format!("{:.1}G", mib / 1024.0)
+    } else if mib >= 1.0 {
+        format!("{:.0}M", mib)
+    } else {
+        format!("{}K", bytes / 1024)
+    }
+}
+
+// A process as listed, or the question whether to kill it.
+fn row(process: &Process, confirming: bool) -> String {
+    if confirming {
+        return format!("Kill {} {}? y/n", process.pid, process.name);
+    }
+    format!(
+        "{:>7} {:>5.1}% {:>6}  {}",
+        process.pid,
+        process.cpu,
+        format_bytes(process.rss),
+        process.name
+    )
+}
+
+/// The processes using the most CPU or memory, sampled from /proc. Clicking
+/// a process selects it, after which the arrow keys move the selection,
+/// Delete or k asks to kill the selected process, and y or Return confirms.
+pub struct Processes<'a> {
+    next_update: NaiveDateTime,
+    // The CPU ticks of every process at the last sample
+    last_sample: Option<(Instant, HashMap<i32, u64>)>,
+    processes: Vec<Process>,
+    // The selected process by pid, which stays selected as the order changes
+    selected: Option<i32>,
+    confirming: bool,
+    dirty: bool,
+    font: Font<'a>,
+    font_size: u32,
+    length: u32,
+    count: usize,
+    sort_by: SortBy,
+    page_size: u64,
+    ticks_per_second: u64,
+}
+
+impl<'a> Processes<'a> {
+    pub fn new(
+        time: NaiveDateTime,
+        font: FontRef,
+        font_size: f32,
+        length: u32,
+        count: usize,
+        sort_by: SortBy,
+    ) -> Box<Processes> {
+        let mut font = Font::new(font, font_size);
+        font.add_str_to_cache("0123456789.%KMG Kill? y/n");
+        let sysconf_or = |var, default| match sysconf(var) {
+            Ok(Some(v)) if v > 0 => v as u64,
+            _ => default,
+        };
+        Box::new(Processes {
+            next_update: time,
+            last_sample: None,
+            processes: Vec::new(),
+            selected: None,
+            confirming: false,
+            dirty: true,
+            font,
+            font_size: font_size as u32,
+            length,
+            count,
+            sort_by,
+            page_size: sysconf_or(SysconfVar::PAGE_SIZE, 4096),
+            ticks_per_second: sysconf_or(SysconfVar::CLK_TCK, 100),
+        })
+    }
+
+    fn sample(&mut self) {
+        let entries = match fs::read_dir("/proc") {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("Unable to list processes: {}", err);
+                return;
+            }
+        };
+
+        let now = Instant::now();
+        let mut ticks = HashMap::new();
+        let mut processes = Vec::new();
+        for entry in entries.flatten() {
+            let pid = match entry
+                .file_name()
+                .to_str()
+                .and_then(|s| s.parse::<i32>().ok())
+            {
+                Some(pid) => pid,
+                None => continue,
+            };
+            // The process may have exited since the listing
+            let stat = match read_stat(pid) {
+                Some(stat) => stat,
+                None => continue,
+            };
+            let cpu = match &self.last_sample {
+                Some((then, last)) => {
+                    let elapsed = now.duration_since(*then).as_secs_f64();
+                    // Processes that started since count from zero
+                    let used = stat
+                        .cpu_ticks
+                        .saturating_sub(last.get(&pid).copied().unwrap_or(0));
+                    if elapsed > 0.0 {
+                        used as f64 / self.ticks_per_second as f64 / elapsed * 100.0
+                    } else {
+                        0.0
+                    }
+                }
+                None => 0.0,
+            };
+            ticks.insert(pid, stat.cpu_ticks);
+            processes.push(Process {
+                pid,
+                name: stat.name,
+                start: stat.start,
+                cpu,
+                rss: stat.rss_pages * self.page_size,
+            });
+        }
+        self.last_sample = Some((now, ticks));
+
+        match self.sort_by {
+            SortBy::Cpu => processes.sort_by(|a, b| {
+                b.cpu
+                    .partial_cmp(&a.cpu)
+                    .unwrap_or(Ordering::Equal)
+                    .then(b.rss.cmp(&a.rss))
+            }),
+            SortBy::Memory => processes.sort_by_key(|p| Reverse(p.rss)),
+        }
+        processes.truncate(self.count);
+        self.processes = processes;
+
+        // A selected process that exited or dropped out is deselected
+        if let Some(pid) = self.selected {
+            if !self.processes.iter().any(|p| p.pid == pid) {
+                self.selected = None;
+                self.confirming = false;
+            }
+        }
+    }
+
+    fn selected_idx(&self) -> Option<usize> {
+        let pid = self.selected?;
+        self.processes.iter().position(|p| p.pid == pid)
+    }
+
+    fn select(&mut self, idx: usize) {
+        if let Some(process) = self.processes.get(idx) {
+            self.selected = Some(process.pid);
+            self.confirming = false;
+        }
+    }
+
+    fn deselect(&mut self) {
+        self.selected = None;
+        self.confirming = false;
+    }
+
+    fn kill_selected(&mut self) {
+        if let Some(process) = self.selected_idx().map(|idx| &self.processes[idx]) {
+            // The sample may be seconds old, so the process that was
+            // confirmed may have exited and its pid been reused
+            if !same_process(process) {
+                eprintln!(
+                    "Not killing process {}: {} has exited",
+                    process.pid, process.name
+                );
+            } else if let Err(err) = kill(Pid::from_raw(process.pid), Signal::SIGTERM) {
+                eprintln!("Unable to kill process {}: {}", process.pid, err);
+            }
+        }
+        self.deselect();
+        // Sample again right away, so that the process is gone
+        self.next_update = Local::now().naive_local();
+    }
+}
+
+impl<'a> Widget for Processes<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        ctx.set_time(self.next_update);
+    }
+    fn enter(&mut self) {}
+    fn leave(&mut self) {
+        self.deselect();
+        self.dirty = true;
+    }
+    fn size(&self) -> (u32, u32) {
+        (self.length, self.font_size * self.count as u32)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+        let update = ctx.time >= self.next_update;
+        if !update && !self.dirty && !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+        if update {
+            self.sample();
+            self.next_update = ctx.time + Duration::seconds(INTERVAL);
+        }
+        self.dirty = false;
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+
+        for (idx, process) in self.processes.iter().enumerate() {
+            let selected = self.selected == Some(process.pid);
+            let confirming = selected && self.confirming;
+            let c = if confirming {
+                Color::new(1.0, 0.4, 0.4, 1.0)
+            } else if selected {
+                Color::new(1.0, 1.0, 1.0, 1.0)
+            } else {
+                Color::new(0.75, 0.75, 0.75, 1.0)
+            };
+            let y = idx as u32 * self.font_size;
+            let row_buf = &mut buf.subdimensions((0, y, width, self.font_size))?;
+            let text = row(process, confirming);
+            self.font.auto_draw_text(row_buf, ctx.bg, &c, &text)?;
+            if selected {
+                draw_box(
+                    row_buf,
+                    &Color::new(0.5, 0.5, 0.5, 1.0),
+                    (width, self.font_size),
+                )?;
+            }
+        }
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, key: u32, _: ModifiersState, _: KeyState, _: Option<String>) {
+        let idx = match self.selected_idx() {
+            Some(idx) => idx,
+            None => return,
+        };
+        if self.confirming {
+            match key {
+                keysyms::XKB_KEY_y | keysyms::XKB_KEY_Return => self.kill_selected(),
+                keysyms::XKB_KEY_n | keysyms::XKB_KEY_BackSpace => self.confirming = false,
+                _ => return,
+            }
+        } else {
+            match key {
+                keysyms::XKB_KEY_Up => self.select(idx.saturating_sub(1)),
+                keysyms::XKB_KEY_Down => self.select(idx + 1),
+                keysyms::XKB_KEY_Delete | keysyms::XKB_KEY_k => self.confirming = true,
+                keysyms::XKB_KEY_BackSpace => self.deselect(),
+                _ => return,
+            }
+        }
+        self.dirty = true;
+    }
+
+    fn mouse_click(&mut self, button: u32, pos: (u32, u32)) {
+        if button != BTN_LEFT {
+            return;
+        }
+        let idx = (pos.1 / self.font_size.max(1)) as usize;
+        if self.selected_idx() == Some(idx) {
+            self.deselect();
+        } else {
+            self.select(idx);
+        }
+        self.dirty = true;
+    }
+
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+
+    fn action(&mut self, action: &Action) {
+        if let Action::Refresh = action {
+            self.next_update = Local::now().naive_local();
+        }
+    }
+
+    fn has_focus(&self) -> bool {
+        self.selected.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_pid() {
+        let pid = std::process::id() as i32;
+        let stat = read_stat(pid).unwrap();
+        let mut process = Process {
+            pid,
+            name: stat.name,
+            start: stat.start,
+            cpu: 0.0,
+            rss: 0,
+        };
+        assert!(same_process(&process));
+
+        // A process with the same pid that started at another time
+        process.start += 1;
+        assert!(!same_process(&process));
+        process.start -= 1;
+        process.name.push('x');
+        assert!(!same_process(&process));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::color::Color;
+use crate::draw::Font;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+const FONT_SIZE: f32 = 16.0;
+
+/// A line of text telling the user that something is wrong with the config.
+pub struct Warning<'a> {
+    text: String,
+    font: Font<'a>,
+    length: u32,
+}
+
+impl<'a> Warning<'a> {
+    pub fn new(text: String, font: FontRef) -> Box<Warning> {
+        Box::new(Warning {
+            text,
+            font: Font::new(font, FONT_SIZE),
+            length: 0,
+        })
+    }
+}
+
+impl<'a> Widget for Warning<'a> {
+    fn wait(&mut self, _: &mut WaitContext) {}
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        (self.length, FONT_SIZE as u32)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+        if !ctx.force {
+            return Ok(DrawReport::empty(width, height));
+        }
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+        self.font
+            .auto_draw_text(buf, ctx.bg, &Color::new(1.0, 0.5, 0.0, 1.0), &self.text)?;
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+}
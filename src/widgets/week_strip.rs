@@ -0,0 +1,175 @@
+use crate::color::Color;
+use crate::draw::{draw_box, Font};
+use crate::events::EventFile;
+use crate::widgets::calendar::Holidays;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use std::path::PathBuf;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Weekday};
+
+const DAYS: u32 = 7;
+
+fn day_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MON",
+        Weekday::Tue => "TUE",
+        Weekday::Wed => "WED",
+        Weekday::Thu => "THU",
+        Weekday::Fri => "FRI",
+        Weekday::Sat => "SAT",
+        Weekday::Sun => "SUN",
+    }
+}
+
+/// The next seven days in a row, from today on, each with its day name and
+/// date. Days with a holiday or an event are marked with a dot below the
+/// date, and today is outlined.
+pub struct WeekStrip<'a> {
+    cur_date: NaiveDate,
+    dirty: bool,
+    holidays: Holidays,
+    events: Option<EventFile>,
+    font: Font<'a>,
+    font_size: u32,
+    length: u32,
+}
+
+impl<'a> WeekStrip<'a> {
+    pub fn new(
+        time: NaiveDateTime,
+        font: FontRef,
+        font_size: f32,
+        length: u32,
+        holidays: Vec<String>,
+        holiday_file: Option<PathBuf>,
+        event_file: Option<PathBuf>,
+    ) -> Box<WeekStrip> {
+        let mut font = Font::new(font, font_size);
+        font.add_str_to_cache("0123456789MONTUEWDHFRISA");
+        Box::new(WeekStrip {
+            cur_date: time.date(),
+            dirty: true,
+            holidays: Holidays::new(holidays, holiday_file),
+            events: event_file.map(EventFile::new),
+            font,
+            font_size: font_size as u32,
+            length,
+        })
+    }
+
+    fn has_event(&self, date: NaiveDate) -> bool {
+        self.events.as_ref().is_some_and(|events| events.on(date))
+    }
+}
+
+impl<'a> Widget for WeekStrip<'a> {
+    fn wait(&mut self, _: &mut WaitContext) {}
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        // The day name and date, and the dots below them
+        (self.length, self.font_size * 2 + self.font_size / 2)
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        if self.length == 0 {
+            self.length = expansion.0;
+        }
+        let (width, height) = self.size();
+        if self.holidays.reload() {
+            self.dirty = true;
+        }
+        if self.events.as_mut().is_some_and(|events| events.reload()) {
+            self.dirty = true;
+        }
+        if ctx.time.date() == self.cur_date && !ctx.force && !self.dirty {
+            return Ok(DrawReport::empty(width, height));
+        }
+        self.dirty = false;
+        self.cur_date = ctx.time.date();
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+
+        let column = width / DAYS;
+        let dot = (self.font_size / 4).max(2);
+        let mut date = self.cur_date;
+        for idx in 0..DAYS {
+            let today = idx == 0;
+            let holiday = self.holidays.is_holiday(date);
+            let c = if today {
+                Color::new(1.0, 1.0, 1.0, 1.0)
+            } else if holiday {
+                Color::new(0.8, 0.35, 0.35, 1.0)
+            } else {
+                Color::new(0.5, 0.5, 0.5, 1.0)
+            };
+            let col_buf = &mut buf.subdimensions((idx * column, 0, column, height))?;
+
+            for (line, text) in [day_name(date.weekday()), &format!("{:02}", date.day())]
+                .iter()
+                .enumerate()
+            {
+                let text_width = self.font.auto_text_width(text).min(column);
+                self.font.auto_draw_text(
+                    &mut col_buf.subdimensions((
+                        (column - text_width) / 2,
+                        line as u32 * self.font_size,
+                        text_width,
+                        self.font_size,
+                    ))?,
+                    ctx.bg,
+                    &c,
+                    text,
+                )?;
+            }
+
+            // A holiday takes the red dot, and an event the blue one
+            let dots: Vec<Color> = [
+                (holiday, Color::new(0.8, 0.35, 0.35, 1.0)),
+                (self.has_event(date), Color::new(0.4, 0.6, 0.9, 1.0)),
+            ]
+            .into_iter()
+            .filter(|(marked, _)| *marked)
+            .map(|(_, c)| c)
+            .collect();
+            let dots_width = dots.len() as u32 * dot * 2;
+            let mut x = column.saturating_sub(dots_width) / 2 + dot / 2;
+            for c in dots {
+                col_buf
+                    .subdimensions((x, self.font_size * 2 + dot / 2, dot, dot))?
+                    .memset(&c);
+                x += dot * 2;
+            }
+
+            if today {
+                draw_box(col_buf, &Color::new(0.5, 0.5, 0.5, 1.0), (column, height))?;
+            }
+
+            date = match date.succ_opt() {
+                Some(date) => date,
+                None => break,
+            };
+        }
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+}
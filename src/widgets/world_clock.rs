@@ -0,0 +1,143 @@
+use crate::color::Color;
+use crate::draw::Font;
+use crate::zoneinfo::Zone;
+use crate::{
+    fonts::FontRef,
+    widget::{DrawContext, DrawReport, KeyState, ModifiersState, Scroll, WaitContext, Widget},
+};
+
+use chrono::{Duration, Local, NaiveDateTime, Timelike};
+
+// The label of a zone, which is its city, e.g. "New York" for
+// "America/New_York".
+fn label(name: &str) -> String {
+    name.rsplit('/').next().unwrap_or(name).replace('_', " ")
+}
+
+/// The time in other time zones, one line per zone.
+pub struct WorldClock<'a> {
+    zones: Vec<(String, Option<Zone>)>,
+    cur_time: NaiveDateTime,
+    font: Font<'a>,
+    size: f32,
+    digit: u32,
+    colon: u32,
+    label_width: u32,
+}
+
+impl<'a> WorldClock<'a> {
+    pub fn new(
+        time: NaiveDateTime,
+        zones: &[String],
+        font: FontRef<'a>,
+        size: f32,
+    ) -> ::std::io::Result<Box<WorldClock<'a>>> {
+        let zones: Vec<(String, Option<Zone>)> = zones
+            .iter()
+            .map(|name| match Zone::load(name) {
+                Ok(zone) => (label(name), Some(zone)),
+                Err(err) => {
+                    eprintln!("Unable to load time zone {}: {}", name, err);
+                    (label(name), None)
+                }
+            })
+            .collect();
+
+        let mut font = Font::new(font, size);
+        font.add_str_to_cache("0123456789:-");
+        let digit = font.auto_widest("0123456789-")?;
+        let colon = font.auto_widest(":")?;
+        let mut label_width = 0;
+        for (label, _) in zones.iter() {
+            let widest = font.auto_widest(label)?;
+            label_width = label_width.max(widest * label.chars().count() as u32);
+        }
+
+        Ok(Box::new(WorldClock {
+            zones,
+            cur_time: time,
+            font,
+            size,
+            digit,
+            colon,
+            label_width,
+        }))
+    }
+}
+
+impl<'a> Widget for WorldClock<'a> {
+    fn wait(&mut self, ctx: &mut WaitContext) {
+        let target = (self.cur_time + Duration::seconds(60))
+            .with_second(0)
+            .unwrap()
+            .with_nanosecond(0)
+            .unwrap();
+        ctx.set_time(target);
+    }
+    fn enter(&mut self) {}
+    fn leave(&mut self) {}
+    fn size(&self) -> (u32, u32) {
+        let line = self.size.ceil() as u32;
+        (
+            self.digit * 5 + self.colon + self.label_width,
+            line * self.zones.len() as u32,
+        )
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut DrawContext,
+        pos: (u32, u32),
+        _expansion: (u32, u32),
+    ) -> Result<DrawReport, ::std::io::Error> {
+        let (width, height) = self.size();
+        if !(ctx.time.date() != self.cur_time.date()
+            || ctx.time.hour() != self.cur_time.hour()
+            || ctx.time.minute() != self.cur_time.minute()
+            || ctx.force)
+        {
+            return Ok(DrawReport::empty(width, height));
+        }
+
+        self.cur_time = ctx.time;
+
+        let buf = &mut ctx.buf.subdimensions((pos.0, pos.1, width, height))?;
+        buf.memset(ctx.bg);
+
+        let utc = Local::now().timestamp();
+        let line = self.size.ceil() as u32;
+        let digit = self.digit;
+        let colon = self.colon;
+        for (idx, (label, zone)) in self.zones.iter().enumerate() {
+            let time = match zone.as_ref().and_then(|zone| zone.local_time(utc)) {
+                Some(t) => format!("{:02}:{:02}", t.hour(), t.minute()),
+                None => "--:--".to_string(),
+            };
+            let buf = &mut buf.offset((0, idx as u32 * line))?;
+            self.font.draw_text_fixed_width(
+                buf,
+                ctx.bg,
+                &Color::new(1.0, 1.0, 1.0, 1.0),
+                &[digit, digit, colon, digit, digit],
+                &time,
+            )?;
+            self.font.auto_draw_text(
+                &mut buf.offset((digit * 5 + colon, 0))?,
+                ctx.bg,
+                &Color::new(0.75, 0.75, 0.75, 1.0),
+                label,
+            )?;
+        }
+
+        Ok(DrawReport {
+            width,
+            height,
+            damage: vec![buf.get_signed_bounds()],
+            full_damage: false,
+        })
+    }
+
+    fn keyboard_input(&mut self, _: u32, _: ModifiersState, _: KeyState, _: Option<String>) {}
+    fn mouse_click(&mut self, _: u32, _: (u32, u32)) {}
+    fn mouse_scroll(&mut self, _: Scroll, _: (u32, u32)) {}
+}
@@ -0,0 +1,323 @@
+//! Time zones read from the system zoneinfo database
+//!
+//! The TZif files in /usr/share/zoneinfo list the UTC offset changes of a
+//! zone up to some year, and end with a POSIX TZ rule for the times after
+//! that. Only the rules of the form used by the database are understood,
+//! that is with the transitions given as Mm.w.d.
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+// A day of the year as month, week of the month (5 for the last) and day of
+// the week, counted from Sunday, along with the time of day in seconds.
+#[derive(Clone, Copy, Debug)]
+struct RuleDate {
+    month: u32,
+    week: u32,
+    weekday: u32,
+    time: i64,
+}
+
+impl RuleDate {
+    // Seconds since the epoch of the date in the year, in local time.
+    fn local_time(&self, year: i32) -> Option<i64> {
+        let first = NaiveDate::from_ymd_opt(year, self.month, 1)?;
+        let first_weekday = first.weekday().num_days_from_sunday();
+        let mut day = 1 + (self.weekday + 7 - first_weekday) % 7 + (self.week - 1) * 7;
+        let days_in_month = match NaiveDate::from_ymd_opt(year, self.month + 1, 1) {
+            Some(next) => next.pred_opt()?.day(),
+            None => 31,
+        };
+        while day > days_in_month {
+            day -= 7;
+        }
+        let date = NaiveDate::from_ymd_opt(year, self.month, day)?;
+        Some(date.and_hms_opt(0, 0, 0)?.timestamp() + self.time)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    std: (i32, String),
+    // The daylight saving time offset, and when it starts and ends
+    dst: Option<((i32, String), RuleDate, RuleDate)>,
+}
+
+// Parses a zone abbreviation, either letters or quoted in angle brackets.
+fn parse_name(s: &str) -> Option<(String, &str)> {
+    if let Some(rest) = s.strip_prefix('<') {
+        let end = rest.find('>')?;
+        return Some((rest[..end].to_string(), &rest[end + 1..]));
+    }
+    let end = s
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(s.len());
+    if end < 3 {
+        return None;
+    }
+    Some((s[..end].to_string(), &s[end..]))
+}
+
+// Parses [+-]hh[:mm[:ss]] into seconds.
+fn parse_time(s: &str) -> Option<(i64, &str)> {
+    let (sign, s) = match s.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let end = s
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(s.len());
+    let mut secs = 0;
+    let mut unit = 3600;
+    for part in s[..end].split(':') {
+        secs += part.parse::<i64>().ok()? * unit;
+        unit /= 60;
+    }
+    Some((sign * secs, &s[end..]))
+}
+
+// Parses Mm.w.d[/time].
+fn parse_rule_date(s: &str) -> Option<RuleDate> {
+    let (date, time) = match s.split_once('/') {
+        Some((date, time)) => (date, parse_time(time)?.0),
+        None => (s, 2 * 3600),
+    };
+    let mut parts = date.strip_prefix('M')?.split('.');
+    let mut next = || parts.next()?.parse::<u32>().ok();
+    let (month, week, weekday) = (next()?, next()?, next()?);
+    if !(1..=12).contains(&month) || !(1..=5).contains(&week) || weekday > 6 {
+        return None;
+    }
+    Some(RuleDate {
+        month,
+        week,
+        weekday,
+        time,
+    })
+}
+
+impl Rule {
+    fn parse(s: &str) -> Option<Rule> {
+        let (std_name, rest) = parse_name(s)?;
+        // POSIX offsets are west of UTC
+        let (std_offset, rest) = parse_time(rest)?;
+        let std = (-std_offset as i32, std_name);
+        if rest.is_empty() {
+            return Some(Rule { std, dst: None });
+        }
+        let (dst_name, rest) = parse_name(rest)?;
+        let (dst_offset, rest) = if rest.starts_with(',') {
+            (std.0 + 3600, rest)
+        } else {
+            let (offset, rest) = parse_time(rest)?;
+            (-offset as i32, rest)
+        };
+        let mut dates = rest.strip_prefix(',')?.split(',');
+        let start = parse_rule_date(dates.next()?)?;
+        let end = parse_rule_date(dates.next()?)?;
+        Some(Rule {
+            std,
+            dst: Some(((dst_offset, dst_name), start, end)),
+        })
+    }
+
+    fn offset(&self, utc: i64) -> (i32, String) {
+        let (dst, start, end) = match &self.dst {
+            Some(dst) => dst,
+            None => return self.std.clone(),
+        };
+        let year = match NaiveDateTime::from_timestamp_opt(utc + self.std.0 as i64, 0) {
+            Some(t) => t.year(),
+            None => return self.std.clone(),
+        };
+        // The start is given in standard time, and the end in daylight
+        // saving time.
+        let start = start.local_time(year).map(|t| t - self.std.0 as i64);
+        let end = end.local_time(year).map(|t| t - dst.0 as i64);
+        let in_dst = match (start, end) {
+            (Some(start), Some(end)) if start < end => utc >= start && utc < end,
+            // Southern hemisphere, where daylight saving time spans the
+            // turn of the year
+            (Some(start), Some(end)) => utc >= start || utc < end,
+            _ => false,
+        };
+        if in_dst {
+            dst.clone()
+        } else {
+            self.std.clone()
+        }
+    }
+}
+
+/// A time zone, giving the UTC offset and abbreviation at any time.
+#[derive(Clone, Debug)]
+pub struct Zone {
+    transitions: Vec<(i64, usize)>,
+    types: Vec<(i32, String)>,
+    rule: Option<Rule>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() < n {
+            return Err(invalid("truncated zoneinfo file"));
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i64(&mut self) -> Result<i64, Error> {
+        let b = self.take(8)?;
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(b);
+        Ok(i64::from_be_bytes(bytes))
+    }
+
+    // The header, returning the version and the counts of leap second
+    // indicators, standard/wall indicators, leap seconds, transitions,
+    // types and abbreviation bytes.
+    fn header(&mut self) -> Result<(u8, [usize; 6]), Error> {
+        if self.take(4)? != b"TZif" {
+            return Err(invalid("not a zoneinfo file"));
+        }
+        let version = self.take(16)?[0];
+        let mut counts = [0; 6];
+        for count in counts.iter_mut() {
+            *count = self.u32()? as usize;
+        }
+        Ok((version, counts))
+    }
+}
+
+impl Zone {
+    /// UTC, which needs no zoneinfo file.
+    pub fn utc() -> Zone {
+        Zone {
+            transitions: Vec::new(),
+            types: vec![(0, "UTC".to_string())],
+            rule: None,
+        }
+    }
+
+    /// Loads a zone by its name in the database, e.g. "America/New_York".
+    pub fn load(name: &str) -> Result<Zone, Error> {
+        if name == "UTC" {
+            return Ok(Zone::utc());
+        }
+        if name.split('/').any(|part| part == ".." || part.is_empty()) {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid zone name"));
+        }
+        Zone::parse(&fs::read(Path::new(ZONEINFO).join(name))?)
+    }
+
+    fn parse(data: &[u8]) -> Result<Zone, Error> {
+        let mut reader = Reader { data };
+        let (version, [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt]) = reader.header()?;
+        let mut time_size = 4;
+        let (isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt) = if version >= b'2' {
+            // Skip the 32-bit data for the 64-bit data following it
+            reader.take(timecnt * 5 + typecnt * 6 + charcnt + leapcnt * 8 + isstdcnt + isutcnt)?;
+            time_size = 8;
+            let (_, counts) = reader.header()?;
+            (
+                counts[0], counts[1], counts[2], counts[3], counts[4], counts[5],
+            )
+        } else {
+            (isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt)
+        };
+
+        let mut times = Vec::with_capacity(timecnt);
+        for _ in 0..timecnt {
+            times.push(match time_size {
+                8 => reader.i64()?,
+                _ => reader.u32()? as i32 as i64,
+            });
+        }
+        let indices = reader.take(timecnt)?.to_vec();
+        let mut raw_types = Vec::with_capacity(typecnt);
+        for _ in 0..typecnt {
+            let offset = reader.u32()? as i32;
+            let b = reader.take(2)?;
+            raw_types.push((offset, b[1] as usize));
+        }
+        let chars = reader.take(charcnt)?;
+        reader.take(leapcnt * (time_size + 4) + isstdcnt + isutcnt)?;
+
+        let types: Vec<(i32, String)> = raw_types
+            .into_iter()
+            .map(|(offset, idx)| {
+                let abbr = chars.get(idx..).unwrap_or_default();
+                let end = abbr.iter().position(|&b| b == 0).unwrap_or(abbr.len());
+                (offset, String::from_utf8_lossy(&abbr[..end]).into_owned())
+            })
+            .collect();
+        if types.is_empty() {
+            return Err(invalid("zoneinfo file without types"));
+        }
+        let mut transitions = Vec::with_capacity(timecnt);
+        for (time, idx) in times.into_iter().zip(indices) {
+            if idx as usize >= types.len() {
+                return Err(invalid("invalid type in zoneinfo file"));
+            }
+            transitions.push((time, idx as usize));
+        }
+
+        // The rule for the times after the last transition
+        let rule = if version >= b'2' {
+            std::str::from_utf8(reader.data)
+                .ok()
+                .and_then(|footer| Rule::parse(footer.trim()))
+        } else {
+            None
+        };
+
+        Ok(Zone {
+            transitions,
+            types,
+            rule,
+        })
+    }
+
+    /// The offset from UTC in seconds, and the abbreviation, at the time in
+    /// seconds since the epoch.
+    pub fn offset(&self, utc: i64) -> (i32, String) {
+        match self.transitions.last() {
+            Some(&(last, _)) if utc >= last && self.rule.is_some() => {
+                return self.rule.as_ref().unwrap().offset(utc)
+            }
+            None if self.rule.is_some() => return self.rule.as_ref().unwrap().offset(utc),
+            _ => (),
+        }
+        let idx = self.transitions.partition_point(|&(t, _)| t <= utc);
+        let ty = match idx {
+            0 => 0,
+            idx => self.transitions[idx - 1].1,
+        };
+        self.types[ty].clone()
+    }
+
+    /// The local time in the zone at the time in seconds since the epoch.
+    pub fn local_time(&self, utc: i64) -> Option<NaiveDateTime> {
+        let (offset, _) = self.offset(utc);
+        NaiveDateTime::from_timestamp_opt(utc + offset as i64, 0)
+    }
+}